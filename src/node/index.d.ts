@@ -10,4 +10,6 @@ export function parseHeader(path: string): any
 export function parseEvent(path: string, eventName: string, playerExtra?: Array<string> | undefined | null, otherExtra?: Array<string> | undefined | null): any
 export function parseEvents(path: string, eventNames?: Array<string> | undefined | null, playerExtra?: Array<string> | undefined | null, otherExtra?: Array<string> | undefined | null): any
 export function parseTicks(path: string, wantedProps: Array<string>, wantedTicks?: Array<number> | undefined | null, structOfArrays?: boolean | undefined | null): any
+export function parseEventsArrow(path: string, eventNames: Array<string>, playerExtra?: Array<string> | undefined | null, otherExtra?: Array<string> | undefined | null): Buffer
+export function parseTicksArrow(path: string, wantedProps: Array<string>, wantedTicks?: Array<number> | undefined | null): Buffer
 export function parsePlayerInfo(path: string): any
@@ -252,7 +252,7 @@ if (!nativeBinding) {
   throw new Error(`Failed to load native binding`)
 }
 
-const { parseChatMessages, listGameEvents, parseGrenades, parseHeader, parseEvent, parseEvents, parseTicks, parsePlayerInfo } = nativeBinding
+const { parseChatMessages, listGameEvents, parseGrenades, parseHeader, parseEvent, parseEvents, parseTicks, parseEventsArrow, parseTicksArrow, parsePlayerInfo } = nativeBinding
 
 module.exports.parseChatMessages = parseChatMessages
 module.exports.listGameEvents = listGameEvents
@@ -261,4 +261,6 @@ module.exports.parseHeader = parseHeader
 module.exports.parseEvent = parseEvent
 module.exports.parseEvents = parseEvents
 module.exports.parseTicks = parseTicks
+module.exports.parseEventsArrow = parseEventsArrow
+module.exports.parseTicksArrow = parseTicksArrow
 module.exports.parsePlayerInfo = parsePlayerInfo
@@ -2,14 +2,12 @@
 
 #[macro_use]
 extern crate napi_derive;
-use ahash::AHashMap;
 use memmap2::MmapOptions;
 use napi::bindgen_prelude::*;
 use napi::Either;
+use parser::arrow_export::record_batch_to_ipc;
 use parser::parser_settings::rm_user_friendly_names;
-use parser::parser_settings::Parser;
-use parser::parser_settings::ParserInputs;
-use parser::parser_thread_settings::create_huffman_lookup_table;
+use parser::query::DemoQuery;
 use parser::read_bits::DemoParserError;
 use parser::variants::soa_to_aos;
 use parser::variants::BytesVariant;
@@ -18,30 +16,15 @@ use serde_json::Value;
 use std::collections::HashMap;
 use std::fs::File;
 use std::result::Result;
-use std::sync::Arc;
 
 #[napi]
 pub fn parse_chat_messages(path_or_buf: Either<String, Buffer>) -> napi::Result<Value> {
   let bytes = resolve_byte_type(path_or_buf)?;
-  let arc_huf = Arc::new(create_huffman_lookup_table());
 
-  let settings = ParserInputs {
-    real_name_to_og_name: AHashMap::default(),
-    bytes: Arc::new(bytes),
-    wanted_player_props: vec![],
-    wanted_player_props_og_names: vec![],
-    wanted_other_props: vec![],
-    wanted_other_props_og_names: vec![],
-    wanted_events: vec![],
-    parse_ents: true,
-    wanted_ticks: vec![],
-    parse_projectiles: false,
-    only_header: true,
-    count_props: false,
-    only_convars: false,
-    huffman_lookup_table: arc_huf.clone(),
-  };
-  let mut parser = Parser::new(settings);
+  let mut parser = match DemoQuery::new().parser(bytes) {
+    Ok(parser) => parser,
+    Err(e) => return Err(Error::new(Status::InvalidArg, format!("{}", e).to_owned())),
+  };
   let output = match parser.parse_demo() {
     Ok(output) => output,
     Err(e) => return Err(Error::new(Status::InvalidArg, format!("{}", e).to_owned())),
@@ -58,24 +41,10 @@ pub fn parse_chat_messages(path_or_buf: Either<String, Buffer>) -> napi::Result<
 pub fn list_game_events(path_or_buf: Either<String, Buffer>) -> napi::Result<Value> {
   let bytes = resolve_byte_type(path_or_buf)?;
 
-  let arc_huf = Arc::new(create_huffman_lookup_table());
-  let settings = ParserInputs {
-    real_name_to_og_name: AHashMap::default(),
-    bytes: Arc::new(bytes),
-    wanted_player_props: vec![],
-    wanted_player_props_og_names: vec![],
-    wanted_other_props: vec![],
-    wanted_other_props_og_names: vec![],
-    wanted_events: vec!["all".to_string()],
-    parse_ents: false,
-    wanted_ticks: vec![],
-    parse_projectiles: false,
-    only_header: false,
-    count_props: false,
-    only_convars: false,
-    huffman_lookup_table: arc_huf.clone(),
-  };
-  let mut parser = Parser::new(settings);
+  let mut parser = match DemoQuery::new().events(["all"]).entities(false).parser(bytes) {
+    Ok(parser) => parser,
+    Err(e) => return Err(Error::new(Status::InvalidArg, format!("{}", e).to_owned())),
+  };
   let output = match parser.parse_demo() {
     Ok(output) => output,
     Err(e) => return Err(Error::new(Status::InvalidArg, format!("{}", e).to_owned())),
@@ -91,25 +60,11 @@ pub fn list_game_events(path_or_buf: Either<String, Buffer>) -> napi::Result<Val
 #[napi]
 pub fn parse_grenades(path_or_buf: Either<String, Buffer>) -> napi::Result<Value> {
   let bytes = resolve_byte_type(path_or_buf)?;
-  let arc_huf = Arc::new(create_huffman_lookup_table());
 
-  let settings = ParserInputs {
-    real_name_to_og_name: AHashMap::default(),
-    bytes: Arc::new(bytes),
-    wanted_player_props: vec![],
-    wanted_player_props_og_names: vec![],
-    wanted_other_props: vec![],
-    wanted_other_props_og_names: vec![],
-    wanted_events: vec![],
-    parse_ents: true,
-    wanted_ticks: vec![],
-    parse_projectiles: true,
-    only_header: true,
-    count_props: false,
-    only_convars: false,
-    huffman_lookup_table: arc_huf.clone(),
-  };
-  let mut parser = Parser::new(settings);
+  let mut parser = match DemoQuery::new().projectiles().parser(bytes) {
+    Ok(parser) => parser,
+    Err(e) => return Err(Error::new(Status::InvalidArg, format!("{}", e).to_owned())),
+  };
   let output = match parser.parse_demo() {
     Ok(output) => output,
     Err(e) => return Err(Error::new(Status::InvalidArg, format!("{}", e).to_owned())),
@@ -124,25 +79,11 @@ pub fn parse_grenades(path_or_buf: Either<String, Buffer>) -> napi::Result<Value
 #[napi]
 pub fn parse_header(path_or_buf: Either<String, Buffer>) -> napi::Result<Value> {
   let bytes = resolve_byte_type(path_or_buf)?;
-  let arc_huf = Arc::new(create_huffman_lookup_table());
 
-  let settings = ParserInputs {
-    real_name_to_og_name: AHashMap::default(),
-    bytes: Arc::new(bytes),
-    wanted_player_props: vec![],
-    wanted_player_props_og_names: vec![],
-    wanted_other_props: vec![],
-    wanted_other_props_og_names: vec![],
-    wanted_events: vec![],
-    parse_ents: false,
-    wanted_ticks: vec![],
-    parse_projectiles: true,
-    only_header: true,
-    count_props: false,
-    only_convars: false,
-    huffman_lookup_table: arc_huf.clone(),
-  };
-  let mut parser = Parser::new(settings);
+  let mut parser = match DemoQuery::new().entities(false).projectiles().parser(bytes) {
+    Ok(parser) => parser,
+    Err(e) => return Err(Error::new(Status::InvalidArg, format!("{}", e).to_owned())),
+  };
   let _output = match parser.parse_demo() {
     Ok(output) => output,
     Err(e) => return Err(Error::new(Status::InvalidArg, format!("{}", e).to_owned())),
@@ -172,43 +113,16 @@ pub fn parse_event(
     Some(p) => p,
     None => vec![],
   };
-  let real_names_player = match rm_user_friendly_names(&player_props) {
-    Ok(names) => names,
-    Err(e) => return Err(Error::new(Status::InvalidArg, format!("{}", e).to_owned())),
-  };
-  let real_other_props = match rm_user_friendly_names(&other_props) {
-    Ok(names) => names,
-    Err(e) => return Err(Error::new(Status::InvalidArg, format!("{}", e).to_owned())),
-  };
-
-  let mut real_name_to_og_name = AHashMap::default();
-  for (real_name, user_friendly_name) in real_names_player.iter().zip(&player_props) {
-    real_name_to_og_name.insert(real_name.clone(), user_friendly_name.clone());
-  }
-  for (real_name, user_friendly_name) in real_other_props.iter().zip(&other_props) {
-    real_name_to_og_name.insert(real_name.clone(), user_friendly_name.clone());
-  }
-
   let bytes = resolve_byte_type(path_or_buf)?;
-  let arc_huf = Arc::new(create_huffman_lookup_table());
 
-  let settings = ParserInputs {
-    real_name_to_og_name: real_name_to_og_name,
-    bytes: Arc::new(bytes),
-    wanted_player_props: real_names_player.clone(),
-    wanted_player_props_og_names: vec![],
-    wanted_other_props: real_other_props,
-    wanted_other_props_og_names: vec![],
-    wanted_events: vec![event_name],
-    parse_ents: true,
-    wanted_ticks: vec![],
-    parse_projectiles: false,
-    only_header: true,
-    count_props: false,
-    only_convars: false,
-    huffman_lookup_table: arc_huf.clone(),
-  };
-  let mut parser = Parser::new(settings);
+  let query = DemoQuery::new()
+    .player_props(player_props)
+    .other_props(other_props)
+    .events([event_name]);
+  let mut parser = match query.parser(bytes) {
+    Ok(parser) => parser,
+    Err(e) => return Err(Error::new(Status::InvalidArg, format!("{}", e).to_owned())),
+  };
   let output = match parser.parse_demo() {
     Ok(output) => output,
     Err(e) => return Err(Error::new(Status::InvalidArg, format!("{}", e).to_owned())),
@@ -238,43 +152,16 @@ pub fn parse_events(
     Some(p) => p,
     None => vec![],
   };
-  let real_names_player = match rm_user_friendly_names(&player_props) {
-    Ok(names) => names,
-    Err(e) => return Err(Error::new(Status::InvalidArg, format!("{}", e).to_owned())),
-  };
-  let real_other_props = match rm_user_friendly_names(&other_props) {
-    Ok(names) => names,
-    Err(e) => return Err(Error::new(Status::InvalidArg, format!("{}", e).to_owned())),
-  };
-
-  let mut real_name_to_og_name = AHashMap::default();
-  for (real_name, user_friendly_name) in real_names_player.iter().zip(&player_props) {
-    real_name_to_og_name.insert(real_name.clone(), user_friendly_name.clone());
-  }
-  for (real_name, user_friendly_name) in real_other_props.iter().zip(&other_props) {
-    real_name_to_og_name.insert(real_name.clone(), user_friendly_name.clone());
-  }
-
   let bytes = resolve_byte_type(path_or_buf)?;
-  let arc_huf = Arc::new(create_huffman_lookup_table());
 
-  let settings = ParserInputs {
-    real_name_to_og_name: real_name_to_og_name,
-    bytes: Arc::new(bytes),
-    wanted_player_props: real_names_player.clone(),
-    wanted_player_props_og_names: vec![],
-    wanted_other_props: real_other_props.clone(),
-    wanted_other_props_og_names: vec![],
-    wanted_events: event_names,
-    parse_ents: true,
-    wanted_ticks: vec![],
-    parse_projectiles: false,
-    only_header: true,
-    count_props: false,
-    only_convars: false,
-    huffman_lookup_table: arc_huf.clone(),
-  };
-  let mut parser = Parser::new(settings);
+  let query = DemoQuery::new()
+    .player_props(player_props)
+    .other_props(other_props)
+    .events(event_names);
+  let mut parser = match query.parser(bytes) {
+    Ok(parser) => parser,
+    Err(e) => return Err(Error::new(Status::InvalidArg, format!("{}", e).to_owned())),
+  };
   let output = match parser.parse_demo() {
     Ok(output) => output,
     Err(e) => return Err(Error::new(Status::InvalidArg, format!("{}", e).to_owned())),
@@ -299,34 +186,16 @@ pub fn parse_ticks(
   };
 
   let bytes = resolve_byte_type(path_or_buf)?;
-  let arc_huf = Arc::new(create_huffman_lookup_table());
-  let mut real_name_to_og_name = AHashMap::default();
-
-  for (real_name, user_friendly_name) in real_names.iter().zip(&wanted_props) {
-    real_name_to_og_name.insert(real_name.clone(), user_friendly_name.clone());
-  }
   let wanted_ticks = match wanted_ticks {
     Some(t) => t,
     None => vec![],
   };
 
-  let settings = ParserInputs {
-    real_name_to_og_name: real_name_to_og_name,
-    bytes: Arc::new(bytes),
-    wanted_player_props: real_names.clone(),
-    wanted_player_props_og_names: wanted_props.clone(),
-    wanted_other_props: vec![],
-    wanted_other_props_og_names: vec![],
-    wanted_events: vec![],
-    parse_ents: true,
-    wanted_ticks: wanted_ticks,
-    parse_projectiles: false,
-    only_header: false,
-    count_props: false,
-    only_convars: false,
-    huffman_lookup_table: arc_huf.clone(),
-  };
-  let mut parser = Parser::new(settings);
+  let query = DemoQuery::new().player_props(&wanted_props).ticks(wanted_ticks);
+  let mut parser = match query.parser(bytes) {
+    Ok(parser) => parser,
+    Err(e) => return Err(Error::new(Status::InvalidArg, format!("{}", e).to_owned())),
+  };
   let output = match parser.parse_demo() {
     Ok(output) => output,
     Err(e) => return Err(Error::new(Status::InvalidArg, format!("{}", e).to_owned())),
@@ -365,28 +234,67 @@ pub fn parse_ticks(
   }
 }
 
+// Same as parse_events/parse_ticks but as an Arrow IPC stream instead of JSON, read it with tableFromIPC
+#[napi]
+pub fn parse_events_arrow(
+  path_or_buf: Either<String, Buffer>,
+  event_names: Vec<String>,
+  player_extra: Option<Vec<String>>,
+  other_extra: Option<Vec<String>>,
+) -> napi::Result<Buffer> {
+  let bytes = resolve_byte_type(path_or_buf)?;
+
+  let query = DemoQuery::new()
+    .player_props(player_extra.unwrap_or_default())
+    .other_props(other_extra.unwrap_or_default())
+    .events(event_names);
+  let mut parser = match query.parser(bytes) {
+    Ok(parser) => parser,
+    Err(e) => return Err(Error::new(Status::InvalidArg, format!("{}", e).to_owned())),
+  };
+  let output = match parser.parse_demo() {
+    Ok(output) => output,
+    Err(e) => return Err(Error::new(Status::InvalidArg, format!("{}", e).to_owned())),
+  };
+  match output.events_record_batch(&parser.ge_list).and_then(|batch| record_batch_to_ipc(&batch)) {
+    Ok(ipc) => Ok(ipc.into()),
+    Err(e) => Err(Error::new(Status::GenericFailure, format!("{}", e).to_owned())),
+  }
+}
+
+#[napi]
+pub fn parse_ticks_arrow(
+  path_or_buf: Either<String, Buffer>,
+  wanted_props: Vec<String>,
+  wanted_ticks: Option<Vec<i32>>,
+) -> napi::Result<Buffer> {
+  let bytes = resolve_byte_type(path_or_buf)?;
+
+  let query = DemoQuery::new()
+    .player_props(&wanted_props)
+    .ticks(wanted_ticks.unwrap_or_default());
+  let mut parser = match query.parser(bytes) {
+    Ok(parser) => parser,
+    Err(e) => return Err(Error::new(Status::InvalidArg, format!("{}", e).to_owned())),
+  };
+  let output = match parser.parse_demo() {
+    Ok(output) => output,
+    Err(e) => return Err(Error::new(Status::InvalidArg, format!("{}", e).to_owned())),
+  };
+  match output.ticks_record_batch().and_then(|batch| record_batch_to_ipc(&batch)) {
+    Ok(ipc) => Ok(ipc.into()),
+    Err(e) => Err(Error::new(Status::GenericFailure, format!("{}", e).to_owned())),
+  }
+}
+
 #[napi]
 pub fn parse_player_info(path_or_buf: Either<String, Buffer>) -> napi::Result<Value> {
   let bytes = resolve_byte_type(path_or_buf)?;
-  let arc_huf = Arc::new(create_huffman_lookup_table());
 
-  let settings = ParserInputs {
-    real_name_to_og_name: AHashMap::default(),
-    bytes: Arc::new(bytes),
-    wanted_player_props: vec![],
-    wanted_player_props_og_names: vec![],
-    wanted_other_props: vec![],
-    wanted_other_props_og_names: vec![],
-    wanted_events: vec![],
-    parse_ents: true,
-    wanted_ticks: vec![],
-    parse_projectiles: false,
-    only_header: true,
-    count_props: false,
-    only_convars: false,
-    huffman_lookup_table: arc_huf.clone(),
-  };
-  let mut parser = Parser::new(settings);
+  let mut parser = match DemoQuery::new().parser(bytes) {
+    Ok(parser) => parser,
+    Err(e) => return Err(Error::new(Status::InvalidArg, format!("{}", e).to_owned())),
+  };
   let output = match parser.parse_demo() {
     Ok(output) => output,
     Err(e) => return Err(Error::new(Status::InvalidArg, format!("{}", e).to_owned())),
@@ -0,0 +1,14 @@
+use parser::query::DemoQuery;
+use std::error::Error;
+
+// Every wallbang hit
+//
+// cargo run --example damage -- match.dem
+fn main() -> Result<(), Box<dyn Error>> {
+    let path = std::env::args().nth(1).ok_or("usage: damage <demo>")?;
+    let damage = DemoQuery::new().parser_from_path(&path)?.parse_damage()?;
+    for d in damage.iter().filter(|d| d.penetrated == Some(true)) {
+        println!("{} {:?} -> {:?} {} {}", d.tick, d.attacker_name, d.victim_name, d.weapon, d.health_damage);
+    }
+    Ok(())
+}
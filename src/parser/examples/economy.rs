@@ -0,0 +1,17 @@
+use parser::query::DemoQuery;
+use std::error::Error;
+
+// Buy type, equipment and money spent of both teams every round
+//
+// cargo run --example economy -- match.dem
+fn main() -> Result<(), Box<dyn Error>> {
+    let path = std::env::args().nth(1).ok_or("usage: economy <demo>")?;
+    let economy = DemoQuery::new().parser_from_path(&path)?.parse_economy()?;
+    for e in &economy {
+        println!(
+            "{} {} {:?} equipment {} spent {}",
+            e.round, e.side, e.buy_type, e.equipment_value, e.money_spent
+        );
+    }
+    Ok(())
+}
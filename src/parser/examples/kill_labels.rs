@@ -0,0 +1,37 @@
+use parser::kill_labels::DEFAULT_TRADE_WINDOW;
+use parser::query::DemoQuery;
+use std::error::Error;
+
+// Opening kills and trades, and every clutch with its outcome
+//
+// cargo run --example kill_labels -- match.dem
+fn main() -> Result<(), Box<dyn Error>> {
+    let path = std::env::args().nth(1).ok_or("usage: kill_labels <demo>")?;
+    let output = DemoQuery::new()
+        .events(["player_death"])
+        .kill_labels(DEFAULT_TRADE_WINDOW)
+        .parser_from_path(&path)?
+        .parse_demo()?;
+    for event in &output.game_events {
+        let field = |name: &str| event.fields.iter().find(|f| f.name == name).and_then(|f| f.data.clone());
+        match event.name.as_str() {
+            "player_death" => println!(
+                "{} {:?} -> {:?} opening {:?} trade {:?}",
+                event.tick,
+                field("attacker_name"),
+                field("user_name"),
+                field("opening_kill"),
+                field("trade")
+            ),
+            "clutch" => println!(
+                "{} clutch {:?} 1v{:?} won {:?}",
+                event.tick,
+                field("user_name"),
+                field("enemies"),
+                field("won")
+            ),
+            _ => {}
+        }
+    }
+    Ok(())
+}
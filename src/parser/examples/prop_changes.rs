@@ -0,0 +1,19 @@
+use parser::query::DemoQuery;
+use std::error::Error;
+
+// Every health change of every player instead of the health of every player on every tick
+//
+// cargo run --example prop_changes -- match.dem
+fn main() -> Result<(), Box<dyn Error>> {
+    let path = std::env::args().nth(1).ok_or("usage: prop_changes <demo>")?;
+    let output = DemoQuery::new()
+        .player_props(["health"])
+        .prop_changes()
+        .parser_from_path(&path)?
+        .parse_demo()?;
+    let names = output.prop_info.change_tracked_props();
+    for c in &output.prop_changes {
+        println!("{} {} {} {:?}", c.tick, c.entity_id, names[&c.prop_id], c.value);
+    }
+    Ok(())
+}
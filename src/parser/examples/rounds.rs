@@ -0,0 +1,14 @@
+use parser::query::DemoQuery;
+use std::error::Error;
+
+// Winner, end reason and score of every round
+//
+// cargo run --example rounds -- match.dem
+fn main() -> Result<(), Box<dyn Error>> {
+    let path = std::env::args().nth(1).ok_or("usage: rounds <demo>")?;
+    let rounds = DemoQuery::new().parser_from_path(&path)?.parse_rounds()?;
+    for r in &rounds {
+        println!("{} {:?} {:?} {}-{}", r.round, r.winner, r.reason, r.t_score, r.ct_score);
+    }
+    Ok(())
+}
@@ -0,0 +1,17 @@
+use parser::query::DemoQuery;
+use std::error::Error;
+
+// Scoreboard, best rating first
+//
+// cargo run --example stats -- match.dem
+fn main() -> Result<(), Box<dyn Error>> {
+    let path = std::env::args().nth(1).ok_or("usage: stats <demo>")?;
+    let stats = DemoQuery::new().parser_from_path(&path)?.parse_stats()?;
+    for p in &stats.players {
+        println!(
+            "{} {}/{}/{} adr {:.1} kast {:.1}% rating {:.2}",
+            p.name, p.kills, p.deaths, p.assists, p.adr, p.kast, p.rating
+        );
+    }
+    Ok(())
+}
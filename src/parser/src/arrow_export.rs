@@ -0,0 +1,336 @@
+use crate::game_events::GameEvent;
+use crate::parser::DemoOutput;
+use crate::read_bits::DemoParserError;
+use crate::variants::PropColumn;
+use crate::variants::VarVec;
+use crate::variants::Variant;
+use ahash::AHashMap;
+use arrow::array::*;
+use arrow::compute::cast;
+use arrow::datatypes::DataType;
+use arrow::datatypes::Field;
+use arrow::datatypes::Schema;
+use arrow::ipc::writer::StreamWriter;
+use arrow::record_batch::RecordBatch;
+use csgoproto::netmessages::csvcmsg_game_event_list::Descriptor_t;
+use std::sync::Arc;
+
+// Columnar output as Arrow RecordBatches (feature "arrow").
+//
+// Ticks: one column per wanted prop, named by its friendly name, in the order of prop_info.prop_infos.
+// Events: ("event_name" and) "tick", then the keys of the game event list (Descriptor_t) and then the
+// fields added by the parser (player names, steamids, wanted props) in the order they are first seen.
+// Keys are typed from the descriptor, so a column has the same type in every demo even if it is always
+// null in one of them. Added fields take their type from the first non-null value.
+// StringVec and U64Vec props become list arrays, VecXY/VecXYZ become lists of floats.
+//
+// Building the arrays copies each column once out of the parser's Vec<Option<T>>. After that, handing a
+// batch to another Arrow implementation (pyarrow etc.) through the C data interface copies nothing.
+// Bindings without the C data interface (Node) get the batch as an IPC stream from record_batch_to_ipc.
+
+impl DemoOutput {
+    pub fn ticks_record_batch(&self) -> Result<RecordBatch, DemoParserError> {
+        let mut fields = vec![];
+        let mut columns = vec![];
+        for prop_info in &self.prop_info.prop_infos {
+            if let Some(col) = self.df.get(&prop_info.id) {
+                let array = prop_column_to_arrow(col);
+                fields.push(Field::new(&prop_info.prop_friendly_name, array.data_type().clone(), true));
+                columns.push(array);
+            }
+        }
+        to_record_batch(fields, columns)
+    }
+    pub fn events_record_batch(&self, ge_list: &AHashMap<i32, Descriptor_t>) -> Result<RecordBatch, DemoParserError> {
+        events_to_record_batch(&self.game_events, ge_list)
+    }
+}
+
+pub fn prop_column_to_arrow(col: &PropColumn) -> ArrayRef {
+    match &col.data {
+        Some(VarVec::Bool(v)) => Arc::new(BooleanArray::from_iter(v.iter())),
+        Some(VarVec::I32(v)) => Arc::new(Int32Array::from_iter(v.iter())),
+        Some(VarVec::U32(v)) => Arc::new(UInt32Array::from_iter(v.iter())),
+        Some(VarVec::U64(v)) => Arc::new(UInt64Array::from_iter(v.iter())),
+        Some(VarVec::F32(v)) => Arc::new(Float32Array::from_iter(v.iter())),
+        Some(VarVec::String(v)) => Arc::new(StringArray::from_iter(v.iter().map(|s| s.as_deref()))),
+        Some(VarVec::StringVec(v)) => {
+            let mut builder = ListBuilder::new(StringBuilder::new());
+            for row in v {
+                for s in row {
+                    builder.values().append_value(s);
+                }
+                builder.append(true);
+            }
+            Arc::new(builder.finish())
+        }
+        Some(VarVec::U64Vec(v)) => {
+            let mut builder = ListBuilder::new(UInt64Builder::new());
+            for row in v {
+                builder.values().append_slice(row);
+                builder.append(true);
+            }
+            Arc::new(builder.finish())
+        }
+        None => Arc::new(NullArray::new(col.len())),
+    }
+}
+
+// Same types parse_key gives the values
+pub fn descriptor_key_type(key_type: i32) -> Option<DataType> {
+    match key_type {
+        1 => Some(DataType::Utf8),
+        2 => Some(DataType::Float32),
+        3 | 4 | 5 | 8 | 9 => Some(DataType::Int32),
+        6 => Some(DataType::Boolean),
+        7 => Some(DataType::UInt64),
+        _ => None,
+    }
+}
+
+pub fn events_to_record_batch(
+    events: &[GameEvent],
+    ge_list: &AHashMap<i32, Descriptor_t>,
+) -> Result<RecordBatch, DemoParserError> {
+    let events: Vec<&GameEvent> = events.iter().collect();
+    let by_name: AHashMap<&str, &Descriptor_t> = ge_list.values().map(|d| (d.name(), d)).collect();
+    let mut descriptors: Vec<&Descriptor_t> = vec![];
+    for event in &events {
+        if let Some(descriptor) = by_name.get(event.name.as_str()) {
+            if !descriptors.iter().any(|d| d.name() == event.name) {
+                descriptors.push(descriptor);
+            }
+        }
+    }
+    let mut fields = vec![Field::new("event_name", DataType::Utf8, false)];
+    let mut columns: Vec<ArrayRef> = vec![Arc::new(StringArray::from_iter_values(
+        events.iter().map(|e| e.name.as_str()),
+    ))];
+    push_event_columns(&events, &descriptors, &mut fields, &mut columns)?;
+    to_record_batch(fields, columns)
+}
+
+// Events of one type
+pub fn event_record_batch(events: &[&GameEvent], descriptor: Option<&Descriptor_t>) -> Result<RecordBatch, DemoParserError> {
+    let mut fields = vec![];
+    let mut columns = vec![];
+    push_event_columns(events, descriptor.as_slice(), &mut fields, &mut columns)?;
+    to_record_batch(fields, columns)
+}
+
+fn push_event_columns(
+    events: &[&GameEvent],
+    descriptors: &[&Descriptor_t],
+    fields: &mut Vec<Field>,
+    columns: &mut Vec<ArrayRef>,
+) -> Result<(), DemoParserError> {
+    fields.push(Field::new("tick", DataType::Int32, false));
+    columns.push(Arc::new(Int32Array::from_iter_values(events.iter().map(|e| e.tick))));
+
+    let mut field_names: Vec<&str> = vec![];
+    for key in descriptors.iter().flat_map(|d| d.keys.iter()) {
+        // Internal fields like userid are removed from the events
+        if !field_names.contains(&key.name()) && events.iter().any(|e| e.fields.iter().any(|f| f.name == key.name())) {
+            field_names.push(key.name());
+        }
+    }
+    for event in events {
+        for field in &event.fields {
+            // Already the tick column
+            if field.name != "tick" && !field_names.contains(&field.name.as_str()) {
+                field_names.push(&field.name);
+            }
+        }
+    }
+    for name in field_names {
+        let values: Vec<Option<&Variant>> = events.iter().map(|e| e.field(name)).collect();
+        let mut array = variants_to_arrow(&values);
+        let declared_type = descriptors
+            .iter()
+            .flat_map(|d| d.keys.iter())
+            .find(|k| k.name() == name)
+            .and_then(|k| descriptor_key_type(k.type_()));
+        if let Some(data_type) = declared_type {
+            if array.data_type() != &data_type {
+                array = match cast(&array, &data_type) {
+                    Ok(array) => array,
+                    Err(e) => return Err(DemoParserError::ArrowError(format!("{}", e))),
+                };
+            }
+        }
+        fields.push(Field::new(name, array.data_type().clone(), true));
+        columns.push(array);
+    }
+    Ok(())
+}
+
+pub fn variants_to_arrow(values: &[Option<&Variant>]) -> ArrayRef {
+    let first = match values.iter().flatten().next() {
+        Some(v) => *v,
+        None => return Arc::new(NullArray::new(values.len())),
+    };
+    macro_rules! primitive {
+        ($array:ty, $variant:ident) => {
+            Arc::new(<$array>::from_iter(values.iter().map(|v| match v {
+                Some(Variant::$variant(x)) => Some(x.to_owned()),
+                _ => None,
+            })))
+        };
+    }
+    match first {
+        Variant::Bool(_) => primitive!(BooleanArray, Bool),
+        Variant::U32(_) => primitive!(UInt32Array, U32),
+        Variant::I32(_) => primitive!(Int32Array, I32),
+        Variant::I16(_) => primitive!(Int16Array, I16),
+        Variant::F32(_) => primitive!(Float32Array, F32),
+        Variant::U64(_) => primitive!(UInt64Array, U64),
+        Variant::U8(_) => primitive!(UInt8Array, U8),
+        Variant::String(_) => primitive!(StringArray, String),
+        Variant::VecXY(_) | Variant::VecXYZ(_) => {
+            let mut builder = ListBuilder::new(Float32Builder::new());
+            for v in values {
+                match v {
+                    Some(Variant::VecXY(xy)) => {
+                        builder.values().append_slice(xy);
+                        builder.append(true);
+                    }
+                    Some(Variant::VecXYZ(xyz)) => {
+                        builder.values().append_slice(xyz);
+                        builder.append(true);
+                    }
+                    _ => builder.append(false),
+                }
+            }
+            Arc::new(builder.finish())
+        }
+        Variant::StringVec(_) => {
+            let mut builder = ListBuilder::new(StringBuilder::new());
+            for v in values {
+                match v {
+                    Some(Variant::StringVec(strings)) => {
+                        for s in strings {
+                            builder.values().append_value(s);
+                        }
+                        builder.append(true);
+                    }
+                    _ => builder.append(false),
+                }
+            }
+            Arc::new(builder.finish())
+        }
+        Variant::U64Vec(_) => {
+            let mut builder = ListBuilder::new(UInt64Builder::new());
+            for v in values {
+                match v {
+                    Some(Variant::U64Vec(ints)) => {
+                        builder.values().append_slice(ints);
+                        builder.append(true);
+                    }
+                    _ => builder.append(false),
+                }
+            }
+            Arc::new(builder.finish())
+        }
+    }
+}
+
+// Arrow IPC stream format, readable with tableFromIPC in apache-arrow (js) or pyarrow.ipc.open_stream
+pub fn record_batch_to_ipc(batch: &RecordBatch) -> Result<Vec<u8>, DemoParserError> {
+    let mut buf = vec![];
+    let mut writer = match StreamWriter::try_new(&mut buf, &batch.schema()) {
+        Ok(writer) => writer,
+        Err(e) => return Err(DemoParserError::ArrowError(format!("{}", e))),
+    };
+    if let Err(e) = writer.write(batch).and_then(|_| writer.finish()) {
+        return Err(DemoParserError::ArrowError(format!("{}", e)));
+    }
+    drop(writer);
+    Ok(buf)
+}
+
+fn to_record_batch(fields: Vec<Field>, columns: Vec<ArrayRef>) -> Result<RecordBatch, DemoParserError> {
+    match RecordBatch::try_new(Arc::new(Schema::new(fields)), columns) {
+        Ok(batch) => Ok(batch),
+        Err(e) => Err(DemoParserError::ArrowError(format!("{}", e))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game_events::test_event as event;
+    use crate::game_events::EventField;
+    use csgoproto::netmessages::csvcmsg_game_event_list::Key_t;
+
+    #[test]
+    fn test_events_schema() {
+        let events = vec![
+            event("player_death", 10, vec![("weapon", Variant::String("ak47".to_string()))]),
+            event("player_hurt", 20, vec![("dmg_health", Variant::I32(27))]),
+        ];
+        let batch = events_to_record_batch(&events, &AHashMap::default()).unwrap();
+        let names: Vec<String> = batch.schema().fields().iter().map(|f| f.name().clone()).collect();
+        assert_eq!(names, vec!["event_name", "tick", "weapon", "dmg_health"]);
+        assert_eq!(batch.num_rows(), 2);
+        let dmg = batch.column(3).as_any().downcast_ref::<Int32Array>().unwrap();
+        assert!(dmg.is_null(0));
+        assert_eq!(dmg.value(1), 27);
+    }
+    #[test]
+    fn test_event_columns_typed_from_descriptor() {
+        let mut descriptor = Descriptor_t::new();
+        descriptor.set_name("bomb_planted".to_string());
+        for (name, key_type) in [("userid", 4), ("site", 4), ("hasbomb", 6)] {
+            let mut key = Key_t::new();
+            key.set_name(name.to_string());
+            key.set_type(key_type);
+            descriptor.keys.push(key);
+        }
+        let event = GameEvent {
+            name: "bomb_planted".to_string(),
+            tick: 100,
+            fields: vec![
+                EventField {
+                    name: "tick".to_string(),
+                    data: Some(Variant::I32(100)),
+                },
+                EventField {
+                    name: "site".to_string(),
+                    data: Some(Variant::I32(2)),
+                },
+                EventField {
+                    name: "hasbomb".to_string(),
+                    data: None,
+                },
+                EventField {
+                    name: "user_name".to_string(),
+                    data: Some(Variant::String("player".to_string())),
+                },
+            ],
+        };
+        let batch = event_record_batch(&[&event], Some(&descriptor)).unwrap();
+        let schema = batch.schema();
+        let names: Vec<&String> = schema.fields().iter().map(|f| f.name()).collect();
+        assert_eq!(names, vec!["tick", "site", "hasbomb", "user_name"]);
+        // Always null but still typed by the descriptor
+        assert_eq!(schema.field(2).data_type(), &DataType::Boolean);
+        assert_eq!(schema.field(3).data_type(), &DataType::Utf8);
+    }
+    #[test]
+    fn test_ipc_roundtrip() {
+        let events = vec![event("player_hurt", 20, vec![("dmg_health", Variant::I32(27))])];
+        let batch = events_to_record_batch(&events, &AHashMap::default()).unwrap();
+        let bytes = record_batch_to_ipc(&batch).unwrap();
+        let mut reader = arrow::ipc::reader::StreamReader::try_new(bytes.as_slice(), None).unwrap();
+        assert_eq!(reader.next().unwrap().unwrap(), batch);
+        assert!(reader.next().is_none());
+    }
+    #[test]
+    fn test_list_column() {
+        let values = [Some(&Variant::StringVec(vec!["knife".to_string(), "ak47".to_string()])), None];
+        let array = variants_to_arrow(&values);
+        let list = array.as_any().downcast_ref::<ListArray>().unwrap();
+        assert_eq!(list.value_length(0), 2);
+        assert!(list.is_null(1));
+    }
+}
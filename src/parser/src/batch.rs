@@ -0,0 +1,242 @@
+use crate::collect_data::PropType;
+use crate::game_events::EventField;
+use crate::parser::DemoOutput;
+use crate::parser_settings::create_mmap;
+use crate::parser_settings::Parser;
+use crate::parser_settings::ParserInputs;
+use crate::prop_controller::PropInfo;
+use crate::prop_controller::DEMO_ID_ID;
+use crate::read_bits::DemoParserError;
+use crate::variants::BytesVariant;
+use crate::variants::PropColumn;
+use crate::variants::Variant;
+use rayon::iter::IntoParallelIterator;
+use rayon::iter::IntoParallelRefIterator;
+use rayon::iter::ParallelIterator;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::RwLock;
+
+// Parses many demos with one query. Instead of every demo running its own par_iter over its fullpackets,
+// all (demo, fullpacket chunk) pairs go into one rayon pool so that cores don't sit idle waiting for the
+// last chunks of a demo. Whichever worker finishes the last chunk of a demo merges that demo, so chunk
+// outputs don't pile up until the whole batch is done. A failing demo only fails its own result.
+
+#[derive(Debug)]
+pub struct BatchResult {
+    // Index of the demo in the paths passed to parse_many. Also written into every row as "demo_id".
+    pub demo_id: u32,
+    pub path: String,
+    pub output: Result<DemoOutput, DemoParserError>,
+}
+
+struct PendingDemo {
+    // Read by the chunks, written once by the merge
+    parser: RwLock<Parser>,
+    progress: Mutex<Progress>,
+}
+
+struct Progress {
+    chunks_left: usize,
+    outputs: Vec<DemoOutput>,
+    // Set by the merge or by the first failing chunk
+    result: Option<Result<DemoOutput, DemoParserError>>,
+}
+
+impl PendingDemo {
+    fn new(parser: Parser) -> Self {
+        let chunks_left = parser.chunks().len();
+        PendingDemo {
+            parser: RwLock::new(parser),
+            progress: Mutex::new(Progress {
+                chunks_left,
+                outputs: vec![],
+                result: None,
+            }),
+        }
+    }
+    fn parse_chunk(&self, demo_id: u32, offset: usize, parse_all: bool) {
+        let failed = matches!(self.progress.lock().unwrap().result, Some(Err(_)));
+        // Rest of a failed demo is skipped
+        let output = match failed {
+            true => None,
+            false => Some(self.parser.read().unwrap().parse_chunk(offset, parse_all)),
+        };
+        let mut progress = self.progress.lock().unwrap();
+        progress.chunks_left -= 1;
+        match output {
+            Some(Ok(output)) if progress.result.is_none() => progress.outputs.push(output),
+            Some(Err(e)) if progress.result.is_none() => {
+                progress.outputs.clear();
+                progress.result = Some(Err(e));
+            }
+            _ => {}
+        }
+        self.merge_if_done(&mut progress, demo_id);
+    }
+    fn merge_if_done(&self, progress: &mut Progress, demo_id: u32) {
+        if progress.chunks_left > 0 || progress.result.is_some() {
+            return;
+        }
+        let mut outputs = std::mem::take(&mut progress.outputs);
+        let mut output = self.parser.write().unwrap().finish_outputs(&mut outputs);
+        tag_with_demo_id(&mut output, demo_id);
+        progress.result = Some(Ok(output));
+    }
+    fn into_result(self) -> Result<DemoOutput, DemoParserError> {
+        // Every chunk has run by now, so the demo was either merged or failed
+        self.progress.into_inner().unwrap().result.expect("demo was not merged")
+    }
+}
+
+// Bytes of query are ignored, every demo gets its own.
+pub fn parse_many(paths: &[String], query: &ParserInputs) -> Vec<BatchResult> {
+    // First passes
+    let demos: Vec<Result<PendingDemo, DemoParserError>> = paths
+        .par_iter()
+        .map(|path| {
+            let mmap = create_mmap(path.clone())?;
+            let mut settings = query.clone();
+            settings.bytes = Arc::new(BytesVariant::Mmap(mmap));
+            let mut parser = Parser::new(settings);
+            parser.first_pass()?;
+            parser.resolve_tick_selectors()?;
+            parser.prepare_chunks()?;
+            Ok(PendingDemo::new(parser))
+        })
+        .collect();
+
+    let mut units = vec![];
+    for (demo_idx, demo) in demos.iter().enumerate() {
+        if let Ok(demo) = demo {
+            let chunks = demo.parser.read().unwrap().chunks();
+            if chunks.is_empty() {
+                demo.merge_if_done(&mut demo.progress.lock().unwrap(), demo_idx as u32);
+            }
+            for (offset, parse_all) in chunks {
+                units.push((demo_idx as u32, demo, offset, parse_all));
+            }
+        }
+    }
+    units
+        .into_par_iter()
+        .for_each(|(demo_id, demo, offset, parse_all)| demo.parse_chunk(demo_id, offset, parse_all));
+
+    demos
+        .into_iter()
+        .enumerate()
+        .map(|(demo_idx, demo)| BatchResult {
+            demo_id: demo_idx as u32,
+            path: paths[demo_idx].clone(),
+            output: demo.and_then(|demo| demo.into_result()),
+        })
+        .collect()
+}
+
+pub fn tag_with_demo_id(output: &mut DemoOutput, demo_id: u32) {
+    let n_rows = output.df.values().map(|col| col.len()).max().unwrap_or(0);
+    if n_rows > 0 {
+        let mut col = PropColumn::new();
+        for _ in 0..n_rows {
+            col.push(Some(Variant::U32(demo_id)));
+        }
+        output.df.insert(DEMO_ID_ID, col);
+        output.prop_info.prop_infos.push(PropInfo {
+            id: DEMO_ID_ID,
+            prop_type: PropType::Custom,
+            prop_name: "demo_id".to_string(),
+            prop_friendly_name: "demo_id".to_string(),
+            is_player_prop: true,
+        });
+    }
+    for event in &mut output.game_events {
+        event.fields.push(EventField {
+            name: "demo_id".to_string(),
+            data: Some(Variant::U32(demo_id)),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::query::DemoQuery;
+    use crate::test_demo::TestDemo;
+    use crate::test_demo::DEM_PACKET;
+
+    fn demo(health: i32, broken: bool) -> Vec<u8> {
+        let mut demo = TestDemo::new();
+        demo.add_player(1, 76561198000000001, "alice", 2);
+        demo.add_teams_and_rules();
+        demo.packet(1);
+        demo.full_packet(2);
+        demo.event("round_start", &[]);
+        demo.set(101, "m_iHealth", health);
+        demo.packet(3);
+        if broken {
+            demo.frame(DEM_PACKET, 4, b"not a packet");
+        }
+        demo.packet(5);
+        demo.finish(5)
+    }
+    fn write_demos(name: &str, demos: Vec<Vec<u8>>) -> Vec<String> {
+        demos
+            .into_iter()
+            .enumerate()
+            .map(|(idx, bytes)| {
+                let path = std::env::temp_dir().join(format!("batch_{}_{}_{}.dem", name, std::process::id(), idx));
+                std::fs::write(&path, bytes).unwrap();
+                path.to_string_lossy().to_string()
+            })
+            .collect()
+    }
+    fn query(query: DemoQuery) -> ParserInputs {
+        query.build(BytesVariant::Vec(vec![])).unwrap()
+    }
+    fn ticks_query() -> ParserInputs {
+        query(DemoQuery::new().player_props(["health"]))
+    }
+    fn demo_ids(output: &DemoOutput) -> Vec<Option<Variant>> {
+        let col = &output.df[&DEMO_ID_ID];
+        (0..col.len()).map(|idx| col.get(idx)).collect()
+    }
+
+    #[test]
+    fn test_rows_and_events_tagged_with_demo_id() {
+        let paths = write_demos("tagged", vec![demo(90, false), demo(80, false)]);
+        let tick_results = parse_many(&paths, &ticks_query());
+        let event_results = parse_many(&paths, &query(DemoQuery::new().events(["round_start"])));
+        for path in &paths {
+            std::fs::remove_file(path).unwrap();
+        }
+
+        assert_eq!(tick_results.len(), 2);
+        for (idx, (ticks, events)) in tick_results.iter().zip(&event_results).enumerate() {
+            assert_eq!(ticks.demo_id, idx as u32);
+            assert_eq!(ticks.path, paths[idx]);
+            let ids = demo_ids(ticks.output.as_ref().unwrap());
+            assert!(!ids.is_empty());
+            assert!(ids.iter().all(|id| *id == Some(Variant::U32(idx as u32))));
+
+            let events = &events.output.as_ref().unwrap().game_events;
+            assert_eq!(events.len(), 1);
+            assert_eq!(events[0].field("demo_id"), Some(&Variant::U32(idx as u32)));
+        }
+    }
+    #[test]
+    fn test_failing_demo_does_not_fail_the_rest() {
+        let mut paths = write_demos("failing", vec![demo(90, false), demo(80, true), demo(70, false)]);
+        paths.push("does/not/exist.dem".to_string());
+        let results = parse_many(&paths, &ticks_query());
+        for path in &paths[..3] {
+            std::fs::remove_file(path).unwrap();
+        }
+
+        assert!(results[0].output.is_ok());
+        assert!(results[1].output.is_err());
+        assert!(results[2].output.is_ok());
+        assert!(matches!(results[3].output, Err(DemoParserError::FileNotFound(_))));
+        let ids = demo_ids(results[2].output.as_ref().unwrap());
+        assert!(!ids.is_empty() && ids.iter().all(|id| *id == Some(Variant::U32(2))));
+    }
+}
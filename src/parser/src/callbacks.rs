@@ -0,0 +1,358 @@
+use crate::entities::Entity;
+use crate::game_events::GameEvent;
+use crate::netmessage_types::NetmessageType;
+use crate::parser_thread_settings::ParserThread;
+use crate::prop_controller::PropInfo;
+use crate::variants::Variant;
+use ahash::AHashMap;
+use std::ops::ControlFlow;
+
+// Push-style alternative to collecting everything into a DemoOutput.
+// Handlers are called from ParserThread::start as packets are decoded.
+// Returning ControlFlow::Break(()) from any handler stops the parse after the current frame.
+
+pub type EventCallback = Box<dyn FnMut(&GameEvent) -> ControlFlow<()>>;
+pub type EntityCallback = Box<dyn FnMut(&ParserThread, &Entity) -> ControlFlow<()>>;
+pub type TickCallback = Box<dyn FnMut(&ParserThread) -> ControlFlow<()>>;
+pub type UserMessageCallback = Box<dyn FnMut(i32, &[u8]) -> ControlFlow<()>>;
+pub type PlayerTickCallback = Box<dyn FnMut(&[PropInfo], &[Option<Variant>]) -> ControlFlow<()>>;
+
+#[derive(Default)]
+pub struct ParserCallbacks {
+    pub events: AHashMap<String, Vec<EventCallback>>,
+    pub entities: AHashMap<String, Vec<EntityCallback>>,
+    pub ticks: Vec<TickCallback>,
+    pub user_messages: AHashMap<String, Vec<UserMessageCallback>>,
+    pub player_ticks: Vec<PlayerTickCallback>,
+}
+
+impl ParserCallbacks {
+    pub fn new() -> Self {
+        ParserCallbacks::default()
+    }
+    /// Called for every game event with this name. "all" matches every event.
+    pub fn on_event<F>(&mut self, event_name: &str, f: F) -> &mut Self
+    where
+        F: FnMut(&GameEvent) -> ControlFlow<()> + 'static,
+    {
+        self.events.entry(event_name.to_string()).or_default().push(Box::new(f));
+        self
+    }
+    /// Called every time an entity of this class (for example "CChicken") is created or updated.
+    pub fn on_entity<F>(&mut self, class_name: &str, f: F) -> &mut Self
+    where
+        F: FnMut(&ParserThread, &Entity) -> ControlFlow<()> + 'static,
+    {
+        self.entities.entry(class_name.to_string()).or_default().push(Box::new(f));
+        self
+    }
+    /// Called once per tick with the world state after the last frame of that tick.
+    pub fn on_tick<F>(&mut self, f: F) -> &mut Self
+    where
+        F: FnMut(&ParserThread) -> ControlFlow<()> + 'static,
+    {
+        self.ticks.push(Box::new(f));
+        self
+    }
+    /// Called with the raw protobuf bytes of every message of this type, for example "UM_SayText2".
+    pub fn on_user_message<F>(&mut self, msg_name: &str, f: F) -> &mut Self
+    where
+        F: FnMut(i32, &[u8]) -> ControlFlow<()> + 'static,
+    {
+        self.user_messages.entry(msg_name.to_string()).or_default().push(Box::new(f));
+        self
+    }
+    /// Called with one row per player for every tick (or every wanted tick), values in the order of the prop infos.
+    /// The rows are not collected into DemoOutput.df when this is set.
+    pub fn on_player_tick<F>(&mut self, f: F) -> &mut Self
+    where
+        F: FnMut(&[PropInfo], &[Option<Variant>]) -> ControlFlow<()> + 'static,
+    {
+        self.player_ticks.push(Box::new(f));
+        self
+    }
+    pub fn wanted_events(&self) -> Vec<String> {
+        self.events.keys().cloned().collect()
+    }
+}
+
+impl ParserThread {
+    // Events go to the handlers instead of being stored when callbacks are set.
+    pub fn emit_event(&mut self, event: GameEvent) {
+        let mut callbacks = match self.callbacks.take() {
+            Some(cbs) => cbs,
+            None => {
+                self.game_events.push(event);
+                return;
+            }
+        };
+        for name in [event.name.as_str(), "all"] {
+            if let Some(handlers) = callbacks.events.get_mut(name) {
+                for handler in handlers {
+                    if handler(&event).is_break() {
+                        self.stop_requested = true;
+                    }
+                }
+            }
+        }
+        self.callbacks = Some(callbacks);
+    }
+    pub fn emit_entity(&mut self, entity_id: i32) {
+        let mut callbacks = match self.callbacks.take() {
+            Some(cbs) => cbs,
+            None => return,
+        };
+        if let Some(entity) = self.entities.get(&entity_id) {
+            if let Some(cls) = self.cls_by_id.get(&entity.cls_id) {
+                if let Some(handlers) = callbacks.entities.get_mut(&cls.name) {
+                    for handler in handlers {
+                        if handler(self, entity).is_break() {
+                            self.stop_requested = true;
+                        }
+                    }
+                }
+            }
+        }
+        self.callbacks = Some(callbacks);
+    }
+    pub fn emit_tick(&mut self) {
+        if self.tick == self.last_callback_tick {
+            return;
+        }
+        let mut callbacks = match self.callbacks.take() {
+            Some(cbs) => cbs,
+            None => return,
+        };
+        self.last_callback_tick = self.tick;
+        for handler in &mut callbacks.ticks {
+            if handler(self).is_break() {
+                self.stop_requested = true;
+            }
+        }
+        self.callbacks = Some(callbacks);
+        self.emit_player_ticks();
+    }
+    pub fn emit_user_message(&mut self, msg_name: &str, bytes: &[u8]) {
+        let mut callbacks = match self.callbacks.take() {
+            Some(cbs) => cbs,
+            None => return,
+        };
+        if let Some(handlers) = callbacks.user_messages.get_mut(msg_name) {
+            for handler in handlers {
+                if handler(self.tick, bytes).is_break() {
+                    self.stop_requested = true;
+                }
+            }
+        }
+        self.callbacks = Some(callbacks);
+    }
+    pub fn emit_player_ticks(&mut self) {
+        if !self.is_wanted_tick() {
+            return;
+        }
+        let mut callbacks = match self.callbacks.take() {
+            Some(cbs) => cbs,
+            None => return,
+        };
+        let mut row = Vec::with_capacity(self.prop_controller.prop_infos.len());
+        for (entity_id, player) in &self.players {
+            row.clear();
+            for prop_info in &self.prop_controller.prop_infos {
+                row.push(self.find_prop(prop_info, entity_id, player).ok());
+            }
+            for handler in &mut callbacks.player_ticks {
+                if handler(&self.prop_controller.prop_infos, &row).is_break() {
+                    self.stop_requested = true;
+                }
+            }
+        }
+        self.callbacks = Some(callbacks);
+    }
+    pub fn has_entity_callbacks(&self) -> bool {
+        match &self.callbacks {
+            Some(cbs) => !cbs.entities.is_empty(),
+            None => false,
+        }
+    }
+    // The callbacks are keyed by message name, the name is only formatted once per message id.
+    pub fn wants_user_message(&mut self, msg_id: i32, msg_type: &NetmessageType) -> bool {
+        let cbs = match &self.callbacks {
+            Some(cbs) if !cbs.user_messages.is_empty() => cbs,
+            _ => return false,
+        };
+        *self
+            .user_message_wanted
+            .entry(msg_id)
+            .or_insert_with(|| cbs.user_messages.contains_key(&format!("{:?}", msg_type)))
+    }
+    pub fn has_player_tick_callbacks(&self) -> bool {
+        match &self.callbacks {
+            Some(cbs) => !cbs.player_ticks.is_empty(),
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::DemoOutput;
+    use crate::query::DemoQuery;
+    use crate::test_demo::TestDemo;
+    use crate::test_demo::DEM_ANIMATION_DATA;
+    use crate::variants::BytesVariant;
+    use csgoproto::usermessages::CUserMessageSayText2;
+    use protobuf::Message;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    // Team 2 scoring every tick. Tick 3 is split over two packets with an animation frame in between.
+    fn demo() -> Vec<u8> {
+        let mut demo = TestDemo::new();
+        demo.add_player(1, 76561198000000001, "alice", 2);
+        demo.add_teams_and_rules();
+        demo.set(52, "m_iScore", 1);
+        demo.packet(1);
+        // Only read for the userinfo table, the single threaded parse starts from the packets
+        demo.full_packet(1);
+        demo.event("round_start", &[]);
+        demo.chat(2, "gl hf");
+        demo.set(52, "m_iScore", 2);
+        demo.set(101, "m_iHealth", 80);
+        demo.packet(2);
+        demo.event("player_hurt", &[("userid", 1.into()), ("dmg_health", 20.into())]);
+        demo.set(52, "m_iScore", 3);
+        demo.packet(3);
+        demo.frame(DEM_ANIMATION_DATA, 3, &[]);
+        demo.set(52, "m_iScore", 4);
+        demo.set(101, "m_iHealth", 60);
+        demo.packet(3);
+        demo.finish(3)
+    }
+    fn parse(query: DemoQuery, callbacks: ParserCallbacks) -> DemoOutput {
+        let query = query.entity_props(["CCSTeam.m_iScore"]);
+        let mut parser = query.parser(BytesVariant::Vec(demo())).unwrap();
+        parser.parse_demo_with_callbacks(callbacks).unwrap()
+    }
+    fn score(parser: &ParserThread) -> Option<Variant> {
+        let prop_id = parser.prop_controller.entity_prop_ids.get("CCSTeam.m_iScore")?;
+        parser.entities.get(&52)?.props.get(prop_id).cloned()
+    }
+
+    #[test]
+    fn test_on_event_by_name_and_all() {
+        let hurts = Rc::new(RefCell::new(vec![]));
+        let all = Rc::new(RefCell::new(vec![]));
+        let (h, a) = (hurts.clone(), all.clone());
+        let mut callbacks = ParserCallbacks::new();
+        callbacks
+            .on_event("player_hurt", move |event| {
+                h.borrow_mut().push((event.tick, event.player_name("user")));
+                ControlFlow::Continue(())
+            })
+            .on_event("all", move |event| {
+                a.borrow_mut().push(event.name.clone());
+                ControlFlow::Continue(())
+            });
+        let output = parse(DemoQuery::new(), callbacks);
+
+        assert_eq!(*hurts.borrow(), vec![(3, Some("alice".to_string()))]);
+        assert_eq!(*all.borrow(), vec!["round_start".to_string(), "player_hurt".to_string()]);
+        assert!(output.game_events.is_empty());
+    }
+    #[test]
+    fn test_on_event_break_stops_parse() {
+        let ticks = Rc::new(RefCell::new(vec![]));
+        let t = ticks.clone();
+        let mut callbacks = ParserCallbacks::new();
+        callbacks
+            .on_event("round_start", |_| ControlFlow::Break(()))
+            .on_tick(move |parser| {
+                t.borrow_mut().push(parser.tick);
+                ControlFlow::Continue(())
+            });
+        parse(DemoQuery::new(), callbacks);
+
+        assert_eq!(ticks.borrow().last(), Some(&2));
+    }
+    #[test]
+    fn test_on_entity_sees_every_update() {
+        let scores = Rc::new(RefCell::new(vec![]));
+        let h = scores.clone();
+        let mut callbacks = ParserCallbacks::new();
+        callbacks.on_entity("CCSTeam", move |parser, entity| {
+            if entity.entity_id == 52 {
+                h.borrow_mut().push(score(parser));
+            }
+            ControlFlow::Continue(())
+        });
+        parse(DemoQuery::new(), callbacks);
+
+        let expected: Vec<_> = [1, 2, 3, 4].into_iter().map(|x| Some(Variant::I32(x))).collect();
+        assert_eq!(*scores.borrow(), expected);
+    }
+    #[test]
+    fn test_on_tick_fires_once_per_tick_after_last_frame() {
+        let seen = Rc::new(RefCell::new(vec![]));
+        let s = seen.clone();
+        let mut callbacks = ParserCallbacks::new();
+        callbacks.on_tick(move |parser| {
+            s.borrow_mut().push((parser.tick, score(parser)));
+            ControlFlow::Continue(())
+        });
+        parse(DemoQuery::new(), callbacks);
+
+        let seen = seen.borrow();
+        assert!(seen.windows(2).all(|w| w[0].0 < w[1].0));
+        let played: Vec<_> = seen.iter().filter(|(tick, _)| *tick > 0).cloned().collect();
+        assert_eq!(
+            played,
+            vec![
+                (1, Some(Variant::I32(1))),
+                (2, Some(Variant::I32(2))),
+                (3, Some(Variant::I32(4)))
+            ]
+        );
+    }
+    #[test]
+    fn test_on_user_message_only_named_type() {
+        let chat = Rc::new(RefCell::new(vec![]));
+        let c = chat.clone();
+        let mut callbacks = ParserCallbacks::new();
+        callbacks
+            .on_user_message("UM_SayText2", move |tick, bytes| {
+                let msg = CUserMessageSayText2::parse_from_bytes(bytes).unwrap();
+                c.borrow_mut().push((tick, msg.param2().to_string()));
+                ControlFlow::Continue(())
+            })
+            .on_user_message("CS_UM_RadioText", |_, _| panic!("no radio messages in the demo"));
+        parse(DemoQuery::new(), callbacks);
+
+        assert_eq!(*chat.borrow(), vec![(2, "gl hf".to_string())]);
+    }
+    #[test]
+    fn test_on_player_tick_one_row_per_player_and_tick() {
+        let rows = Rc::new(RefCell::new(vec![]));
+        let r = rows.clone();
+        let mut callbacks = ParserCallbacks::new();
+        callbacks.on_player_tick(move |prop_infos, row| {
+            let value = |name: &str| {
+                let idx = prop_infos.iter().position(|p| p.prop_friendly_name == name).unwrap();
+                row[idx].clone()
+            };
+            r.borrow_mut().push((value("tick"), value("health")));
+            ControlFlow::Continue(())
+        });
+        let output = parse(DemoQuery::new().player_props(["health"]), callbacks);
+
+        assert_eq!(
+            *rows.borrow(),
+            vec![
+                (Some(Variant::I32(1)), Some(Variant::I32(100))),
+                (Some(Variant::I32(2)), Some(Variant::I32(80))),
+                (Some(Variant::I32(3)), Some(Variant::I32(60))),
+            ]
+        );
+        assert!(output.df.is_empty());
+    }
+}
@@ -0,0 +1,196 @@
+use crate::maps::NON_MULTITHREADABLE_PROPS;
+use crate::parser_settings::Parser;
+use crate::parser_thread_settings::ParserThread;
+use crate::parser_threads::FrameResult;
+use crate::read_bits::DemoParserError;
+use crate::variants::Variant;
+use ahash::AHashMap;
+use ahash::AHashSet;
+use rayon::iter::IntoParallelRefIterator;
+use rayon::iter::ParallelIterator;
+use std::sync::Arc;
+
+// Props in NON_MULTITHREADABLE_PROPS are not (correctly) included in fullpackets, so a thread that starts
+// from a fullpacket does not know their values until they change. By default demos with these props are
+// parsed in one thread from the start. With DemoQuery::seeded_chunks they are parsed in chunks instead:
+//   1. Every chunk is parsed once without collecting anything, recording the last value written to
+//      these props per entity (and which entities got created/deleted).
+//   2. The chunk states are folded in order, giving the values at the start of every chunk.
+//   3. Chunks are parsed normally, with the values from step 2 put back into the entities right after
+//      the fullpacket has been read.
+// The output is the same as the single threaded parse. It stays opt-in because every chunk is decoded twice,
+// which is slower than one thread on machines with only a couple of cores.
+
+pub type EntitySeed = AHashMap<i32, AHashMap<u32, Variant>>;
+
+#[derive(Debug, Clone, Default)]
+pub struct ChunkState {
+    // Entities that were created or deleted during the chunk. Older values of these are stale.
+    pub reset: AHashSet<i32>,
+    pub written: EntitySeed,
+}
+
+impl ChunkState {
+    pub fn reset_entity(&mut self, entity_id: i32) {
+        self.written.remove(&entity_id);
+        self.reset.insert(entity_id);
+    }
+    pub fn write(&mut self, entity_id: i32, prop_id: u32, value: Variant) {
+        self.written.entry(entity_id).or_default().insert(prop_id, value);
+    }
+    // Values at the end of the chunk, given the values at its start
+    pub fn apply_to(&self, seed: &EntitySeed) -> EntitySeed {
+        let mut out = seed.clone();
+        for entity_id in &self.reset {
+            out.remove(entity_id);
+        }
+        for (entity_id, props) in &self.written {
+            let entity_props = out.entry(*entity_id).or_default();
+            for (prop_id, value) in props {
+                entity_props.insert(*prop_id, value.clone());
+            }
+        }
+        out
+    }
+}
+
+impl ParserThread {
+    pub fn apply_seed(&mut self) {
+        let seed = match self.seed.take() {
+            Some(seed) => seed,
+            None => return,
+        };
+        for (entity_id, props) in seed.iter() {
+            if let Some(entity) = self.entities.get_mut(entity_id) {
+                for (prop_id, value) in props {
+                    entity.props.insert(*prop_id, value.clone());
+                }
+            }
+        }
+    }
+}
+
+impl Parser {
+    pub fn non_multithreadable_prop_ids(&self) -> AHashSet<u32> {
+        self.prop_controller
+            .prop_infos
+            .iter()
+            .filter(|info| NON_MULTITHREADABLE_PROPS.contains(&info.prop_name))
+            .map(|info| info.id)
+            .collect()
+    }
+    // Step 1 and 2 from above. Needs the first pass.
+    pub fn prepare_chunks(&mut self) -> Result<(), DemoParserError> {
+        if self.is_multithreadable || !self.settings.seeded_chunks {
+            return Ok(());
+        }
+        let tracked = self.non_multithreadable_prop_ids();
+        let mut offsets = self.fullpacket_offsets.clone();
+        offsets.sort();
+        let states: Vec<Result<ChunkState, DemoParserError>> =
+            offsets.par_iter().map(|offset| self.chunk_state(*offset, &tracked)).collect();
+
+        let mut seed = EntitySeed::default();
+        for (offset, state) in offsets.iter().zip(states) {
+            self.chunk_seeds.insert(*offset, Arc::new(seed.clone()));
+            seed = state?.apply_to(&seed);
+        }
+        Ok(())
+    }
+    fn chunk_state(&self, offset: usize, tracked: &AHashSet<u32>) -> Result<ChunkState, DemoParserError> {
+        let input = self.create_parser_thread_input(offset, false);
+        let mut parser = ParserThread::new(input)?;
+        parser.tracked_props = tracked.clone();
+        while parser.parse_frame()? != FrameResult::Stop {}
+        Ok(parser.chunk_state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::query::DemoQuery;
+    use crate::test_demo::TestDemo;
+    use crate::variants::BytesVariant;
+
+    const CASH_EARNED: &str = "m_pActionTrackingServices.m_perRoundStats.m_iCashEarned";
+
+    // Cash earned only changes before the first and second full packet, which don't have it
+    fn demo() -> Vec<u8> {
+        let mut demo = TestDemo::new();
+        demo.leave_out_of_full_packets(CASH_EARNED);
+        demo.add_player(1, 76561198000000001, "alice", 2);
+        demo.add_player(2, 76561198000000002, "bob", 3);
+        demo.add_teams_and_rules();
+        demo.packet(1);
+        demo.set(2, CASH_EARNED, 300);
+        demo.packet(5);
+        demo.full_packet(10);
+        demo.set(101, "m_iHealth", 90);
+        demo.packet(12);
+        demo.full_packet(20);
+        demo.set(3, CASH_EARNED, 600);
+        demo.set(2, CASH_EARNED, 0);
+        demo.packet(22);
+        demo.full_packet(30);
+        demo.set(102, "m_iHealth", 50);
+        demo.packet(32);
+        demo.finish(32)
+    }
+    // Every row with the columns in prop id order, sorted since chunks may come back in any order
+    fn rows(query: DemoQuery) -> Vec<Vec<Option<Variant>>> {
+        let mut parser = query.parser(BytesVariant::Vec(demo())).unwrap();
+        let output = parser.parse_demo().unwrap();
+        let mut ids: Vec<u32> = output.prop_info.prop_infos.iter().map(|info| info.id).collect();
+        ids.sort();
+        let n_rows = output.df.values().map(|col| col.len()).max().unwrap_or(0);
+        let mut rows: Vec<Vec<Option<Variant>>> = (0..n_rows)
+            .map(|row| ids.iter().map(|id| output.df[id].get(row)).collect())
+            .collect();
+        rows.sort_by_key(|row| format!("{:?}", row));
+        rows
+    }
+
+    #[test]
+    fn test_seeded_chunks_match_single_thread() {
+        let query = || DemoQuery::new().player_props(["cash_earned_this_round", "health"]);
+        let single_thread = rows(query());
+        let seeded = rows(query().seeded_chunks());
+        assert_eq!(seeded, single_thread);
+
+        let cash_earned = Some(Variant::I32(300));
+        assert!(seeded.iter().filter(|row| row.contains(&cash_earned)).count() > 3);
+
+        let mut parser = query().seeded_chunks().parser(BytesVariant::Vec(demo())).unwrap();
+        parser.first_pass().unwrap();
+        assert_eq!(parser.chunks().len(), 4);
+    }
+
+    #[test]
+    fn test_apply_carries_unchanged_values() {
+        let mut seed = EntitySeed::default();
+        seed.entry(1).or_default().insert(1000, Variant::I32(5));
+        seed.entry(2).or_default().insert(1000, Variant::I32(7));
+
+        let mut state = ChunkState::default();
+        state.write(2, 1000, Variant::I32(8));
+        let out = state.apply_to(&seed);
+        assert_eq!(out[&1][&1000], Variant::I32(5));
+        assert_eq!(out[&2][&1000], Variant::I32(8));
+    }
+    #[test]
+    fn test_apply_reset_entity() {
+        let mut seed = EntitySeed::default();
+        seed.entry(1).or_default().insert(1000, Variant::I32(5));
+        seed.entry(1).or_default().insert(1001, Variant::I32(6));
+
+        let mut state = ChunkState::default();
+        state.write(1, 1000, Variant::I32(9));
+        // Recreated after the write, only values written after this count
+        state.reset_entity(1);
+        state.write(1, 1001, Variant::I32(1));
+        let out = state.apply_to(&seed);
+        assert_eq!(out[&1].get(&1000), None);
+        assert_eq!(out[&1][&1001], Variant::I32(1));
+    }
+}
@@ -0,0 +1,204 @@
+use crate::decoder::QfMapper;
+use crate::other_netmessages::Class;
+use crate::parser_settings::needs_velocity;
+use crate::parser_settings::Parser;
+use crate::prop_controller::PropController;
+use crate::read_bits::DemoParserError;
+use crate::sendtables::SendTableLayout;
+use ahash::AHashMap;
+use lazy_static::lazy_static;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::VecDeque;
+use std::hash::Hash;
+use std::hash::Hasher;
+use std::sync::Arc;
+use std::sync::Mutex;
+
+// Process-wide cache of the classes per game build and wanted props. Demos from the same build have identical
+// sendtables, so when parsing many demos with the same query the serializers, class map and decoders are only
+// built once and every parser gets a clone of the Arcs. A query with other props reuses the sendtable layout
+// of the build and only rebuilds the serializers.
+// Only the most recently used entries are kept.
+
+const MAX_CACHED_BUILDS: usize = 8;
+
+#[derive(Debug, Clone)]
+pub struct CachedClasses {
+    pub classes: Vec<(i32, String)>,
+    pub sendtable_layout: Arc<SendTableLayout>,
+    pub cls_by_id: Arc<AHashMap<u32, Class>>,
+    pub qf_mapper: Arc<QfMapper>,
+    pub prop_controller: PropController,
+}
+
+// (sendtables and class info, wanted props)
+type CacheKey = (u64, u64);
+
+lazy_static! {
+    // Most recently used at the back
+    static ref CLASS_CACHE: Mutex<VecDeque<(CacheKey, CachedClasses)>> = Mutex::new(VecDeque::new());
+}
+
+pub fn clear_class_cache() {
+    if let Ok(mut cache) = CLASS_CACHE.lock() {
+        cache.clear();
+    }
+}
+pub fn class_cache_len() -> usize {
+    match CLASS_CACHE.lock() {
+        Ok(cache) => cache.len(),
+        Err(_) => 0,
+    }
+}
+
+impl Parser {
+    pub fn class_cache_key(&self, sendtables: &[u8], class_info: &[u8]) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        sendtables.hash(&mut hasher);
+        class_info.hash(&mut hasher);
+        hasher.finish()
+    }
+    // Everything build_serializers reads from the parser
+    fn wanted_props_key(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.wanted_player_props.hash(&mut hasher);
+        self.wanted_other_props.hash(&mut hasher);
+        self.wanted_entity_props.hash(&mut hasher);
+        let mut renames: Vec<_> = self.real_name_to_og_name.iter().collect();
+        renames.sort();
+        renames.hash(&mut hasher);
+        (!self.wanted_events.is_empty() && needs_velocity(&self.wanted_player_props)).hash(&mut hasher);
+        hasher.finish()
+    }
+    pub fn load_cached_classes(&mut self, build_key: u64) -> Result<bool, DemoParserError> {
+        let key = (build_key, self.wanted_props_key());
+        let (cached, exact) = match CLASS_CACHE.lock() {
+            Ok(mut cache) => match take_entry(&mut cache, key) {
+                Some(cached) => {
+                    cache.push_back((key, cached.clone()));
+                    (cached, true)
+                }
+                None => match cache.iter().rev().find(|((build, _), _)| *build == build_key) {
+                    Some((_, cached)) => (cached.clone(), false),
+                    None => return Ok(false),
+                },
+            },
+            Err(_) => return Ok(false),
+        };
+        if exact {
+            self.cls_by_id = Some(cached.cls_by_id);
+            self.qf_mapper = cached.qf_mapper;
+            self.prop_controller = cached.prop_controller;
+            self.sendtable_layout = Some(cached.sendtable_layout);
+            return Ok(true);
+        }
+        // Same build, other props
+        let (serializers, prop_controller) = self.build_serializers(&cached.sendtable_layout)?;
+        self.set_classes(
+            cached.classes.clone(),
+            serializers,
+            cached.sendtable_layout.qf_mapper.clone(),
+            prop_controller,
+        )?;
+        self.sendtable_layout = Some(cached.sendtable_layout);
+        self.cache_classes(build_key, cached.classes);
+        Ok(true)
+    }
+    pub fn cache_classes(&self, build_key: u64, classes: Vec<(i32, String)>) {
+        let (sendtable_layout, cls_by_id) = match (&self.sendtable_layout, &self.cls_by_id) {
+            (Some(layout), Some(cls_by_id)) => (layout.clone(), cls_by_id.clone()),
+            _ => return,
+        };
+        if let Ok(mut cache) = CLASS_CACHE.lock() {
+            insert_entry(
+                &mut cache,
+                (build_key, self.wanted_props_key()),
+                CachedClasses {
+                    classes,
+                    sendtable_layout,
+                    cls_by_id,
+                    qf_mapper: self.qf_mapper.clone(),
+                    prop_controller: self.prop_controller.clone(),
+                },
+            );
+        }
+    }
+}
+
+fn take_entry<K: PartialEq + Copy, T>(cache: &mut VecDeque<(K, T)>, key: K) -> Option<T> {
+    let idx = cache.iter().position(|(k, _)| *k == key)?;
+    cache.remove(idx).map(|(_, v)| v)
+}
+
+fn insert_entry<K: PartialEq + Copy, T>(cache: &mut VecDeque<(K, T)>, key: K, value: T) {
+    take_entry(cache, key);
+    cache.push_back((key, value));
+    while cache.len() > MAX_CACHED_BUILDS {
+        cache.pop_front();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::query::DemoQuery;
+    use crate::test_demo::TestDemo;
+    use crate::variants::BytesVariant;
+
+    fn first_pass(query: DemoQuery) -> Parser {
+        let mut parser = query.parser(BytesVariant::Vec(TestDemo::new().finish(1))).unwrap();
+        parser.first_pass().unwrap();
+        parser
+    }
+
+    #[test]
+    fn test_cache_hit_shares_built_classes() {
+        // Props no other test asks for, so the entries are this test's own
+        let query = || {
+            DemoQuery::new()
+                .player_props(["armor_value"])
+                .entity_props(["CChicken.m_iHealth"])
+        };
+        let first = first_pass(query());
+        let second = first_pass(query());
+        assert!(Arc::ptr_eq(
+            first.cls_by_id.as_ref().unwrap(),
+            second.cls_by_id.as_ref().unwrap()
+        ));
+        assert!(Arc::ptr_eq(&first.qf_mapper, &second.qf_mapper));
+        assert_eq!(
+            first.prop_controller.prop_infos.len(),
+            second.prop_controller.prop_infos.len()
+        );
+
+        // Same build with other props only shares the layout
+        let other = first_pass(DemoQuery::new().player_props(["armor_value"]));
+        assert!(!Arc::ptr_eq(
+            first.cls_by_id.as_ref().unwrap(),
+            other.cls_by_id.as_ref().unwrap()
+        ));
+        assert!(Arc::ptr_eq(
+            first.sendtable_layout.as_ref().unwrap(),
+            other.sendtable_layout.as_ref().unwrap()
+        ));
+        assert!(other.prop_controller.entity_prop_ids.is_empty());
+        assert!(first.prop_controller.entity_prop_ids.contains_key("CChicken.m_iHealth"));
+    }
+
+    #[test]
+    fn test_least_recently_used_build_is_evicted() {
+        let mut cache = VecDeque::new();
+        for key in 0..MAX_CACHED_BUILDS as u64 {
+            insert_entry(&mut cache, key, key);
+        }
+        // Use 0 again so 1 is the oldest
+        let value = take_entry(&mut cache, 0).unwrap();
+        cache.push_back((0, value));
+        insert_entry(&mut cache, 100, 100);
+        assert_eq!(cache.len(), MAX_CACHED_BUILDS);
+        assert!(cache.iter().any(|(k, _)| *k == 0));
+        assert!(!cache.iter().any(|(k, _)| *k == 1));
+        insert_entry(&mut cache, 100, 101);
+        assert_eq!(cache.iter().filter(|(k, _)| *k == 100).count(), 1);
+    }
+}
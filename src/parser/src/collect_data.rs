@@ -103,6 +103,7 @@ pub enum PropCollectionError {
     AgentPropNotFound,
     AgentSpecialIdNotSet,
     UseridNotFound,
+    SpecialIdNotSet,
 }
 // DONT KNOW IF THESE ARE CORRECT. SEEMS TO GIVE CORRECT VALUES
 const CELL_BITS: i32 = 9;
@@ -127,6 +128,15 @@ pub struct ProjectileRecord {
     pub grenade_type: Option<String>,
     pub entity_id: Option<i32>,
 }
+// One entity of a class asked for with DemoQuery::entity_props at one tick
+#[derive(Debug, Clone)]
+pub struct EntityPropRecord {
+    pub tick: i32,
+    pub entity_id: i32,
+    pub class_name: String,
+    // Same order as wanted_entity_props, None if the prop belongs to another class or is not set
+    pub values: Vec<Option<Variant>>,
+}
 pub enum CoordinateAxis {
     X,
     Y,
@@ -137,18 +147,34 @@ pub enum CoordinateAxis {
 
 impl ParserThread {
     pub fn collect_entities(&mut self) {
+        // Sent once the tick is complete, see emit_tick
+        if self.has_player_tick_callbacks() {
+            return;
+        }
         if !self.prop_controller.event_with_velocity {
-            if !self.wanted_ticks.contains(&self.tick) && self.wanted_ticks.len() != 0 || self.wanted_events.len() != 0 {
+            if !self.is_wanted_tick() || self.wanted_events.len() != 0 {
                 return;
             }
         }
         if self.parse_projectiles {
             self.collect_projectiles();
         }
+        // With prop_changes the props stored on entities (entity props included) are recorded in
+        // decode_entity_update, the computed ones (X, is_alive, weapon_name...) are still collected every tick.
+        let tracking_changes = !self.change_tracked_props.is_empty();
+        if !self.prop_controller.wanted_entity_props.is_empty() && !tracking_changes {
+            self.collect_entity_props();
+        }
+        if tracking_changes && !self.prop_controller.prop_infos.iter().any(|p| self.is_computed_prop(p)) {
+            return;
+        }
         // iterate every player and every wanted prop name
         // if either one is missing then push None to output
         for (entity_id, player) in &self.players {
             for prop_info in &self.prop_controller.prop_infos {
+                if tracking_changes && self.change_tracked_props.contains(&prop_info.id) {
+                    continue;
+                }
                 // All values come trough here. None if cant be found.
                 match self.find_prop(prop_info, entity_id, player) {
                     Ok(prop) => {
@@ -168,6 +194,11 @@ impl ParserThread {
             }
         }
     }
+    // Wanted props that can't be recorded as changes, tick, steamid and name only go along with them
+    fn is_computed_prop(&self, prop_info: &PropInfo) -> bool {
+        !self.change_tracked_props.contains(&prop_info.id)
+            && !matches!(prop_info.prop_type, PropType::Tick | PropType::Steamid | PropType::Name)
+    }
     pub fn find_prop(
         &self,
         prop_info: &PropInfo,
@@ -197,6 +228,13 @@ impl ParserThread {
             },
         }
     }
+    // Special ids are None if the prop was not in the sendtables
+    pub fn get_special_prop(&self, prop_id: Option<u32>, entity_id: &i32) -> Result<Variant, PropCollectionError> {
+        match prop_id {
+            Some(prop_id) => self.get_prop_from_ent(&prop_id, entity_id),
+            None => Err(PropCollectionError::SpecialIdNotSet),
+        }
+    }
     fn create_tick(&self) -> Result<Variant, PropCollectionError> {
         // This can't actually fail
         return Ok(Variant::I32(self.tick));
@@ -338,6 +376,37 @@ impl ParserThread {
         }
     }
 
+    pub fn collect_entity_props(&mut self) {
+        let wanted = &self.prop_controller.wanted_entity_props;
+        for (entity_id, entity) in self.entities.iter().sorted_by_key(|(entity_id, _)| **entity_id) {
+            let class_name = match self.cls_by_id.get(&entity.cls_id) {
+                Some(cls) => &cls.name,
+                None => continue,
+            };
+            // CPlantedC4.m_bBombTicking => CPlantedC4
+            if !wanted.iter().any(|name| name.split(".").next() == Some(class_name)) {
+                continue;
+            }
+            let values = wanted
+                .iter()
+                .map(|name| match name.split(".").next() == Some(class_name) {
+                    true => self
+                        .prop_controller
+                        .entity_prop_ids
+                        .get(name)
+                        .and_then(|id| entity.props.get(id))
+                        .cloned(),
+                    false => None,
+                })
+                .collect();
+            self.entity_prop_records.push(EntityPropRecord {
+                tick: self.tick,
+                entity_id: *entity_id,
+                class_name: class_name.clone(),
+                values,
+            });
+        }
+    }
     fn find_weapon_name(&self, entity_id: &i32) -> Result<Variant, PropCollectionError> {
         let item_def_id = match self.prop_controller.special_ids.item_def {
             Some(x) => x,
@@ -633,7 +702,7 @@ impl ParserThread {
                         continue;
                     }
                     unique_eids.push(eid);
-                    let res = match self.get_prop_from_ent(&self.prop_controller.special_ids.item_def.unwrap(), &eid) {
+                    let res = match self.get_special_prop(self.prop_controller.special_ids.item_def, &eid) {
                         Err(_e) => continue,
                         Ok(def) => def,
                     };
@@ -800,9 +869,11 @@ mod tests {
     use crate::entities::EntityType;
     use crate::entities::PlayerMetaData;
     use crate::maps::BUTTONMAP;
+    use crate::other_netmessages::Class;
     use crate::parser_settings::Parser;
     use crate::prop_controller::PropInfo;
     use crate::prop_controller::WEAPON_SKIN_ID;
+    use crate::sendtables::Serializer;
     use crate::variants::*;
     use crate::{parser_settings::ParserInputs, parser_thread_settings::ParserThread, prop_controller::PropController};
     use ahash::AHashMap;
@@ -845,13 +916,20 @@ mod tests {
             wanted_player_props_og_names: vec![],
             wanted_other_props: vec![],
             wanted_other_props_og_names: vec![],
+            wanted_entity_props: vec![],
             wanted_events: vec![],
             parse_ents: true,
             wanted_ticks: vec![],
+            tick_selectors: vec![],
+            tick_stride: 0,
             parse_projectiles: false,
+            prop_changes: false,
+            kill_labels: None,
+            seeded_chunks: false,
             only_header: true,
             count_props: false,
             only_convars: false,
+            lenient: false,
             huffman_lookup_table: Arc::new(vec![]),
         };
         let player_md = PlayerMetaData {
@@ -2710,4 +2788,80 @@ mod tests {
         let player_md = parser_thread.find_player_metadata(player_md_og.player_entity_id.unwrap());
         assert_eq!(Err(PropCollectionError::PlayerNotFound), player_md);
     }
+    #[test]
+    fn test_entity_created_event() {
+        let (mut parser_thread, _) = default_setup();
+        let mut cls_by_id = AHashMap::default();
+        cls_by_id.insert(
+            0,
+            Class {
+                class_id: 0,
+                name: "CPlantedC4".to_string(),
+                serializer: Serializer {
+                    name: "CPlantedC4".to_string(),
+                    fields: vec![],
+                },
+            },
+        );
+        parser_thread.cls_by_id = Arc::new(cls_by_id);
+        let mut prop_controller = PropController::new(vec![], vec![], AHashMap::default())
+            .with_entity_props(vec!["CPlantedC4.m_bBombTicking".to_string(), "CChicken.m_leader".to_string()]);
+        prop_controller
+            .entity_prop_ids
+            .insert("CPlantedC4.m_bBombTicking".to_string(), WANTED_PROP_ID);
+        parser_thread.prop_controller = Arc::new(prop_controller);
+        parser_thread.entities.insert(
+            42,
+            Entity {
+                cls_id: 0,
+                entity_id: 42,
+                props: AHashMap::from_iter([(WANTED_PROP_ID, Variant::Bool(true))]),
+                entity_type: EntityType::Normal,
+            },
+        );
+        parser_thread.create_custom_event_entity_lifecycle("entity_created", 42);
+        assert!(parser_thread.game_events.is_empty());
+
+        parser_thread.wanted_events = vec!["entity_created".to_string()];
+        parser_thread.create_custom_event_entity_lifecycle("entity_created", 42);
+        let event = &parser_thread.game_events[0];
+        let fields: Vec<(&str, Option<Variant>)> = event.fields.iter().map(|f| (f.name.as_str(), f.data.clone())).collect();
+        assert_eq!(
+            fields,
+            vec![
+                ("tick", Some(Variant::I32(parser_thread.tick))),
+                ("entity_id", Some(Variant::I32(42))),
+                ("class_name", Some(Variant::String("CPlantedC4".to_string()))),
+                ("CPlantedC4.m_bBombTicking", Some(Variant::Bool(true))),
+            ]
+        );
+    }
+    #[test]
+    fn test_entity_props_of_class_outside_default_list() {
+        use crate::query::DemoQuery;
+        use crate::test_demo::TestDemo;
+
+        let mut demo = TestDemo::new();
+        demo.create(10, "CChicken");
+        demo.set(10, "m_iHealth", 30);
+        demo.packet(1);
+        demo.set(10, "m_iHealth", 20);
+        demo.packet(2);
+        let bytes = demo.finish(2);
+        let mut parser = DemoQuery::new()
+            .entity_props(["CChicken.m_iHealth"])
+            .parser(BytesVariant::Vec(bytes))
+            .unwrap();
+        let output = parser.parse_demo().unwrap();
+
+        let records: Vec<_> = output
+            .entity_props
+            .iter()
+            .map(|r| (r.tick, r.entity_id, r.values.clone()))
+            .collect();
+        assert_eq!(
+            records,
+            vec![(1, 10, vec![Some(Variant::I32(30))]), (2, 10, vec![Some(Variant::I32(20))])]
+        );
+    }
 }
@@ -0,0 +1,391 @@
+use crate::game_events::GameEvent;
+use crate::parser_settings::Parser;
+use crate::read_bits::DemoParserError;
+use crate::variants::Variant;
+use ahash::AHashMap;
+use serde::Serialize;
+
+// One row per player_hurt with the damage that was actually done, hitgroup, distance and wallbang attribution.
+// examples/damage.rs shows it in use.
+
+pub const DAMAGE_EVENTS: [&str; 5] = ["player_hurt", "player_blind", "bullet_impact", "weapon_fire", "round_start"];
+const DAMAGE_PLAYER_PROPS: [&str; 4] = ["X", "Y", "Z", "team_num"];
+const NON_BULLET_WEAPONS: [&str; 11] = [
+    "",
+    "world",
+    "hegrenade",
+    "flashbang",
+    "smokegrenade",
+    "decoy",
+    "molotov",
+    "incgrenade",
+    "inferno",
+    "taser",
+    "bayonet",
+];
+const TICKS_PER_SECOND: f32 = 64.0;
+// weapon_fire can be a tick or two before the hit
+const SHOT_TICKS: i32 = 2;
+// Impacts closer than this to the victim are on the victim
+const VICTIM_RADIUS: f32 = 48.0;
+// Positions are at the feet, shots come from the eyes
+const LINE_TOLERANCE: f32 = 72.0;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DamageRecord {
+    pub tick: i32,
+    pub attacker_steamid: Option<u64>,
+    pub attacker_name: Option<String>,
+    pub victim_steamid: Option<u64>,
+    pub victim_name: Option<String>,
+    pub weapon: String,
+    pub hitgroup: String,
+    // As in the event
+    pub dmg_health: i32,
+    pub dmg_armor: i32,
+    // Clamped to what the victim had left (an AWP body shot does 115), what ADR counts
+    pub health_damage: i32,
+    pub armor_damage: i32,
+    // Victim after the hit
+    pub health: i32,
+    pub armor: i32,
+    // player_hurt does not say, see damage_ledger. None for grenades, fire, knives and hits with no impacts.
+    pub penetrated: Option<bool>,
+    // Units between the attacker and the victim
+    pub distance: Option<f32>,
+    pub victim_blinded: bool,
+    pub team_damage: Option<bool>,
+}
+
+impl Parser {
+    pub fn parse_damage(&mut self) -> Result<Vec<DamageRecord>, DemoParserError> {
+        // Props attached to the events are read from the entities
+        let query = self
+            .events_only_query(DAMAGE_EVENTS)
+            .entities(true)
+            .player_props(DAMAGE_PLAYER_PROPS);
+        let output = self.parser_for(&query)?.parse_demo()?;
+        Ok(damage_ledger(&output.game_events))
+    }
+}
+
+// Events need X, Y, Z and team_num as player props.
+// Health is followed from one player_hurt to the next and reset at round_start and after deaths, armor too as far
+// as it is known. A hit counts as penetrated when the attacker's bullet_impacts from the same shot (weapon_fire)
+// include one on the line between attacker and victim, clearly before the victim.
+pub fn damage_ledger(events: &[GameEvent]) -> Vec<DamageRecord> {
+    let mut events: Vec<&GameEvent> = events.iter().collect();
+    events.sort_by_key(|e| e.tick);
+
+    // steamid => (tick, duration in ticks)
+    let mut blinds: AHashMap<u64, Vec<(i32, i32)>> = AHashMap::default();
+    // steamid => ticks
+    let mut shots: AHashMap<u64, Vec<i32>> = AHashMap::default();
+    // steamid => (tick, position)
+    let mut impacts: AHashMap<u64, Vec<(i32, [f32; 3])>> = AHashMap::default();
+    for event in &events {
+        let steamid = match event.steamid("user") {
+            Some(steamid) => steamid,
+            None => continue,
+        };
+        match event.name.as_str() {
+            "player_blind" => {
+                let duration = match event.field("blind_duration") {
+                    Some(Variant::F32(seconds)) => (seconds * TICKS_PER_SECOND) as i32,
+                    _ => 0,
+                };
+                blinds.entry(steamid).or_default().push((event.tick, duration));
+            }
+            "weapon_fire" => shots.entry(steamid).or_default().push(event.tick),
+            "bullet_impact" => {
+                if let Some(position) = position(event, "x", "y", "z") {
+                    impacts.entry(steamid).or_default().push((event.tick, position));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let mut health: AHashMap<u64, i32> = AHashMap::default();
+    let mut armor: AHashMap<u64, i32> = AHashMap::default();
+    let mut ledger = vec![];
+    for event in events {
+        if event.name == "round_start" {
+            health.clear();
+            armor.clear();
+            continue;
+        }
+        if event.name != "player_hurt" {
+            continue;
+        }
+        let attacker = event.steamid("attacker");
+        let victim = event.steamid("user");
+        let dmg_health = event.int_field("dmg_health").unwrap_or(0) as i32;
+        let dmg_armor = event.int_field("dmg_armor").unwrap_or(0) as i32;
+        let health_after = event.int_field("health").unwrap_or(0) as i32;
+        let armor_after = event.int_field("armor").unwrap_or(0) as i32;
+        // Damage that did not kill is exact, on kills the victim can only lose what it had
+        let health_damage = match (health_after, victim.and_then(|v| health.get(&v))) {
+            (0, Some(before)) => dmg_health.min(*before),
+            (0, None) => dmg_health.min(100),
+            _ => dmg_health,
+        };
+        let armor_damage = match (armor_after, victim.and_then(|v| armor.get(&v))) {
+            (0, Some(before)) => dmg_armor.min(*before),
+            _ => dmg_armor,
+        };
+        if let Some(victim) = victim {
+            // Respawns with full health
+            health.insert(victim, if health_after == 0 { 100 } else { health_after });
+            armor.insert(victim, armor_after);
+        }
+
+        let weapon = match event.field("weapon") {
+            Some(Variant::String(weapon)) => weapon.clone(),
+            _ => String::new(),
+        };
+        let attacker_position = position(event, "attacker_X", "attacker_Y", "attacker_Z");
+        let victim_position = position(event, "user_X", "user_Y", "user_Z");
+        let penetrated = match (attacker, attacker_position, victim_position) {
+            (Some(attacker), Some(from), Some(to)) if is_bullet_weapon(&weapon) => {
+                // Impacts of the same shot: from the last weapon_fire before the hit to the hit
+                let shot_tick = shots
+                    .get(&attacker)
+                    .and_then(|ticks| {
+                        ticks
+                            .iter()
+                            .rev()
+                            .find(|t| **t <= event.tick && event.tick - **t <= SHOT_TICKS)
+                    })
+                    .copied()
+                    .unwrap_or(event.tick);
+                let shot_impacts: Vec<[f32; 3]> = impacts
+                    .get(&attacker)
+                    .map(|i| {
+                        i.iter()
+                            .filter(|(t, _)| (shot_tick..=event.tick).contains(t))
+                            .map(|(_, p)| *p)
+                            .collect()
+                    })
+                    .unwrap_or_default();
+                match shot_impacts.is_empty() {
+                    true => None,
+                    false => Some(shot_impacts.iter().any(|p| is_before_victim(from, to, *p))),
+                }
+            }
+            _ => None,
+        };
+        let victim_blinded = match victim.and_then(|v| blinds.get(&v)) {
+            Some(blinds) => blinds
+                .iter()
+                .any(|(t, duration)| *t <= event.tick && event.tick < t + duration),
+            None => false,
+        };
+        let team_damage = match (event.int_field("attacker_team_num"), event.int_field("user_team_num")) {
+            (Some(a), Some(v)) if attacker.is_some() && attacker != victim => Some(a == v),
+            _ => None,
+        };
+        ledger.push(DamageRecord {
+            tick: event.tick,
+            attacker_steamid: attacker,
+            attacker_name: event.player_name("attacker"),
+            victim_steamid: victim,
+            victim_name: event.player_name("user"),
+            hitgroup: hitgroup_name(event.int_field("hitgroup").unwrap_or(0) as i32).to_string(),
+            weapon,
+            dmg_health,
+            dmg_armor,
+            health_damage,
+            armor_damage,
+            health: health_after,
+            armor: armor_after,
+            penetrated,
+            distance: match (attacker_position, victim_position) {
+                (Some(from), Some(to)) => Some(length(sub(to, from))),
+                _ => None,
+            },
+            victim_blinded,
+            team_damage,
+        });
+    }
+    ledger
+}
+
+pub fn hitgroup_name(hitgroup: i32) -> &'static str {
+    match hitgroup {
+        0 => "generic",
+        1 => "head",
+        2 => "chest",
+        3 => "stomach",
+        4 => "left_arm",
+        5 => "right_arm",
+        6 => "left_leg",
+        7 => "right_leg",
+        8 => "neck",
+        10 => "gear",
+        _ => "unknown",
+    }
+}
+
+fn is_bullet_weapon(weapon: &str) -> bool {
+    !NON_BULLET_WEAPONS.contains(&weapon) && !weapon.contains("knife")
+}
+
+// On the line from the attacker to the victim and clearly before the victim
+fn is_before_victim(from: [f32; 3], to: [f32; 3], impact: [f32; 3]) -> bool {
+    let line = sub(to, from);
+    let distance = length(line);
+    if distance == 0.0 {
+        return false;
+    }
+    let offset = sub(impact, from);
+    let along = dot(offset, line) / distance;
+    let off_line = length(sub(offset, scale(line, along / distance)));
+    along > 0.0 && along < distance - VICTIM_RADIUS && off_line < LINE_TOLERANCE
+}
+
+fn sub(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+fn scale(a: [f32; 3], s: f32) -> [f32; 3] {
+    [a[0] * s, a[1] * s, a[2] * s]
+}
+
+fn dot(a: [f32; 3], b: [f32; 3]) -> f32 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn length(a: [f32; 3]) -> f32 {
+    dot(a, a).sqrt()
+}
+
+fn position(event: &GameEvent, x: &str, y: &str, z: &str) -> Option<[f32; 3]> {
+    let coordinate = |name: &str| match event.field(name) {
+        Some(Variant::F32(v)) => Some(*v),
+        _ => None,
+    };
+    Some([coordinate(x)?, coordinate(y)?, coordinate(z)?])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game_events::test_event as event;
+    use crate::game_events::test_hurt;
+    use crate::game_events::test_steamid as steamid;
+
+    const ATTACKER: u64 = 1;
+    const VICTIM: u64 = 2;
+
+    // Attacker at the origin, victim 1000 units away on the x axis
+    fn hurt(tick: i32, weapon: &str, dmg_health: i32, health: i32) -> GameEvent {
+        test_hurt(
+            tick,
+            ATTACKER,
+            VICTIM,
+            dmg_health,
+            health,
+            vec![
+                ("weapon", Variant::String(weapon.to_string())),
+                ("dmg_armor", Variant::I32(0)),
+                ("armor", Variant::I32(0)),
+                ("hitgroup", Variant::I32(2)),
+                ("attacker_X", Variant::F32(0.0)),
+                ("attacker_Y", Variant::F32(0.0)),
+                ("attacker_Z", Variant::F32(0.0)),
+                ("user_X", Variant::F32(1000.0)),
+                ("user_Y", Variant::F32(0.0)),
+                ("user_Z", Variant::F32(0.0)),
+            ],
+        )
+    }
+    fn shot(tick: i32) -> GameEvent {
+        event("weapon_fire", tick, vec![("user_steamid", steamid(ATTACKER))])
+    }
+    fn impact(tick: i32, x: f32) -> GameEvent {
+        event(
+            "bullet_impact",
+            tick,
+            vec![
+                ("user_steamid", steamid(ATTACKER)),
+                ("x", Variant::F32(x)),
+                ("y", Variant::F32(10.0)),
+                ("z", Variant::F32(50.0)),
+            ],
+        )
+    }
+    fn health_damage(events: Vec<GameEvent>) -> Vec<i32> {
+        damage_ledger(&events).iter().map(|d| d.health_damage).collect()
+    }
+
+    #[test]
+    fn test_damage_clamped_to_health_left() {
+        // The AWP does 115 but only 73 were left
+        assert_eq!(
+            health_damage(vec![hurt(100, "ak47", 27, 73), hurt(200, "awp", 115, 0)]),
+            vec![27, 73]
+        );
+        // Nothing known about the victim, at most full health
+        assert_eq!(health_damage(vec![hurt(200, "awp", 115, 0)]), vec![100]);
+    }
+    #[test]
+    fn test_health_reset_at_round_start() {
+        let events = vec![
+            hurt(100, "ak47", 27, 73),
+            event("round_start", 150, vec![]),
+            hurt(200, "awp", 115, 0),
+        ];
+        assert_eq!(health_damage(events), vec![27, 100]);
+    }
+    #[test]
+    fn test_wallbang() {
+        let events = vec![
+            shot(100),
+            impact(100, 1000.0),
+            hurt(100, "ak47", 27, 73),
+            // Through a wall at x = 500
+            shot(199),
+            impact(199, 500.0),
+            impact(199, 1000.0),
+            hurt(200, "ak47", 27, 46),
+        ];
+        let penetrated: Vec<Option<bool>> = damage_ledger(&events).iter().map(|d| d.penetrated).collect();
+        assert_eq!(penetrated, vec![Some(false), Some(true)]);
+    }
+    #[test]
+    fn test_no_wallbang_without_bullets() {
+        let ledger = damage_ledger(&[
+            hurt(300, "hegrenade", 40, 60),
+            hurt(400, "knife_t", 40, 20),
+            hurt(500, "ak47", 20, 0),
+        ]);
+        let penetrated: Vec<Option<bool>> = ledger.iter().map(|d| d.penetrated).collect();
+        // The ak47 hit has no impacts
+        assert_eq!(penetrated, vec![None, None, None]);
+    }
+    #[test]
+    fn test_victim_blinded() {
+        let events = vec![
+            event(
+                "player_blind",
+                150,
+                vec![("user_steamid", steamid(VICTIM)), ("blind_duration", Variant::F32(2.0))],
+            ),
+            hurt(200, "ak47", 27, 73),
+            hurt(150 + 128, "ak47", 27, 46),
+        ];
+        let blinded: Vec<bool> = damage_ledger(&events).iter().map(|d| d.victim_blinded).collect();
+        assert_eq!(blinded, vec![true, false]);
+    }
+    #[test]
+    fn test_hit_details() {
+        let ledger = damage_ledger(&[hurt(100, "ak47", 27, 73)]);
+        assert_eq!(ledger[0].hitgroup, "chest");
+        assert_eq!(ledger[0].distance, Some(1000.0));
+        assert_eq!(
+            (ledger[0].attacker_steamid, ledger[0].victim_steamid),
+            (Some(ATTACKER), Some(VICTIM))
+        );
+    }
+}
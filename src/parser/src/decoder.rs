@@ -7,8 +7,9 @@ use super::{
 use crate::q_float::QuantalizedFloat;
 use crate::sendtables::Decoder::*;
 use ahash::AHashMap;
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct QfMapper {
     pub idx: u32,
     pub map: AHashMap<u32, QuantalizedFloat>,
@@ -22,7 +23,15 @@ impl<'a> Bitreader<'a> {
             FloatSimulationTimeDecoder => Ok(Variant::F32(self.decode_simul_time()?)),
             UnsignedDecoder => Ok(Variant::U32(self.read_varint()?)),
             QuantalizedFloatDecoder(qf_idx) => {
-                let mut qf = qf_map.map[&((*qf_idx) as u32)];
+                let mut qf = match qf_map.map.get(&((*qf_idx) as u32)) {
+                    Some(qf) => *qf,
+                    None => {
+                        return Err(DemoParserError::UnsupportedDecoder(format!(
+                            "no quantalized float {}",
+                            qf_idx
+                        )))
+                    }
+                };
                 Ok(Variant::F32(qf.decode(self)))
             }
             Qangle3Decoder => Ok(Variant::VecXYZ(self.decode_qangle_all_3()?)),
@@ -44,7 +53,7 @@ impl<'a> Bitreader<'a> {
             AmmoDecoder => Ok(Variant::U32(self.decode_ammo()?)),
             QanglePresDecoder => Ok(Variant::VecXYZ(self.decode_qangle_variant_pres()?)),
             GameModeRulesDecoder => Ok(Variant::U32(self.read_nbits(7)?)),
-            _ => panic!("huh {:?}", decoder),
+            _ => Err(DemoParserError::UnsupportedDecoder(format!("{:?}", decoder))),
         }
     }
     pub fn decode_qangle_variant_pres(&mut self) -> Result<[f32; 3], DemoParserError> {
@@ -0,0 +1,418 @@
+use crate::game_events::GameEvent;
+use crate::parser::DemoOutput;
+use crate::parser_settings::Parser;
+use crate::read_bits::DemoParserError;
+use crate::rounds::Round;
+use crate::tick_selectors::variant_to_i64;
+use crate::variants::Variant;
+use ahash::AHashMap;
+use serde::Serialize;
+
+// Money and equipment per team per round with a buy classification. examples/economy.rs shows it in use.
+
+pub const ECONOMY_EVENTS: [&str; 1] = ["item_purchase"];
+const ECONOMY_PLAYER_PROPS: [&str; 9] = [
+    "team_num",
+    "start_balance",
+    "balance",
+    "current_equip_value",
+    "cash_spent_this_round",
+    "cash_earned_this_round",
+    "inventory",
+    "t_losing_streak",
+    "ct_losing_streak",
+];
+// Average equipment value per player
+const ECO_MAX_EQUIPMENT: i32 = 1000;
+const FULL_BUY_MIN_EQUIPMENT: i32 = 3500;
+// Average money left per player, less than this and the team spent everything it could
+const FORCE_BUY_MAX_MONEY_LEFT: i32 = 1000;
+const LOSS_BONUS_BASE: i32 = 1400;
+const LOSS_BONUS_STEP: i32 = 500;
+const LOSS_BONUS_MAX_LEVEL: i32 = 4;
+const PRIMARY_WEAPONS: [&str; 24] = [
+    "ak47",
+    "aug",
+    "awp",
+    "famas",
+    "g3sg1",
+    "galilar",
+    "m249",
+    "m4a1",
+    "m4a1_silencer",
+    "mac10",
+    "p90",
+    "mp5sd",
+    "ump45",
+    "xm1014",
+    "bizon",
+    "mag7",
+    "negev",
+    "sawedoff",
+    "mp7",
+    "mp9",
+    "nova",
+    "scar20",
+    "sg556",
+    "ssg08",
+];
+const T: i32 = 2;
+const CT: i32 = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BuyType {
+    Pistol,
+    Eco,
+    Force,
+    Half,
+    Full,
+}
+
+impl BuyType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BuyType::Pistol => "pistol",
+            BuyType::Eco => "eco",
+            BuyType::Force => "force",
+            BuyType::Half => "half",
+            BuyType::Full => "full",
+        }
+    }
+}
+
+// One player at one tick
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct EconomySnapshot {
+    pub tick: i32,
+    pub steamid: u64,
+    pub team_num: i32,
+    pub start_balance: i32,
+    pub balance: i32,
+    pub equipment_value: i32,
+    pub cash_spent_this_round: i32,
+    pub cash_earned_this_round: i32,
+    pub has_primary: bool,
+    pub t_losing_streak: i32,
+    pub ct_losing_streak: i32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TeamEconomy {
+    pub round: i32,
+    // "T" or "CT"
+    pub side: String,
+    pub players: i32,
+    // Sums over the players of the team
+    pub start_money: i32,
+    pub money_spent: i32,
+    pub money_left: i32,
+    pub cash_earned: i32,
+    pub equipment_value: i32,
+    // Players with a rifle, smg, heavy weapon or sniper
+    pub primary_weapons: i32,
+    pub items_purchased: i32,
+    // Rounds lost in a row before this one, capped at the highest loss bonus
+    pub loss_bonus_level: i32,
+    // Money every player gets if the team loses this round
+    pub loss_bonus: i32,
+    pub buy_type: BuyType,
+}
+
+impl Parser {
+    // Parses the rounds first, then the purchases and the players at the round boundaries (two separate parses)
+    pub fn parse_economy(&mut self) -> Result<Vec<TeamEconomy>, DemoParserError> {
+        let rounds = self.parse_rounds()?;
+        if rounds.is_empty() {
+            return Ok(vec![]);
+        }
+        let mut ticks: Vec<i32> = rounds.iter().map(freeze_end_tick).collect();
+        ticks.extend(rounds.iter().filter_map(end_tick));
+        ticks.sort();
+        ticks.dedup();
+
+        let events_query = self
+            .events_only_query(ECONOMY_EVENTS)
+            .entities(true)
+            .player_props(["team_num"]);
+        let events = self.parser_for(&events_query)?.parse_demo()?.game_events;
+        let snapshots = self
+            .parser_for(&self.player_ticks_query(ECONOMY_PLAYER_PROPS, ticks))?
+            .parse_demo()?;
+        Ok(compute_economy(&rounds, &events, &snapshots_from_output(&snapshots)))
+    }
+}
+
+// Snapshots are used at freeze end (start if freeze time never ended) and at the end tick (officially ended, or
+// end) of every round. item_purchase events need team_num as a player prop (user_team_num).
+// Equipment, money left, inventories and the losing streak are from freeze end, money spent and cash earned
+// from the round end. Things bought after freeze time are in money_spent and items_purchased but not in the
+// equipment value.
+pub fn compute_economy(rounds: &[Round], events: &[GameEvent], snapshots: &[EconomySnapshot]) -> Vec<TeamEconomy> {
+    let mut snapshots_at: AHashMap<i32, Vec<&EconomySnapshot>> = AHashMap::default();
+    for snapshot in snapshots {
+        snapshots_at.entry(snapshot.tick).or_default().push(snapshot);
+    }
+    let mut economy = vec![];
+    for (idx, round) in rounds.iter().enumerate() {
+        let last_tick = match (round.officially_ended_tick, rounds.get(idx + 1)) {
+            (Some(tick), _) => tick,
+            (None, Some(next)) => next.start_tick - 1,
+            (None, None) => i32::MAX,
+        };
+        let at_freeze_end = snapshots_at.get(&freeze_end_tick(round)).cloned().unwrap_or_default();
+        let at_end = end_tick(round)
+            .and_then(|tick| snapshots_at.get(&tick))
+            .cloned()
+            .unwrap_or_default();
+        for team in [T, CT] {
+            let players: Vec<&EconomySnapshot> = at_freeze_end.iter().filter(|s| s.team_num == team).copied().collect();
+            if players.is_empty() {
+                continue;
+            }
+            let ended: Vec<&EconomySnapshot> = at_end.iter().filter(|s| s.team_num == team).copied().collect();
+            // Falls back to freeze end for players that left before the round ended
+            let at_round_end = |steamid: u64| ended.iter().find(|s| s.steamid == steamid).copied();
+            let items_purchased = events
+                .iter()
+                .filter(|e| e.name == "item_purchase" && (round.start_tick..=last_tick).contains(&e.tick))
+                .filter(|e| event_team(e) == Some(team))
+                .count() as i32;
+            let losing_streak = match team {
+                T => players[0].t_losing_streak,
+                _ => players[0].ct_losing_streak,
+            };
+            let loss_bonus_level = losing_streak.clamp(0, LOSS_BONUS_MAX_LEVEL);
+            let mut team_economy = TeamEconomy {
+                round: round.round,
+                side: if team == T { "T" } else { "CT" }.to_string(),
+                players: players.len() as i32,
+                start_money: players.iter().map(|p| p.start_balance).sum(),
+                money_spent: players
+                    .iter()
+                    .map(|p| at_round_end(p.steamid).unwrap_or(p).cash_spent_this_round)
+                    .sum(),
+                money_left: players.iter().map(|p| p.balance).sum(),
+                cash_earned: players
+                    .iter()
+                    .map(|p| at_round_end(p.steamid).unwrap_or(p).cash_earned_this_round)
+                    .sum(),
+                equipment_value: players.iter().map(|p| p.equipment_value).sum(),
+                primary_weapons: players.iter().filter(|p| p.has_primary).count() as i32,
+                items_purchased,
+                loss_bonus_level,
+                loss_bonus: LOSS_BONUS_BASE + LOSS_BONUS_STEP * loss_bonus_level,
+                buy_type: BuyType::Eco,
+            };
+            team_economy.buy_type = buy_type(round, &team_economy);
+            economy.push(team_economy);
+        }
+    }
+    economy
+}
+
+// First round of each half in regulation is a pistol round, the rest goes by the average equipment value
+// per player. Teams that bought less than a full buy spent all they could (force) or kept money (half).
+fn buy_type(round: &Round, team: &TeamEconomy) -> BuyType {
+    if round.round == 1 || (round.sides_switched && round.overtime == 0) {
+        return BuyType::Pistol;
+    }
+    let players = team.players.max(1);
+    let equipment = team.equipment_value / players;
+    if equipment < ECO_MAX_EQUIPMENT {
+        return BuyType::Eco;
+    }
+    if equipment >= FULL_BUY_MIN_EQUIPMENT && team.primary_weapons >= team.players - 1 {
+        return BuyType::Full;
+    }
+    match team.money_left / players < FORCE_BUY_MAX_MONEY_LEFT {
+        true => BuyType::Force,
+        false => BuyType::Half,
+    }
+}
+
+fn freeze_end_tick(round: &Round) -> i32 {
+    round.freeze_end_tick.unwrap_or(round.start_tick)
+}
+
+fn end_tick(round: &Round) -> Option<i32> {
+    round.officially_ended_tick.or(round.end_tick)
+}
+
+fn event_team(event: &GameEvent) -> Option<i32> {
+    event
+        .int_field("team")
+        .or_else(|| event.int_field("user_team_num"))
+        .map(|t| t as i32)
+}
+
+// Rows of the player tick output
+fn snapshots_from_output(output: &DemoOutput) -> Vec<EconomySnapshot> {
+    let column = |name: &str| {
+        output
+            .prop_info
+            .prop_infos
+            .iter()
+            .find(|info| info.prop_friendly_name == name)
+            .and_then(|info| output.df.get(&info.id))
+    };
+    let (ticks, steamids) = match (column("tick"), column("steamid")) {
+        (Some(ticks), Some(steamids)) => (ticks, steamids),
+        _ => return vec![],
+    };
+    let int_at = |name: &str, idx: usize| {
+        column(name)
+            .and_then(|c| c.get(idx))
+            .and_then(|v| variant_to_i64(&v))
+            .unwrap_or(0) as i32
+    };
+    let mut snapshots = vec![];
+    for idx in 0..ticks.len() {
+        let (tick, steamid) = match (ticks.get(idx), steamids.get(idx)) {
+            (Some(Variant::I32(tick)), Some(Variant::U64(steamid))) => (tick, steamid),
+            _ => continue,
+        };
+        let has_primary = match column("inventory").and_then(|c| c.get(idx)) {
+            Some(Variant::StringVec(items)) => items.iter().any(|item| PRIMARY_WEAPONS.contains(&item.as_str())),
+            _ => false,
+        };
+        snapshots.push(EconomySnapshot {
+            tick,
+            steamid,
+            team_num: int_at("team_num", idx),
+            start_balance: int_at("start_balance", idx),
+            balance: int_at("balance", idx),
+            equipment_value: int_at("current_equip_value", idx),
+            cash_spent_this_round: int_at("cash_spent_this_round", idx),
+            cash_earned_this_round: int_at("cash_earned_this_round", idx),
+            has_primary,
+            t_losing_streak: int_at("t_losing_streak", idx),
+            ct_losing_streak: int_at("ct_losing_streak", idx),
+        });
+    }
+    snapshots
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game_events::test_event as event;
+    use crate::game_events::test_round as round;
+    use crate::game_events::test_team_economy as team;
+
+    fn rounds() -> Vec<Round> {
+        vec![round(1, 0, None), round(2, 1000, None), round(3, 2000, None)]
+    }
+    fn buy_types(economy: &[TeamEconomy]) -> Vec<(i32, &str, BuyType)> {
+        economy.iter().map(|e| (e.round, e.side.as_str(), e.buy_type)).collect()
+    }
+
+    #[test]
+    fn test_buy_types() {
+        let mut snapshots = vec![];
+        snapshots.extend(team(10, T, 800, 0, false));
+        snapshots.extend(team(10, CT, 800, 0, false));
+        snapshots.extend(team(1010, T, 300, 2000, false));
+        snapshots.extend(team(1010, CT, 2500, 200, false));
+        snapshots.extend(team(2010, T, 4500, 500, true));
+        snapshots.extend(team(2010, CT, 2500, 3000, false));
+        let economy = compute_economy(&rounds(), &[], &snapshots);
+        assert_eq!(
+            buy_types(&economy),
+            vec![
+                (1, "T", BuyType::Pistol),
+                (1, "CT", BuyType::Pistol),
+                (2, "T", BuyType::Eco),
+                (2, "CT", BuyType::Force),
+                (3, "T", BuyType::Full),
+                (3, "CT", BuyType::Half),
+            ]
+        );
+    }
+    #[test]
+    fn test_pistol_after_halftime() {
+        let mut second_half = round(13, 0, None);
+        second_half.sides_switched = true;
+        let economy = compute_economy(&[second_half], &[], &team(10, T, 800, 0, false));
+        assert_eq!(economy[0].buy_type, BuyType::Pistol);
+    }
+    #[test]
+    fn test_team_totals() {
+        let mut snapshots = team(1010, CT, 2500, 200, false);
+        // Bought more after freeze time
+        snapshots.extend(team(1990, CT, 2500, 0, false).into_iter().map(|mut s| {
+            s.cash_spent_this_round = 2700;
+            s
+        }));
+        let purchase = |tick: i32| event("item_purchase", tick, vec![("team", Variant::I32(CT))]);
+        let events = vec![purchase(1005), purchase(1500), purchase(2500)];
+        let economy = compute_economy(&rounds()[1..2], &events, &snapshots);
+        let ct = &economy[0];
+        assert_eq!(
+            (ct.players, ct.equipment_value, ct.money_left, ct.start_money),
+            (5, 12500, 1000, 13500)
+        );
+        assert_eq!((ct.money_spent, ct.cash_earned, ct.items_purchased), (13500, 7000, 2));
+    }
+    #[test]
+    fn test_loss_bonus() {
+        let economy = compute_economy(&rounds()[1..2], &[], &team(1010, T, 300, 2000, false));
+        assert_eq!((economy[0].loss_bonus_level, economy[0].loss_bonus), (2, 2400));
+    }
+    #[test]
+    fn test_team_without_players_left_out() {
+        let economy = compute_economy(&rounds()[..1], &[], &team(10, CT, 800, 0, false));
+        assert_eq!(buy_types(&economy), vec![(1, "CT", BuyType::Pistol)]);
+    }
+    #[test]
+    fn test_parse_economy_reads_players_at_round_boundaries() {
+        use crate::query::DemoQuery;
+        use crate::test_demo::TestDemo;
+        use crate::variants::BytesVariant;
+
+        let mut demo = TestDemo::new();
+        demo.add_teams_and_rules();
+        demo.set(40, "m_pGameRules.m_iNumConsecutiveTerroristLoses", 1);
+        demo.add_player(1, 76561198000000001, "alice", T);
+        demo.add_player(2, 76561198000000002, "bob", CT);
+        for controller in [2, 3] {
+            demo.set(controller, "m_pInGameMoneyServices.m_iStartAccount", 800);
+            demo.set(controller, "m_pInGameMoneyServices.m_iAccount", 800);
+        }
+        demo.packet(1);
+        demo.full_packet(2);
+        demo.event("round_start", &[]);
+        demo.packet(10);
+        demo.event(
+            "item_purchase",
+            &[("userid", 1.into()), ("team", T.into()), ("weapon", "glock".into())],
+        );
+        demo.set(2, "m_pInGameMoneyServices.m_iAccount", 600);
+        demo.set(2, "m_pInGameMoneyServices.m_iCashSpentThisRound", 200);
+        demo.set(101, "m_unCurrentEquipmentValue", 400);
+        demo.packet(15);
+        demo.event("round_freeze_end", &[]);
+        demo.packet(20);
+        demo.event("round_end", &[("winner", T.into()), ("reason", 9.into())]);
+        demo.set(2, "m_pActionTrackingServices.m_perRoundStats.m_iCashEarned", 3250);
+        demo.packet(40);
+        demo.event("round_officially_ended", &[]);
+        demo.packet(50);
+        let bytes = demo.finish(60);
+
+        let economy = DemoQuery::new()
+            .parser(BytesVariant::Vec(bytes))
+            .unwrap()
+            .parse_economy()
+            .unwrap();
+        let sides: Vec<&str> = economy.iter().map(|e| e.side.as_str()).collect();
+        assert_eq!(sides, vec!["T", "CT"]);
+        let t = &economy[0];
+        assert_eq!(
+            (t.start_money, t.money_left, t.money_spent, t.cash_earned, t.equipment_value),
+            (800, 600, 200, 3250, 400)
+        );
+        assert_eq!((t.items_purchased, t.loss_bonus_level, t.buy_type), (1, 1, BuyType::Pistol));
+        assert_eq!((economy[1].money_left, economy[1].items_purchased), (800, 0));
+    }
+}
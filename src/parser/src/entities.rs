@@ -1,6 +1,7 @@
 use super::read_bits::DemoParserError;
 use crate::entities_utils::*;
 use crate::parser_thread_settings::ParserThread;
+use crate::prop_changes::PropChange;
 use crate::read_bits::Bitreader;
 use crate::sendtables::DebugFieldAndPath;
 use crate::variants::Variant;
@@ -74,15 +75,35 @@ impl ParserThread {
             };
             match cmd {
                 EntityCmd::Delete => {
+                    if !self.tracked_props.is_empty() && self.track_writes {
+                        self.chunk_state.reset_entity(entity_id);
+                    }
+                    self.create_custom_event_entity_lifecycle("entity_deleted", entity_id);
+                    self.record_entity_removed(entity_id);
                     self.projectiles.remove(&entity_id);
                     self.entities.remove(&entity_id);
                 }
                 EntityCmd::CreateAndUpdate => {
+                    if !self.tracked_props.is_empty() && self.track_writes {
+                        self.chunk_state.reset_entity(entity_id);
+                    }
+                    // Id reused without a delete
+                    if self.entities.contains_key(&entity_id) {
+                        self.create_custom_event_entity_lifecycle("entity_deleted", entity_id);
+                        self.record_entity_removed(entity_id);
+                    }
                     self.create_new_entity(&mut bitreader, &entity_id)?;
                     self.update_entity(&mut bitreader, entity_id, false)?;
+                    self.create_custom_event_entity_lifecycle("entity_created", entity_id);
+                    if self.has_entity_callbacks() {
+                        self.emit_entity(entity_id);
+                    }
                 }
                 EntityCmd::Update => {
                     self.update_entity(&mut bitreader, entity_id, false)?;
+                    if self.has_entity_callbacks() {
+                        self.emit_entity(entity_id);
+                    }
                 }
             }
         }
@@ -116,6 +137,21 @@ impl ParserThread {
             for field_info in &self.field_infos[..n_updates] {
                 let result = bitreader.decode(&field_info.decoder, &self.qf_mapper)?;
                 if field_info.should_parse {
+                    if !self.tracked_props.is_empty() && self.track_writes && self.tracked_props.contains(&field_info.prop_id) {
+                        self.chunk_state.write(entity_id, field_info.prop_id, result.clone());
+                    }
+                    if !self.change_tracked_props.is_empty()
+                        && self.track_writes
+                        && self.change_tracked_props.contains(&field_info.prop_id)
+                        && entity.props.get(&field_info.prop_id) != Some(&result)
+                    {
+                        self.prop_changes.push(PropChange {
+                            tick: self.tick,
+                            entity_id,
+                            prop_id: field_info.prop_id,
+                            value: Some(result.clone()),
+                        });
+                    }
                     entity.props.insert(field_info.prop_id as u32, result);
                 }
             }
@@ -217,16 +253,19 @@ impl ParserThread {
                 break;
             }
             do_op(symbol, bitreader, &mut fp)?;
+            if idx >= self.field_infos.len() {
+                return Err(DemoParserError::InvalidFieldPath(format!("more than {} field updates", idx)));
+            }
 
             if self.is_debug_mode {
                 self.debug_fields[idx] = DebugFieldAndPath {
-                    field: class.serializer.debug_find_decoder(&fp, 0, class.name.to_string()),
+                    field: class.serializer.debug_find_decoder(&fp, 0, class.name.to_string())?,
                     path: fp.path.clone(),
                 };
             }
             // We reuse one big vector for holding paths. Purely for performance.
             // Alternatively we could create a new vector in this function and return it.
-            self.field_infos[idx] = class.serializer.find_decoder(&fp, 0, self.parse_inventory);
+            self.field_infos[idx] = class.serializer.find_decoder(&fp, 0, self.parse_inventory)?;
             idx += 1;
         }
         Ok(idx)
@@ -244,9 +283,7 @@ impl ParserThread {
         }
 
         if entity.entity_type == EntityType::Team && !is_baseline {
-            if let Ok(Variant::U32(t)) =
-                self.get_prop_from_ent(self.prop_controller.special_ids.team_team_num.as_ref().unwrap(), entity_id)
-            {
+            if let Ok(Variant::U32(t)) = self.get_special_prop(self.prop_controller.special_ids.team_team_num, entity_id) {
                 match t {
                     1 => self.teams.team1_entid = Some(*entity_id),
                     2 => self.teams.team2_entid = Some(*entity_id),
@@ -257,7 +294,7 @@ impl ParserThread {
             return Ok(());
         }
 
-        let team_num = match self.get_prop_from_ent(self.prop_controller.special_ids.teamnum.as_ref().unwrap(), entity_id) {
+        let team_num = match self.get_special_prop(self.prop_controller.special_ids.teamnum, entity_id) {
             Ok(team_num) => match team_num {
                 Variant::U32(team_num) => Some(team_num),
                 // Signals that something went very wrong
@@ -265,22 +302,21 @@ impl ParserThread {
             },
             Err(_) => None,
         };
-        let name = match self.get_prop_from_ent(self.prop_controller.special_ids.player_name.as_ref().unwrap(), entity_id) {
+        let name = match self.get_special_prop(self.prop_controller.special_ids.player_name, entity_id) {
             Ok(name) => match name {
                 Variant::String(name) => Some(name),
                 _ => return Err(DemoParserError::IncorrectMetaDataProp),
             },
             Err(_) => None,
         };
-        let steamid = match self.get_prop_from_ent(self.prop_controller.special_ids.steamid.as_ref().unwrap(), entity_id) {
+        let steamid = match self.get_special_prop(self.prop_controller.special_ids.steamid, entity_id) {
             Ok(steamid) => match steamid {
                 Variant::U64(steamid) => Some(steamid),
                 _ => return Err(DemoParserError::IncorrectMetaDataProp),
             },
             Err(_) => None,
         };
-        let player_entid = match self.get_prop_from_ent(self.prop_controller.special_ids.player_pawn.as_ref().unwrap(), entity_id)
-        {
+        let player_entid = match self.get_special_prop(self.prop_controller.special_ids.player_pawn, entity_id) {
             Ok(player_entid) => match player_entid {
                 Variant::U32(handle) => Some((handle & 0x7FF) as i32),
                 _ => return Err(DemoParserError::IncorrectMetaDataProp),
@@ -56,11 +56,23 @@ pub struct FieldPath {
     pub last: usize,
 }
 impl FieldPath {
-    pub fn pop_special(&mut self, n: usize) {
+    // Paths come from the bitstream, corrupt data could go past either end
+    pub fn pop_special(&mut self, n: usize) -> Result<(), DemoParserError> {
+        if n > self.last {
+            return Err(DemoParserError::InvalidFieldPath(format!("pop {} from {:?}", n, self)));
+        }
         for _ in 0..n {
             self.path[self.last] = 0;
             self.last -= 1;
         }
+        Ok(())
+    }
+    pub fn push(&mut self) -> Result<(), DemoParserError> {
+        if self.last + 1 >= self.path.len() {
+            return Err(DemoParserError::InvalidFieldPath(format!("push onto {:?}", self)));
+        }
+        self.last += 1;
+        Ok(())
     }
 }
 
@@ -90,41 +102,41 @@ fn plus_n(bitreader: &mut Bitreader, field_path: &mut FieldPath) -> Result<(), D
 }
 
 fn push_one_left_delta_zero_right_zero(_bitreader: &mut Bitreader, field_path: &mut FieldPath) -> Result<(), DemoParserError> {
-    field_path.last += 1;
+    field_path.push()?;
     field_path.path[field_path.last] = 0;
     Ok(())
 }
 
 fn push_one_left_delta_zero_right_non_zero(bitreader: &mut Bitreader, field_path: &mut FieldPath) -> Result<(), DemoParserError> {
-    field_path.last += 1;
+    field_path.push()?;
     field_path.path[field_path.last] += bitreader.read_ubit_var_fp()? as i32;
     Ok(())
 }
 
 fn push_one_left_delta_one_right_zero(_bitreader: &mut Bitreader, field_path: &mut FieldPath) -> Result<(), DemoParserError> {
     field_path.path[field_path.last] += 1;
-    field_path.last += 1;
+    field_path.push()?;
     field_path.path[field_path.last] = 0;
     Ok(())
 }
 
 fn push_one_left_delta_one_right_non_zero(bitreader: &mut Bitreader, field_path: &mut FieldPath) -> Result<(), DemoParserError> {
     field_path.path[field_path.last] += 1;
-    field_path.last += 1;
+    field_path.push()?;
     field_path.path[field_path.last] = bitreader.read_ubit_var_fp()? as i32;
     Ok(())
 }
 
 fn push_one_left_delta_n_right_zero(bitreader: &mut Bitreader, field_path: &mut FieldPath) -> Result<(), DemoParserError> {
     field_path.path[field_path.last] += bitreader.read_ubit_var_fp()? as i32;
-    field_path.last += 1;
+    field_path.push()?;
     field_path.path[field_path.last] = 0;
     Ok(())
 }
 
 fn push_one_left_delta_n_right_non_zero(bitreader: &mut Bitreader, field_path: &mut FieldPath) -> Result<(), DemoParserError> {
     field_path.path[field_path.last] += bitreader.read_ubit_var_fp()? as i32 + 2;
-    field_path.last += 1;
+    field_path.push()?;
     field_path.path[field_path.last] = bitreader.read_ubit_var_fp()? as i32 + 1;
     Ok(())
 }
@@ -134,7 +146,7 @@ fn push_one_left_delta_n_right_non_zero_pack6_bits(
     field_path: &mut FieldPath,
 ) -> Result<(), DemoParserError> {
     field_path.path[field_path.last] += (bitreader.read_nbits(3)? + 2) as i32;
-    field_path.last += 1;
+    field_path.push()?;
     field_path.path[field_path.last] = (bitreader.read_nbits(3)? + 1) as i32;
     Ok(())
 }
@@ -144,123 +156,123 @@ fn push_one_left_delta_n_right_non_zero_pack8_bits(
     field_path: &mut FieldPath,
 ) -> Result<(), DemoParserError> {
     field_path.path[field_path.last] += (bitreader.read_nbits(4)? + 2) as i32;
-    field_path.last += 1;
+    field_path.push()?;
     field_path.path[field_path.last] = (bitreader.read_nbits(4)? + 1) as i32;
     Ok(())
 }
 
 fn push_two_left_delta_zero(bitreader: &mut Bitreader, field_path: &mut FieldPath) -> Result<(), DemoParserError> {
-    field_path.last += 1;
+    field_path.push()?;
     field_path.path[field_path.last] += bitreader.read_ubit_var_fp()? as i32;
-    field_path.last += 1;
+    field_path.push()?;
     field_path.path[field_path.last] += bitreader.read_ubit_var_fp()? as i32;
     Ok(())
 }
 
 fn push_two_pack5_left_delta_zero(bitreader: &mut Bitreader, field_path: &mut FieldPath) -> Result<(), DemoParserError> {
-    field_path.last += 1;
+    field_path.push()?;
     field_path.path[field_path.last] = bitreader.read_nbits(5)? as i32;
-    field_path.last += 1;
+    field_path.push()?;
     field_path.path[field_path.last] = bitreader.read_nbits(5)? as i32;
     Ok(())
 }
 
 fn push_three_left_delta_zero(bitreader: &mut Bitreader, field_path: &mut FieldPath) -> Result<(), DemoParserError> {
-    field_path.last += 1;
+    field_path.push()?;
     field_path.path[field_path.last] += bitreader.read_ubit_var_fp()? as i32;
-    field_path.last += 1;
+    field_path.push()?;
     field_path.path[field_path.last] += bitreader.read_ubit_var_fp()? as i32;
-    field_path.last += 1;
+    field_path.push()?;
     field_path.path[field_path.last] += bitreader.read_ubit_var_fp()? as i32;
     Ok(())
 }
 
 fn push_three_pack5_left_delta_zero(bitreader: &mut Bitreader, field_path: &mut FieldPath) -> Result<(), DemoParserError> {
-    field_path.last += 1;
+    field_path.push()?;
     field_path.path[field_path.last] = bitreader.read_nbits(5)? as i32;
-    field_path.last += 1;
+    field_path.push()?;
     field_path.path[field_path.last] = bitreader.read_nbits(5)? as i32;
-    field_path.last += 1;
+    field_path.push()?;
     field_path.path[field_path.last] = bitreader.read_nbits(5)? as i32;
     Ok(())
 }
 
 fn push_two_left_delta_one(bitreader: &mut Bitreader, field_path: &mut FieldPath) -> Result<(), DemoParserError> {
     field_path.path[field_path.last] += 1;
-    field_path.last += 1;
+    field_path.push()?;
     field_path.path[field_path.last] += bitreader.read_ubit_var_fp()? as i32;
-    field_path.last += 1;
+    field_path.push()?;
     field_path.path[field_path.last] += bitreader.read_ubit_var_fp()? as i32;
     Ok(())
 }
 
 fn push_two_pack5_left_delta_one(bitreader: &mut Bitreader, field_path: &mut FieldPath) -> Result<(), DemoParserError> {
     field_path.path[field_path.last] += 1;
-    field_path.last += 1;
+    field_path.push()?;
     field_path.path[field_path.last] += bitreader.read_nbits(5)? as i32;
-    field_path.last += 1;
+    field_path.push()?;
     field_path.path[field_path.last] += bitreader.read_nbits(5)? as i32;
     Ok(())
 }
 
 fn push_three_left_delta_one(bitreader: &mut Bitreader, field_path: &mut FieldPath) -> Result<(), DemoParserError> {
     field_path.path[field_path.last] += 1;
-    field_path.last += 1;
+    field_path.push()?;
     field_path.path[field_path.last] += bitreader.read_ubit_var_fp()? as i32;
-    field_path.last += 1;
+    field_path.push()?;
     field_path.path[field_path.last] += bitreader.read_ubit_var_fp()? as i32;
-    field_path.last += 1;
+    field_path.push()?;
     field_path.path[field_path.last] += bitreader.read_ubit_var_fp()? as i32;
     Ok(())
 }
 
 fn push_three_pack5_left_delta_one(bitreader: &mut Bitreader, field_path: &mut FieldPath) -> Result<(), DemoParserError> {
     field_path.path[field_path.last] += 1;
-    field_path.last += 1;
+    field_path.push()?;
     field_path.path[field_path.last] += bitreader.read_nbits(5)? as i32;
-    field_path.last += 1;
+    field_path.push()?;
     field_path.path[field_path.last] += bitreader.read_nbits(5)? as i32;
-    field_path.last += 1;
+    field_path.push()?;
     field_path.path[field_path.last] += bitreader.read_nbits(5)? as i32;
     Ok(())
 }
 
 fn push_two_left_delta_n(bitreader: &mut Bitreader, field_path: &mut FieldPath) -> Result<(), DemoParserError> {
     field_path.path[field_path.last] += (bitreader.read_u_bit_var()? + 2) as i32;
-    field_path.last += 1;
+    field_path.push()?;
     field_path.path[field_path.last] += bitreader.read_ubit_var_fp()? as i32;
-    field_path.last += 1;
+    field_path.push()?;
     field_path.path[field_path.last] += bitreader.read_ubit_var_fp()? as i32;
     Ok(())
 }
 
 fn push_two_pack5_left_delta_n(bitreader: &mut Bitreader, field_path: &mut FieldPath) -> Result<(), DemoParserError> {
     field_path.path[field_path.last] += (bitreader.read_u_bit_var()? + 2) as i32;
-    field_path.last += 1;
+    field_path.push()?;
     field_path.path[field_path.last] += bitreader.read_nbits(5)? as i32;
-    field_path.last += 1;
+    field_path.push()?;
     field_path.path[field_path.last] += bitreader.read_nbits(5)? as i32;
     Ok(())
 }
 
 fn push_three_left_delta_n(bitreader: &mut Bitreader, field_path: &mut FieldPath) -> Result<(), DemoParserError> {
     field_path.path[field_path.last] += (bitreader.read_u_bit_var()? + 2) as i32;
-    field_path.last += 1;
+    field_path.push()?;
     field_path.path[field_path.last] += bitreader.read_ubit_var_fp()? as i32;
-    field_path.last += 1;
+    field_path.push()?;
     field_path.path[field_path.last] += bitreader.read_ubit_var_fp()? as i32;
-    field_path.last += 1;
+    field_path.push()?;
     field_path.path[field_path.last] += bitreader.read_ubit_var_fp()? as i32;
     Ok(())
 }
 
 fn push_three_pack5_left_delta_n(bitreader: &mut Bitreader, field_path: &mut FieldPath) -> Result<(), DemoParserError> {
     field_path.path[field_path.last] += (bitreader.read_u_bit_var()? + 2) as i32;
-    field_path.last += 1;
+    field_path.push()?;
     field_path.path[field_path.last] += bitreader.read_nbits(5)? as i32;
-    field_path.last += 1;
+    field_path.push()?;
     field_path.path[field_path.last] += bitreader.read_nbits(5)? as i32;
-    field_path.last += 1;
+    field_path.push()?;
     field_path.path[field_path.last] += bitreader.read_nbits(5)? as i32;
     Ok(())
 }
@@ -269,7 +281,7 @@ fn push_n(bitreader: &mut Bitreader, field_path: &mut FieldPath) -> Result<(), D
     let n = bitreader.read_u_bit_var()? as i32;
     field_path.path[field_path.last] += bitreader.read_u_bit_var()? as i32;
     for _ in 0..n {
-        field_path.last += 1;
+        field_path.push()?;
         field_path.path[field_path.last] += bitreader.read_ubit_var_fp()? as i32;
     }
     Ok(())
@@ -283,62 +295,62 @@ fn push_n_and_non_topological(bitreader: &mut Bitreader, field_path: &mut FieldP
     }
     let count = bitreader.read_u_bit_var()?;
     for _ in 0..count {
-        field_path.last += 1;
+        field_path.push()?;
         field_path.path[field_path.last] = bitreader.read_ubit_var_fp()? as i32;
     }
     Ok(())
 }
 
 fn pop_one_plus_one(_bitreader: &mut Bitreader, field_path: &mut FieldPath) -> Result<(), DemoParserError> {
-    field_path.pop_special(1);
+    field_path.pop_special(1)?;
     field_path.path[field_path.last] += 1;
     Ok(())
 }
 
 fn pop_one_plus_n(bitreader: &mut Bitreader, field_path: &mut FieldPath) -> Result<(), DemoParserError> {
-    field_path.pop_special(1);
+    field_path.pop_special(1)?;
     field_path.path[field_path.last] += bitreader.read_ubit_var_fp()? as i32 + 1;
     Ok(())
 }
 
 fn pop_all_but_one_plus_one(_bitreader: &mut Bitreader, field_path: &mut FieldPath) -> Result<(), DemoParserError> {
-    field_path.pop_special(field_path.last);
+    field_path.pop_special(field_path.last)?;
     field_path.path[0] += 1;
     Ok(())
 }
 
 fn pop_all_but_one_plus_n(bitreader: &mut Bitreader, field_path: &mut FieldPath) -> Result<(), DemoParserError> {
-    field_path.pop_special(field_path.last);
+    field_path.pop_special(field_path.last)?;
     field_path.path[0] += bitreader.read_ubit_var_fp()? as i32 + 1;
     Ok(())
 }
 
 fn pop_all_but_one_plus_n_pack3_bits(bitreader: &mut Bitreader, field_path: &mut FieldPath) -> Result<(), DemoParserError> {
-    field_path.pop_special(field_path.last);
+    field_path.pop_special(field_path.last)?;
     field_path.path[0] += bitreader.read_nbits(3)? as i32 + 1;
     Ok(())
 }
 
 fn pop_all_but_one_plus_n_pack6_bits(bitreader: &mut Bitreader, field_path: &mut FieldPath) -> Result<(), DemoParserError> {
-    field_path.pop_special(field_path.last);
+    field_path.pop_special(field_path.last)?;
     field_path.path[0] += bitreader.read_nbits(6)? as i32 + 1;
     Ok(())
 }
 
 fn pop_n_plus_one(bitreader: &mut Bitreader, field_path: &mut FieldPath) -> Result<(), DemoParserError> {
-    field_path.pop_special(bitreader.read_ubit_var_fp()? as usize);
+    field_path.pop_special(bitreader.read_ubit_var_fp()? as usize)?;
     field_path.path[field_path.last] += 1;
     Ok(())
 }
 
 fn pop_n_plus_n(bitreader: &mut Bitreader, field_path: &mut FieldPath) -> Result<(), DemoParserError> {
-    field_path.pop_special(bitreader.read_ubit_var_fp()? as usize);
+    field_path.pop_special(bitreader.read_ubit_var_fp()? as usize)?;
     field_path.path[field_path.last] += bitreader.read_varint32()?;
     Ok(())
 }
 
 fn pop_n_and_non_topographical(bitreader: &mut Bitreader, field_path: &mut FieldPath) -> Result<(), DemoParserError> {
-    field_path.pop_special(bitreader.read_ubit_var_fp()? as usize);
+    field_path.pop_special(bitreader.read_ubit_var_fp()? as usize)?;
     for i in 0..field_path.last + 1 {
         if bitreader.read_boolean()? {
             field_path.path[i] += bitreader.read_varint32()?;
@@ -357,6 +369,12 @@ fn non_topo_complex(bitreader: &mut Bitreader, field_path: &mut FieldPath) -> Re
 }
 
 fn non_topo_penultimate_plus_one(_bitreader: &mut Bitreader, field_path: &mut FieldPath) -> Result<(), DemoParserError> {
+    if field_path.last == 0 {
+        return Err(DemoParserError::InvalidFieldPath(format!(
+            "no penultimate in {:?}",
+            field_path
+        )));
+    }
     field_path.path[field_path.last - 1] += 1;
     Ok(())
 }
@@ -415,3 +433,32 @@ value, weight, len(prefix), prefix
 1	10334	5	1110
 11	10530	5	1111
 */
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_corrupt_field_path_ops_are_errors() {
+        let mut bitreader = Bitreader::new(&[]);
+        let mut fp = FieldPath { path: [0; 7], last: 0 };
+        // Pushing past the 7 levels a path can have
+        for _ in 0..6 {
+            do_op(5, &mut bitreader, &mut fp).unwrap();
+        }
+        assert!(matches!(
+            do_op(5, &mut bitreader, &mut fp),
+            Err(DemoParserError::InvalidFieldPath(_))
+        ));
+        // Popping more than there is
+        let mut fp = FieldPath { path: [0; 7], last: 0 };
+        assert!(matches!(
+            do_op(27, &mut bitreader, &mut fp),
+            Err(DemoParserError::InvalidFieldPath(_))
+        ));
+        assert!(matches!(
+            do_op(37, &mut bitreader, &mut fp),
+            Err(DemoParserError::InvalidFieldPath(_))
+        ));
+    }
+}
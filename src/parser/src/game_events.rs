@@ -5,6 +5,7 @@ use crate::parser_thread_settings::ParserThread;
 use crate::prop_controller::PropInfo;
 use crate::read_bits::DemoParserError;
 use crate::stringtables::UserInfo;
+use crate::tick_selectors::variant_to_i64;
 use crate::variants::*;
 use ahash::AHashMap;
 use ahash::RandomState;
@@ -38,7 +39,10 @@ const STEAMID64INDIVIDUALIDENTIFIER: u64 = 0x0110000100000000;
 impl Parser {
     // Message that should come before first game event
     pub fn parse_game_event_list(&mut self, bytes: &[u8]) -> Result<AHashMap<i32, Descriptor_t>, DemoParserError> {
-        let event_list: CSVCMsg_GameEventList = Message::parse_from_bytes(bytes).unwrap();
+        let event_list: CSVCMsg_GameEventList = match Message::parse_from_bytes(bytes) {
+            Ok(list) => list,
+            Err(_e) => return Err(DemoParserError::MalformedMessage),
+        };
         let mut hm: AHashMap<i32, Descriptor_t, RandomState> = AHashMap::default();
         for event_desc in event_list.descriptors {
             hm.insert(event_desc.eventid(), event_desc);
@@ -47,7 +51,10 @@ impl Parser {
     }
     pub fn parse_fallback_event_list(&mut self) -> Result<(), DemoParserError> {
         use crate::fallbackbytes::GAME_EVENT_LIST_FALLBACK_BYTES;
-        let event_list: CSVCMsg_GameEventList = Message::parse_from_bytes(&GAME_EVENT_LIST_FALLBACK_BYTES).unwrap();
+        let event_list: CSVCMsg_GameEventList = match Message::parse_from_bytes(&GAME_EVENT_LIST_FALLBACK_BYTES) {
+            Ok(list) => list,
+            Err(_e) => return Err(DemoParserError::MalformedMessage),
+        };
         for event_desc in event_list.descriptors {
             self.ge_list.insert(event_desc.eventid(), event_desc);
         }
@@ -60,7 +67,10 @@ impl ParserThread {
         if self.wanted_events.len() == 0 && self.wanted_events.first() != Some(&"all".to_string()) {
             return Ok(None);
         }
-        let event: CSVCMsg_GameEvent = Message::parse_from_bytes(&bytes).unwrap();
+        let event: CSVCMsg_GameEvent = match Message::parse_from_bytes(&bytes) {
+            Ok(event) => event,
+            Err(_e) => return Err(DemoParserError::MalformedMessage),
+        };
         // Check if this events id is found in our game event list
         let event_desc = match self.ge_list.get(&event.eventid()) {
             Some(desc) => desc,
@@ -68,11 +78,10 @@ impl ParserThread {
                 return Ok(None);
             }
         };
-        self.game_events_counter.insert(event_desc.name.as_ref().unwrap().clone());
+        self.game_events_counter.insert(event_desc.name().to_string());
 
         // Return early if this is not a wanted event.
-        if !self.wanted_events.contains(&event_desc.name().to_string()) && self.wanted_events.first() != Some(&"all".to_string())
-        {
+        if !self.wanted_events.contains(&event_desc.name().to_string()) && !self.wanted_events.iter().any(|e| e == "all") {
             return Ok(None);
         }
         if REMOVEDEVENTS.contains(&event_desc.name()) {
@@ -99,6 +108,9 @@ impl ParserThread {
         } else {
             // Add extra fields
             event_fields.extend(self.find_extra(&event_fields)?);
+            if self.kill_labels && event_desc.name() == "player_death" {
+                event_fields.extend(self.kill_context_fields(&event_fields));
+            }
             // Remove fields that user does nothing with like userid and user_pawn
             event_fields.retain(|ref x| !INTERNALEVENTFIELDS.contains(&x.name.as_str()));
             let event = GameEvent {
@@ -106,7 +118,7 @@ impl ParserThread {
                 name: event_desc.name().to_string(),
                 tick: self.tick,
             };
-            self.game_events.push(event);
+            self.emit_event(event);
         }
         Ok(None)
     }
@@ -120,7 +132,7 @@ impl ParserThread {
                 name: event.name.to_string(),
                 tick: self.tick,
             };
-            self.game_events.push(event);
+            self.emit_event(event);
         }
         Ok(())
     }
@@ -145,7 +157,7 @@ impl ParserThread {
         if let Some(userinfo) = self.find_user_by_userid(userid) {
             for player in self.players.values() {
                 if player.steamid == Some(userinfo.steamid) {
-                    return Some(player.player_entity_id.unwrap());
+                    return player.player_entity_id;
                 }
             }
         }
@@ -155,7 +167,7 @@ impl ParserThread {
         if let Some(userinfo) = self.find_user_by_userid(userid) {
             for player in self.players.values() {
                 if player.steamid == Some(userinfo.steamid) {
-                    return Some(player.player_entity_id.unwrap());
+                    return player.player_entity_id;
                 }
             }
         }
@@ -391,7 +403,7 @@ impl ParserThread {
         for (_entid, player) in &self.players {
             if let Some(steamid) = player.steamid {
                 if steamid - STEAMID64INDIVIDUALIDENTIFIER == steamid32 as u64 {
-                    return Some(player.player_entity_id.unwrap());
+                    return player.player_entity_id;
                 }
             }
         }
@@ -428,7 +440,7 @@ impl ParserThread {
                 fields: fields,
                 tick: self.tick,
             };
-            self.game_events.push(ge);
+            self.emit_event(ge);
             self.game_events_counter.insert("server_cvar".to_string());
         }
         Ok(())
@@ -446,7 +458,7 @@ impl ParserThread {
         for update in update_msg.rank_update {
             let mut fields = vec![];
 
-            let entity_id = match self.player_from_steamid32(update.account_id.unwrap()) {
+            let entity_id = match self.player_from_steamid32(update.account_id()) {
                 Some(eid) => eid,
                 None => continue,
             };
@@ -480,12 +492,66 @@ impl ParserThread {
                 fields: fields,
                 tick: self.tick,
             };
-            self.game_events.push(ge);
+            self.emit_event(ge);
             self.game_events_counter.insert("rank_update".to_string());
         }
 
         Ok(())
     }
+    // "entity_created" / "entity_deleted", only when asked for by name. Created comes after the first update
+    // so the props are the initial values, deleted has the last values. Updates go to ParserCallbacks::on_entity.
+    pub fn create_custom_event_entity_lifecycle(&mut self, event_name: &str, entity_id: i32) {
+        if !self.game_events_counter.contains(event_name) {
+            self.game_events_counter.insert(event_name.to_string());
+        }
+        // Entities in the fullpacket a thread starts from were already created in the previous chunk
+        if !self.track_writes || !self.wanted_events.iter().any(|e| e == event_name) {
+            return;
+        }
+        let entity = match self.entities.get(&entity_id) {
+            Some(entity) => entity,
+            None => return,
+        };
+        let class_name = match self.cls_by_id.get(&entity.cls_id) {
+            Some(cls) => cls.name.clone(),
+            None => return,
+        };
+        let mut fields = vec![
+            EventField {
+                data: Some(Variant::I32(self.tick)),
+                name: "tick".to_string(),
+            },
+            EventField {
+                data: Some(Variant::I32(entity_id)),
+                name: "entity_id".to_string(),
+            },
+            EventField {
+                data: Some(Variant::String(class_name.clone())),
+                name: "class_name".to_string(),
+            },
+        ];
+        // Props from DemoQuery::entity_props that belong to this class
+        for name in &self.prop_controller.wanted_entity_props {
+            if name.split(".").next() != Some(class_name.as_str()) {
+                continue;
+            }
+            fields.push(EventField {
+                data: self
+                    .prop_controller
+                    .entity_prop_ids
+                    .get(name)
+                    .and_then(|id| entity.props.get(id))
+                    .cloned(),
+                name: name.clone(),
+            });
+        }
+        let ge = GameEvent {
+            name: event_name.to_string(),
+            fields,
+            tick: self.tick,
+        };
+        self.emit_event(ge);
+    }
 }
 // what is this shit
 fn parse_key(key: &Key_t) -> Option<Variant> {
@@ -518,16 +584,150 @@ pub struct GameEvent {
     pub tick: i32,
 }
 
+// Field accessors for code working on finished events (rounds, stats, damage, exports...)
+impl GameEvent {
+    pub fn field(&self, name: &str) -> Option<&Variant> {
+        self.fields.iter().find(|f| f.name == name).and_then(|f| f.data.as_ref())
+    }
+    pub fn int_field(&self, name: &str) -> Option<i64> {
+        variant_to_i64(self.field(name)?)
+    }
+    pub fn string_field(&self, name: &str) -> Option<&str> {
+        match self.field(name)? {
+            Variant::String(s) => Some(s),
+            _ => None,
+        }
+    }
+    // "<prefix>_steamid", a string in events. None for bots, they all have 0 and can't be told apart.
+    pub fn steamid(&self, prefix: &str) -> Option<u64> {
+        let steamid = match self.field(&format!("{}_steamid", prefix))? {
+            Variant::String(s) => s.parse().ok()?,
+            Variant::U64(s) => *s,
+            _ => return None,
+        };
+        match steamid {
+            0 => None,
+            s => Some(s),
+        }
+    }
+    pub fn player_name(&self, prefix: &str) -> Option<String> {
+        self.string_field(&format!("{}_name", prefix)).map(|s| s.to_string())
+    }
+}
+
+// Test fixtures for the modules working on events
+#[cfg(test)]
+pub fn test_event(name: &str, tick: i32, fields: Vec<(&str, Variant)>) -> GameEvent {
+    GameEvent {
+        name: name.to_string(),
+        tick,
+        fields: fields
+            .into_iter()
+            .map(|(name, v)| EventField {
+                name: name.to_string(),
+                data: Some(v),
+            })
+            .collect(),
+    }
+}
+// Steamids are strings in events
+#[cfg(test)]
+pub fn test_steamid(steamid: u64) -> Variant {
+    Variant::String(steamid.to_string())
+}
+#[cfg(test)]
+pub fn test_kill(tick: i32, attacker: u64, victim: u64, extra: Vec<(&str, Variant)>) -> GameEvent {
+    let mut fields = vec![
+        ("attacker_steamid", test_steamid(attacker)),
+        ("user_steamid", test_steamid(victim)),
+    ];
+    fields.extend(extra);
+    test_event("player_death", tick, fields)
+}
+#[cfg(test)]
+pub fn test_hurt(tick: i32, attacker: u64, victim: u64, dmg_health: i32, health: i32, extra: Vec<(&str, Variant)>) -> GameEvent {
+    let mut fields = vec![
+        ("attacker_steamid", test_steamid(attacker)),
+        ("user_steamid", test_steamid(victim)),
+        ("dmg_health", Variant::I32(dmg_health)),
+        ("health", Variant::I32(health)),
+    ];
+    fields.extend(extra);
+    test_event("player_hurt", tick, fields)
+}
+// Freeze time ends 10 ticks after the start, the round 900 ticks after it and the round end delay 90 ticks later
+#[cfg(test)]
+pub fn test_round(round: i32, start_tick: i32, winner: Option<&str>) -> crate::rounds::Round {
+    crate::rounds::Round {
+        round,
+        start_tick,
+        freeze_end_tick: Some(start_tick + 10),
+        end_tick: Some(start_tick + 900),
+        officially_ended_tick: Some(start_tick + 990),
+        winner: winner.map(|w| w.to_string()),
+        reason: None,
+        reason_code: None,
+        bomb_plant_tick: None,
+        bomb_defuse_tick: None,
+        t_score: 0,
+        ct_score: 0,
+        t_team: None,
+        ct_team: None,
+        sides_switched: false,
+        overtime: 0,
+    }
+}
+// (steamid, team_num, mvps) per player
+#[cfg(test)]
+pub fn test_player_snapshots(tick: i32, players: &[(u64, i32, i32)]) -> Vec<crate::stats::PlayerSnapshot> {
+    players
+        .iter()
+        .map(|(steamid, team_num, mvps)| crate::stats::PlayerSnapshot {
+            tick,
+            steamid: *steamid,
+            name: steamid.to_string(),
+            team_num: *team_num,
+            mvps: *mvps,
+            utility_damage_this_round: 0,
+        })
+        .collect()
+}
+// Five players with the same money and equipment, steamids team_num * 10 + 0..5
+#[cfg(test)]
+pub fn test_team_economy(
+    tick: i32,
+    team_num: i32,
+    equipment_value: i32,
+    balance: i32,
+    has_primary: bool,
+) -> Vec<crate::economy::EconomySnapshot> {
+    (0..5)
+        .map(|i| crate::economy::EconomySnapshot {
+            tick,
+            steamid: (team_num * 10 + i) as u64,
+            team_num,
+            start_balance: balance + equipment_value,
+            balance,
+            equipment_value,
+            cash_spent_this_round: equipment_value,
+            cash_earned_this_round: 1400,
+            has_primary,
+            t_losing_streak: 2,
+            ct_losing_streak: 0,
+        })
+        .collect()
+}
+
 impl Serialize for GameEvent {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: serde::Serializer,
     {
         let mut map = serializer.serialize_map(Some(2))?;
-        map.serialize_entry(&"tick", &self.tick).unwrap();
-        map.serialize_entry(&"event_name", &self.name).unwrap();
+        map.serialize_entry(&"tick", &self.tick)?;
+        map.serialize_entry(&"event_name", &self.name)?;
         for field in &self.fields {
-            map.serialize_entry(&field.name, &field.data).unwrap();
+            map.serialize_entry(&field.name, &field.data)?;
         }
         map.end()
     }
@@ -0,0 +1,325 @@
+use crate::game_events::EventField;
+use crate::game_events::GameEvent;
+use crate::parser_thread_settings::ParserThread;
+use crate::variants::Variant;
+use ahash::AHashMap;
+use ahash::AHashSet;
+
+// Opening kill / trade / traded labels for player_death events and derived clutch events (DemoQuery::kill_labels).
+// examples/kill_labels.rs shows it in use.
+
+// 5 seconds
+pub const DEFAULT_TRADE_WINDOW: i32 = 5 * 64;
+// Parsed even if not asked for and removed again afterwards
+pub const KILL_LABEL_EVENTS: [&str; 2] = ["player_death", "round_end"];
+const T: i64 = 2;
+const CT: i64 = 3;
+
+impl ParserThread {
+    // Added to every player_death while parsing: the round, the sides, how many are still alive on each side
+    // and the lone survivor when the victim's side is down to one player
+    pub fn kill_context_fields(&self, fields: &[EventField]) -> Vec<EventField> {
+        let entity_id = |name: &str| match fields.iter().find(|f| f.name == name).and_then(|f| f.data.as_ref()) {
+            Some(Variant::I32(userid)) => self.entity_id_from_userid(*userid),
+            _ => None,
+        };
+        let victim = entity_id("userid");
+        let attacker = entity_id("attacker");
+        let team = |entity_id: Option<i32>| self.players.get(&entity_id?)?.team_num.map(|t| t as i64);
+
+        let mut alive: AHashMap<i64, Vec<i32>> = AHashMap::default();
+        for (entity_id, player) in &self.players {
+            // The victim's pawn can still be alive on the tick of the event
+            if Some(*entity_id) == victim || !matches!(self.find_is_alive(entity_id), Ok(Variant::Bool(true))) {
+                continue;
+            }
+            if let Some(team) = player.team_num {
+                alive.entry(team as i64).or_default().push(*entity_id);
+            }
+        }
+        let alive_count = |team: i64| alive.get(&team).map_or(0, |a| a.len() as i32);
+        let victim_team = team(victim);
+        let lone_survivor = match victim_team {
+            Some(victim_team) if alive_count(victim_team) == 1 && alive_count(other_side(victim_team)) > 0 => {
+                self.players.get(&alive[&victim_team][0])
+            }
+            _ => None,
+        };
+        vec![
+            EventField {
+                name: "round".to_string(),
+                data: self.round_in_progress().map(Variant::I32),
+            },
+            EventField {
+                name: "attacker_side".to_string(),
+                data: team(attacker).and_then(side_name),
+            },
+            EventField {
+                name: "user_side".to_string(),
+                data: victim_team.and_then(side_name),
+            },
+            EventField {
+                name: "t_alive".to_string(),
+                data: Some(Variant::I32(alive_count(T))),
+            },
+            EventField {
+                name: "ct_alive".to_string(),
+                data: Some(Variant::I32(alive_count(CT))),
+            },
+            EventField {
+                name: "lone_survivor_steamid".to_string(),
+                data: lone_survivor.and_then(|p| p.steamid).map(|s| Variant::String(s.to_string())),
+            },
+            EventField {
+                name: "lone_survivor_name".to_string(),
+                data: lone_survivor.and_then(|p| p.name.clone()).map(Variant::String),
+            },
+        ]
+    }
+}
+
+struct Kill {
+    idx: usize,
+    tick: i32,
+    round: Option<i64>,
+    attacker: Option<u64>,
+    victim: Option<u64>,
+    attacker_side: Option<String>,
+    victim_side: Option<String>,
+}
+
+// Once all chunks are combined:
+//   opening_kill   first kill of the round
+//   trade          the victim had killed a teammate of the attacker within the trade window
+//   traded         a teammate of the victim killed the attacker within the trade window
+// and a "clutch" event after the kill that left a player alone against the other side, with the number of
+// enemies and if the clutch was won (from round_end). Needs the whole demo, so callbacks don't get labels.
+// Events must be in tick order (DemoOutput.game_events is)
+pub fn label_kills(events: &mut Vec<GameEvent>, trade_window: i32) {
+    let kills: Vec<Kill> = events
+        .iter()
+        .enumerate()
+        .filter(|(_, e)| e.name == "player_death")
+        .map(|(idx, e)| Kill {
+            idx,
+            tick: e.tick,
+            round: e.int_field("round"),
+            attacker: e.steamid("attacker"),
+            victim: e.steamid("user"),
+            attacker_side: e.string_field("attacker_side").map(|s| s.to_string()),
+            victim_side: e.string_field("user_side").map(|s| s.to_string()),
+        })
+        .collect();
+    let round_ends: Vec<(i32, Option<i64>)> = events
+        .iter()
+        .filter(|e| e.name == "round_end")
+        .map(|e| (e.tick, e.int_field("winner")))
+        .collect();
+
+    let mut opening_rounds = AHashSet::default();
+    let mut opening = AHashSet::default();
+    let mut trades = AHashSet::default();
+    let mut traded = AHashSet::default();
+    for (i, kill) in kills.iter().enumerate() {
+        let round = match kill.round {
+            Some(round) => round,
+            None => continue,
+        };
+        if kill.attacker_side.is_none() || kill.attacker_side == kill.victim_side {
+            continue;
+        }
+        if opening_rounds.insert(round) {
+            opening.insert(kill.idx);
+        }
+        for earlier in kills[..i].iter().rev().take_while(|k| kill.tick - k.tick <= trade_window) {
+            if earlier.round == kill.round
+                && earlier.attacker.is_some()
+                && earlier.attacker == kill.victim
+                && earlier.victim_side == kill.attacker_side
+            {
+                trades.insert(kill.idx);
+                traded.insert(earlier.idx);
+            }
+        }
+    }
+
+    // The first kill in a round that leaves someone alone starts the clutch
+    let mut clutch_rounds = AHashSet::default();
+    let mut clutches = vec![];
+    for kill in &kills {
+        let event = &events[kill.idx];
+        let steamid = match (kill.round, event.string_field("lone_survivor_steamid")) {
+            (Some(round), Some(steamid)) if clutch_rounds.insert(round) => steamid,
+            _ => continue,
+        };
+        let enemies = match kill.victim_side.as_deref() {
+            Some("T") => event.int_field("ct_alive"),
+            _ => event.int_field("t_alive"),
+        };
+        let winner = round_ends
+            .iter()
+            .find(|(tick, _)| *tick >= kill.tick)
+            .and_then(|(_, winner)| *winner);
+        let won = match (winner, kill.victim_side.as_deref()) {
+            (Some(winner), Some("T")) => Some(winner == T),
+            (Some(winner), Some("CT")) => Some(winner == CT),
+            _ => None,
+        };
+        let fields = vec![
+            ("round", kill.round.map(|r| Variant::I32(r as i32))),
+            ("user_steamid", Some(Variant::String(steamid.to_string()))),
+            ("user_name", event.player_name("lone_survivor").map(Variant::String)),
+            ("user_side", kill.victim_side.clone().map(Variant::String)),
+            ("enemies", enemies.map(|e| Variant::I32(e as i32))),
+            ("won", won.map(Variant::Bool)),
+        ];
+        clutches.push((
+            kill.idx,
+            GameEvent {
+                name: "clutch".to_string(),
+                tick: kill.tick,
+                fields: fields
+                    .into_iter()
+                    .map(|(name, data)| EventField {
+                        name: name.to_string(),
+                        data,
+                    })
+                    .collect(),
+            },
+        ));
+    }
+
+    for kill in &kills {
+        let event = &mut events[kill.idx];
+        for (name, label) in [
+            ("opening_kill", opening.contains(&kill.idx)),
+            ("trade", trades.contains(&kill.idx)),
+            ("traded", traded.contains(&kill.idx)),
+        ] {
+            event.fields.push(EventField {
+                name: name.to_string(),
+                data: Some(Variant::Bool(label)),
+            });
+        }
+    }
+    // Insert from the back so the indices stay valid
+    for (idx, clutch) in clutches.into_iter().rev() {
+        events.insert(idx + 1, clutch);
+    }
+}
+
+fn other_side(team: i64) -> i64 {
+    match team {
+        T => CT,
+        _ => T,
+    }
+}
+
+fn side_name(team: i64) -> Option<Variant> {
+    match team {
+        T => Some(Variant::String("T".to_string())),
+        CT => Some(Variant::String("CT".to_string())),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game_events::test_event as event;
+    use crate::game_events::test_kill;
+    use crate::game_events::test_steamid as steamid;
+
+    // Steamids: T 1-3, CT 11-13
+    fn kill(tick: i32, round: i32, attacker: u64, victim: u64, lone_survivor: Option<u64>) -> GameEvent {
+        let side = |steamid: u64| Variant::String(if steamid < 10 { "T" } else { "CT" }.to_string());
+        let mut fields = vec![
+            ("round", Variant::I32(round)),
+            ("attacker_side", side(attacker)),
+            ("user_side", side(victim)),
+            ("t_alive", Variant::I32(2)),
+            ("ct_alive", Variant::I32(1)),
+        ];
+        if let Some(lone_survivor) = lone_survivor {
+            fields.push(("lone_survivor_steamid", steamid(lone_survivor)));
+        }
+        test_kill(tick, attacker, victim, fields)
+    }
+    fn round_end(tick: i32, winner: i64) -> GameEvent {
+        event("round_end", tick, vec![("winner", Variant::I32(winner as i32))])
+    }
+    fn labels(event: &GameEvent) -> (bool, bool, bool) {
+        let label = |name: &str| event.int_field(name) == Some(1);
+        (label("opening_kill"), label("trade"), label("traded"))
+    }
+    fn labeled(mut events: Vec<GameEvent>) -> Vec<GameEvent> {
+        label_kills(&mut events, DEFAULT_TRADE_WINDOW);
+        events
+    }
+
+    #[test]
+    fn test_opening_kill_per_round() {
+        let events = labeled(vec![
+            kill(100, 1, 1, 11, None),
+            kill(200, 1, 2, 12, None),
+            kill(1100, 2, 12, 1, None),
+        ]);
+        let opening: Vec<bool> = events.iter().map(|e| labels(e).0).collect();
+        assert_eq!(opening, vec![true, false, true]);
+    }
+    #[test]
+    fn test_trade_window_edge() {
+        let events = labeled(vec![
+            kill(100, 1, 11, 1, None),
+            // Exactly at the end of the window
+            kill(100 + DEFAULT_TRADE_WINDOW, 1, 2, 11, None),
+            kill(200, 1, 12, 3, None),
+            // One tick too late
+            kill(201 + DEFAULT_TRADE_WINDOW, 1, 2, 12, None),
+        ]);
+        assert_eq!(labels(&events[0]), (true, false, true));
+        assert_eq!(labels(&events[1]), (false, true, false));
+        assert_eq!(labels(&events[2]), (false, false, false));
+        assert_eq!(labels(&events[3]), (false, false, false));
+    }
+    #[test]
+    fn test_trade_must_be_of_the_killer() {
+        // 3 kills 12 but 12 did not kill a T
+        let events = labeled(vec![kill(100, 1, 11, 1, None), kill(150, 1, 3, 12, None)]);
+        assert_eq!(labels(&events[1]), (false, false, false));
+        assert_eq!(labels(&events[0]), (true, false, false));
+    }
+    #[test]
+    fn test_clutch_lost() {
+        // 13 is left alone against two and the Ts win the round
+        let events = labeled(vec![
+            kill(300, 1, 3, 12, Some(13)),
+            kill(400, 1, 2, 13, None),
+            round_end(500, T),
+        ]);
+        let names: Vec<&str> = events.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, vec!["player_death", "clutch", "player_death", "round_end"]);
+        let clutch = &events[1];
+        assert_eq!(clutch.string_field("user_steamid"), Some("13"));
+        assert_eq!(clutch.string_field("user_side"), Some("CT"));
+        assert_eq!(clutch.int_field("enemies"), Some(2));
+        assert_eq!(clutch.int_field("won"), Some(0));
+    }
+    #[test]
+    fn test_clutch_won() {
+        let events = labeled(vec![kill(300, 1, 3, 12, Some(13)), round_end(500, CT)]);
+        assert_eq!(events[1].int_field("won"), Some(1));
+    }
+    #[test]
+    fn test_one_clutch_per_round() {
+        let events = labeled(vec![kill(300, 1, 3, 12, Some(13)), kill(350, 1, 13, 2, Some(13))]);
+        assert_eq!(events.iter().filter(|e| e.name == "clutch").count(), 1);
+    }
+    #[test]
+    fn test_bots_are_not_traded() {
+        // Two different bots, both with steamid 0
+        let events = labeled(vec![kill(100, 1, 0, 11, None), kill(150, 1, 12, 0, None)]);
+        assert_eq!(labels(&events[0]), (true, false, false));
+        assert_eq!(labels(&events[1]), (false, false, false));
+    }
+}
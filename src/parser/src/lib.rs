@@ -1,20 +1,46 @@
+#[cfg(feature = "arrow")]
+pub mod arrow_export;
+pub mod batch;
+pub mod callbacks;
+pub mod chunk_seeds;
+pub mod class_cache;
 pub mod collect_data;
+pub mod damage;
 pub mod decoder;
+pub mod economy;
 pub mod entities;
 pub mod entities_utils;
 pub mod fallbackbytes;
 pub mod game_events;
+pub mod kill_labels;
 pub mod maps;
 pub mod netmessage_types;
 pub mod other_netmessages;
+#[cfg(feature = "parquet")]
+pub mod parquet_export;
 pub mod parser;
 pub mod parser_settings;
 pub mod parser_thread_settings;
 pub mod parser_threads;
+pub mod prop_changes;
 pub mod prop_controller;
 pub mod q_float;
+pub mod query;
 pub mod read_bits;
 pub mod read_bytes;
+pub mod rounds;
+pub mod schema;
+pub mod seek;
 pub mod sendtables;
+pub mod sidecar;
+#[cfg(feature = "sqlite")]
+pub mod sqlite_export;
+pub mod stats;
+pub mod stream_writers;
 pub mod stringtables;
+#[cfg(test)]
+pub mod test_demo;
+pub mod tick_iterator;
+pub mod tick_selectors;
 pub mod variants;
+pub mod warnings;
@@ -0,0 +1,1022 @@
+use ahash::AHashMap;
+use clap::Args;
+use clap::Parser as ClapParser;
+use clap::Subcommand;
+use clap::ValueEnum;
+use itertools::Itertools;
+use parser::damage::DamageRecord;
+use parser::economy::TeamEconomy;
+use parser::maps::FRIENDLY_NAMES_MAPPING;
+use parser::parser::DemoOutput;
+use parser::parser_settings::Parser;
+use parser::query::DemoQuery;
+use parser::read_bits::DemoParserError;
+use parser::rounds::Round;
+use parser::schema::PropSchema;
+use parser::stats::MatchStats;
+use parser::stream_writers::json_object;
+use parser::stream_writers::json_value;
+use parser::stream_writers::StreamFormat;
+use parser::stream_writers::StreamWriter;
+use parser::tick_selectors::TickSelector;
+use parser::variants::Variant;
+use std::error::Error;
+use std::fs::File;
+use std::io::BufWriter;
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+
+// demoparser <subcommand> <demo> [--format csv|json|ndjson|parquet] [-o file]
+//
+// Every subcommand produces a table (columns + rows of optional Variants) that is then written
+// in the wanted format. Output goes to stdout unless -o is given, parquet always needs -o.
+
+#[derive(ClapParser)]
+#[command(name = "demoparser", version, about = "Parse CS2 demos into tables")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+    /// Output format
+    #[arg(short, long, value_enum, default_value_t = Format::Csv, global = true)]
+    format: Format,
+    /// Write to this file instead of stdout
+    #[arg(short, long, global = true)]
+    output: Option<PathBuf>,
+    /// Return what can be parsed from truncated or corrupted demos, problems are printed to stderr
+    #[arg(long, global = true)]
+    lenient: bool,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Demo header (map, server name, build...)
+    Header { demo: String },
+    /// Game events, "all" or a comma separated list of event names
+    Events {
+        demo: String,
+        name: String,
+        /// Player props added to every player in the event (user_X, attacker_X ...)
+        #[arg(long, value_delimiter = ',')]
+        player_props: Vec<String>,
+        /// Non-player props added to the events
+        #[arg(long, value_delimiter = ',')]
+        other_props: Vec<String>,
+        /// Entity props added to entity_created / entity_deleted events, "CPlantedC4.m_bBombTicking"
+        #[arg(long, value_delimiter = ',')]
+        entity_props: Vec<String>,
+        /// Label player_death as opening kill / trade / traded and add clutch events, trade window in ticks
+        #[arg(long, num_args = 0..=1, default_missing_value = "320", conflicts_with = "stream")]
+        kill_labels: Option<i32>,
+        /// Write events while parsing (csv/ndjson), single threaded but memory use stays flat
+        #[arg(long)]
+        stream: bool,
+    },
+    /// Player props for every player at every (or the wanted) tick
+    Ticks {
+        demo: String,
+        #[arg(long, value_delimiter = ',', required = true)]
+        props: Vec<String>,
+        #[command(flatten)]
+        ticks: TickArgs,
+        /// Write rows while parsing (csv/ndjson), single threaded but memory use stays flat
+        #[arg(long)]
+        stream: bool,
+        /// Only write a row when a prop gets a new value: tick, entity_id, prop, value
+        #[arg(long, conflicts_with = "stream")]
+        changes: bool,
+    },
+    /// Raw props of any entity class for every entity of that class, "CPlantedC4.m_bBombTicking"
+    Entities {
+        demo: String,
+        #[arg(long, value_delimiter = ',', required = true)]
+        props: Vec<String>,
+        #[command(flatten)]
+        ticks: TickArgs,
+    },
+    /// One row per round: ticks, winner, end reason, bomb plant/defuse and score
+    Rounds { demo: String },
+    /// Money and equipment per team per round with a buy classification (pistol, eco, force, half, full)
+    Economy { demo: String },
+    /// Scoreboard: K/D/A, ADR, KAST, entries, multi-kills, clutches, utility, MVPs and rating per player
+    Stats {
+        demo: String,
+        /// One row per player per round instead of match totals
+        #[arg(long)]
+        per_round: bool,
+    },
+    /// One row per hit: damage clamped to the victim's health, hitgroup, wallbangs, distance and if the victim was flashed
+    Damage { demo: String },
+    /// Grenade positions for every tick they are in the air
+    Grenades { demo: String },
+    /// Chat messages
+    Chat { demo: String },
+    /// Console vars set during the demo
+    Convars { demo: String },
+    /// Names of the game events found in the demo
+    ListEvents { demo: String },
+    /// Props that can be asked for with --props, with a demo every raw prop in its sendtables
+    ListProps { demo: Option<String> },
+}
+
+// Which ticks to collect, every tick if none are given
+#[derive(Args)]
+struct TickArgs {
+    /// Only these ticks
+    #[arg(long, value_delimiter = ',')]
+    ticks: Vec<i32>,
+    /// Every tick in START-END
+    #[arg(long, value_parser = parse_tick_range)]
+    range: Vec<TickSelector>,
+    /// Rounds FIRST-LAST from freeze time end to round end, first round is 1
+    #[arg(long, value_parser = parse_rounds)]
+    rounds: Vec<TickSelector>,
+    /// EVENT:BEFORE:AFTER, the ticks around every event with this name
+    #[arg(long, value_parser = parse_around_event)]
+    around: Vec<TickSelector>,
+    /// Every Nth tick, counted from the start of each range/round
+    #[arg(long)]
+    every: Option<i32>,
+}
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+enum Format {
+    Csv,
+    Json,
+    Ndjson,
+    Parquet,
+}
+
+struct Table {
+    columns: Vec<String>,
+    rows: Vec<Vec<Option<Variant>>>,
+}
+
+fn main() {
+    let cli = Cli::parse();
+    if let Err(e) = run(&cli) {
+        eprintln!("demoparser: {}", e);
+        std::process::exit(1);
+    }
+}
+
+fn run(cli: &Cli) -> Result<(), Box<dyn Error>> {
+    let table = match &cli.command {
+        Command::Header { demo } => {
+            let (_, header) = parse(cli, demo, DemoQuery::new().entities(false))?;
+            let columns: Vec<String> = header.keys().sorted().cloned().collect();
+            let row = columns.iter().map(|k| header.get(k).cloned().map(Variant::String)).collect();
+            Table {
+                columns,
+                rows: vec![row],
+            }
+        }
+        Command::Events {
+            demo,
+            name,
+            player_props,
+            other_props,
+            entity_props,
+            kill_labels,
+            stream: streaming,
+        } => {
+            let mut query = DemoQuery::new()
+                .events(name.split(','))
+                .player_props(player_props)
+                .other_props(other_props)
+                .entity_props(entity_props);
+            if let Some(trade_window) = kill_labels {
+                query = query.kill_labels(*trade_window);
+            }
+            if *streaming {
+                return stream(cli, demo, query, |parser, out, format| parser.stream_events(out, format));
+            }
+            let (output, _) = parse(cli, demo, query)?;
+            events_table(&output)
+        }
+        Command::Ticks {
+            demo,
+            props,
+            ticks,
+            stream: streaming,
+            changes,
+        } => {
+            let query = with_ticks(DemoQuery::new().player_props(props), ticks);
+            if *streaming {
+                return stream(cli, demo, query, |parser, out, format| parser.stream_ticks(out, format));
+            }
+            if *changes {
+                let (output, _) = parse(cli, demo, query.prop_changes())?;
+                // Computed props are collected per tick next to the changes, the changes table has no place for them
+                let names = output.prop_info.change_tracked_props();
+                if let Some(info) = output.prop_info.prop_infos.iter().find(|p| {
+                    output.df.contains_key(&p.id)
+                        && !names.contains_key(&p.id)
+                        && !["tick", "steamid", "name"].contains(&p.prop_name.as_str())
+                }) {
+                    return Err(format!("--changes can't be used with {}", info.prop_friendly_name).into());
+                }
+                return write_table(&changes_table(&output), cli.format, cli.output.as_deref());
+            }
+            let (output, _) = parse(cli, demo, query)?;
+            ticks_table(&output)
+        }
+        Command::Entities { demo, props, ticks } => {
+            let query = with_ticks(DemoQuery::new().entity_props(props), ticks);
+            let (output, _) = parse(cli, demo, query)?;
+            entities_table(&output, props)
+        }
+        Command::Rounds { demo } => {
+            let rounds = open_demo(cli, demo, DemoQuery::new())?.parse_rounds()?;
+            rounds_table(&rounds)
+        }
+        Command::Economy { demo } => {
+            let economy = open_demo(cli, demo, DemoQuery::new())?.parse_economy()?;
+            economy_table(&economy)
+        }
+        Command::Stats { demo, per_round } => {
+            let stats = open_demo(cli, demo, DemoQuery::new())?.parse_stats()?;
+            match per_round {
+                true => round_stats_table(&stats),
+                false => stats_table(&stats),
+            }
+        }
+        Command::Damage { demo } => {
+            let damage = open_demo(cli, demo, DemoQuery::new())?.parse_damage()?;
+            damage_table(&damage)
+        }
+        Command::Grenades { demo } => {
+            let (output, _) = parse(cli, demo, DemoQuery::new().projectiles())?;
+            grenades_table(&output)
+        }
+        Command::Chat { demo } => {
+            let (output, _) = parse(cli, demo, DemoQuery::new().entities(false))?;
+            chat_table(&output)
+        }
+        Command::Convars { demo } => {
+            let (output, _) = parse(cli, demo, DemoQuery::new().entities(false))?;
+            Table {
+                columns: vec!["name".to_string(), "value".to_string()],
+                rows: output
+                    .convars
+                    .iter()
+                    .sorted()
+                    .map(|(k, v)| vec![Some(Variant::String(k.clone())), Some(Variant::String(v.clone()))])
+                    .collect(),
+            }
+        }
+        Command::ListEvents { demo } => {
+            let (output, _) = parse(cli, demo, DemoQuery::new().events(["all"]).entities(false))?;
+            Table {
+                columns: vec!["event_name".to_string()],
+                rows: output
+                    .game_events_counter
+                    .iter()
+                    .sorted()
+                    .map(|name| vec![Some(Variant::String(name.clone()))])
+                    .collect(),
+            }
+        }
+        Command::ListProps { demo: Some(demo) } => {
+            let schema = open_demo(cli, demo, DemoQuery::new())?.prop_schema()?;
+            schema_table(&schema)
+        }
+        Command::ListProps { demo: None } => Table {
+            columns: vec!["prop".to_string(), "real_name".to_string()],
+            rows: FRIENDLY_NAMES_MAPPING
+                .entries()
+                .sorted()
+                .map(|(k, v)| vec![Some(Variant::String(k.to_string())), Some(Variant::String(v.to_string()))])
+                .collect(),
+        },
+    };
+    write_table(&table, cli.format, cli.output.as_deref())
+}
+
+// Header is stored on the parser, not in the output
+fn parse(cli: &Cli, demo: &str, query: DemoQuery) -> Result<(DemoOutput, AHashMap<String, String>), Box<dyn Error>> {
+    let mut parser = open_demo(cli, demo, query)?;
+    let output = parser.parse_demo()?;
+    print_warnings(&output);
+    Ok((output, std::mem::take(&mut parser.header)))
+}
+
+// Rows are written while the demo is parsed instead of being collected first
+fn stream<F>(cli: &Cli, demo: &str, query: DemoQuery, f: F) -> Result<(), Box<dyn Error>>
+where
+    F: FnOnce(&mut Parser, BufWriter<Box<dyn Write>>, StreamFormat) -> Result<DemoOutput, DemoParserError>,
+{
+    let format = match stream_format(cli.format) {
+        Some(format) => format,
+        None => return Err("--stream only works with csv and ndjson".into()),
+    };
+    let mut parser = open_demo(cli, demo, query)?;
+    let output = f(&mut parser, open_output(cli.output.as_deref())?, format)?;
+    print_warnings(&output);
+    Ok(())
+}
+
+fn with_ticks(mut query: DemoQuery, args: &TickArgs) -> DemoQuery {
+    query = query.ticks(args.ticks.iter().copied());
+    query
+        .tick_selectors
+        .extend(args.range.iter().chain(&args.rounds).chain(&args.around).cloned());
+    if let Some(n) = args.every {
+        query = query.every_nth_tick(n);
+    }
+    query
+}
+
+fn parse_pair(s: &str) -> Result<(i32, i32), String> {
+    let parse = |x: &str| x.trim().parse::<i32>().map_err(|e| format!("{}: {}", x, e));
+    match s.split_once('-') {
+        Some((a, b)) => Ok((parse(a)?, parse(b)?)),
+        None => Err(format!("expected FIRST-LAST, got {}", s)),
+    }
+}
+
+fn parse_tick_range(s: &str) -> Result<TickSelector, String> {
+    let (start, end) = parse_pair(s)?;
+    Ok(TickSelector::Range { start, end })
+}
+
+fn parse_rounds(s: &str) -> Result<TickSelector, String> {
+    let (first, last) = parse_pair(s)?;
+    Ok(TickSelector::Rounds { first, last })
+}
+
+fn parse_around_event(s: &str) -> Result<TickSelector, String> {
+    let parts: Vec<&str> = s.split(':').collect();
+    let parse = |x: &str| x.parse::<i32>().map_err(|e| format!("{}: {}", x, e));
+    match parts[..] {
+        [event_name, before, after] => Ok(TickSelector::AroundEvent {
+            event_name: event_name.to_string(),
+            before: parse(before)?,
+            after: parse(after)?,
+        }),
+        _ => Err(format!("expected EVENT:BEFORE:AFTER, got {}", s)),
+    }
+}
+
+fn open_demo(cli: &Cli, demo: &str, mut query: DemoQuery) -> Result<Parser, DemoParserError> {
+    if cli.lenient {
+        query = query.lenient();
+    }
+    query.parser_from_path(demo)
+}
+
+fn print_warnings(output: &DemoOutput) {
+    for warning in &output.warnings {
+        eprintln!("warning: {:?}: {}", warning.kind, warning.message);
+    }
+}
+
+fn schema_table(schema: &[PropSchema]) -> Table {
+    let columns = ["prop", "full_name", "class", "type", "decoder", "friendly_names"];
+    Table {
+        columns: columns.iter().map(|c| c.to_string()).collect(),
+        rows: schema
+            .iter()
+            .map(|p| {
+                vec![
+                    Some(Variant::String(p.prop_name.clone())),
+                    Some(Variant::String(p.full_name.clone())),
+                    Some(Variant::String(p.class_name.clone())),
+                    Some(Variant::String(p.var_type.clone())),
+                    Some(Variant::String(p.decoder.to_string())),
+                    Some(Variant::StringVec(p.friendly_names.clone())),
+                ]
+            })
+            .collect(),
+    }
+}
+
+fn events_table(output: &DemoOutput) -> Table {
+    let mut columns = vec!["event_name".to_string(), "tick".to_string()];
+    for event in &output.game_events {
+        for field in &event.fields {
+            if !columns.contains(&field.name) {
+                columns.push(field.name.clone());
+            }
+        }
+    }
+    let rows = output
+        .game_events
+        .iter()
+        .map(|event| {
+            let mut row = vec![Some(Variant::String(event.name.clone())), Some(Variant::I32(event.tick))];
+            for name in &columns[2..] {
+                row.push(event.fields.iter().find(|f| &f.name == name).and_then(|f| f.data.clone()));
+            }
+            row
+        })
+        .collect();
+    Table { columns, rows }
+}
+
+fn ticks_table(output: &DemoOutput) -> Table {
+    let mut columns = vec![];
+    let mut props = vec![];
+    for prop_info in &output.prop_info.prop_infos {
+        if let Some(col) = output.df.get(&prop_info.id) {
+            columns.push(prop_info.prop_friendly_name.clone());
+            props.push(col);
+        }
+    }
+    let n_rows = props.iter().map(|col| col.len()).max().unwrap_or(0);
+    let rows = (0..n_rows)
+        .map(|idx| props.iter().map(|col| col.get(idx)).collect())
+        .collect();
+    Table { columns, rows }
+}
+
+fn changes_table(output: &DemoOutput) -> Table {
+    let names = output.prop_info.change_tracked_props();
+    let columns = ["tick", "entity_id", "prop", "value"];
+    // One value column for all props, typed columns (parquet) need one type
+    let mixed_types = output
+        .prop_changes
+        .iter()
+        .filter_map(|c| c.value.as_ref().map(std::mem::discriminant))
+        .unique()
+        .count()
+        > 1;
+    let rows = output
+        .prop_changes
+        .iter()
+        .map(|c| {
+            vec![
+                Some(Variant::I32(c.tick)),
+                Some(Variant::I32(c.entity_id)),
+                names.get(&c.prop_id).cloned().map(Variant::String),
+                match mixed_types {
+                    true => c.value.as_ref().map(|v| Variant::String(json_value(v))),
+                    false => c.value.clone(),
+                },
+            ]
+        })
+        .collect();
+    Table {
+        columns: columns.iter().map(|c| c.to_string()).collect(),
+        rows,
+    }
+}
+
+fn entities_table(output: &DemoOutput, props: &[String]) -> Table {
+    let mut columns = vec!["tick".to_string(), "entity_id".to_string(), "class_name".to_string()];
+    columns.extend(props.iter().cloned());
+    let rows = output
+        .entity_props
+        .iter()
+        .map(|record| {
+            let mut row = vec![
+                Some(Variant::I32(record.tick)),
+                Some(Variant::I32(record.entity_id)),
+                Some(Variant::String(record.class_name.clone())),
+            ];
+            row.extend(record.values.iter().cloned());
+            row
+        })
+        .collect();
+    Table { columns, rows }
+}
+
+fn rounds_table(rounds: &[Round]) -> Table {
+    let columns = [
+        "round",
+        "start_tick",
+        "freeze_end_tick",
+        "end_tick",
+        "officially_ended_tick",
+        "winner",
+        "reason",
+        "bomb_plant_tick",
+        "bomb_defuse_tick",
+        "t_score",
+        "ct_score",
+        "t_team",
+        "ct_team",
+        "sides_switched",
+        "overtime",
+    ];
+    let rows = rounds
+        .iter()
+        .map(|r| {
+            vec![
+                Some(Variant::I32(r.round)),
+                Some(Variant::I32(r.start_tick)),
+                r.freeze_end_tick.map(Variant::I32),
+                r.end_tick.map(Variant::I32),
+                r.officially_ended_tick.map(Variant::I32),
+                r.winner.clone().map(Variant::String),
+                r.reason.clone().map(Variant::String),
+                r.bomb_plant_tick.map(Variant::I32),
+                r.bomb_defuse_tick.map(Variant::I32),
+                Some(Variant::I32(r.t_score)),
+                Some(Variant::I32(r.ct_score)),
+                r.t_team.clone().map(Variant::String),
+                r.ct_team.clone().map(Variant::String),
+                Some(Variant::Bool(r.sides_switched)),
+                Some(Variant::I32(r.overtime)),
+            ]
+        })
+        .collect();
+    Table {
+        columns: columns.iter().map(|c| c.to_string()).collect(),
+        rows,
+    }
+}
+
+fn economy_table(economy: &[TeamEconomy]) -> Table {
+    let columns = [
+        "round",
+        "side",
+        "players",
+        "start_money",
+        "money_spent",
+        "money_left",
+        "cash_earned",
+        "equipment_value",
+        "primary_weapons",
+        "items_purchased",
+        "loss_bonus_level",
+        "loss_bonus",
+        "buy_type",
+    ];
+    let rows = economy
+        .iter()
+        .map(|e| {
+            vec![
+                Some(Variant::I32(e.round)),
+                Some(Variant::String(e.side.clone())),
+                Some(Variant::I32(e.players)),
+                Some(Variant::I32(e.start_money)),
+                Some(Variant::I32(e.money_spent)),
+                Some(Variant::I32(e.money_left)),
+                Some(Variant::I32(e.cash_earned)),
+                Some(Variant::I32(e.equipment_value)),
+                Some(Variant::I32(e.primary_weapons)),
+                Some(Variant::I32(e.items_purchased)),
+                Some(Variant::I32(e.loss_bonus_level)),
+                Some(Variant::I32(e.loss_bonus)),
+                Some(Variant::String(e.buy_type.as_str().to_string())),
+            ]
+        })
+        .collect();
+    Table {
+        columns: columns.iter().map(|c| c.to_string()).collect(),
+        rows,
+    }
+}
+
+fn damage_table(damage: &[DamageRecord]) -> Table {
+    let columns = [
+        "tick",
+        "attacker_steamid",
+        "attacker_name",
+        "victim_steamid",
+        "victim_name",
+        "weapon",
+        "hitgroup",
+        "dmg_health",
+        "dmg_armor",
+        "health_damage",
+        "armor_damage",
+        "health",
+        "armor",
+        "penetrated",
+        "distance",
+        "victim_blinded",
+        "team_damage",
+    ];
+    let rows = damage
+        .iter()
+        .map(|d| {
+            vec![
+                Some(Variant::I32(d.tick)),
+                d.attacker_steamid.map(Variant::U64),
+                d.attacker_name.clone().map(Variant::String),
+                d.victim_steamid.map(Variant::U64),
+                d.victim_name.clone().map(Variant::String),
+                Some(Variant::String(d.weapon.clone())),
+                Some(Variant::String(d.hitgroup.clone())),
+                Some(Variant::I32(d.dmg_health)),
+                Some(Variant::I32(d.dmg_armor)),
+                Some(Variant::I32(d.health_damage)),
+                Some(Variant::I32(d.armor_damage)),
+                Some(Variant::I32(d.health)),
+                Some(Variant::I32(d.armor)),
+                d.penetrated.map(Variant::Bool),
+                d.distance.map(Variant::F32),
+                Some(Variant::Bool(d.victim_blinded)),
+                d.team_damage.map(Variant::Bool),
+            ]
+        })
+        .collect();
+    Table {
+        columns: columns.iter().map(|c| c.to_string()).collect(),
+        rows,
+    }
+}
+
+fn stats_table(stats: &MatchStats) -> Table {
+    let columns = [
+        "steamid",
+        "name",
+        "rounds_played",
+        "kills",
+        "deaths",
+        "assists",
+        "flash_assists",
+        "headshot_pct",
+        "adr",
+        "kast",
+        "entry_kills",
+        "entry_deaths",
+        "1k",
+        "2k",
+        "3k",
+        "4k",
+        "5k",
+        "clutches_attempted",
+        "clutches_won",
+        "utility_damage",
+        "enemies_flashed",
+        "mvps",
+        "rating",
+    ];
+    let rows = stats
+        .players
+        .iter()
+        .map(|p| {
+            vec![
+                Some(Variant::U64(p.steamid)),
+                Some(Variant::String(p.name.clone())),
+                Some(Variant::I32(p.rounds_played)),
+                Some(Variant::I32(p.kills)),
+                Some(Variant::I32(p.deaths)),
+                Some(Variant::I32(p.assists)),
+                Some(Variant::I32(p.flash_assists)),
+                Some(Variant::F32(p.headshot_pct)),
+                Some(Variant::F32(p.adr)),
+                Some(Variant::F32(p.kast)),
+                Some(Variant::I32(p.entry_kills)),
+                Some(Variant::I32(p.entry_deaths)),
+                Some(Variant::I32(p.one_kill_rounds)),
+                Some(Variant::I32(p.two_kill_rounds)),
+                Some(Variant::I32(p.three_kill_rounds)),
+                Some(Variant::I32(p.four_kill_rounds)),
+                Some(Variant::I32(p.five_kill_rounds)),
+                Some(Variant::I32(p.clutches_attempted)),
+                Some(Variant::I32(p.clutches_won)),
+                Some(Variant::I32(p.utility_damage)),
+                Some(Variant::I32(p.enemies_flashed)),
+                Some(Variant::I32(p.mvps)),
+                Some(Variant::F32(p.rating)),
+            ]
+        })
+        .collect();
+    Table {
+        columns: columns.iter().map(|c| c.to_string()).collect(),
+        rows,
+    }
+}
+
+fn round_stats_table(stats: &MatchStats) -> Table {
+    let columns = [
+        "round",
+        "steamid",
+        "name",
+        "side",
+        "kills",
+        "deaths",
+        "assists",
+        "flash_assists",
+        "headshot_kills",
+        "damage",
+        "utility_damage",
+        "enemies_flashed",
+        "enemy_blind_time",
+        "survived",
+        "traded",
+        "kast",
+        "entry_kill",
+        "entry_death",
+        "clutch_enemies",
+        "clutch_won",
+        "mvp",
+    ];
+    let rows = stats
+        .rounds
+        .iter()
+        .map(|r| {
+            vec![
+                Some(Variant::I32(r.round)),
+                Some(Variant::U64(r.steamid)),
+                Some(Variant::String(r.name.clone())),
+                r.side.clone().map(Variant::String),
+                Some(Variant::I32(r.kills)),
+                Some(Variant::I32(r.deaths)),
+                Some(Variant::I32(r.assists)),
+                Some(Variant::I32(r.flash_assists)),
+                Some(Variant::I32(r.headshot_kills)),
+                Some(Variant::I32(r.damage)),
+                Some(Variant::I32(r.utility_damage)),
+                Some(Variant::I32(r.enemies_flashed)),
+                Some(Variant::F32(r.enemy_blind_time)),
+                Some(Variant::Bool(r.survived)),
+                Some(Variant::Bool(r.traded)),
+                Some(Variant::Bool(r.kast)),
+                Some(Variant::Bool(r.entry_kill)),
+                Some(Variant::Bool(r.entry_death)),
+                r.clutch_enemies.map(Variant::I32),
+                Some(Variant::Bool(r.clutch_won)),
+                Some(Variant::Bool(r.mvp)),
+            ]
+        })
+        .collect();
+    Table {
+        columns: columns.iter().map(|c| c.to_string()).collect(),
+        rows,
+    }
+}
+
+fn grenades_table(output: &DemoOutput) -> Table {
+    let columns = [
+        "tick",
+        "grenade_type",
+        "entity_id",
+        "x",
+        "y",
+        "z",
+        "thrower_steamid",
+        "thrower_name",
+    ];
+    let rows = output
+        .projectiles
+        .iter()
+        .map(|p| {
+            vec![
+                p.tick.map(Variant::I32),
+                p.grenade_type.clone().map(Variant::String),
+                p.entity_id.map(Variant::I32),
+                p.x.map(Variant::F32),
+                p.y.map(Variant::F32),
+                p.z.map(Variant::F32),
+                p.steamid.map(Variant::U64),
+                p.name.clone().map(Variant::String),
+            ]
+        })
+        .collect();
+    Table {
+        columns: columns.iter().map(|c| c.to_string()).collect(),
+        rows,
+    }
+}
+
+fn chat_table(output: &DemoOutput) -> Table {
+    let columns = ["entity_idx", "param1", "param2", "param3", "param4"];
+    let rows = output
+        .chat_messages
+        .iter()
+        .map(|m| {
+            vec![
+                m.entity_idx.map(Variant::I32),
+                m.param1.clone().map(Variant::String),
+                m.param2.clone().map(Variant::String),
+                m.param3.clone().map(Variant::String),
+                m.param4.clone().map(Variant::String),
+            ]
+        })
+        .collect();
+    Table {
+        columns: columns.iter().map(|c| c.to_string()).collect(),
+        rows,
+    }
+}
+
+fn write_table(table: &Table, format: Format, path: Option<&Path>) -> Result<(), Box<dyn Error>> {
+    let mut out = match format {
+        Format::Parquet => {
+            return match path {
+                Some(path) => write_parquet(table, path),
+                None => Err("parquet output needs a file, use -o".into()),
+            }
+        }
+        _ => open_output(path)?,
+    };
+    match stream_format(format) {
+        Some(stream_format) => {
+            let mut writer = StreamWriter::new(out, stream_format);
+            writer.set_columns(table.columns.clone())?;
+            for row in &table.rows {
+                let values: Vec<Option<&Variant>> = row.iter().map(|v| v.as_ref()).collect();
+                writer.write_row(&values)?;
+            }
+            writer.flush()?;
+        }
+        None => {
+            writeln!(out, "[")?;
+            for (idx, row) in table.rows.iter().enumerate() {
+                let values: Vec<Option<&Variant>> = row.iter().map(|v| v.as_ref()).collect();
+                let sep = if idx + 1 < table.rows.len() { "," } else { "" };
+                writeln!(out, "{}{}", json_object(&table.columns, &values), sep)?;
+            }
+            writeln!(out, "]")?;
+            out.flush()?;
+        }
+    }
+    Ok(())
+}
+
+fn open_output(path: Option<&Path>) -> Result<BufWriter<Box<dyn Write>>, Box<dyn Error>> {
+    let out: Box<dyn Write> = match path {
+        Some(path) => Box::new(File::create(path)?),
+        None => Box::new(std::io::stdout().lock()),
+    };
+    Ok(BufWriter::new(out))
+}
+
+fn stream_format(format: Format) -> Option<StreamFormat> {
+    match format {
+        Format::Csv => Some(StreamFormat::Csv),
+        Format::Ndjson => Some(StreamFormat::Ndjson),
+        Format::Json | Format::Parquet => None,
+    }
+}
+
+#[cfg(feature = "parquet")]
+fn write_parquet(table: &Table, path: &Path) -> Result<(), Box<dyn Error>> {
+    use arrow::datatypes::Field;
+    use arrow::datatypes::Schema;
+    use arrow::record_batch::RecordBatch;
+    use parser::arrow_export::variants_to_arrow;
+    use std::sync::Arc;
+
+    let mut fields = vec![];
+    let mut columns = vec![];
+    for (idx, name) in table.columns.iter().enumerate() {
+        let values: Vec<Option<&Variant>> = table.rows.iter().map(|row| row[idx].as_ref()).collect();
+        let array = variants_to_arrow(&values);
+        fields.push(Field::new(name, array.data_type().clone(), true));
+        columns.push(array);
+    }
+    let batch = RecordBatch::try_new(Arc::new(Schema::new(fields)), columns)?;
+    parser::parquet_export::write_parquet(path, &batch)?;
+    Ok(())
+}
+
+#[cfg(not(feature = "parquet"))]
+fn write_parquet(_table: &Table, _path: &Path) -> Result<(), Box<dyn Error>> {
+    Err("demoparser was built without the \"parquet\" feature".into())
+}
+
+// Shared with the library tests, the CLI tests only use part of it
+#[cfg(test)]
+#[allow(dead_code)]
+#[path = "test_demo.rs"]
+mod test_demo;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_demo::TestDemo;
+
+    fn write_demo(name: &str) -> String {
+        let mut demo = TestDemo::new();
+        demo.add_teams_and_rules();
+        demo.add_player(1, 76561198000000001, "alice", 2);
+        demo.add_player(2, 76561198000000002, "bob", 3);
+        demo.packet(1);
+        demo.full_packet(2);
+        demo.event("round_start", &[]);
+        demo.packet(10);
+        demo.event(
+            "player_death",
+            &[("userid", 2.into()), ("attacker", 1.into()), ("weapon", "ak47".into())],
+        );
+        demo.packet(20);
+        let path = std::env::temp_dir().join(format!("cli_{}_{}.dem", name, std::process::id()));
+        std::fs::write(&path, demo.finish(30)).unwrap();
+        path.to_string_lossy().to_string()
+    }
+    // Runs the command on a test demo and returns what it wrote
+    fn run_cli(name: &str, args: &[&str]) -> String {
+        let demo = write_demo(name);
+        let out = std::env::temp_dir().join(format!("cli_{}_{}.out", name, std::process::id()));
+        let mut argv = vec!["demoparser"];
+        argv.extend(args.iter().map(|a| if *a == "DEMO" { demo.as_str() } else { a }));
+        argv.extend(["-o", out.to_str().unwrap()]);
+        run(&Cli::try_parse_from(argv).unwrap()).unwrap();
+        std::fs::read_to_string(out).unwrap()
+    }
+
+    #[test]
+    fn test_tick_args() {
+        let cli = Cli::try_parse_from([
+            "demoparser",
+            "ticks",
+            "x.dem",
+            "--props",
+            "health,armor_value",
+            "--ticks",
+            "5,6",
+            "--range",
+            "100-200",
+            "--rounds",
+            "3-5",
+            "--around",
+            "player_death:64:32",
+            "--every",
+            "16",
+            "-f",
+            "ndjson",
+        ])
+        .unwrap();
+        assert!(cli.format == Format::Ndjson);
+        let (props, ticks) = match &cli.command {
+            Command::Ticks { props, ticks, .. } => (props, ticks),
+            _ => panic!("not ticks"),
+        };
+        assert_eq!(props, &vec!["health".to_string(), "armor_value".to_string()]);
+        let query = with_ticks(DemoQuery::new(), ticks);
+        assert_eq!(query.ticks, vec![5, 6]);
+        assert_eq!(query.tick_stride, 16);
+        assert_eq!(
+            query.tick_selectors,
+            vec![
+                TickSelector::Range { start: 100, end: 200 },
+                TickSelector::Rounds { first: 3, last: 5 },
+                TickSelector::AroundEvent {
+                    event_name: "player_death".to_string(),
+                    before: 64,
+                    after: 32,
+                },
+            ]
+        );
+    }
+    #[test]
+    fn test_bad_args() {
+        assert!(Cli::try_parse_from(["demoparser", "ticks", "x.dem", "--props", "health", "--range", "100"]).is_err());
+        assert!(Cli::try_parse_from([
+            "demoparser",
+            "ticks",
+            "x.dem",
+            "--props",
+            "health",
+            "--around",
+            "player_death:64"
+        ])
+        .is_err());
+        // --props is required
+        assert!(Cli::try_parse_from(["demoparser", "ticks", "x.dem"]).is_err());
+        assert!(Cli::try_parse_from(["demoparser", "events", "x.dem", "all", "--stream", "--kill-labels"]).is_err());
+    }
+    #[test]
+    fn test_kill_labels_default_window() {
+        let cli = Cli::try_parse_from(["demoparser", "events", "x.dem", "player_death", "--kill-labels"]).unwrap();
+        match cli.command {
+            Command::Events { kill_labels, .. } => assert_eq!(kill_labels, Some(320)),
+            _ => panic!("not events"),
+        }
+    }
+    #[test]
+    fn test_header_csv() {
+        let out = run_cli("header", &["header", "DEMO"]);
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines.len(), 2);
+        let columns: Vec<&str> = lines[0].split(',').collect();
+        let values: Vec<&str> = lines[1].split(',').collect();
+        let map_idx = columns.iter().position(|c| *c == "map_name").unwrap();
+        assert_eq!(values[map_idx], "de_test");
+    }
+    #[test]
+    fn test_events_ndjson() {
+        let out = run_cli("events", &["events", "DEMO", "player_death", "-f", "ndjson"]);
+        let rows: Vec<serde_json::Value> = out.lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0]["event_name"], "player_death");
+        assert_eq!(rows[0]["tick"], 20);
+        assert_eq!(rows[0]["weapon"], "ak47");
+        assert_eq!(rows[0]["attacker_name"], "alice");
+        assert_eq!(rows[0]["user_name"], "bob");
+    }
+    #[test]
+    fn test_events_csv() {
+        let out = run_cli("events_csv", &["events", "DEMO", "round_start,player_death"]);
+        let lines: Vec<&str> = out.lines().collect();
+        assert!(lines[0].starts_with("event_name,tick,"));
+        assert_eq!(lines.len(), 3);
+        assert!(lines[1].starts_with("round_start,10"));
+        assert!(lines[2].starts_with("player_death,20"));
+    }
+    #[test]
+    fn test_list_events() {
+        assert_eq!(
+            run_cli("list_events", &["list-events", "DEMO"]),
+            "event_name\nplayer_death\nround_start\n"
+        );
+        let out = run_cli("list_events_ndjson", &["list-events", "DEMO", "-f", "ndjson"]);
+        assert_eq!(out, "{\"event_name\":\"player_death\"}\n{\"event_name\":\"round_start\"}\n");
+    }
+}
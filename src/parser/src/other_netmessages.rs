@@ -24,16 +24,19 @@ pub struct Class {
 
 impl ParserThread {
     pub fn parse_item_drops(&mut self, bytes: &[u8]) -> Result<(), DemoParserError> {
-        let drops: CCSUsrMsg_SendPlayerItemDrops = Message::parse_from_bytes(&bytes).unwrap();
+        let drops: CCSUsrMsg_SendPlayerItemDrops = match Message::parse_from_bytes(&bytes) {
+            Ok(msg) => msg,
+            Err(_e) => return Err(DemoParserError::MalformedMessage),
+        };
         for item in &drops.entity_updates {
-            let item_name = match WEAPINDICIES.get(&item.defindex.unwrap()) {
-                Some(name) => Some(name.to_string()),
-                None => None,
-            };
-            let skin_name = match PAINTKITS.get(&item.paintindex.unwrap()) {
-                Some(name) => Some(name.to_string()),
-                None => None,
-            };
+            let item_name = item
+                .defindex
+                .and_then(|idx| WEAPINDICIES.get(&idx))
+                .map(|name| name.to_string());
+            let skin_name = item
+                .paintindex
+                .and_then(|idx| PAINTKITS.get(&idx))
+                .map(|name| name.to_string());
             self.item_drops.push(EconItem {
                 account_id: item.accountid,
                 item_id: item.itemid,
@@ -56,7 +59,10 @@ impl ParserThread {
         Ok(())
     }
     pub fn parse_chat_messages(&mut self, bytes: &[u8]) -> Result<(), DemoParserError> {
-        let chat_msg: CUserMessageSayText2 = Message::parse_from_bytes(bytes).unwrap();
+        let chat_msg: CUserMessageSayText2 = match Message::parse_from_bytes(bytes) {
+            Ok(msg) => msg,
+            Err(_e) => return Err(DemoParserError::MalformedMessage),
+        };
         self.chat_messages.push(ChatMessageRecord {
             entity_idx: chat_msg.entityindex,
             param1: chat_msg.param1,
@@ -67,7 +73,10 @@ impl ParserThread {
         Ok(())
     }
     pub fn parse_convars(&mut self, bytes: &[u8]) -> Result<(), DemoParserError> {
-        let convar: CNETMsg_SetConVar = Message::parse_from_bytes(bytes).unwrap();
+        let convar: CNETMsg_SetConVar = match Message::parse_from_bytes(bytes) {
+            Ok(msg) => msg,
+            Err(_e) => return Err(DemoParserError::MalformedMessage),
+        };
         for cv in &convar.convars {
             for var in &cv.cvars {
                 self.convars.insert(var.name().to_owned(), var.value().to_owned());
@@ -77,7 +86,10 @@ impl ParserThread {
     }
 
     pub fn parse_player_end_msg(&mut self, bytes: &[u8]) -> Result<(), DemoParserError> {
-        let end_data: CCSUsrMsg_EndOfMatchAllPlayersData = Message::parse_from_bytes(&bytes).unwrap();
+        let end_data: CCSUsrMsg_EndOfMatchAllPlayersData = match Message::parse_from_bytes(&bytes) {
+            Ok(msg) => msg,
+            Err(_e) => return Err(DemoParserError::MalformedMessage),
+        };
         /*
         Todo parse "accolade", seems to be the awards at the end like "most mvps in game"
         But seems to only have integers so need to figure out what they mean
@@ -103,14 +115,14 @@ impl ParserThread {
             });
             for item in &player.items {
                 if item.itemid() != 0 {
-                    let item_name = match WEAPINDICIES.get(&item.defindex.unwrap()) {
-                        Some(name) => Some(name.to_string()),
-                        None => None,
-                    };
-                    let skin_name = match PAINTKITS.get(&item.paintindex.unwrap()) {
-                        Some(name) => Some(name.to_string()),
-                        None => None,
-                    };
+                    let item_name = item
+                        .defindex
+                        .and_then(|idx| WEAPINDICIES.get(&idx))
+                        .map(|name| name.to_string());
+                    let skin_name = item
+                        .paintindex
+                        .and_then(|idx| PAINTKITS.get(&idx))
+                        .map(|name| name.to_string());
                     self.skins.push(EconItem {
                         account_id: item.accountid,
                         item_id: item.itemid,
@@ -140,7 +152,10 @@ impl ParserThread {
         Ok(())
     }
     pub fn parse_file_info(&mut self, bytes: &[u8]) -> Result<(), DemoParserError> {
-        let _info: CDemoFileInfo = Message::parse_from_bytes(bytes).unwrap();
+        let _info: CDemoFileInfo = match Message::parse_from_bytes(bytes) {
+            Ok(msg) => msg,
+            Err(_e) => return Err(DemoParserError::MalformedMessage),
+        };
         Ok(())
     }
 }
@@ -0,0 +1,252 @@
+use crate::arrow_export::event_record_batch;
+use crate::collect_data::ProjectileRecord;
+use crate::game_events::GameEvent;
+use crate::parser::DemoOutput;
+use crate::parser_settings::Parser;
+use crate::parser_thread_settings::ChatMessageRecord;
+use crate::parser_thread_settings::EconItem;
+use crate::read_bits::DemoParserError;
+use ahash::AHashMap;
+use arrow::array::*;
+use arrow::datatypes::Field;
+use arrow::datatypes::Schema;
+use arrow::record_batch::RecordBatch;
+use csgoproto::netmessages::csvcmsg_game_event_list::Descriptor_t;
+use parquet::arrow::ArrowWriter;
+use parquet::basic::Compression;
+use parquet::file::properties::WriterProperties;
+use std::fs;
+use std::fs::File;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+// Writes a parsed demo as a Parquet dataset (feature "parquet"), one file per table:
+//
+// <root>/ticks/map=<map>/demo=<demo>/data.parquet
+// <root>/events/<event_name>/map=<map>/demo=<demo>/data.parquet
+// <root>/grenades/...  <root>/chat/...  <root>/skins/...  <root>/header/...
+//
+// Event tables get their columns typed from the game event list, see arrow_export.
+
+impl DemoOutput {
+    pub fn write_parquet_dataset(
+        &self,
+        root: &Path,
+        demo_name: &str,
+        ge_list: &AHashMap<i32, Descriptor_t>,
+    ) -> Result<Vec<PathBuf>, DemoParserError> {
+        let map_name = match &self.header {
+            Some(header) => header.get("map_name").cloned().unwrap_or_default(),
+            None => String::new(),
+        };
+        let partition = |table: &str| {
+            root.join(table)
+                .join(format!("map={}", partition_value(&map_name)))
+                .join(format!("demo={}", partition_value(demo_name)))
+                .join("data.parquet")
+        };
+        let mut written = vec![];
+
+        if !self.df.is_empty() {
+            written.push(write_parquet(&partition("ticks"), &self.ticks_record_batch()?)?);
+        }
+        let mut events_by_name: AHashMap<&str, Vec<&GameEvent>> = AHashMap::default();
+        for event in &self.game_events {
+            events_by_name.entry(&event.name).or_default().push(event);
+        }
+        let descriptors: AHashMap<&str, &Descriptor_t> = ge_list.values().map(|d| (d.name(), d)).collect();
+        for (name, events) in events_by_name {
+            let batch = event_record_batch(&events, descriptors.get(name).copied())?;
+            written.push(write_parquet(&partition(&format!("events/{}", name)), &batch)?);
+        }
+        if !self.projectiles.is_empty() {
+            written.push(write_parquet(
+                &partition("grenades"),
+                &projectiles_record_batch(&self.projectiles)?,
+            )?);
+        }
+        if !self.chat_messages.is_empty() {
+            written.push(write_parquet(&partition("chat"), &chat_record_batch(&self.chat_messages)?)?);
+        }
+        if !self.skins.is_empty() {
+            written.push(write_parquet(&partition("skins"), &skins_record_batch(&self.skins)?)?);
+        }
+        if let Some(header) = &self.header {
+            written.push(write_parquet(&partition("header"), &header_record_batch(header)?)?);
+        }
+        Ok(written)
+    }
+}
+
+impl Parser {
+    pub fn parse_to_parquet(&mut self, root: &Path, demo_name: &str) -> Result<Vec<PathBuf>, DemoParserError> {
+        let output = self.parse_demo()?;
+        output.write_parquet_dataset(root, demo_name, &self.ge_list)
+    }
+}
+
+pub fn projectiles_record_batch(projectiles: &[ProjectileRecord]) -> Result<RecordBatch, DemoParserError> {
+    let columns: Vec<(&str, ArrayRef)> = vec![
+        ("tick", Arc::new(Int32Array::from_iter(projectiles.iter().map(|p| p.tick)))),
+        (
+            "grenade_type",
+            Arc::new(StringArray::from_iter(projectiles.iter().map(|p| p.grenade_type.as_deref()))),
+        ),
+        (
+            "entity_id",
+            Arc::new(Int32Array::from_iter(projectiles.iter().map(|p| p.entity_id))),
+        ),
+        ("x", Arc::new(Float32Array::from_iter(projectiles.iter().map(|p| p.x)))),
+        ("y", Arc::new(Float32Array::from_iter(projectiles.iter().map(|p| p.y)))),
+        ("z", Arc::new(Float32Array::from_iter(projectiles.iter().map(|p| p.z)))),
+        (
+            "thrower_steamid",
+            Arc::new(UInt64Array::from_iter(projectiles.iter().map(|p| p.steamid))),
+        ),
+        (
+            "thrower_name",
+            Arc::new(StringArray::from_iter(projectiles.iter().map(|p| p.name.as_deref()))),
+        ),
+    ];
+    named_record_batch(columns)
+}
+
+pub fn chat_record_batch(messages: &[ChatMessageRecord]) -> Result<RecordBatch, DemoParserError> {
+    let columns: Vec<(&str, ArrayRef)> = vec![
+        (
+            "entity_idx",
+            Arc::new(Int32Array::from_iter(messages.iter().map(|m| m.entity_idx))),
+        ),
+        (
+            "param1",
+            Arc::new(StringArray::from_iter(messages.iter().map(|m| m.param1.as_deref()))),
+        ),
+        (
+            "param2",
+            Arc::new(StringArray::from_iter(messages.iter().map(|m| m.param2.as_deref()))),
+        ),
+        (
+            "param3",
+            Arc::new(StringArray::from_iter(messages.iter().map(|m| m.param3.as_deref()))),
+        ),
+        (
+            "param4",
+            Arc::new(StringArray::from_iter(messages.iter().map(|m| m.param4.as_deref()))),
+        ),
+    ];
+    named_record_batch(columns)
+}
+
+pub fn skins_record_batch(skins: &[EconItem]) -> Result<RecordBatch, DemoParserError> {
+    let columns: Vec<(&str, ArrayRef)> = vec![
+        ("steamid", Arc::new(UInt64Array::from_iter(skins.iter().map(|s| s.steamid)))),
+        (
+            "account_id",
+            Arc::new(UInt32Array::from_iter(skins.iter().map(|s| s.account_id))),
+        ),
+        ("item_id", Arc::new(UInt64Array::from_iter(skins.iter().map(|s| s.item_id)))),
+        (
+            "def_index",
+            Arc::new(UInt32Array::from_iter(skins.iter().map(|s| s.def_index))),
+        ),
+        (
+            "paint_index",
+            Arc::new(UInt32Array::from_iter(skins.iter().map(|s| s.paint_index))),
+        ),
+        ("rarity", Arc::new(UInt32Array::from_iter(skins.iter().map(|s| s.rarity)))),
+        ("quality", Arc::new(UInt32Array::from_iter(skins.iter().map(|s| s.quality)))),
+        (
+            "paint_wear",
+            Arc::new(UInt32Array::from_iter(skins.iter().map(|s| s.paint_wear))),
+        ),
+        (
+            "paint_seed",
+            Arc::new(UInt32Array::from_iter(skins.iter().map(|s| s.paint_seed))),
+        ),
+        (
+            "custom_name",
+            Arc::new(StringArray::from_iter(skins.iter().map(|s| s.custom_name.as_deref()))),
+        ),
+        (
+            "item_name",
+            Arc::new(StringArray::from_iter(skins.iter().map(|s| s.item_name.as_deref()))),
+        ),
+        (
+            "skin_name",
+            Arc::new(StringArray::from_iter(skins.iter().map(|s| s.skin_name.as_deref()))),
+        ),
+    ];
+    named_record_batch(columns)
+}
+
+// One row, one column per header key
+pub fn header_record_batch(header: &AHashMap<String, String>) -> Result<RecordBatch, DemoParserError> {
+    let mut keys: Vec<&String> = header.keys().collect();
+    keys.sort();
+    let columns: Vec<(&str, ArrayRef)> = keys
+        .iter()
+        .map(|k| (k.as_str(), Arc::new(StringArray::from(vec![header[*k].as_str()])) as ArrayRef))
+        .collect();
+    named_record_batch(columns)
+}
+
+pub fn write_parquet(path: &Path, batch: &RecordBatch) -> Result<PathBuf, DemoParserError> {
+    if let Some(dir) = path.parent() {
+        if let Err(e) = fs::create_dir_all(dir) {
+            return Err(DemoParserError::ParquetError(format!("{}: {}", dir.display(), e)));
+        }
+    }
+    let file = match File::create(path) {
+        Ok(f) => f,
+        Err(e) => return Err(DemoParserError::ParquetError(format!("{}: {}", path.display(), e))),
+    };
+    let props = WriterProperties::builder().set_compression(Compression::SNAPPY).build();
+    let result = ArrowWriter::try_new(file, batch.schema(), Some(props)).and_then(|mut writer| {
+        writer.write(batch)?;
+        writer.close()
+    });
+    match result {
+        Ok(_) => Ok(path.to_path_buf()),
+        Err(e) => Err(DemoParserError::ParquetError(format!("{}", e))),
+    }
+}
+
+// Keeps partition directories one level deep
+fn partition_value(value: &str) -> String {
+    let cleaned: String = value
+        .chars()
+        .map(|c| if c == '/' || c == '\\' || c == '=' { '_' } else { c })
+        .collect();
+    match cleaned.is_empty() {
+        true => "unknown".to_string(),
+        false => cleaned,
+    }
+}
+
+fn named_record_batch(columns: Vec<(&str, ArrayRef)>) -> Result<RecordBatch, DemoParserError> {
+    let fields = columns
+        .iter()
+        .map(|(name, array)| Field::new(*name, array.data_type().clone(), true))
+        .collect();
+    to_record_batch(fields, columns.into_iter().map(|(_, array)| array).collect())
+}
+
+fn to_record_batch(fields: Vec<Field>, columns: Vec<ArrayRef>) -> Result<RecordBatch, DemoParserError> {
+    match RecordBatch::try_new(Arc::new(Schema::new(fields)), columns) {
+        Ok(batch) => Ok(batch),
+        Err(e) => Err(DemoParserError::ArrowError(format!("{}", e))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_partition_value() {
+        assert_eq!(partition_value("de_dust2"), "de_dust2");
+        assert_eq!(partition_value("workshop/123/de_x"), "workshop_123_de_x");
+        assert_eq!(partition_value(""), "unknown");
+    }
+}
@@ -1,18 +1,27 @@
+use crate::callbacks::ParserCallbacks;
+use crate::chunk_seeds::EntitySeed;
+use crate::collect_data::EntityPropRecord;
 use crate::collect_data::ProjectileRecord;
 use crate::decoder::QfMapper;
 use crate::game_events::GameEvent;
+use crate::kill_labels::label_kills;
 use crate::netmessage_types;
 use crate::netmessage_types::netmessage_type_from_int;
 use crate::parser_settings::Parser;
 use crate::parser_settings::ParserInputs;
 use crate::parser_thread_settings::*;
 use crate::parser_threads::demo_cmd_type_from_int;
+use crate::prop_changes::PropChange;
 use crate::prop_controller::PropController;
 use crate::read_bits::Bitreader;
+use crate::sendtables::Serializer;
 use crate::stringtables::parse_userinfo;
 use crate::stringtables::StringTable;
 use crate::stringtables::UserInfo;
 use crate::variants::PropColumn;
+use crate::warnings::merge_warnings;
+use crate::warnings::ParseWarning;
+use crate::warnings::ParseWarningKind;
 use crate::{other_netmessages::Class, read_bits::DemoParserError};
 use ahash::AHashMap;
 use ahash::AHashSet;
@@ -42,29 +51,95 @@ pub struct DemoOutput {
     pub game_events_counter: AHashSet<String>,
     pub prop_info: PropController,
     pub projectiles: Vec<ProjectileRecord>,
+    pub entity_props: Vec<EntityPropRecord>,
+    pub prop_changes: Vec<PropChange>,
     pub ptr: usize,
+    pub warnings: Vec<ParseWarning>,
 }
 
 impl Parser {
     pub fn parse_demo(&mut self) -> Result<DemoOutput, DemoParserError> {
-        Parser::handle_short_header(self.bytes.get_len(), &self.bytes[..16])?;
+        self.first_pass()?;
+        self.resolve_tick_selectors()?;
+        if !self.is_multithreadable && !self.settings.seeded_chunks {
+            return self.parse_demo_single_thread();
+        }
+        self.prepare_chunks()?;
+        self.parse_demo_multithread()
+    }
+    // Streaming version of parse_demo. Everything is parsed in one thread from the start of the demo and
+    // game events are handed to the callbacks instead of being stored in the output.
+    pub fn parse_demo_with_callbacks(&mut self, callbacks: ParserCallbacks) -> Result<DemoOutput, DemoParserError> {
+        for event_name in callbacks.wanted_events() {
+            if !self.wanted_events.contains(&event_name) {
+                self.wanted_events.push(event_name.clone());
+                self.settings.wanted_events.push(event_name);
+            }
+        }
+        self.first_pass()?;
+        self.resolve_tick_selectors()?;
+        let input = self.create_parser_thread_input(16, true);
+        let mut parser = ParserThread::new(input)?;
+        parser.callbacks = Some(callbacks);
+        parser.start()?;
+        let x = parser.create_output();
+        Ok(self.finish_outputs(&mut vec![x]))
+    }
+    // Reads everything needed before the actual parsing can start: header, sendtables, class info,
+    // game event list and the offsets of all fullpackets.
+    pub fn first_pass(&mut self) -> Result<(), DemoParserError> {
+        if self.first_pass_done {
+            return Ok(());
+        }
+        match Parser::handle_short_header(self.bytes.get_len(), &self.bytes[..self.bytes.get_len().min(16)]) {
+            Err(DemoParserError::DemoEndsEarly(msg)) if self.settings.lenient => {
+                self.warnings.push(ParseWarning {
+                    kind: ParseWarningKind::DemoEndsEarly,
+                    tick: None,
+                    offset: Some(self.bytes.get_len()),
+                    message: msg,
+                });
+            }
+            Err(e) => return Err(e),
+            Ok(_) => {}
+        };
         self.ptr = 16;
         let mut sendtable = None;
         loop {
             let frame_starts_at = self.ptr;
 
-            let cmd = self.read_varint()?;
-            let tick = self.read_varint()?;
-            let size = self.read_varint()?;
+            let header = (self.read_varint(), self.read_varint(), self.read_varint());
+            let (cmd, tick, size) = match header {
+                (Ok(cmd), Ok(tick), Ok(size)) => (cmd, tick, size),
+                (Err(e), _, _) | (_, Err(e), _) | (_, _, Err(e)) => {
+                    self.stop_or_fail(e.with_frame(self.tick, frame_starts_at, None))?;
+                    break;
+                }
+            };
             self.tick = tick as i32;
+            let frame_ends_at = self.ptr + size as usize;
 
             // Safety check
-            if self.ptr + size as usize >= self.bytes.get_len() {
+            if frame_ends_at >= self.bytes.get_len() {
+                if self.settings.lenient {
+                    self.warnings.push(ParseWarning::stopped(
+                        self.tick,
+                        frame_starts_at,
+                        "frame goes past the end of the file",
+                    ));
+                }
                 break;
             }
             let msg_type = cmd & !64;
             let is_compressed = (cmd & 64) == 64;
-            let demo_cmd = demo_cmd_type_from_int(msg_type as i32).unwrap();
+            let demo_cmd = match demo_cmd_type_from_int(msg_type as i32) {
+                Some(demo_cmd) => demo_cmd,
+                None => {
+                    let e = DemoParserError::UnknownDemoCommand(msg_type as i32).with_frame(self.tick, frame_starts_at, None);
+                    self.skip_frame_or_fail(e, frame_ends_at)?;
+                    continue;
+                }
+            };
 
             // skip these for performance reasons
             if demo_cmd == DEM_Packet || demo_cmd == DEM_AnimationData {
@@ -74,51 +149,54 @@ impl Parser {
 
             let bytes = match is_compressed {
                 true => match SnapDecoder::new().decompress_vec(self.read_n_bytes(size)?) {
-                    Ok(b) => b,
-                    Err(e) => return Err(DemoParserError::DecompressionFailure(format!("{}", e))),
+                    Ok(b) => Ok(b),
+                    Err(e) => Err(DemoParserError::DecompressionFailure(format!("{}", e))),
                 },
-                false => self.read_n_bytes(size)?.to_vec(),
+                false => self.read_n_bytes(size).map(|b| b.to_vec()),
             };
-            let ok: Result<(), DemoParserError> = match demo_cmd {
-                DEM_SendTables => {
-                    sendtable = match Message::parse_from_bytes(&bytes) {
-                        Ok(m) => Some(m),
-                        Err(_e) => return Err(DemoParserError::MalformedMessage),
-                    };
-                    Ok(())
+            let bytes = match bytes {
+                Ok(bytes) => bytes,
+                Err(e) => {
+                    let e = e.with_frame(self.tick, frame_starts_at, Some(format!("{:?}", demo_cmd)));
+                    self.skip_frame_or_fail(e, frame_ends_at)?;
+                    continue;
                 }
+            };
+            let ok: Result<(), DemoParserError> = match demo_cmd {
+                DEM_SendTables => match Message::parse_from_bytes(&bytes) {
+                    Ok(m) => {
+                        sendtable = Some(m);
+                        Ok(())
+                    }
+                    Err(_e) => Err(DemoParserError::MalformedMessage),
+                },
                 DEM_FileHeader => self.parse_header(&bytes),
-                DEM_ClassInfo => {
-                    let table = match sendtable.take() {
-                        Some(table) => table,
-                        None => return Err(DemoParserError::NoSendTableMessage),
-                    };
-                    self.parse_class_info(&bytes, table)?;
-                    Ok(())
-                }
+                DEM_ClassInfo => match sendtable.take() {
+                    Some(table) => self.parse_class_info(&bytes, table),
+                    None => Err(DemoParserError::NoSendTableMessage),
+                },
                 DEM_SignonPacket => self.parse_packet(&bytes),
                 DEM_Stop => break,
-                DEM_FullPacket => {
-                    self.parse_full_packet(&bytes).unwrap();
+                DEM_FullPacket => self.parse_full_packet(&bytes).map(|_| {
                     self.fullpacket_offsets.push(frame_starts_at);
-                    Ok(())
-                }
+                    self.demo_index.push(self.tick, frame_starts_at);
+                }),
                 _ => Ok(()),
             };
-            ok?;
+            if let Err(e) = ok {
+                let e = e.with_frame(self.tick, frame_starts_at, Some(format!("{:?}", demo_cmd)));
+                self.skip_frame_or_fail(e, frame_ends_at)?;
+            }
         }
         self.check_needed()?;
-
-        if self.is_multithreadable {
-            self.parse_demo_multithread()
-        } else {
-            self.parse_demo_single_thread()
-        }
+        self.first_pass_done = true;
+        Ok(())
     }
     fn check_needed(&mut self) -> Result<(), DemoParserError> {
         if !self.fullpacket_offsets.contains(&16) {
             self.fullpacket_offsets.push(16);
         }
+        self.demo_index.push_start(16);
         if self.ge_list.is_empty() {
             self.parse_fallback_event_list()?;
         }
@@ -126,26 +204,14 @@ impl Parser {
     }
 
     fn parse_demo_single_thread(&mut self) -> Result<DemoOutput, DemoParserError> {
-        let input = self.create_parser_thread_input(16, true);
-        let mut parser = ParserThread::new(input).unwrap();
-        parser.start()?;
-        let x = parser.create_output();
-        for prop in &self.added_temp_props {
-            self.wanted_player_props.retain(|x| x != prop);
-            self.prop_controller.prop_infos.retain(|x| &x.prop_name != prop);
-        }
-        return Ok(self.combine_thread_outputs(&mut vec![x]));
+        let x = self.parse_chunk(16, true)?;
+        Ok(self.finish_outputs(&mut vec![x]))
     }
     fn parse_demo_multithread(&mut self) -> Result<DemoOutput, DemoParserError> {
         let outputs: Vec<Result<DemoOutput, DemoParserError>> = self
             .fullpacket_offsets
             .par_iter()
-            .map(|offset| {
-                let input = self.create_parser_thread_input(*offset, false);
-                let mut parser = ParserThread::new(input).unwrap();
-                parser.start()?;
-                Ok(parser.create_output())
-            })
+            .map(|offset| self.parse_chunk(*offset, false))
             .collect();
 
         // check for errors
@@ -156,11 +222,33 @@ impl Parser {
                 Ok(r) => ok.push(r),
             };
         }
+        Ok(self.finish_outputs(&mut ok))
+    }
+    // Parses from the offset until the next fullpacket, or until the end of the demo if parse_all is set.
+    pub fn parse_chunk(&self, offset: usize, parse_all: bool) -> Result<DemoOutput, DemoParserError> {
+        let input = self.create_parser_thread_input(offset, parse_all);
+        let mut parser = ParserThread::new(input)?;
+        parser.start()?;
+        Ok(parser.create_output())
+    }
+    // (offset, parse_all) of every chunk parse_demo would split the demo into. Needs the first pass.
+    pub fn chunks(&self) -> Vec<(usize, bool)> {
+        match self.is_multithreadable || self.settings.seeded_chunks {
+            true => self.fullpacket_offsets.iter().map(|offset| (*offset, false)).collect(),
+            false => vec![(16, true)],
+        }
+    }
+    pub fn finish_outputs(&mut self, outputs: &mut Vec<DemoOutput>) -> DemoOutput {
         for prop in &self.added_temp_props {
             self.wanted_player_props.retain(|x| x != prop);
             self.prop_controller.prop_infos.retain(|x| &x.prop_name != prop);
         }
-        Ok(self.combine_thread_outputs(&mut ok))
+        let mut output = self.combine_thread_outputs(outputs);
+        if let Some(trade_window) = self.settings.kill_labels {
+            label_kills(&mut output.game_events, trade_window);
+            output.game_events.retain(|e| !self.added_temp_events.contains(&e.name));
+        }
+        output
     }
 
     // fn parse_stringtables_cmd(bytes: &[u8]) -> Result<(), DemoParserError> {}
@@ -175,22 +263,26 @@ impl Parser {
             baselines: self.baselines.clone(),
             prop_controller: self.prop_controller.clone(),
             cls_by_id: cls_by_id,
-            qfmap: Arc::new(self.qf_mapper.clone()),
+            qfmap: self.qf_mapper.clone(),
             ge_list: Arc::new(self.ge_list.clone()),
             parse_all_packets: parse_all,
             // arc?
             wanted_ticks: self.wanted_ticks.clone(),
             string_tables: self.string_tables.clone(),
             stringtable_players: self.stringtable_players.clone(),
+            seed: self.chunk_seeds.get(&offset).cloned(),
         }
     }
     pub fn parse_full_packet(&mut self, bytes: &[u8]) -> Result<(), DemoParserError> {
-        let full_packet: CDemoFullPacket = Message::parse_from_bytes(bytes).unwrap();
+        let full_packet: CDemoFullPacket = match Message::parse_from_bytes(bytes) {
+            Ok(p) => p,
+            Err(_e) => return Err(DemoParserError::MalformedMessage),
+        };
         for item in &full_packet.string_table.tables {
-            if item.table_name.as_ref().unwrap() == "instancebaseline" {
+            if item.table_name() == "instancebaseline" {
                 for i in &item.items {
                     let k = i.str().parse::<u32>().unwrap_or(999999);
-                    self.baselines.insert(k, i.data.as_ref().unwrap().clone());
+                    self.baselines.insert(k, i.data().to_vec());
                 }
             }
             if item.table_name == Some("userinfo".to_string()) {
@@ -225,7 +317,16 @@ impl Parser {
             game_events_counter: all_game_events,
             prop_info: self.prop_controller.clone(),
             projectiles: outputs.iter().flat_map(|x| x.projectiles.clone()).collect(),
+            entity_props: outputs.iter().flat_map(|x| x.entity_props.clone()).collect(),
+            prop_changes: outputs.iter().flat_map(|x| x.prop_changes.clone()).collect(),
             ptr: self.ptr,
+            warnings: merge_warnings(
+                self.warnings
+                    .iter()
+                    .chain(outputs.iter().flat_map(|x| x.warnings.iter()))
+                    .cloned()
+                    .collect(),
+            ),
         }
     }
     fn combine_dfs(&self, v: &mut Vec<AHashMap<u32, PropColumn>>) -> AHashMap<u32, PropColumn> {
@@ -248,8 +349,14 @@ impl Parser {
 
 impl Parser {
     pub fn parse_packet(&mut self, bytes: &[u8]) -> Result<(), DemoParserError> {
-        let packet: CDemoPacket = Message::parse_from_bytes(bytes).unwrap();
-        let packet_data = packet.data.unwrap();
+        let packet: CDemoPacket = match Message::parse_from_bytes(bytes) {
+            Ok(p) => p,
+            Err(_e) => return Err(DemoParserError::MalformedMessage),
+        };
+        let packet_data = match packet.data {
+            Some(data) => data,
+            None => return Err(DemoParserError::MalformedMessage),
+        };
         let mut bitreader = Bitreader::new(&packet_data);
         // Inner loop
         while bitreader.reader.has_bits_remaining(8) {
@@ -257,7 +364,8 @@ impl Parser {
             let size = bitreader.read_varint()?;
             let msg_bytes = bitreader.read_n_bytes(size as usize)?;
 
-            let ok = match netmessage_type_from_int(msg_type as i32) {
+            let msg_type = netmessage_type_from_int(msg_type as i32);
+            let ok = match msg_type {
                 GE_Source1LegacyGameEventList => {
                     let hm = self.parse_game_event_list(&msg_bytes)?;
                     self.ge_list = hm;
@@ -269,7 +377,9 @@ impl Parser {
                 svc_ClearAllStringTables => self.clear_stringtables(),
                 _ => Ok(()),
             };
-            ok?
+            if let Err(e) = ok {
+                return Err(e.with_netmessage(format!("{:?}", msg_type)));
+            }
         }
         Ok(())
     }
@@ -279,7 +389,10 @@ impl Parser {
     }
 
     pub fn parse_header(&mut self, bytes: &[u8]) -> Result<(), DemoParserError> {
-        let header: CDemoFileHeader = Message::parse_from_bytes(bytes).unwrap();
+        let header: CDemoFileHeader = match Message::parse_from_bytes(bytes) {
+            Ok(header) => header,
+            Err(_e) => return Err(DemoParserError::MalformedMessage),
+        };
         self.header
             .insert("demo_file_stamp".to_string(), header.demo_file_stamp().to_string());
         self.header
@@ -315,6 +428,9 @@ impl Parser {
         Ok(())
     }
     fn handle_short_header(file_len: usize, bytes: &[u8]) -> Result<(), DemoParserError> {
+        if bytes.len() < 16 {
+            return Err(DemoParserError::OutOfBytesError);
+        }
         match std::str::from_utf8(&bytes[..8]) {
             Ok(magic) => match magic {
                 "PBDEMS2\0" => {}
@@ -329,7 +445,7 @@ impl Parser {
         };
         // hmmmm not sure where the 18 comes from if the header is only 16?
         // can be used to check that file ends early
-        let file_length_expected = u32::from_le_bytes(bytes[8..12].try_into().unwrap()) + 18;
+        let file_length_expected = u32::from_le_bytes([bytes[8], bytes[9], bytes[10], bytes[11]]) + 18;
         let missing_percentage = 100.0 - (file_len as f32 / file_length_expected as f32 * 100.0);
         if missing_percentage > 10.0 {
             return Err(DemoParserError::DemoEndsEarly(format!(
@@ -340,29 +456,55 @@ impl Parser {
             )));
         }
         // seems to be byte offset to where DEM_END command happens. After that comes Spawngroups and fileinfo. odd...
-        let _no_clue_what_this_is = i32::from_le_bytes(bytes[12..].try_into().unwrap());
+        let _no_clue_what_this_is = i32::from_le_bytes([bytes[12], bytes[13], bytes[14], bytes[15]]);
         Ok(())
     }
 
     pub fn parse_class_info(&mut self, bytes: &[u8], sendtables: CDemoSendTables) -> Result<(), DemoParserError> {
-        let (mut serializers, qf_mapper, p) = self.parse_sendtable(sendtables)?;
-        let msg: CDemoClassInfo = Message::parse_from_bytes(&bytes).unwrap();
+        // Demos from the same game build have identical sendtables
+        let cache_key = self.class_cache_key(sendtables.data(), bytes);
+        if self.load_cached_classes(cache_key)? {
+            return Ok(());
+        }
+        let (serializers, qf_mapper, p) = self.parse_sendtable(sendtables)?;
+        let msg: CDemoClassInfo = match Message::parse_from_bytes(&bytes) {
+            Ok(msg) => msg,
+            Err(_e) => return Err(DemoParserError::MalformedMessage),
+        };
+        let classes: Vec<(i32, String)> = msg
+            .classes
+            .iter()
+            .map(|class_t| (class_t.class_id(), class_t.network_name().to_string()))
+            .collect();
+        self.set_classes(classes.clone(), serializers, qf_mapper, p)?;
+        self.cache_classes(cache_key, classes);
+        Ok(())
+    }
+    pub fn set_classes(
+        &mut self,
+        classes: Vec<(i32, String)>,
+        mut serializers: AHashMap<String, Serializer>,
+        qf_mapper: QfMapper,
+        prop_controller: PropController,
+    ) -> Result<(), DemoParserError> {
         let mut cls_by_id = AHashMap::default();
-        for class_t in msg.classes {
-            let cls_id = class_t.class_id();
-            let network_name = class_t.network_name();
+        for (cls_id, network_name) in classes {
+            let serializer = match serializers.remove(&network_name) {
+                Some(ser) => ser,
+                None => return Err(DemoParserError::ClassNotFound),
+            };
             cls_by_id.insert(
                 cls_id as u32,
                 Class {
                     class_id: cls_id,
-                    name: network_name.to_string(),
-                    serializer: serializers.remove(network_name).unwrap(), // [network_name].clone(),
+                    name: network_name,
+                    serializer,
                 },
             );
         }
         self.cls_by_id = Some(Arc::new(cls_by_id));
-        self.qf_mapper = qf_mapper;
-        self.prop_controller = p;
+        self.qf_mapper = Arc::new(qf_mapper);
+        self.prop_controller = prop_controller;
         return Ok(());
     }
 }
@@ -378,6 +520,7 @@ pub struct ParserThreadInput {
     pub wanted_ticks: AHashSet<i32>,
     pub string_tables: Vec<StringTable>,
     pub stringtable_players: BTreeMap<u64, UserInfo>,
+    pub seed: Option<Arc<EntitySeed>>,
 }
 
 pub struct ClassInfoThreadResult {
@@ -1,6 +1,9 @@
+use super::sendtables::SendTableLayout;
 use super::sendtables::Serializer;
 use super::stringtables::StringTable;
+use crate::chunk_seeds::EntitySeed;
 use crate::decoder::QfMapper;
+use crate::kill_labels::KILL_LABEL_EVENTS;
 use crate::maps::FRIENDLY_NAMES_MAPPING;
 use crate::maps::NON_MULTITHREADABLE_PROPS;
 use crate::other_netmessages::Class;
@@ -9,8 +12,11 @@ use crate::parser_thread_settings::SpecialIDs;
 use crate::prop_controller::PropController;
 use crate::prop_controller::PropInfo;
 use crate::read_bits::DemoParserError;
+use crate::seek::DemoIndex;
 use crate::stringtables::UserInfo;
+use crate::tick_selectors::TickSelector;
 use crate::variants::BytesVariant;
+use crate::warnings::ParseWarning;
 use ahash::AHashMap;
 use ahash::AHashSet;
 use ahash::RandomState;
@@ -28,27 +34,45 @@ pub struct ParserInputs {
     pub wanted_player_props_og_names: Vec<String>,
     pub wanted_other_props: Vec<String>,
     pub wanted_other_props_og_names: Vec<String>,
+    // Raw props on any entity class, "CPlantedC4.m_bBombTicking". See collect_entity_props
+    pub wanted_entity_props: Vec<String>,
 
     pub wanted_ticks: Vec<i32>,
+    pub tick_selectors: Vec<TickSelector>,
+    // Only every nth of the wanted ticks, 0 or 1 for all of them
+    pub tick_stride: i32,
     pub wanted_events: Vec<String>,
     pub parse_ents: bool,
     pub parse_projectiles: bool,
+    // Record changes of the wanted props instead of collecting them every tick, see prop_changes.rs
+    pub prop_changes: bool,
+    // Trade window in ticks, labels kills and adds clutch events when set, see kill_labels.rs
+    pub kill_labels: Option<i32>,
+    // Parse non-multithreadable props in seeded chunks instead of in one thread, see chunk_seeds.rs
+    pub seeded_chunks: bool,
     pub only_header: bool,
     pub count_props: bool,
     pub only_convars: bool,
+    // Skip broken frames and stop at the end of truncated demos instead of failing, see warnings.rs
+    pub lenient: bool,
     pub huffman_lookup_table: Arc<Vec<(u32, u8)>>,
 }
 
 pub struct Parser {
     pub added_temp_props: Vec<String>,
+    pub added_temp_events: Vec<String>,
     pub real_name_to_og_name: AHashMap<String, String>,
     pub fullpacket_offsets: Vec<usize>,
+    pub demo_index: DemoIndex,
+    pub chunk_seeds: AHashMap<usize, Arc<EntitySeed>>,
+    pub first_pass_done: bool,
     pub ptr: usize,
     pub bytes: Arc<BytesVariant>,
     pub tick: i32,
     pub huf: Arc<Vec<(u32, u8)>>,
     pub settings: ParserInputs,
     pub serializers: AHashMap<String, Serializer>,
+    pub sendtable_layout: Option<Arc<SendTableLayout>>,
     pub cls_by_id: Option<Arc<AHashMap<u32, Class>>>,
     pub string_tables: Vec<StringTable>,
     pub baselines: AHashMap<u32, Vec<u8>, RandomState>,
@@ -58,7 +82,7 @@ pub struct Parser {
     pub prop_controller: PropController,
     pub prop_controller_is_set: bool,
     pub ge_list: AHashMap<i32, Descriptor_t>,
-    pub qf_mapper: QfMapper,
+    pub qf_mapper: Arc<QfMapper>,
     pub stringtable_players: BTreeMap<u64, UserInfo>,
 
     pub qf_map_set: bool,
@@ -72,6 +96,7 @@ pub struct Parser {
     // Team and rules props
     pub wanted_other_props: Vec<String>,
     pub wanted_other_props_og_names: Vec<String>,
+    pub wanted_entity_props: Vec<String>,
     pub wanted_events: Vec<String>,
     pub parse_entities: bool,
     pub parse_projectiles: bool,
@@ -89,6 +114,7 @@ pub struct Parser {
     pub header: AHashMap<String, String>,
     pub threads_spawned: u32,
     pub is_multithreadable: bool,
+    pub warnings: Vec<ParseWarning>,
 }
 pub fn needs_velocity(props: &[String]) -> bool {
     for prop in props {
@@ -111,9 +137,19 @@ impl Parser {
                 .extend(vec!["X".to_string(), "Y".to_string(), "Z".to_string()]);
             added_temp_props.extend(vec!["X".to_string(), "Y".to_string(), "Z".to_string()]);
         }
+        let mut added_temp_events = vec![];
+        if inputs.kill_labels.is_some() && !inputs.wanted_events.iter().any(|e| e == "all") {
+            for event_name in KILL_LABEL_EVENTS {
+                if !inputs.wanted_events.iter().any(|e| e == event_name) {
+                    inputs.wanted_events.push(event_name.to_string());
+                    added_temp_events.push(event_name.to_string());
+                }
+            }
+        }
 
         Parser {
             added_temp_props: added_temp_props,
+            added_temp_events,
             threads_spawned: 0,
             is_multithreadable: check_multithreadability(&inputs.wanted_player_props),
             largest_wanted_tick: *inputs.wanted_ticks.iter().max().unwrap_or(&999999999),
@@ -127,7 +163,8 @@ impl Parser {
                 inputs.wanted_player_props.clone(),
                 inputs.wanted_other_props.clone(),
                 inputs.real_name_to_og_name.clone(),
-            ),
+            )
+            .with_entity_props(inputs.wanted_entity_props.clone()),
             prop_controller_is_set: false,
             cls_by_id: None,
             player_md: vec![],
@@ -137,23 +174,28 @@ impl Parser {
             bytes: arc_bytes.clone(),
             string_tables: vec![],
             fullpacket_offsets: vec![],
+            demo_index: DemoIndex::default(),
+            chunk_seeds: AHashMap::default(),
+            first_pass_done: false,
             ptr: 0,
             baselines: AHashMap::default(),
             tick: 0,
             huf: arc_huf,
-            qf_mapper: QfMapper {
+            qf_mapper: Arc::new(QfMapper {
                 idx: 0,
                 map: AHashMap::default(),
-            },
+            }),
             ge_list: AHashMap::default(),
             parse_entities: true,
             serializers: AHashMap::default(),
+            sendtable_layout: None,
             parse_projectiles: false,
             wanted_player_props: inputs.wanted_player_props.clone(),
             wanted_events: inputs.wanted_events.clone(),
             wanted_ticks: AHashSet::from_iter(inputs.wanted_ticks.iter().cloned()),
             wanted_other_props: inputs.wanted_other_props.clone(),
             wanted_other_props_og_names: inputs.wanted_other_props_og_names.clone(),
+            wanted_entity_props: inputs.wanted_entity_props.clone(),
             settings: inputs,
             wanted_player_props_og_names: vec![],
             controller_ids: SpecialIDs::new(),
@@ -163,6 +205,7 @@ impl Parser {
             prop_out_id: 0,
             prop_infos: vec![],
             header: AHashMap::default(),
+            warnings: vec![],
         }
     }
 }
@@ -180,11 +223,17 @@ pub fn rm_user_friendly_names(names: &Vec<String>) -> Result<Vec<String>, DemoPa
     for name in names {
         match FRIENDLY_NAMES_MAPPING.get(name) {
             Some(real_name) => real_names.push(real_name.to_string()),
+            // Raw names as listed by Parser::prop_schema, for example "CCSPlayerPawn.m_flFlashDuration" or "m_iClip1".
+            // Checked against the sendtables in PropController::check_raw_props
+            None if is_raw_prop_name(name) => real_names.push(name.clone()),
             None => return Err(DemoParserError::UnknownPropName(name.clone())),
         }
     }
     Ok(real_names)
 }
+fn is_raw_prop_name(name: &str) -> bool {
+    name.contains('.') || name.starts_with("m_")
+}
 use memmap2::MmapOptions;
 use std::fs::File;
 
@@ -3,6 +3,10 @@ use super::read_bits::DemoParserError;
 use super::sendtables::Serializer;
 use super::stringtables::StringTable;
 use super::variants::PropColumn;
+use crate::callbacks::ParserCallbacks;
+use crate::chunk_seeds::ChunkState;
+use crate::chunk_seeds::EntitySeed;
+use crate::collect_data::EntityPropRecord;
 use crate::collect_data::ProjectileRecord;
 use crate::decoder::QfMapper;
 use crate::entities::Entity;
@@ -10,13 +14,16 @@ use crate::entities::PlayerMetaData;
 use crate::other_netmessages::Class;
 use crate::parser::DemoOutput;
 use crate::parser::ParserThreadInput;
+use crate::prop_changes::PropChange;
 use crate::prop_controller::PropController;
 use crate::sendtables::DebugField;
 use crate::sendtables::DebugFieldAndPath;
 use crate::sendtables::FieldInfo;
 use crate::sendtables::FieldModel;
 use crate::stringtables::UserInfo;
+use crate::tick_selectors::TickSelector;
 use crate::variants::BytesVariant;
+use crate::warnings::ParseWarning;
 use ahash::AHashMap;
 use ahash::AHashSet;
 use ahash::HashMap;
@@ -41,6 +48,7 @@ pub struct ParserThread {
     pub parse_inventory: bool,
 
     pub ptr: usize,
+    pub start_offset: usize,
     pub bytes: Arc<BytesVariant>,
     pub parse_all_packets: bool,
     // Parsing state
@@ -64,7 +72,13 @@ pub struct ParserThread {
     pub packets_parsed: u32,
     pub cnt: AHashMap<FieldModel, u32>,
     pub projectile_records: Vec<ProjectileRecord>,
+    pub entity_prop_records: Vec<EntityPropRecord>,
+    pub prop_changes: Vec<PropChange>,
+    // Prop ids recorded into prop_changes, empty if not wanted
+    pub change_tracked_props: AHashSet<u32>,
     pub wanted_ticks: AHashSet<i32>,
+    pub tick_selectors: Vec<TickSelector>,
+    pub tick_stride: i32,
 
     // Output from parsing
     pub output: AHashMap<u32, PropColumn, RandomState>,
@@ -80,8 +94,27 @@ pub struct ParserThread {
     pub wanted_events: Vec<String>,
     pub parse_entities: bool,
     pub parse_projectiles: bool,
+    pub kill_labels: bool,
     pub debug_fields: Vec<DebugFieldAndPath>,
     pub is_debug_mode: bool,
+
+    // Streaming
+    pub callbacks: Option<ParserCallbacks>,
+    pub stop_requested: bool,
+    pub last_callback_tick: i32,
+    // Message id -> whether a user message callback is registered for its name
+    pub user_message_wanted: AHashMap<i32, bool>,
+
+    // Chunked parsing of non-multithreadable props, see chunk_seeds.rs
+    pub tracked_props: AHashSet<u32>,
+    pub track_writes: bool,
+    pub chunk_state: ChunkState,
+    pub seed: Option<Arc<EntitySeed>>,
+
+    pub lenient: bool,
+    pub warnings: Vec<ParseWarning>,
+    // A packet failed halfway through so entity state can't be trusted until the next fullpacket
+    pub desynced: bool,
 }
 #[derive(Debug, Clone)]
 pub struct Teams {
@@ -149,7 +182,10 @@ impl ParserThread {
             game_events_counter: self.game_events_counter,
             prop_info: PropController::new(vec![], vec![], AHashMap::default()),
             projectiles: self.projectile_records,
+            entity_props: self.entity_prop_records,
+            prop_changes: self.prop_changes,
             ptr: self.ptr,
+            warnings: self.warnings,
         }
     }
     pub fn new(input: ParserThreadInput) -> Result<Self, DemoParserError> {
@@ -160,6 +196,10 @@ impl ParserThread {
             .extend(vec!["tick".to_owned(), "steamid".to_owned(), "name".to_owned()]);
         let args: Vec<String> = env::args().collect();
         let debug = if args.len() > 2 { args[2] == "true" } else { false };
+        let change_tracked_props = match input.settings.prop_changes {
+            true => input.prop_controller.change_tracked_props().into_keys().collect(),
+            false => AHashSet::default(),
+        };
 
         // Dont allocate vec in release mode
         let debug_vec_len = match debug {
@@ -184,8 +224,13 @@ impl ParserThread {
             stringtable_players: input.stringtable_players,
             is_debug_mode: debug,
             projectile_records: vec![],
+            entity_prop_records: vec![],
+            prop_changes: vec![],
+            change_tracked_props,
             parse_all_packets: input.parse_all_packets,
             wanted_ticks: input.wanted_ticks.clone(),
+            tick_selectors: input.settings.tick_selectors.clone(),
+            tick_stride: input.settings.tick_stride,
             prop_controller: Arc::new(input.prop_controller),
             qf_mapper: input.qfmap,
             fullpackets_parsed: 0,
@@ -193,6 +238,7 @@ impl ParserThread {
             cnt: AHashMap::default(),
             serializers: AHashMap::default(),
             ptr: input.offset,
+            start_offset: input.offset,
             ge_list: input.ge_list.clone(),
             bytes: input.settings.bytes.clone(),
             cls_by_id: input.cls_by_id,
@@ -220,6 +266,7 @@ impl ParserThread {
             teams: Teams::new(),
             game_events_counter: AHashSet::default(),
             parse_projectiles: input.settings.parse_projectiles,
+            kill_labels: input.settings.kill_labels.is_some(),
             rules_entity_id: None,
             convars: AHashMap::default(),
             chat_messages: vec![],
@@ -228,6 +275,17 @@ impl ParserThread {
             player_end_data: vec![],
             huffman_lookup_table: input.settings.huffman_lookup_table.clone(),
             header: HashMap::default(),
+            callbacks: None,
+            stop_requested: false,
+            last_callback_tick: -99999,
+            user_message_wanted: AHashMap::default(),
+            tracked_props: AHashSet::default(),
+            track_writes: true,
+            chunk_state: ChunkState::default(),
+            seed: input.seed,
+            lenient: input.settings.lenient,
+            warnings: vec![],
+            desynced: false,
         })
     }
 }
@@ -271,6 +329,12 @@ pub struct SpecialIDs {
 
     pub h_owner_entity: Option<u32>,
     pub agent_skin_idx: Option<u32>,
+
+    pub total_rounds_played: Option<u32>,
+    pub freeze_period: Option<u32>,
+    pub warmup_period: Option<u32>,
+    pub round_win_status: Option<u32>,
+    pub round_start_time: Option<u32>,
 }
 impl SpecialIDs {
     pub fn new() -> Self {
@@ -304,6 +368,11 @@ impl SpecialIDs {
             orig_own_low: None,
             life_state: None,
             agent_skin_idx: None,
+            total_rounds_played: None,
+            freeze_period: None,
+            warmup_period: None,
+            round_win_status: None,
+            round_start_time: None,
         }
     }
 }
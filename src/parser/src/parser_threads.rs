@@ -5,6 +5,7 @@ use crate::netmessage_types::NetmessageType;
 use crate::parser_thread_settings::ParserThread;
 use crate::read_bits::Bitreader;
 use crate::stringtables::parse_userinfo;
+use crate::warnings::ParseWarning;
 use bitter::BitReader;
 use csgoproto::demo::*;
 use csgoproto::netmessages::*;
@@ -14,61 +15,145 @@ use protobuf::Message;
 use snap::raw::Decoder as SnapDecoder;
 use EDemoCommands::*;
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FrameResult {
+    Parsed,
+    Skipped,
+    Stop,
+}
+
 impl ParserThread {
     pub fn start(&mut self) -> Result<(), DemoParserError> {
-        let started_at = self.ptr;
         loop {
-            let cmd = self.read_varint()?;
-            let tick = self.read_varint()?;
-            let size = self.read_varint()?;
-
-            self.tick = tick as i32;
-            self.packets_parsed += 1;
-
-            if self.ptr + size as usize >= self.bytes.get_len() {
+            let tick_before = self.tick;
+            match self.parse_frame()? {
+                FrameResult::Parsed => self.collect_entities(),
+                FrameResult::Skipped => {}
+                FrameResult::Stop => {
+                    // The last tick is only complete once the file ends
+                    if self.callbacks.is_some() && self.tick == tick_before {
+                        self.emit_tick();
+                    }
+                    break;
+                }
+            }
+            // A tick can span several frames, the tick callbacks only see it once all of them are parsed
+            if self.callbacks.is_some() && self.peek_next_tick() != Some(self.tick) {
+                self.emit_tick();
+            }
+            if self.stop_requested {
                 break;
             }
+        }
+        Ok(())
+    }
+    // Reads and handles one demo command (frame).
+    pub fn parse_frame(&mut self) -> Result<FrameResult, DemoParserError> {
+        let frame_starts_at = self.ptr;
+        let header = (self.read_varint(), self.read_varint(), self.read_varint());
+        let (cmd, tick, size) = match header {
+            (Ok(cmd), Ok(tick), Ok(size)) => (cmd, tick, size),
+            (Err(e), _, _) | (_, Err(e), _) | (_, _, Err(e)) => {
+                return self.stop_or_fail(e.with_frame(self.tick, frame_starts_at, None));
+            }
+        };
 
-            let msg_type = cmd & !64;
-            let is_compressed = (cmd & 64) == 64;
-            let demo_cmd = demo_cmd_type_from_int(msg_type as i32).unwrap();
+        self.tick = tick as i32;
+        self.packets_parsed += 1;
+        let frame_ends_at = self.ptr + size as usize;
 
-            if demo_cmd == DEM_AnimationData || demo_cmd == DEM_SendTables || demo_cmd == DEM_StringTables {
-                self.ptr += size as usize;
-                continue;
+        if frame_ends_at >= self.bytes.get_len() {
+            if self.lenient {
+                self.warnings.push(ParseWarning::stopped(
+                    self.tick,
+                    frame_starts_at,
+                    "frame goes past the end of the file",
+                ));
             }
-            let bytes = match is_compressed {
-                true => match SnapDecoder::new().decompress_vec(self.read_n_bytes(size)?) {
-                    Ok(b) => b,
-                    Err(e) => return Err(DemoParserError::DecompressionFailure(format!("{}", e))),
-                },
-                false => self.read_n_bytes(size)?.to_vec(),
-            };
+            return Ok(FrameResult::Stop);
+        }
 
-            let ok = match demo_cmd {
-                DEM_SignonPacket => self.parse_packet(&bytes),
-                DEM_Packet => self.parse_packet(&bytes),
-                DEM_FullPacket => {
-                    match self.parse_all_packets {
-                        true => {}
-                        false => {
-                            if self.fullpackets_parsed == 0 && started_at != 16 {
-                                self.parse_full_packet(&bytes)?;
-                                self.fullpackets_parsed += 1;
-                            } else {
-                                break;
-                            }
-                        }
-                    }
-                    Ok(())
+        let msg_type = cmd & !64;
+        let is_compressed = (cmd & 64) == 64;
+        let demo_cmd = match demo_cmd_type_from_int(msg_type as i32) {
+            Some(demo_cmd) => demo_cmd,
+            None => {
+                let e = DemoParserError::UnknownDemoCommand(msg_type as i32).with_frame(self.tick, frame_starts_at, None);
+                return self.skip_frame_or_fail(e, frame_ends_at);
+            }
+        };
+
+        if demo_cmd == DEM_AnimationData || demo_cmd == DEM_SendTables || demo_cmd == DEM_StringTables {
+            self.ptr += size as usize;
+            return Ok(FrameResult::Skipped);
+        }
+        let is_packet = demo_cmd == DEM_Packet || demo_cmd == DEM_SignonPacket;
+        if self.desynced && is_packet {
+            self.ptr += size as usize;
+            return Ok(FrameResult::Skipped);
+        }
+        match self.parse_demo_cmd(demo_cmd, is_compressed, size) {
+            Ok(result) => Ok(result),
+            Err(e) => {
+                let e = e.with_frame(self.tick, frame_starts_at, Some(format!("{:?}", demo_cmd)));
+                match is_packet {
+                    true => self.skip_packet_or_fail(e, frame_ends_at),
+                    false => self.skip_frame_or_fail(e, frame_ends_at),
                 }
-                DEM_Stop => break,
-                _ => Ok(()),
-            };
-            ok?;
-            self.collect_entities();
+            }
         }
-        Ok(())
+    }
+    fn parse_demo_cmd(
+        &mut self,
+        demo_cmd: EDemoCommands,
+        is_compressed: bool,
+        size: u32,
+    ) -> Result<FrameResult, DemoParserError> {
+        let bytes = match is_compressed {
+            true => match SnapDecoder::new().decompress_vec(self.read_n_bytes(size)?) {
+                Ok(b) => b,
+                Err(e) => return Err(DemoParserError::DecompressionFailure(format!("{}", e))),
+            },
+            false => self.read_n_bytes(size)?.to_vec(),
+        };
+
+        let ok = match demo_cmd {
+            DEM_SignonPacket => self.parse_packet(&bytes),
+            DEM_Packet => self.parse_packet(&bytes),
+            DEM_FullPacket => {
+                // A thread that starts from a fullpacket gets its initial state from it
+                if self.fullpackets_parsed == 0 && self.start_offset != 16 {
+                    self.track_writes = false;
+                    self.parse_full_packet(&bytes)?;
+                    self.track_writes = true;
+                    self.apply_seed();
+                    self.fullpackets_parsed += 1;
+                } else if !self.parse_all_packets {
+                    return Ok(FrameResult::Stop);
+                } else if self.desynced {
+                    // Fullpacket has every live entity, start over from it
+                    self.entities.clear();
+                    self.projectiles.clear();
+                    self.parse_full_packet(&bytes)?;
+                    self.desynced = false;
+                }
+                Ok(())
+            }
+            DEM_Stop => return Ok(FrameResult::Stop),
+            _ => Ok(()),
+        };
+        ok?;
+        Ok(FrameResult::Parsed)
+    }
+    // Tick of the next frame without moving the pointer. None if at the end of the file.
+    pub fn peek_next_tick(&mut self) -> Option<i32> {
+        let ptr = self.ptr;
+        let tick = match (self.read_varint(), self.read_varint()) {
+            (Ok(_cmd), Ok(tick)) => Some(tick as i32),
+            _ => None,
+        };
+        self.ptr = ptr;
+        tick
     }
     fn packet_orderer(&self, packet: &NetmessageType) -> i32 {
         match packet {
@@ -84,7 +169,10 @@ impl ParserThread {
             Err(_e) => return Err(DemoParserError::MalformedMessage),
             Ok(p) => p,
         };
-        let packet_data = packet.data.unwrap();
+        let packet_data = match packet.data {
+            Some(data) => data,
+            None => return Err(DemoParserError::MalformedMessage),
+        };
         let mut bitreader = Bitreader::new(&packet_data);
         // Inner loop
         let mut msgs = vec![];
@@ -93,13 +181,16 @@ impl ParserThread {
             let msg_type = bitreader.read_u_bit_var()?;
             let size = bitreader.read_varint()?;
             let msg_bytes = bitreader.read_n_bytes(size as usize)?;
-            msgs.push((msg_bytes, netmessage_type_from_int(msg_type as i32)));
+            msgs.push((msg_bytes, netmessage_type_from_int(msg_type as i32), msg_type as i32));
         }
 
         msgs.sort_by_key(|x| self.packet_orderer(&x.1));
         let mut wrong_order_events = vec![];
 
-        for (msg_bytes, msg_type) in msgs {
+        for (msg_bytes, msg_type, msg_id) in msgs {
+            if self.wants_user_message(msg_id, &msg_type) {
+                self.emit_user_message(&format!("{:?}", msg_type), &msg_bytes);
+            }
             let ok = match msg_type {
                 svc_PacketEntities => self.parse_packet_ents(&msg_bytes),
                 svc_CreateStringTable => self.parse_create_stringtable(&msg_bytes),
@@ -119,11 +210,13 @@ impl ParserThread {
                         Ok(())
                     }
                     Ok(None) => Ok(()),
-                    Err(e) => return Err(e),
+                    Err(e) => Err(e),
                 },
                 _ => Ok(()),
             };
-            ok?;
+            if let Err(e) = ok {
+                return Err(e.with_netmessage(format!("{:?}", msg_type)));
+            }
         }
         if !wrong_order_events.is_empty() {
             self.resolve_wrong_order_event(&mut wrong_order_events)?;
@@ -147,7 +240,7 @@ impl ParserThread {
             if item.table_name == Some("instancebaseline".to_string()) {
                 for i in &item.items {
                     let k = i.str().parse::<u32>().unwrap_or(999999);
-                    self.baselines.insert(k, i.data.as_ref().unwrap().clone());
+                    self.baselines.insert(k, i.data().to_vec());
                 }
             }
             if item.table_name == Some("userinfo".to_string()) {
@@ -160,15 +253,19 @@ impl ParserThread {
                 }
             }
         }
-        let p = full_packet.packet.0.unwrap();
+        let p = match full_packet.packet.0 {
+            Some(p) => p,
+            None => return Err(DemoParserError::MalformedMessage),
+        };
         let mut bitreader = Bitreader::new(p.data());
         // Inner loop
-        while bitreader.reader.bits_remaining().unwrap() > 8 {
+        while bitreader.reader.has_bits_remaining(9) {
             let msg_type = bitreader.read_u_bit_var()?;
             let size = bitreader.read_varint()?;
             let msg_bytes = bitreader.read_n_bytes(size as usize)?;
 
-            let ok = match netmessage_type_from_int(msg_type as i32) {
+            let msg_type = netmessage_type_from_int(msg_type as i32);
+            let ok = match msg_type {
                 svc_PacketEntities => self.parse_packet_ents(&msg_bytes),
                 svc_CreateStringTable => self.parse_create_stringtable(&msg_bytes),
                 svc_UpdateStringTable => self.update_string_table(&msg_bytes),
@@ -182,7 +279,9 @@ impl ParserThread {
                 svc_ClearAllStringTables => self.clear_stringtables(),
                 _ => Ok(()),
             };
-            ok?
+            if let Err(e) = ok {
+                return Err(e.with_netmessage(format!("{:?}", msg_type)));
+            }
         }
         Ok(())
     }
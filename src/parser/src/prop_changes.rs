@@ -0,0 +1,231 @@
+use crate::collect_data::PropType;
+use crate::parser_thread_settings::ParserThread;
+use crate::prop_controller::PropController;
+use crate::variants::Variant;
+use ahash::AHashMap;
+use std::collections::BTreeMap;
+
+// Change-only output (DemoQuery::prop_changes): decode_entity_update records a PropChange every time a decoded
+// value differs from the one the entity had. examples/prop_changes.rs shows it in use.
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PropChange {
+    pub tick: i32,
+    pub entity_id: i32,
+    pub prop_id: u32,
+    // None when the entity was deleted
+    pub value: Option<Variant>,
+}
+
+// One entity at one tick, rebuilt from the changes
+#[derive(Debug, Clone, PartialEq)]
+pub struct PropStateRow {
+    pub tick: i32,
+    pub entity_id: i32,
+    // Same order as the prop ids given to reconstruct
+    pub values: Vec<Option<Variant>>,
+}
+
+impl PropController {
+    // Prop id => name for every prop that can be recorded as changes: the ones stored as is on an entity.
+    // Props computed when collected (X, Y, is_alive, weapon_name...) are still collected every tick.
+    pub fn change_tracked_props(&self) -> AHashMap<u32, String> {
+        let mut names = AHashMap::default();
+        for info in &self.prop_infos {
+            match info.prop_type {
+                PropType::Player | PropType::Controller | PropType::Team | PropType::Rules | PropType::Weapon => {
+                    names.insert(info.id, info.prop_friendly_name.clone());
+                }
+                _ => {}
+            }
+        }
+        for (name, id) in &self.entity_prop_ids {
+            names.insert(*id, name.clone());
+        }
+        names
+    }
+}
+
+impl ParserThread {
+    pub fn record_entity_removed(&mut self, entity_id: i32) {
+        if self.change_tracked_props.is_empty() || !self.track_writes {
+            return;
+        }
+        if let Some(entity) = self.entities.get(&entity_id) {
+            for prop_id in entity.props.keys().filter(|id| self.change_tracked_props.contains(*id)) {
+                self.prop_changes.push(PropChange {
+                    tick: self.tick,
+                    entity_id,
+                    prop_id: *prop_id,
+                    value: None,
+                });
+            }
+        }
+    }
+}
+
+// Full table at the wanted ticks: every entity that has at least one of the props. Changes must be in the
+// order they were recorded (DemoOutput.prop_changes is).
+pub fn reconstruct(changes: &[PropChange], prop_ids: &[u32], ticks: &[i32]) -> Vec<PropStateRow> {
+    let mut state: BTreeMap<i32, AHashMap<u32, Variant>> = BTreeMap::default();
+    let mut ticks = ticks.to_vec();
+    ticks.sort();
+    let mut changes = changes.iter().peekable();
+    let mut rows = vec![];
+    for tick in ticks {
+        while let Some(change) = changes.next_if(|c| c.tick <= tick) {
+            let props = state.entry(change.entity_id).or_default();
+            match &change.value {
+                Some(value) => props.insert(change.prop_id, value.clone()),
+                None => props.remove(&change.prop_id),
+            };
+        }
+        for (entity_id, props) in &state {
+            if !prop_ids.iter().any(|id| props.contains_key(id)) {
+                continue;
+            }
+            rows.push(PropStateRow {
+                tick,
+                entity_id: *entity_id,
+                values: prop_ids.iter().map(|id| props.get(id).cloned()).collect(),
+            });
+        }
+    }
+    rows
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entities::Entity;
+    use crate::entities::EntityType;
+    use crate::other_netmessages::Class;
+    use crate::parser_settings::Parser;
+    use crate::query::DemoQuery;
+    use crate::read_bits::Bitreader;
+    use crate::sendtables::Decoder;
+    use crate::sendtables::Field;
+    use crate::sendtables::FieldModel;
+    use crate::sendtables::FieldType;
+    use crate::sendtables::Serializer;
+    use crate::variants::BytesVariant;
+    use std::sync::Arc;
+
+    const HEALTH_ID: u32 = 1000;
+    const ENTITY_ID: i32 = 5;
+
+    // Thread with one entity whose class has a single varint field
+    fn tracking_thread() -> ParserThread {
+        let inputs = DemoQuery::new().prop_changes().build(BytesVariant::Vec(vec![])).unwrap();
+        let parser = Parser::new(inputs);
+        let mut thread = ParserThread::new(parser.create_parser_thread_input(0, false)).unwrap();
+        let field = Field {
+            var_name: "m_iHealth".to_string(),
+            var_type: "int32".to_string(),
+            send_node: "".to_string(),
+            serializer_name: None,
+            encoder: "".to_string(),
+            encode_flags: 0,
+            bitcount: 0,
+            low_value: 0.0,
+            high_value: 0.0,
+            model: FieldModel::FieldModelSimple,
+            field_type: FieldType {
+                base_type: "int32".to_string(),
+                generic_type: None,
+                pointer: false,
+                count: 0,
+            },
+            decoder: Decoder::BaseDecoder,
+            serializer: None,
+            base_decoder: None,
+            child_decoder: None,
+            should_parse: true,
+            prop_id: HEALTH_ID as usize,
+            is_controller_prop: false,
+            controller_prop: None,
+            idx: 0,
+        };
+        let class = Class {
+            class_id: 0,
+            name: "CCSPlayerPawn".to_string(),
+            serializer: Serializer {
+                name: "CCSPlayerPawn".to_string(),
+                fields: vec![field],
+            },
+        };
+        thread.cls_by_id = Arc::new(AHashMap::from_iter([(0, class)]));
+        thread.entities.insert(
+            ENTITY_ID,
+            Entity {
+                cls_id: 0,
+                entity_id: ENTITY_ID,
+                props: AHashMap::default(),
+                entity_type: EntityType::Normal,
+            },
+        );
+        thread.change_tracked_props.insert(HEALTH_ID);
+        thread
+    }
+    // Field path [0] (huffman "0" then the stop code "10", read lsb first) followed by the value as a varint
+    fn health_update(health: u8) -> Vec<u8> {
+        let mut bytes = vec![0b010 | (health << 3), health >> 5];
+        bytes.resize(16, 0);
+        bytes
+    }
+
+    #[test]
+    fn test_decode_entity_update_records_changes() {
+        let mut thread = tracking_thread();
+        for (tick, health) in [(10, 100), (20, 100), (30, 73)] {
+            thread.tick = tick;
+            let bytes = health_update(health);
+            let mut bitreader = Bitreader::new(&bytes);
+            assert_eq!(thread.decode_entity_update(&mut bitreader, ENTITY_ID).unwrap(), 1);
+        }
+        thread.tick = 40;
+        thread.record_entity_removed(ENTITY_ID);
+        let changes: Vec<(i32, Option<Variant>)> = thread.prop_changes.iter().map(|c| (c.tick, c.value.clone())).collect();
+        // Same value again is not a change
+        assert_eq!(
+            changes,
+            vec![(10, Some(Variant::U32(100))), (30, Some(Variant::U32(73))), (40, None)]
+        );
+        assert!(thread
+            .prop_changes
+            .iter()
+            .all(|c| c.entity_id == ENTITY_ID && c.prop_id == HEALTH_ID));
+    }
+
+    fn change(tick: i32, entity_id: i32, prop_id: u32, value: Option<i32>) -> PropChange {
+        PropChange {
+            tick,
+            entity_id,
+            prop_id,
+            value: value.map(Variant::I32),
+        }
+    }
+
+    #[test]
+    fn test_reconstruct() {
+        let changes = vec![
+            change(1, 5, 100, Some(100)),
+            change(1, 5, 101, Some(7)),
+            change(1, 6, 100, Some(100)),
+            change(10, 5, 100, Some(73)),
+            change(20, 6, 100, None),
+        ];
+        let rows = reconstruct(&changes, &[100, 101], &[15, 1, 20]);
+        let simple: Vec<(i32, i32, Vec<Option<Variant>>)> = rows.into_iter().map(|r| (r.tick, r.entity_id, r.values)).collect();
+        assert_eq!(
+            simple,
+            vec![
+                (1, 5, vec![Some(Variant::I32(100)), Some(Variant::I32(7))]),
+                (1, 6, vec![Some(Variant::I32(100)), None]),
+                (15, 5, vec![Some(Variant::I32(73)), Some(Variant::I32(7))]),
+                (15, 6, vec![Some(Variant::I32(100)), None]),
+                (20, 5, vec![Some(Variant::I32(73)), Some(Variant::I32(7))]),
+            ]
+        );
+    }
+}
@@ -1,7 +1,9 @@
 use crate::collect_data::PropType;
 use crate::maps::BUTTONMAP;
+use crate::maps::FRIENDLY_NAMES_MAPPING;
 use crate::maps::TYPEHM;
 use crate::parser_thread_settings::SpecialIDs;
+use crate::read_bits::DemoParserError;
 use crate::sendtables::Field;
 use crate::sendtables::Serializer;
 use ahash::AHashMap;
@@ -33,6 +35,7 @@ pub const VELOCITY_ID: u32 = 100000007;
 pub const USERID_ID: u32 = 100000008;
 
 pub const AGENT_SKIN_ID: u32 = 100000009;
+pub const DEMO_ID_ID: u32 = 100000010;
 
 #[derive(Clone, Debug)]
 pub struct PropController {
@@ -47,6 +50,9 @@ pub struct PropController {
     pub name_to_special_id: AHashMap<String, u32>,
     pub wanted_other_props: Vec<String>,
     pub event_with_velocity: bool,
+    // "class.prop" as given to DemoQuery::entity_props and the id the prop got
+    pub wanted_entity_props: Vec<String>,
+    pub entity_prop_ids: AHashMap<String, u32>,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -82,8 +88,14 @@ impl PropController {
             wanted_other_props: wanted_other_props,
             real_name_to_og_name: real_name_to_og_name,
             event_with_velocity: false,
+            wanted_entity_props: vec![],
+            entity_prop_ids: AHashMap::default(),
         }
     }
+    pub fn with_entity_props(mut self, wanted_entity_props: Vec<String>) -> Self {
+        self.wanted_entity_props = wanted_entity_props;
+        self
+    }
     pub fn set_custom_propinfos(&mut self) {
         let button_names = BUTTONMAP.keys();
         let mut someid = BUTTONS_BASEID;
@@ -327,12 +339,17 @@ impl PropController {
     }
 
     fn insert_propinfo(&mut self, prop_name: &str, f: &mut Field) {
-        let prop_type = TYPEHM.get(&prop_name);
+        // Raw props that have no friendly name are collected from the entity their class points to,
+        // wanted props of other classes are rejected by check_raw_props.
+        let prop_type = match TYPEHM.get(&prop_name).copied().or_else(|| raw_prop_type(prop_name)) {
+            Some(prop_type) => prop_type,
+            None => return,
+        };
 
         if self.wanted_player_props.contains(&prop_name.to_string()) {
             self.prop_infos.push(PropInfo {
                 id: f.prop_id as u32,
-                prop_type: prop_type.copied().unwrap(),
+                prop_type,
                 prop_name: prop_name.to_string(),
                 prop_friendly_name: self
                     .real_name_to_og_name
@@ -345,7 +362,7 @@ impl PropController {
         if self.wanted_other_props.contains(&prop_name.to_string()) {
             self.prop_infos.push(PropInfo {
                 id: f.prop_id as u32,
-                prop_type: prop_type.copied().unwrap(),
+                prop_type,
                 prop_name: prop_name.to_string(),
                 prop_friendly_name: self
                     .real_name_to_og_name
@@ -357,29 +374,7 @@ impl PropController {
         }
     }
     pub fn handle_prop(&mut self, full_name: &str, f: &mut Field) {
-        // CAK47.m_iClip1 => ["CAK47", "m_iClip1"]
-        let split_at_dot: Vec<&str> = full_name.split(".").collect();
-        let is_weapon_prop = (split_at_dot[0].contains("Weapon") || split_at_dot[0].contains("AK"))
-            && !split_at_dot[0].contains("Player")
-            || split_at_dot[0].contains("Knife")
-            || split_at_dot[0].contains("CDEagle")
-            || split_at_dot[0].contains("C4")
-            || split_at_dot[0].contains("Molo")
-            || split_at_dot[0].contains("Inc")
-            || split_at_dot[0].contains("Infer");
-
-        let is_projectile_prop =
-            (split_at_dot[0].contains("Projectile") || split_at_dot[0].contains("Grenade") || split_at_dot[0].contains("Flash"))
-                && !split_at_dot[0].contains("Player");
-        let is_grenade_or_weapon = is_weapon_prop || is_projectile_prop;
-
-        // Strip first part of name from grenades and weapons.
-        // if weapon prop: CAK47.m_iClip1 => m_iClip1
-        // if grenade: CSmokeGrenadeProjectile.CBodyComponentBaseAnimGraph.m_cellX => CBodyComponentBaseAnimGraph.m_cellX
-        let prop_name = match is_grenade_or_weapon {
-            true => split_at_dot[1..].join("."),
-            false => full_name.to_string(),
-        };
+        let (prop_name, is_grenade_or_weapon) = split_prop_name(full_name);
         let prop_already_exists = self.name_to_id.contains_key(&(prop_name).to_string());
         self.set_id(&prop_name, f, is_grenade_or_weapon);
         if !prop_already_exists {
@@ -388,9 +383,27 @@ impl PropController {
         if self.should_parse(&prop_name) {
             f.should_parse = true;
         }
+        if self.wanted_entity_props.iter().any(|p| p == full_name) {
+            self.entity_prop_ids.insert(full_name.to_string(), f.prop_id as u32);
+            f.should_parse = true;
+        }
         self.id += 1;
     }
 
+    // Raw prop names are only checked for their shape when the query is built, once the sendtables
+    // are parsed every one of them has to exist on a class we collect from.
+    pub fn check_raw_props(&self) -> Result<(), DemoParserError> {
+        for name in self.wanted_player_props.iter().chain(&self.wanted_other_props) {
+            if FRIENDLY_NAMES_MAPPING.contains_key(name.as_str()) || TYPEHM.contains_key(name.as_str()) {
+                continue;
+            }
+            if raw_prop_type(name).is_none() || !self.name_to_id.contains_key(name) {
+                return Err(DemoParserError::UnknownPropName(name.clone()));
+            }
+        }
+        Ok(())
+    }
+
     fn should_parse(&self, name: &str) -> bool {
         if self.wanted_player_props.contains(&"X".to_string())
             || self.wanted_player_props.contains(&"Y".to_string())
@@ -411,6 +424,12 @@ impl PropController {
             "CCSPlayerPawn.CCSPlayer_WeaponServices.m_hActiveWeapon",
             "CCSPlayerPawn.m_iTeamNum",
             "CBasePlayerWeapon.m_nOwnerId",
+            // For DemoQuery::rounds
+            "CCSGameRulesProxy.CCSGameRules.m_totalRoundsPlayed",
+            "CCSGameRulesProxy.CCSGameRules.m_bFreezePeriod",
+            "CCSGameRulesProxy.CCSGameRules.m_bWarmupPeriod",
+            "CCSGameRulesProxy.CCSGameRules.m_iRoundWinStatus",
+            "CCSGameRulesProxy.CCSGameRules.m_fRoundStartTime",
         ];
         if self.wanted_player_props.contains(&("yaw").to_string())
             || self.wanted_player_props.contains(&("pitch").to_string()) && name == "CCSPlayerPawn.m_angEyeAngles"
@@ -476,6 +495,11 @@ impl PropController {
                 "CCSPlayerPawn.CCSPlayer_WeaponServices.m_hActiveWeapon" => self.special_ids.active_weapon = Some(id),
                 "CCSPlayerPawn.m_lifeState" => self.special_ids.life_state = Some(id),
                 "CCSPlayerController.m_nPawnCharacterDefIndex" => self.special_ids.agent_skin_idx = Some(id),
+                "CCSGameRulesProxy.CCSGameRules.m_totalRoundsPlayed" => self.special_ids.total_rounds_played = Some(id),
+                "CCSGameRulesProxy.CCSGameRules.m_bFreezePeriod" => self.special_ids.freeze_period = Some(id),
+                "CCSGameRulesProxy.CCSGameRules.m_bWarmupPeriod" => self.special_ids.warmup_period = Some(id),
+                "CCSGameRulesProxy.CCSGameRules.m_iRoundWinStatus" => self.special_ids.round_win_status = Some(id),
+                "CCSGameRulesProxy.CCSGameRules.m_fRoundStartTime" => self.special_ids.round_start_time = Some(id),
                 _ => {}
             };
         }
@@ -492,13 +516,54 @@ impl PropController {
     }
 }
 
+// Returns the name the parser uses for the prop and whether it belongs to a weapon or grenade.
+// Strip first part of name from grenades and weapons.
+// if weapon prop: CAK47.m_iClip1 => m_iClip1
+// if grenade: CSmokeGrenadeProjectile.CBodyComponentBaseAnimGraph.m_cellX => CBodyComponentBaseAnimGraph.m_cellX
+pub fn split_prop_name(full_name: &str) -> (String, bool) {
+    // CAK47.m_iClip1 => ["CAK47", "m_iClip1"]
+    let split_at_dot: Vec<&str> = full_name.split(".").collect();
+    let is_weapon_prop = (split_at_dot[0].contains("Weapon") || split_at_dot[0].contains("AK"))
+        && !split_at_dot[0].contains("Player")
+        || split_at_dot[0].contains("Knife")
+        || split_at_dot[0].contains("CDEagle")
+        || split_at_dot[0].contains("C4")
+        || split_at_dot[0].contains("Molo")
+        || split_at_dot[0].contains("Inc")
+        || split_at_dot[0].contains("Infer");
+
+    let is_projectile_prop =
+        (split_at_dot[0].contains("Projectile") || split_at_dot[0].contains("Grenade") || split_at_dot[0].contains("Flash"))
+            && !split_at_dot[0].contains("Player");
+    let is_grenade_or_weapon = is_weapon_prop || is_projectile_prop;
+    match is_grenade_or_weapon {
+        true => (split_at_dot[1..].join("."), true),
+        false => (full_name.to_string(), false),
+    }
+}
+
+// For props that are not in TYPEHM, decided by the class the prop starts with.
+// Props without a class ("m_iClip1") are weapon props, other classes go through DemoQuery::entity_props.
+pub fn raw_prop_type(prop_name: &str) -> Option<PropType> {
+    match prop_name.split(".").next() {
+        Some("CCSPlayerController") => Some(PropType::Controller),
+        Some("CCSPlayerPawn") => Some(PropType::Player),
+        Some("CCSTeam") => Some(PropType::Team),
+        Some("CCSGameRulesProxy") => Some(PropType::Rules),
+        Some(class) if class.starts_with("m_") => Some(PropType::Weapon),
+        _ => None,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::PropController;
+    use super::{raw_prop_type, split_prop_name};
     use crate::collect_data::PropType;
     use crate::prop_controller::{PropInfo, NORMAL_PROP_BASEID, TICK_ID, WEAPON_SKIN_ID, YAW_ID};
     use crate::prop_controller::{BUTTONS_BASEID, PITCH_ID};
     use crate::prop_controller::{STEAMID_ID, WEAPON_NAME_ID};
+    use crate::read_bits::DemoParserError;
     use crate::sendtables::Decoder::BaseDecoder;
     use crate::sendtables::FieldModel::FieldModelNOTSET;
     use crate::sendtables::FieldType;
@@ -1113,6 +1178,69 @@ mod tests {
         assert_eq!(f.should_parse, false);
         assert_eq!(f.prop_id, NORMAL_PROP_BASEID as usize);
     }
+    #[test]
+    pub fn test_entity_prop_id() {
+        let mut f = gen_default_field();
+        let mut pc = PropController::new(vec![], vec![], AHashMap::default())
+            .with_entity_props(vec!["CPlantedC4.m_bBombTicking".to_string()]);
+        pc.handle_prop("CPlantedC4.m_bBombTicking", &mut f);
+        assert!(f.should_parse);
+        assert_eq!(pc.entity_prop_ids["CPlantedC4.m_bBombTicking"], f.prop_id as u32);
+        assert!(pc.prop_infos.is_empty());
+    }
+    #[test]
+    pub fn test_split_prop_name() {
+        assert_eq!(split_prop_name("CAK47.m_iClip1"), ("m_iClip1".to_string(), true));
+        assert_eq!(
+            split_prop_name("CSmokeGrenadeProjectile.CBodyComponentBaseAnimGraph.m_cellX"),
+            ("CBodyComponentBaseAnimGraph.m_cellX".to_string(), true)
+        );
+        assert_eq!(
+            split_prop_name("CCSPlayerPawn.m_flFlashDuration"),
+            ("CCSPlayerPawn.m_flFlashDuration".to_string(), false)
+        );
+    }
+    #[test]
+    pub fn test_raw_prop_propinfo() {
+        let mut f = gen_default_field();
+        let wanted = vec!["CCSPlayerPawn.m_flHealthShotBoostExpirationTime".to_string()];
+        let mut pc = PropController::new(wanted, vec![], AHashMap::default());
+        pc.handle_prop("CCSPlayerPawn.m_flHealthShotBoostExpirationTime", &mut f);
+        assert!(f.should_parse);
+        assert_eq!(pc.prop_infos.len(), 1);
+        assert_eq!(pc.prop_infos[0].prop_type, PropType::Player);
+        assert_eq!(
+            pc.prop_infos[0].prop_friendly_name,
+            "CCSPlayerPawn.m_flHealthShotBoostExpirationTime"
+        );
+        assert_eq!(raw_prop_type("CCSTeam.m_iScore"), Some(PropType::Team));
+        assert_eq!(raw_prop_type("m_iClip1"), Some(PropType::Weapon));
+        assert_eq!(raw_prop_type("CChicken.m_leader"), None);
+    }
+    #[test]
+    pub fn test_check_raw_props() {
+        let wanted = |name: &str| {
+            let mut pc = PropController::new(vec!["health".to_string(), name.to_string()], vec![], AHashMap::default());
+            let mut f = gen_default_field();
+            pc.handle_prop("CCSPlayerPawn.m_flFlashDuration", &mut f);
+            let mut f = gen_default_field();
+            pc.handle_prop("CChicken.m_leader", &mut f);
+            pc.check_raw_props()
+        };
+        assert_eq!(wanted("CCSPlayerPawn.m_flFlashDuration"), Ok(()));
+        // Typo
+        assert_eq!(
+            wanted("CCSPlayerPawn.m_flFlashDurationn"),
+            Err(DemoParserError::UnknownPropName(
+                "CCSPlayerPawn.m_flFlashDurationn".to_string()
+            ))
+        );
+        // Exists but is not a player prop
+        assert_eq!(
+            wanted("CChicken.m_leader"),
+            Err(DemoParserError::UnknownPropName("CChicken.m_leader".to_string()))
+        );
+    }
     /*
     #[test]
     pub fn test_player_x_propinfo() {
@@ -1,11 +1,12 @@
 use super::read_bits::Bitreader;
+use serde::{Deserialize, Serialize};
 
 const QFF_ROUNDDOWN: u32 = 1 << 0;
 const QFF_ROUNDUP: u32 = 1 << 1;
 const QFF_ENCODE_ZERO: u32 = 1 << 2;
 const QFF_ENCODE_INTEGERS: u32 = 1 << 3;
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub struct QuantalizedFloat {
     low: f32,
     high: f32,
@@ -0,0 +1,294 @@
+use crate::parser_settings::create_mmap;
+use crate::parser_settings::rm_user_friendly_names;
+use crate::parser_settings::Parser;
+use crate::parser_settings::ParserInputs;
+use crate::parser_thread_settings::create_huffman_lookup_table;
+use crate::read_bits::DemoParserError;
+use crate::tick_selectors::TickSelector;
+use crate::variants::BytesVariant;
+use ahash::AHashMap;
+use lazy_static::lazy_static;
+use std::sync::Arc;
+
+// Builder for ParserInputs. Props are given with their user friendly names (the keys of
+// FRIENDLY_NAMES_MAPPING) or raw names from Parser::prop_schema and are validated when the query is built.
+//
+// let parser = DemoQuery::new()
+//     .player_props(["X", "Y", "health"])
+//     .events(["player_death"])
+//     .ticks([1000, 2000])
+//     .parser_from_path("match.dem")?;
+
+lazy_static! {
+    // Same for every parser, no need to build it for every demo
+    static ref HUFFMAN_LOOKUP_TABLE: Arc<Vec<(u32, u8)>> = Arc::new(create_huffman_lookup_table());
+}
+
+pub fn huffman_lookup_table() -> Arc<Vec<(u32, u8)>> {
+    HUFFMAN_LOOKUP_TABLE.clone()
+}
+
+#[derive(Debug, Clone)]
+pub struct DemoQuery {
+    pub player_props: Vec<String>,
+    pub other_props: Vec<String>,
+    pub entity_props: Vec<String>,
+    pub events: Vec<String>,
+    pub ticks: Vec<i32>,
+    pub tick_selectors: Vec<TickSelector>,
+    pub tick_stride: i32,
+    pub parse_entities: bool,
+    pub parse_projectiles: bool,
+    pub prop_changes: bool,
+    pub kill_labels: Option<i32>,
+    pub seeded_chunks: bool,
+    pub only_header: bool,
+    pub count_props: bool,
+    pub only_convars: bool,
+    pub lenient: bool,
+}
+
+impl Default for DemoQuery {
+    fn default() -> Self {
+        DemoQuery {
+            player_props: vec![],
+            other_props: vec![],
+            entity_props: vec![],
+            events: vec![],
+            ticks: vec![],
+            tick_selectors: vec![],
+            tick_stride: 0,
+            parse_entities: true,
+            parse_projectiles: false,
+            prop_changes: false,
+            kill_labels: None,
+            seeded_chunks: false,
+            only_header: false,
+            count_props: false,
+            only_convars: false,
+            lenient: false,
+        }
+    }
+}
+
+impl DemoQuery {
+    pub fn new() -> Self {
+        DemoQuery::default()
+    }
+    pub fn player_props<I, S>(mut self, props: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.player_props.extend(props.into_iter().map(|p| p.into()));
+        self
+    }
+    /// Team and rules props, for example "team_rounds_total" or "total_rounds_played".
+    pub fn other_props<I, S>(mut self, props: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.other_props.extend(props.into_iter().map(|p| p.into()));
+        self
+    }
+    /// Raw props on any entity class as "class.prop", for example "CPlantedC4.m_bBombTicking" or "CChicken.m_leader".
+    /// Collected for every entity of the class into DemoOutput.entity_props.
+    pub fn entity_props<I, S>(mut self, props: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.entity_props.extend(props.into_iter().map(|p| p.into()));
+        self
+    }
+    /// "all" parses every event.
+    pub fn events<I, S>(mut self, events: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.events.extend(events.into_iter().map(|e| e.into()));
+        self
+    }
+    /// Only collect props on these ticks. Every tick is collected if none are given.
+    pub fn ticks<I>(mut self, ticks: I) -> Self
+    where
+        I: IntoIterator<Item = i32>,
+    {
+        self.ticks.extend(ticks);
+        self
+    }
+    /// Also collect every tick from start to end (inclusive).
+    pub fn tick_range(mut self, start: i32, end: i32) -> Self {
+        self.tick_selectors.push(TickSelector::Range { start, end });
+        self
+    }
+    /// Also collect the ticks from freeze time end to round end of rounds first..=last, the first round is 1.
+    pub fn rounds(mut self, first: i32, last: i32) -> Self {
+        self.tick_selectors.push(TickSelector::Rounds { first, last });
+        self
+    }
+    /// Also collect the ticks from `before` ticks before to `after` ticks after every event with this name.
+    pub fn around_event(mut self, event_name: &str, before: i32, after: i32) -> Self {
+        self.tick_selectors.push(TickSelector::AroundEvent {
+            event_name: event_name.to_string(),
+            before,
+            after,
+        });
+        self
+    }
+    /// Only keep every nth tick of each selector window, counted from the start of the window.
+    /// Ticks given with ticks() are always kept, without a window or ticks n counts from tick 0.
+    pub fn every_nth_tick(mut self, n: i32) -> Self {
+        self.tick_stride = n;
+        self
+    }
+    pub fn projectiles(mut self) -> Self {
+        self.parse_projectiles = true;
+        self
+    }
+    /// Record a change every time a wanted prop gets a new value (DemoOutput.prop_changes)
+    /// instead of collecting every prop for every player every tick.
+    pub fn prop_changes(mut self) -> Self {
+        self.prop_changes = true;
+        self
+    }
+    /// Label player_death events as opening kills, trades and traded deaths and add clutch events,
+    /// a kill counts as a trade within trade_window ticks (DEFAULT_TRADE_WINDOW is 5 seconds).
+    pub fn kill_labels(mut self, trade_window: i32) -> Self {
+        self.kill_labels = Some(trade_window);
+        self
+    }
+    /// Props like "is_alive" or "weapon_name" are missing from fullpackets, so demos with them are parsed in one
+    /// thread. With this they are parsed in chunks too, seeded with the values recorded in an extra pass over
+    /// every chunk. Same output as the single threaded parse, but every chunk is decoded twice so it only
+    /// pays off with several cores. See chunk_seeds.rs.
+    pub fn seeded_chunks(mut self) -> Self {
+        self.seeded_chunks = true;
+        self
+    }
+    /// Entities are parsed by default. Turning them off is much faster when only messages like
+    /// the header, convars or chat are needed.
+    pub fn entities(mut self, parse_entities: bool) -> Self {
+        self.parse_entities = parse_entities;
+        self
+    }
+    pub fn only_header(mut self) -> Self {
+        self.only_header = true;
+        self
+    }
+    pub fn only_convars(mut self) -> Self {
+        self.only_convars = true;
+        self
+    }
+    pub fn count_props(mut self) -> Self {
+        self.count_props = true;
+        self
+    }
+    /// Return what could be parsed from truncated or corrupted demos instead of failing.
+    /// Skipped frames and where parsing stopped are listed in DemoOutput.warnings.
+    pub fn lenient(mut self) -> Self {
+        self.lenient = true;
+        self
+    }
+
+    pub fn build(&self, bytes: BytesVariant) -> Result<ParserInputs, DemoParserError> {
+        self.build_shared(Arc::new(bytes))
+    }
+    // For another query on a demo that is already loaded
+    pub fn build_shared(&self, bytes: Arc<BytesVariant>) -> Result<ParserInputs, DemoParserError> {
+        let real_player_props = rm_user_friendly_names(&self.player_props)?;
+        let real_other_props = rm_user_friendly_names(&self.other_props)?;
+        // The class is needed to know which entities to collect from
+        if let Some(name) = self.entity_props.iter().find(|p| !p.contains('.')) {
+            return Err(DemoParserError::UnknownPropName(name.clone()));
+        }
+
+        let mut real_name_to_og_name = AHashMap::default();
+        for (real_name, friendly_name) in real_player_props.iter().zip(&self.player_props) {
+            real_name_to_og_name.insert(real_name.clone(), friendly_name.clone());
+        }
+        for (real_name, friendly_name) in real_other_props.iter().zip(&self.other_props) {
+            real_name_to_og_name.insert(real_name.clone(), friendly_name.clone());
+        }
+        Ok(ParserInputs {
+            real_name_to_og_name,
+            bytes,
+            wanted_player_props: real_player_props,
+            wanted_player_props_og_names: self.player_props.clone(),
+            wanted_other_props: real_other_props,
+            wanted_other_props_og_names: self.other_props.clone(),
+            wanted_entity_props: self.entity_props.clone(),
+            wanted_events: self.events.clone(),
+            parse_ents: self.parse_entities,
+            wanted_ticks: self.ticks.clone(),
+            tick_selectors: self.tick_selectors.clone(),
+            tick_stride: self.tick_stride,
+            parse_projectiles: self.parse_projectiles,
+            prop_changes: self.prop_changes,
+            kill_labels: self.kill_labels,
+            seeded_chunks: self.seeded_chunks,
+            only_header: self.only_header,
+            count_props: self.count_props,
+            only_convars: self.only_convars,
+            lenient: self.lenient,
+            huffman_lookup_table: huffman_lookup_table(),
+        })
+    }
+    pub fn parser(&self, bytes: BytesVariant) -> Result<Parser, DemoParserError> {
+        Ok(Parser::new(self.build(bytes)?))
+    }
+    pub fn parser_from_path(&self, path: &str) -> Result<Parser, DemoParserError> {
+        let mmap = create_mmap(path.to_string())?;
+        self.parser(BytesVariant::Mmap(mmap))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_maps_friendly_names() {
+        let inputs = DemoQuery::new()
+            .player_props(["score"])
+            .other_props(["team_name"])
+            .events(["player_death"])
+            .ticks([10, 20])
+            .build(BytesVariant::Vec(vec![]))
+            .unwrap();
+        assert_eq!(inputs.wanted_player_props, vec!["CCSPlayerController.m_iScore".to_string()]);
+        assert_eq!(inputs.wanted_other_props, vec!["CCSTeam.m_szTeamname".to_string()]);
+        assert_eq!(inputs.real_name_to_og_name["CCSPlayerController.m_iScore"], "score");
+        assert_eq!(inputs.wanted_events, vec!["player_death".to_string()]);
+        assert_eq!(inputs.wanted_ticks, vec![10, 20]);
+        assert!(inputs.parse_ents);
+    }
+    #[test]
+    fn test_unknown_prop() {
+        let result = DemoQuery::new().player_props(["not_a_prop"]).build(BytesVariant::Vec(vec![]));
+        assert_eq!(result.err(), Some(DemoParserError::UnknownPropName("not_a_prop".to_string())));
+    }
+    #[test]
+    fn test_entity_props_need_class() {
+        let result = DemoQuery::new()
+            .entity_props(["CPlantedC4.m_bBombTicking", "m_bBombTicking"])
+            .build(BytesVariant::Vec(vec![]));
+        assert_eq!(
+            result.err(),
+            Some(DemoParserError::UnknownPropName("m_bBombTicking".to_string()))
+        );
+    }
+    #[test]
+    fn test_raw_prop_names() {
+        let inputs = DemoQuery::new()
+            .player_props(["CCSPlayerPawn.m_flHealthShotBoostExpirationTime", "m_iClip1"])
+            .build(BytesVariant::Vec(vec![]))
+            .unwrap();
+        assert_eq!(
+            inputs.wanted_player_props,
+            ["CCSPlayerPawn.m_flHealthShotBoostExpirationTime", "m_iClip1"]
+        );
+    }
+}
@@ -2,7 +2,7 @@ use bitter::BitReader;
 use bitter::LittleEndianReader;
 use std::fmt;
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum DemoParserError {
     OutOfBitsError,
     OutOfBytesError,
@@ -30,13 +30,102 @@ pub enum DemoParserError {
     NoSendTableMessage,
     UserIdNotFound,
     EventListFallbackNotFound(String),
+    TickNotFound(i32),
+    SidecarError(String),
+    UnknownDemoCommand(i32),
+    ArrowError(String),
+    ParquetError(String),
+    WriteError(String),
+    SqliteError(String),
+    InvalidFieldPath(String),
+    MalformedSendTables(String),
+    UnsupportedDecoder(String),
+    MalformedVarint,
+    WithContext {
+        error: Box<DemoParserError>,
+        context: ErrorContext,
+    },
+}
+
+// Where in the demo an error happened. Filled in on the way up: the netmessage by the packet loop
+// and the rest by the frame loop.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ErrorContext {
+    pub tick: Option<i32>,
+    pub offset: Option<usize>,
+    pub demo_cmd: Option<String>,
+    pub netmessage: Option<String>,
+}
+
+impl DemoParserError {
+    pub fn with_netmessage(self, netmessage: String) -> DemoParserError {
+        self.with_context(|ctx| {
+            ctx.netmessage.get_or_insert(netmessage);
+        })
+    }
+    pub fn with_frame(self, tick: i32, offset: usize, demo_cmd: Option<String>) -> DemoParserError {
+        self.with_context(|ctx| {
+            ctx.tick.get_or_insert(tick);
+            ctx.offset.get_or_insert(offset);
+            if ctx.demo_cmd.is_none() {
+                ctx.demo_cmd = demo_cmd;
+            }
+        })
+    }
+    fn with_context<F: FnOnce(&mut ErrorContext)>(self, f: F) -> DemoParserError {
+        match self {
+            DemoParserError::WithContext { error, mut context } => {
+                f(&mut context);
+                DemoParserError::WithContext { error, context }
+            }
+            error => {
+                let mut context = ErrorContext::default();
+                f(&mut context);
+                DemoParserError::WithContext {
+                    error: Box::new(error),
+                    context,
+                }
+            }
+        }
+    }
+    // The error without the context around it
+    pub fn root(&self) -> &DemoParserError {
+        match self {
+            DemoParserError::WithContext { error, .. } => error.root(),
+            error => error,
+        }
+    }
+    pub fn context(&self) -> Option<&ErrorContext> {
+        match self {
+            DemoParserError::WithContext { context, .. } => Some(context),
+            _ => None,
+        }
+    }
 }
 
 impl std::error::Error for DemoParserError {}
 
 impl fmt::Display for DemoParserError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{:?}", self)
+        match self {
+            DemoParserError::WithContext { error, context } => {
+                write!(f, "{}", error)?;
+                if let Some(tick) = context.tick {
+                    write!(f, " at tick {}", tick)?;
+                }
+                if let Some(offset) = context.offset {
+                    write!(f, " (byte offset {})", offset)?;
+                }
+                if let Some(demo_cmd) = &context.demo_cmd {
+                    write!(f, " in {}", demo_cmd)?;
+                }
+                if let Some(netmessage) = &context.netmessage {
+                    write!(f, " / {}", netmessage)?;
+                }
+                Ok(())
+            }
+            _ => write!(f, "{:?}", self),
+        }
     }
 }
 
@@ -105,12 +194,15 @@ impl<'a> Bitreader<'a> {
             b = self.read_nbits(8)?;
             if b < 0x80 {
                 if count > 9 || count == 9 && b > 1 {
-                    panic!("overflow!");
+                    return Err(DemoParserError::MalformedVarint);
                 }
                 return Ok(result | (b as u64) << s);
             }
             result |= ((b as u64) & 127) << s;
             count += 1;
+            if count > 9 {
+                return Err(DemoParserError::MalformedVarint);
+            }
             if b & 0x80 == 0 {
                 break;
             }
@@ -132,7 +224,7 @@ impl<'a> Bitreader<'a> {
             false => Err(DemoParserError::FailedByteRead(
                 format!(
                     "Failed to read message/command. bytes left in stream: {}, requested bytes: {}",
-                    self.reader.bits_remaining().unwrap() / 8,
+                    self.reader.bits_remaining().unwrap_or(0) / 8,
                     n,
                 )
                 .to_string(),
@@ -216,3 +308,40 @@ static MASKS: [u32; 32 + 1] = [
     u32::MAX >> 1,
     u32::MAX,
 ];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_context_kept_from_innermost() {
+        let e = DemoParserError::MalformedMessage
+            .with_netmessage("svc_PacketEntities".to_string())
+            .with_frame(100, 5000, Some("DEM_Packet".to_string()))
+            .with_frame(200, 9000, None);
+        assert_eq!(e.root(), &DemoParserError::MalformedMessage);
+        assert_eq!(
+            e.context(),
+            Some(&ErrorContext {
+                tick: Some(100),
+                offset: Some(5000),
+                demo_cmd: Some("DEM_Packet".to_string()),
+                netmessage: Some("svc_PacketEntities".to_string()),
+            })
+        );
+        assert_eq!(
+            format!("{}", e),
+            "MalformedMessage at tick 100 (byte offset 5000) in DEM_Packet / svc_PacketEntities"
+        );
+    }
+
+    #[test]
+    fn test_overlong_varint_is_error() {
+        let bytes = [0xff; 12];
+        let mut bitreader = Bitreader::new(&bytes);
+        assert_eq!(bitreader.read_varint_u_64(), Err(DemoParserError::MalformedVarint));
+        // Runs out of bytes in the middle of the varint
+        let mut bitreader = Bitreader::new(&bytes[..3]);
+        assert!(bitreader.read_varint_u_64().is_err());
+    }
+}
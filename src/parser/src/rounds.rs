@@ -0,0 +1,298 @@
+use crate::game_events::GameEvent;
+use crate::parser_settings::Parser;
+use crate::read_bits::DemoParserError;
+use ahash::AHashMap;
+use serde::Serialize;
+
+// One row per played round, built from the round events and the rules/team props attached to them.
+// examples/rounds.rs shows it in use.
+
+pub const ROUND_EVENTS: [&str; 8] = [
+    "begin_new_match",
+    "round_start",
+    "round_freeze_end",
+    "round_end",
+    "round_officially_ended",
+    "bomb_planted",
+    "bomb_defused",
+    "announce_phase_end",
+];
+pub const ROUND_PROPS: [&str; 4] = [
+    "total_rounds_played",
+    "is_warmup_period",
+    "team_rounds_total",
+    "team_clan_name",
+];
+
+// round_end reason when warmup ends, not a real round
+const REASON_GAME_COMMENCING: i64 = 16;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Round {
+    pub round: i32,
+    pub start_tick: i32,
+    pub freeze_end_tick: Option<i32>,
+    pub end_tick: Option<i32>,
+    // End of the round end delay, right before the next round starts
+    pub officially_ended_tick: Option<i32>,
+    // "T" or "CT"
+    pub winner: Option<String>,
+    pub reason: Option<String>,
+    pub reason_code: Option<i32>,
+    pub bomb_plant_tick: Option<i32>,
+    pub bomb_defuse_tick: Option<i32>,
+    // Score after the round
+    pub t_score: i32,
+    pub ct_score: i32,
+    // Clan names, only set on servers that use them
+    pub t_team: Option<String>,
+    pub ct_team: Option<String>,
+    // Teams swapped sides before this round (halftime and overtime halves)
+    pub sides_switched: bool,
+    // 0 in regulation, 1 for the first overtime...
+    pub overtime: i32,
+}
+
+impl Parser {
+    // Separate parse of the round events, can be called before or after parse_demo
+    pub fn parse_rounds(&mut self) -> Result<Vec<Round>, DemoParserError> {
+        // Props attached to the events are read from the entities
+        let query = self.events_only_query(ROUND_EVENTS).entities(true).other_props(ROUND_PROPS);
+        let output = self.parser_for(&query)?.parse_demo()?;
+        Ok(rounds_from_events(&output.game_events, &output.convars))
+    }
+}
+
+// Events need total_rounds_played, is_warmup_period, team_rounds_total and team_clan_name as other props.
+// mp_maxrounds and mp_overtime_maxrounds are read from the convars, defaults are the competitive ones.
+// Round numbers are m_totalRoundsPlayed + 1 at round_start so they match the scoreboard. Warmup rounds and
+// events before the first round_start (demo recorded mid round) are left out. After a restart
+// (begin_new_match) or a backup the rounds from that number on are replaced by the ones played after it.
+pub fn rounds_from_events(events: &[GameEvent], convars: &AHashMap<String, String>) -> Vec<Round> {
+    let max_rounds = convar(convars, "mp_maxrounds", 24);
+    let overtime_max_rounds = convar(convars, "mp_overtime_maxrounds", 6);
+    let mut events: Vec<&GameEvent> = events.iter().collect();
+    events.sort_by_key(|e| e.tick);
+
+    let mut rounds: Vec<Round> = vec![];
+    let mut phase_ended = false;
+    for event in events {
+        if event.name == "begin_new_match" {
+            rounds.clear();
+            phase_ended = false;
+            continue;
+        }
+        if event.int_field("is_warmup_period") == Some(1) {
+            continue;
+        }
+        match event.name.as_str() {
+            "announce_phase_end" => phase_ended = true,
+            "round_start" => {
+                let round = match event.int_field("total_rounds_played") {
+                    Some(played) => played as i32 + 1,
+                    None => rounds.last().map_or(1, |r| r.round + 1),
+                };
+                // Restart or backup, the round is played again
+                rounds.retain(|r| r.round < round);
+                let t_team = event
+                    .string_field("t_team_clan_name")
+                    .filter(|s| !s.is_empty())
+                    .map(|s| s.to_string());
+                let ct_team = event
+                    .string_field("ct_team_clan_name")
+                    .filter(|s| !s.is_empty())
+                    .map(|s| s.to_string());
+                let names_swapped = match rounds.last() {
+                    Some(prev) => t_team.is_some() && t_team != ct_team && t_team == prev.ct_team && ct_team == prev.t_team,
+                    None => false,
+                };
+                rounds.push(Round {
+                    round,
+                    start_tick: event.tick,
+                    freeze_end_tick: None,
+                    end_tick: None,
+                    officially_ended_tick: None,
+                    winner: None,
+                    reason: None,
+                    reason_code: None,
+                    bomb_plant_tick: None,
+                    bomb_defuse_tick: None,
+                    t_score: event.int_field("t_team_rounds_total").unwrap_or(0) as i32,
+                    ct_score: event.int_field("ct_team_rounds_total").unwrap_or(0) as i32,
+                    t_team,
+                    ct_team,
+                    sides_switched: phase_ended || names_swapped,
+                    overtime: overtime(round, max_rounds, overtime_max_rounds),
+                });
+                phase_ended = false;
+            }
+            _ => {
+                let current = match rounds.last_mut() {
+                    Some(current) => current,
+                    None => continue,
+                };
+                let in_progress = current.end_tick.is_none();
+                match event.name.as_str() {
+                    "round_freeze_end" if in_progress => current.freeze_end_tick = Some(event.tick),
+                    "bomb_planted" if in_progress => current.bomb_plant_tick = Some(event.tick),
+                    "bomb_defused" if in_progress => current.bomb_defuse_tick = Some(event.tick),
+                    "round_end" if in_progress => {
+                        let reason = event.int_field("reason");
+                        if reason == Some(REASON_GAME_COMMENCING) {
+                            continue;
+                        }
+                        current.end_tick = Some(event.tick);
+                        current.reason_code = reason.map(|r| r as i32);
+                        current.reason = reason.and_then(round_end_reason).map(|r| r.to_string());
+                        current.winner = match event.int_field("winner") {
+                            Some(2) => {
+                                current.t_score += 1;
+                                Some("T".to_string())
+                            }
+                            Some(3) => {
+                                current.ct_score += 1;
+                                Some("CT".to_string())
+                            }
+                            _ => None,
+                        };
+                    }
+                    "round_officially_ended" => current.officially_ended_tick = Some(event.tick),
+                    _ => {}
+                }
+            }
+        }
+    }
+    rounds
+}
+
+pub fn round_end_reason(code: i64) -> Option<&'static str> {
+    match code {
+        1 => Some("bomb_exploded"),
+        4 => Some("t_escaped"),
+        5 => Some("ct_prevented_escape"),
+        6 => Some("escaping_t_killed"),
+        7 => Some("bomb_defused"),
+        8 => Some("t_killed"),
+        9 => Some("ct_killed"),
+        10 => Some("draw"),
+        11 => Some("hostages_rescued"),
+        12 => Some("time_ran_out"),
+        13 => Some("hostages_not_rescued"),
+        16 => Some("game_start"),
+        17 => Some("t_surrender"),
+        18 => Some("ct_surrender"),
+        _ => None,
+    }
+}
+
+fn overtime(round: i32, max_rounds: i32, overtime_max_rounds: i32) -> i32 {
+    if round <= max_rounds || overtime_max_rounds <= 0 {
+        return 0;
+    }
+    (round - max_rounds - 1) / overtime_max_rounds + 1
+}
+
+fn convar(convars: &AHashMap<String, String>, name: &str, default: i32) -> i32 {
+    convars.get(name).and_then(|v| v.trim().parse().ok()).unwrap_or(default)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game_events::test_event as event;
+    use crate::variants::Variant;
+
+    fn round_start(tick: i32, played: i32, t: i32, ct: i32, warmup: bool) -> GameEvent {
+        event(
+            "round_start",
+            tick,
+            vec![
+                ("total_rounds_played", Variant::I32(played)),
+                ("is_warmup_period", Variant::Bool(warmup)),
+                ("t_team_rounds_total", Variant::I32(t)),
+                ("ct_team_rounds_total", Variant::I32(ct)),
+            ],
+        )
+    }
+    fn round_end(tick: i32, winner: i32, reason: i32) -> GameEvent {
+        event(
+            "round_end",
+            tick,
+            vec![("winner", Variant::I32(winner)), ("reason", Variant::I32(reason))],
+        )
+    }
+
+    fn no_convars() -> AHashMap<String, String> {
+        AHashMap::default()
+    }
+
+    #[test]
+    fn test_warmup_and_restart_left_out() {
+        let events = vec![
+            round_start(10, 0, 0, 0, true),
+            round_end(50, 3, 8),
+            event("begin_new_match", 60, vec![]),
+            round_start(100, 0, 0, 0, false),
+            round_end(400, 2, 1),
+        ];
+        let rounds = rounds_from_events(&events, &no_convars());
+        assert_eq!(rounds.len(), 1);
+        assert_eq!((rounds[0].round, rounds[0].start_tick), (1, 100));
+        assert_eq!(rounds[0].winner.as_deref(), Some("T"));
+        assert_eq!(rounds[0].reason.as_deref(), Some("bomb_exploded"));
+    }
+    #[test]
+    fn test_round_ticks() {
+        let events = vec![
+            round_start(100, 0, 0, 0, false),
+            event("round_freeze_end", 200, vec![]),
+            event("bomb_planted", 300, vec![]),
+            round_end(400, 2, 1),
+            event("round_officially_ended", 500, vec![]),
+        ];
+        let rounds = rounds_from_events(&events, &no_convars());
+        let r = &rounds[0];
+        assert_eq!(
+            (r.freeze_end_tick, r.bomb_plant_tick, r.end_tick, r.officially_ended_tick),
+            (Some(200), Some(300), Some(400), Some(500))
+        );
+        assert_eq!((r.t_score, r.ct_score), (1, 0));
+    }
+    #[test]
+    fn test_backup_replaces_round() {
+        let events = vec![
+            round_start(100, 0, 0, 0, false),
+            round_end(400, 2, 1),
+            round_start(510, 1, 1, 0, false),
+            round_end(600, 3, 9),
+            // Backup of round 2 loaded
+            round_start(700, 1, 1, 0, false),
+            event("bomb_planted", 750, vec![]),
+            event("bomb_defused", 800, vec![]),
+            round_end(810, 3, 7),
+        ];
+        let rounds = rounds_from_events(&events, &no_convars());
+        assert_eq!(rounds.len(), 2);
+        let replayed = &rounds[1];
+        assert_eq!((replayed.round, replayed.start_tick), (2, 700));
+        assert_eq!(replayed.reason.as_deref(), Some("bomb_defused"));
+        assert_eq!(replayed.bomb_defuse_tick, Some(800));
+        assert_eq!((replayed.t_score, replayed.ct_score), (1, 1));
+    }
+    #[test]
+    fn test_sides_switch_into_overtime() {
+        let events = vec![
+            round_start(100, 0, 0, 0, false),
+            round_end(400, 2, 1),
+            round_start(510, 1, 1, 0, false),
+            round_end(600, 3, 7),
+            event("announce_phase_end", 650, vec![]),
+            round_start(700, 2, 1, 1, false),
+        ];
+        let convars = AHashMap::from_iter([("mp_maxrounds".to_string(), "2".to_string())]);
+        let rounds = rounds_from_events(&events, &convars);
+        let simple: Vec<(i32, bool, i32)> = rounds.iter().map(|r| (r.round, r.sides_switched, r.overtime)).collect();
+        assert_eq!(simple, vec![(1, false, 0), (2, false, 0), (3, true, 1)]);
+        assert_eq!((rounds[2].winner.as_deref(), rounds[2].end_tick), (None, None));
+    }
+}
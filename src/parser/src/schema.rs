@@ -0,0 +1,97 @@
+use crate::maps::FRIENDLY_NAMES_MAPPING;
+use crate::parser_settings::Parser;
+use crate::prop_controller::split_prop_name;
+use crate::read_bits::DemoParserError;
+use crate::sendtables::Decoder;
+use crate::sendtables::Field;
+use crate::sendtables::FieldModel;
+use ahash::AHashMap;
+use serde::Serialize;
+
+// Every prop the demo's sendtables contain, one entry per entity class and field:
+//
+// let mut parser = DemoQuery::new().parser_from_path("match.dem")?;
+// for prop in parser.prop_schema()? {
+//     println!("{} {} {:?}", prop.prop_name, prop.var_type, prop.friendly_names);
+// }
+//
+// prop_name can be passed to DemoQuery::player_props / other_props as is. Weapon and grenade props
+// are shared between all weapon classes so their prop_name has the class dropped (CAK47.m_iClip1 => m_iClip1).
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PropSchema {
+    // Name to ask for in DemoQuery
+    pub prop_name: String,
+    // Entity class + nested serializers + field name, for example CCSPlayerPawn.CCSPlayer_WeaponServices.m_hActiveWeapon
+    pub full_name: String,
+    pub class_name: String,
+    pub var_type: String,
+    pub decoder: Decoder,
+    pub model: FieldModel,
+    // Keys of FRIENDLY_NAMES_MAPPING that map to this prop
+    pub friendly_names: Vec<String>,
+}
+
+impl Parser {
+    // Runs the first pass (header, sendtables and class info) if it has not been done yet.
+    // Sorted by class name and then by full name.
+    pub fn prop_schema(&mut self) -> Result<Vec<PropSchema>, DemoParserError> {
+        self.first_pass()?;
+        let cls_by_id = match &self.cls_by_id {
+            Some(cls_by_id) => cls_by_id.clone(),
+            None => return Err(DemoParserError::NoSendTableMessage),
+        };
+        let mut friendly_names: AHashMap<&str, Vec<String>> = AHashMap::default();
+        for (friendly_name, real_name) in FRIENDLY_NAMES_MAPPING.entries() {
+            friendly_names.entry(real_name).or_default().push(friendly_name.to_string());
+        }
+        for names in friendly_names.values_mut() {
+            names.sort();
+        }
+        let mut schema = vec![];
+        for class in cls_by_id.values() {
+            collect_fields(
+                &class.serializer.fields,
+                &class.serializer.name,
+                &class.name,
+                &friendly_names,
+                &mut schema,
+            );
+        }
+        schema.sort_by(|a, b| (&a.class_name, &a.full_name).cmp(&(&b.class_name, &b.full_name)));
+        Ok(schema)
+    }
+}
+
+// Same walk as PropController::traverse_fields
+fn collect_fields(
+    fields: &[Field],
+    path: &str,
+    class_name: &str,
+    friendly_names: &AHashMap<&str, Vec<String>>,
+    schema: &mut Vec<PropSchema>,
+) {
+    for f in fields {
+        if let Some(ser) = &f.serializer {
+            collect_fields(
+                &ser.fields,
+                &(path.to_string() + "." + &ser.name),
+                class_name,
+                friendly_names,
+                schema,
+            );
+            continue;
+        }
+        let full_name = path.to_string() + "." + &f.var_name;
+        let (prop_name, _) = split_prop_name(&full_name);
+        schema.push(PropSchema {
+            friendly_names: friendly_names.get(prop_name.as_str()).cloned().unwrap_or_default(),
+            prop_name,
+            full_name,
+            class_name: class_name.to_string(),
+            var_type: f.var_type.clone(),
+            decoder: f.decoder,
+            model: f.model.clone(),
+        });
+    }
+}
@@ -0,0 +1,113 @@
+use crate::parser_settings::Parser;
+use crate::parser_thread_settings::ParserThread;
+use crate::parser_threads::FrameResult;
+use crate::read_bits::DemoParserError;
+use crate::tick_iterator::TickIterator;
+use crate::tick_iterator::TickState;
+use serde::{Deserialize, Serialize};
+
+// Random access into a demo. Fullpackets contain the complete entity and stringtable state, so any tick
+// can be reached by starting from the closest fullpacket before it and replaying the delta packets after it.
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct IndexEntry {
+    pub tick: i32,
+    pub offset: usize,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DemoIndex {
+    // Sorted by tick
+    pub entries: Vec<IndexEntry>,
+}
+
+impl DemoIndex {
+    pub fn push(&mut self, tick: i32, offset: usize) {
+        let idx = self.entries.partition_point(|e| e.tick <= tick);
+        self.entries.insert(idx, IndexEntry { tick, offset });
+    }
+    // Start of the demo (after the header). Used for ticks before the first fullpacket.
+    pub fn push_start(&mut self, offset: usize) {
+        if !self.entries.iter().any(|e| e.offset == offset) {
+            self.entries.insert(0, IndexEntry { tick: i32::MIN, offset });
+        }
+    }
+    // Closest fullpacket at or before the tick
+    pub fn nearest_before(&self, tick: i32) -> Option<IndexEntry> {
+        let idx = self.entries.partition_point(|e| e.tick <= tick);
+        match idx {
+            0 => None,
+            _ => Some(self.entries[idx - 1]),
+        }
+    }
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+}
+
+impl Parser {
+    pub fn demo_index(&mut self) -> Result<&DemoIndex, DemoParserError> {
+        self.first_pass()?;
+        Ok(&self.demo_index)
+    }
+    // World state at the tick. If the demo has no frame at that exact tick the next tick after it is returned.
+    pub fn seek(&mut self, tick: i32) -> Result<TickState, DemoParserError> {
+        let mut ticks = self.ticks_from(tick)?;
+        match ticks.next_tick() {
+            Some(state) => Ok(state.to_state()),
+            None => match ticks.error.take() {
+                Some(e) => Err(e),
+                None => Err(DemoParserError::TickNotFound(tick)),
+            },
+        }
+    }
+    // Same as ticks() but the first yielded state is the tick passed in.
+    pub fn ticks_from(&mut self, tick: i32) -> Result<TickIterator, DemoParserError> {
+        self.first_pass()?;
+        self.resolve_tick_selectors()?;
+        let offset = match self.demo_index.nearest_before(tick) {
+            Some(entry) => entry.offset,
+            None => 16,
+        };
+        let input = self.create_parser_thread_input(offset, true);
+        let mut parser = ParserThread::new(input)?;
+        // Replay everything before the wanted tick. Events from these ticks are not wanted.
+        while let Some(next_tick) = parser.peek_next_tick() {
+            if next_tick >= tick {
+                break;
+            }
+            if parser.parse_frame()? == FrameResult::Stop {
+                break;
+            }
+            parser.game_events.clear();
+        }
+        Ok(TickIterator::new(parser))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_nearest_before() {
+        let mut index = DemoIndex::default();
+        index.push(3840, 5000);
+        index.push(0, 1000);
+        index.push(7680, 9000);
+        index.push_start(16);
+        assert_eq!(index.nearest_before(-1).map(|e| e.offset), Some(16));
+        assert_eq!(index.nearest_before(0).map(|e| e.offset), Some(1000));
+        assert_eq!(index.nearest_before(3839).map(|e| e.offset), Some(1000));
+        assert_eq!(index.nearest_before(3840).map(|e| e.offset), Some(5000));
+        assert_eq!(index.nearest_before(100000).map(|e| e.offset), Some(9000));
+    }
+    #[test]
+    fn test_empty_index() {
+        let index = DemoIndex::default();
+        assert_eq!(index.nearest_before(100), None);
+    }
+}
@@ -9,6 +9,7 @@ use crate::q_float::QuantalizedFloat;
 use crate::sendtables::Decoder::*;
 use crate::sendtables::FieldModel::*;
 use ahash::AHashMap;
+use ahash::AHashSet;
 use ahash::HashMap;
 use csgoproto::{
     demo::CDemoSendTables,
@@ -18,8 +19,9 @@ use lazy_static::lazy_static;
 use phf_macros::phf_map;
 use protobuf::Message;
 use regex::Regex;
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Field {
     //pub parent_name: String,
     pub var_name: String,
@@ -52,14 +54,14 @@ pub struct FieldInfo {
     pub prop_id: u32,
     pub controller_prop: Option<ControllerProp>,
 }
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub enum ControllerProp {
     SteamId,
     Name,
     TeamNum,
     PlayerEntityId,
 }
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum FieldModel {
     FieldModelSimple,
     FieldModelFixedArray,
@@ -69,6 +71,7 @@ pub enum FieldModel {
     FieldModelNOTSET,
 }
 use std::fmt;
+use std::sync::Arc;
 
 impl fmt::Display for Decoder {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
@@ -78,7 +81,7 @@ impl fmt::Display for Decoder {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub enum Decoder {
     QuantalizedFloatDecoder(u8),
     VectorNormalDecoder,
@@ -170,7 +173,7 @@ pub static BASETYPE_DECODERS: phf::Map<&'static str, Decoder> = phf_map! {
 const WEAPON_SKIN_PATH: [i32; 7] = [87, 0, 1, 0, 0, 0, 0];
 
 impl Field {
-    pub fn decoder_from_path(&self, path: &FieldPath, pos: usize, parse_inventory: bool) -> FieldInfo {
+    pub fn decoder_from_path(&self, path: &FieldPath, pos: usize, parse_inventory: bool) -> Result<FieldInfo, DemoParserError> {
         match self.model {
             FieldModelSimple => {
                 // EHHH IDK WILL HAVE TO DO FOR NOW
@@ -178,168 +181,106 @@ impl Field {
                 // and proper support for these would be much work so lets see if more
                 // arr props are needed, else probably leave it like this.
                 if path.path == WEAPON_SKIN_PATH {
-                    return FieldInfo {
+                    return Ok(FieldInfo {
                         decoder: self.decoder,
                         should_parse: self.should_parse,
                         prop_id: WEAPON_SKIN_ID,
                         controller_prop: self.controller_prop,
-                    };
+                    });
                 }
-                return FieldInfo {
-                    decoder: self.decoder,
-                    should_parse: self.should_parse,
-                    prop_id: self.prop_id as u32,
-                    controller_prop: self.controller_prop,
-                };
-            }
-            FieldModelFixedArray => {
-                return FieldInfo {
-                    decoder: self.decoder,
-                    should_parse: self.should_parse,
-                    prop_id: self.prop_id as u32,
-                    controller_prop: self.controller_prop,
-                };
+                Ok(self.field_info(self.decoder))
             }
+            FieldModelFixedArray => Ok(self.field_info(self.decoder)),
             FieldModelFixedTable => {
-                if path.last == pos - 1 {
-                    if self.base_decoder.is_some() {
-                        return FieldInfo {
-                            decoder: self.base_decoder.unwrap(),
-                            should_parse: self.should_parse,
-                            prop_id: self.prop_id as u32,
-                            controller_prop: self.controller_prop,
-                        };
-                    }
-                    return FieldInfo {
-                        decoder: self.decoder,
-                        should_parse: self.should_parse,
-                        prop_id: self.prop_id as u32,
-                        controller_prop: self.controller_prop,
-                    };
+                if path.last + 1 == pos {
+                    Ok(self.field_info(self.base_decoder.unwrap_or(self.decoder)))
                 } else {
-                    match &self.serializer {
-                        Some(ser) => {
-                            return ser.find_decoder(path, pos, parse_inventory);
-                        }
-                        None => panic!("no serializer for path"),
-                    }
+                    self.child_serializer(path)?.find_decoder(path, pos, parse_inventory)
                 }
             }
             FieldModelVariableArray => {
                 if path.last == pos {
-                    return FieldInfo {
-                        decoder: self.child_decoder.unwrap(),
-                        should_parse: self.should_parse,
-                        prop_id: self.prop_id as u32,
-                        controller_prop: self.controller_prop,
-                    };
+                    Ok(self.field_info(self.decoder_or_err(self.child_decoder, path)?))
                 } else {
-                    return FieldInfo {
-                        decoder: self.base_decoder.unwrap(),
-                        should_parse: self.should_parse,
-                        prop_id: self.prop_id as u32,
-                        controller_prop: self.controller_prop,
-                    };
+                    Ok(self.field_info(self.decoder_or_err(self.base_decoder, path)?))
                 }
             }
             FieldModelVariableTable => {
                 if path.last >= pos + 1 {
-                    match &self.serializer {
-                        Some(ser) => {
-                            return ser.find_decoder(path, pos + 1, parse_inventory);
-                        }
-                        None => panic!("no serializer for path"),
-                    }
+                    self.child_serializer(path)?.find_decoder(path, pos + 1, parse_inventory)
                 } else {
-                    return FieldInfo {
-                        decoder: self.base_decoder.unwrap(),
-                        should_parse: self.should_parse,
-                        prop_id: self.prop_id as u32,
-                        controller_prop: self.controller_prop,
-                    };
+                    Ok(self.field_info(self.decoder_or_err(self.base_decoder, path)?))
                 }
             }
-            _ => panic!("HUH"),
+            FieldModelNOTSET => Err(path_error("field model not set", &self.var_name, path)),
         }
     }
-    pub fn debug_decoder_from_path(&self, path: &FieldPath, pos: usize, prop_name: String) -> DebugField {
-        match self.model {
-            FieldModelSimple => {
-                return DebugField {
-                    full_name: prop_name + "." + &self.var_name.clone(),
-                    field: Some(self.clone()),
-                    decoder: self.decoder,
-                };
-            }
-            FieldModelFixedArray => DebugField {
-                full_name: prop_name + "." + &self.var_name.clone(),
-                field: Some(self.clone()),
-                decoder: self.decoder,
-            },
+    pub fn debug_decoder_from_path(
+        &self,
+        path: &FieldPath,
+        pos: usize,
+        prop_name: String,
+    ) -> Result<DebugField, DemoParserError> {
+        let full_name = prop_name.clone() + "." + &self.var_name;
+        let decoder = match self.model {
+            FieldModelSimple | FieldModelFixedArray => self.decoder,
             FieldModelFixedTable => {
-                if path.last == pos - 1 {
-                    if self.base_decoder.is_some() {
-                        return DebugField {
-                            full_name: prop_name + "." + &self.var_name.clone(),
-                            field: Some(self.clone()),
-                            decoder: self.base_decoder.unwrap(),
-                        };
-                    } else {
-                        return DebugField {
-                            full_name: prop_name + "." + &self.var_name.clone(),
-                            field: Some(self.clone()),
-                            decoder: self.decoder,
-                        };
-                    }
+                if path.last + 1 == pos {
+                    self.base_decoder.unwrap_or(self.decoder)
                 } else {
-                    match &self.serializer {
-                        Some(ser) => {
-                            return ser.debug_find_decoder(path, pos, prop_name + "." + &ser.name);
-                        }
-                        None => panic!("no serializer for path"),
-                    }
+                    let ser = self.child_serializer(path)?;
+                    return ser.debug_find_decoder(path, pos, prop_name + "." + &ser.name);
                 }
             }
             FieldModelVariableArray => {
                 if path.last == pos {
-                    return DebugField {
-                        full_name: prop_name + "." + &self.var_name.clone(),
-                        field: Some(self.clone()),
-                        decoder: self.child_decoder.unwrap(),
-                    };
+                    self.decoder_or_err(self.child_decoder, path)?
                 } else {
-                    return DebugField {
-                        full_name: prop_name + "." + &self.var_name.clone(),
-                        field: Some(self.clone()),
-                        decoder: self.base_decoder.unwrap(),
-                    };
+                    self.decoder_or_err(self.base_decoder, path)?
                 }
             }
             FieldModelVariableTable => {
                 if path.last >= pos + 1 {
-                    match &self.serializer {
-                        Some(ser) => {
-                            return ser.debug_find_decoder(path, pos + 1, prop_name + "." + &ser.name);
-                        }
-                        None => panic!("no serializer for path"),
-                    }
+                    let ser = self.child_serializer(path)?;
+                    return ser.debug_find_decoder(path, pos + 1, prop_name + "." + &ser.name);
                 } else {
-                    return DebugField {
-                        full_name: prop_name + "." + &self.var_name.clone(),
-                        field: Some(self.clone()),
-                        decoder: self.base_decoder.unwrap(),
-                    };
+                    self.decoder_or_err(self.base_decoder, path)?
                 }
             }
-            _ => panic!("HUH"),
+            FieldModelNOTSET => return Err(path_error("field model not set", &self.var_name, path)),
+        };
+        Ok(DebugField {
+            full_name,
+            field: Some(self.clone()),
+            decoder,
+        })
+    }
+    fn field_info(&self, decoder: Decoder) -> FieldInfo {
+        FieldInfo {
+            decoder,
+            should_parse: self.should_parse,
+            prop_id: self.prop_id as u32,
+            controller_prop: self.controller_prop,
+        }
+    }
+    fn child_serializer(&self, path: &FieldPath) -> Result<&Serializer, DemoParserError> {
+        match &self.serializer {
+            Some(ser) => Ok(ser),
+            None => Err(path_error("no serializer", &self.var_name, path)),
+        }
+    }
+    fn decoder_or_err(&self, decoder: Option<Decoder>, path: &FieldPath) -> Result<Decoder, DemoParserError> {
+        match decoder {
+            Some(decoder) => Ok(decoder),
+            None => Err(path_error("no decoder", &self.var_name, path)),
         }
     }
 
-    pub fn find_decoder(&mut self, model: FieldModel, qf_map: &mut QfMapper) {
+    pub fn find_decoder(&mut self, model: FieldModel, qf_map: &mut QfMapper) -> Result<(), DemoParserError> {
         self.model = model.clone();
         match model {
-            FieldModelFixedArray => self.decoder = self.match_decoder(qf_map),
-            FieldModelSimple => self.decoder = self.match_decoder(qf_map),
+            FieldModelFixedArray => self.decoder = self.match_decoder(qf_map)?,
+            FieldModelSimple => self.decoder = self.match_decoder(qf_map)?,
             FieldModelFixedTable => self.decoder = Decoder::BooleanDecoder,
             FieldModelVariableTable => self.base_decoder = Some(Decoder::UnsignedDecoder),
             FieldModelVariableArray => {
@@ -351,28 +292,42 @@ impl Field {
                     "m_OwnerOnlyPredNetVectorVariables" => self.child_decoder = Some(VectorNoscaleDecoder),
                     "m_PredVectorVariables" => self.child_decoder = Some(VectorNoscaleDecoder),
                     _ => {
-                        self.child_decoder = match BASETYPE_DECODERS.get(&self.field_type.generic_type.clone().unwrap().base_type)
-                        {
+                        let generic_type = match &self.field_type.generic_type {
+                            Some(generic_type) => generic_type,
+                            None => {
+                                return Err(DemoParserError::MalformedSendTables(format!(
+                                    "{} ({}) has no element type",
+                                    self.var_name, self.var_type
+                                )))
+                            }
+                        };
+                        self.child_decoder = match BASETYPE_DECODERS.get(&generic_type.base_type) {
                             Some(decoder) => Some(decoder.clone()),
                             None => Some(Decoder::BaseDecoder),
                         };
                     }
                 }
             }
-            FieldModelNOTSET => panic!("Field model not set??"),
+            FieldModelNOTSET => {
+                return Err(DemoParserError::MalformedSendTables(format!(
+                    "{} has no field model",
+                    self.var_name
+                )))
+            }
         }
+        Ok(())
     }
-    pub fn match_decoder(&self, qf_map: &mut QfMapper) -> Decoder {
+    pub fn match_decoder(&self, qf_map: &mut QfMapper) -> Result<Decoder, DemoParserError> {
         if self.var_name == "m_iClip1" {
-            return Decoder::AmmoDecoder;
+            return Ok(Decoder::AmmoDecoder);
         }
         let dec = match BASETYPE_DECODERS.get(&self.field_type.base_type) {
             Some(decoder) => decoder.clone(),
             None => match self.field_type.base_type.as_str() {
                 "float32" => self.find_float_type(qf_map),
-                "Vector" => self.find_vector_type(3, qf_map),
-                "Vector2D" => self.find_vector_type(2, qf_map),
-                "Vector4D" => self.find_vector_type(4, qf_map),
+                "Vector" => self.find_vector_type(3, qf_map)?,
+                "Vector2D" => self.find_vector_type(2, qf_map)?,
+                "Vector4D" => self.find_vector_type(4, qf_map)?,
                 "uint64" => self.find_uint_type(),
                 "QAngle" => self.find_qangle_type(),
                 "CHandle" => UnsignedDecoder,
@@ -382,7 +337,7 @@ impl Field {
                 _ => Decoder::UnsignedDecoder,
             },
         };
-        dec
+        Ok(dec)
     }
     pub fn find_qangle_type(&self) -> Decoder {
         match self.var_name.as_str() {
@@ -430,20 +385,23 @@ impl Field {
             _ => Decoder::Unsigned64Decoder,
         }
     }
-    pub fn find_vector_type(&self, n: u32, qf_map: &mut QfMapper) -> Decoder {
+    pub fn find_vector_type(&self, n: u32, qf_map: &mut QfMapper) -> Result<Decoder, DemoParserError> {
         if n == 3 && self.encoder == "normal" {
-            return Decoder::VectorNormalDecoder;
+            return Ok(Decoder::VectorNormalDecoder);
         }
         let float_type = self.find_float_type(qf_map);
         match float_type {
-            NoscaleDecoder => return VectorNoscaleDecoder,
-            FloatCoordDecoder => return VectorFloatCoordDecoder,
-            _ => panic!("e"),
+            NoscaleDecoder => Ok(VectorNoscaleDecoder),
+            FloatCoordDecoder => Ok(VectorFloatCoordDecoder),
+            _ => Err(DemoParserError::UnsupportedDecoder(format!(
+                "{} vector {} with {:?} components",
+                self.var_name, n, float_type
+            ))),
         }
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FieldType {
     pub base_type: String,
     pub generic_type: Option<Box<FieldType>>,
@@ -451,12 +409,18 @@ pub struct FieldType {
     pub count: i32,
 }
 
-fn find_field_type(name: &str) -> FieldType {
+fn find_field_type(name: &str) -> Result<FieldType, DemoParserError> {
     lazy_static! {
         static ref RE: Regex = Regex::new(r"([^<\[\*]+)(<\s(.*)\s>)?(\*)?(\[(.*)\])?").unwrap();
     }
-    let captures = RE.captures(name).unwrap();
-    let base_type = captures.get(1).unwrap().as_str().to_owned();
+    let captures = match RE.captures(name) {
+        Some(captures) => captures,
+        None => return Err(DemoParserError::MalformedSendTables(format!("bad field type {:?}", name))),
+    };
+    let base_type = match captures.get(1) {
+        Some(base_type) => base_type.as_str().to_owned(),
+        None => return Err(DemoParserError::MalformedSendTables(format!("bad field type {:?}", name))),
+    };
     let pointer = match captures.get(4) {
         Some(s) => {
             if s.as_str() == "*" {
@@ -485,16 +449,19 @@ fn find_field_type(name: &str) -> FieldType {
         count: 0,
     };
     ft.generic_type = match captures.get(3) {
-        Some(generic) => Some(Box::new(find_field_type(generic.as_str()))),
+        Some(generic) => Some(Box::new(find_field_type(generic.as_str())?)),
         None => None,
     };
     ft.count = match captures.get(6) {
-        Some(n) => n.as_str().parse::<i32>().unwrap(),
+        Some(n) => match n.as_str().parse::<i32>() {
+            Ok(count) => count,
+            Err(_) => return Err(DemoParserError::MalformedSendTables(format!("bad array size in {:?}", name))),
+        },
         None => 0,
     };
-    return ft;
+    Ok(ft)
 }
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Serializer {
     pub name: String,
     pub fields: Vec<Field>,
@@ -504,19 +471,23 @@ use crate::prop_controller::GRENADE_AMMO_ID;
 use crate::prop_controller::MY_WEAPONS_OFFSET;
 
 impl Serializer {
-    pub fn find_decoder(&self, path: &FieldPath, pos: usize, parse_inventory: bool) -> FieldInfo {
+    pub fn find_decoder(&self, path: &FieldPath, pos: usize, parse_inventory: bool) -> Result<FieldInfo, DemoParserError> {
         // Edge case for now...
         if parse_inventory {
             if let Some(info) = self.find_inventory_info(path) {
-                return info;
+                return Ok(info);
             }
         }
-        self.fields[path.path[pos] as usize].decoder_from_path(path, pos + 1, parse_inventory)
+        self.field_at(path, pos)?.decoder_from_path(path, pos + 1, parse_inventory)
     }
-    pub fn debug_find_decoder(&self, path: &FieldPath, pos: usize, prop_name: String) -> DebugField {
-        let idx = path.path[pos];
-        let f = &self.fields[idx as usize];
-        f.debug_decoder_from_path(path, pos + 1, prop_name)
+    pub fn debug_find_decoder(&self, path: &FieldPath, pos: usize, prop_name: String) -> Result<DebugField, DemoParserError> {
+        self.field_at(path, pos)?.debug_decoder_from_path(path, pos + 1, prop_name)
+    }
+    fn field_at(&self, path: &FieldPath, pos: usize) -> Result<&Field, DemoParserError> {
+        match path.path.get(pos).and_then(|idx| self.fields.get(*idx as usize)) {
+            Some(field) => Ok(field),
+            None => Err(path_error("no field", &self.name, path)),
+        }
     }
     fn find_inventory_info(&self, path: &FieldPath) -> Option<FieldInfo> {
         if path.path == FLASH_AMMO_PATH && self.name == "CCSPlayerPawn" {
@@ -563,6 +534,94 @@ const POINTER_TYPES: &'static [&'static str] = &[
     "CPhysicsComponent",
 ];
 
+// Everything from the sendtables that does not depend on the wanted props. Fields have their decoders
+// resolved but nested serializers are only attached in build_serializers, because those
+// get mutated by the PropController. Can be cached (see sidecar.rs).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SendTableLayout {
+    // Field idx -> field, in the order they were first seen (qf_mapper idx depends on it)
+    pub fields: Vec<(i32, Field)>,
+    // Serializer name and its field indicies, in message order
+    pub serializers: Vec<(String, Vec<i32>)>,
+    pub qf_mapper: QfMapper,
+}
+
+impl SendTableLayout {
+    pub fn from_msg(tables: &CDemoSendTables) -> Result<Self, DemoParserError> {
+        let mut bitreader = Bitreader::new(tables.data());
+        let n_bytes = bitreader.read_varint()?;
+        let bytes = bitreader.read_n_bytes(n_bytes as usize)?;
+        let serializer_msg: CSVCMsg_FlattenedSerializer = match Message::parse_from_bytes(&bytes) {
+            Ok(msg) => msg,
+            Err(_) => return Err(DemoParserError::MalformedMessage),
+        };
+        let mut qf_mapper = QfMapper {
+            idx: 0,
+            map: AHashMap::default(),
+        };
+        let mut fields = vec![];
+        let mut seen_fields: AHashSet<i32> = AHashSet::default();
+        let mut seen_serializers: AHashSet<String> = AHashSet::default();
+        let mut serializers = vec![];
+
+        for serializer in serializer_msg.serializers.iter() {
+            let name = symbol(&serializer_msg, serializer.serializer_name_sym())?;
+            for idx in &serializer.fields_index {
+                if seen_fields.contains(idx) {
+                    continue;
+                }
+                let field_msg = match serializer_msg.fields.get(*idx as usize) {
+                    Some(field_msg) => field_msg,
+                    None => {
+                        return Err(DemoParserError::MalformedSendTables(format!(
+                            "{} refers to missing field {}",
+                            name, idx
+                        )))
+                    }
+                };
+                let mut field = field_from_msg(field_msg, &serializer_msg)?;
+                let has_serializer = match &field.serializer_name {
+                    Some(name) => seen_serializers.contains(name),
+                    None => false,
+                };
+                if has_serializer {
+                    if field.field_type.pointer || POINTER_TYPES.contains(&field.field_type.base_type.as_str()) {
+                        field.find_decoder(FieldModelFixedTable, &mut qf_mapper)?
+                    } else {
+                        field.find_decoder(FieldModelVariableTable, &mut qf_mapper)?
+                    }
+                } else {
+                    // Serializer name is only kept if it resolves, build_serializers relies on this
+                    field.serializer_name = None;
+                    if field.field_type.count > 0 && field.field_type.base_type != "char" {
+                        field.find_decoder(FieldModelFixedArray, &mut qf_mapper)?
+                    } else if field.field_type.base_type == "CUtlVector" || field.field_type.base_type == "CNetworkUtlVectorBase"
+                    {
+                        field.find_decoder(FieldModelVariableArray, &mut qf_mapper)?
+                    } else {
+                        field.find_decoder(FieldModelSimple, &mut qf_mapper)?
+                    }
+                }
+                if field.var_name == "m_pGameModeRules" {
+                    field.decoder = GameModeRulesDecoder
+                }
+                if field.encoder == "qangle_precise" {
+                    field.decoder = QanglePresDecoder;
+                }
+                seen_fields.insert(*idx);
+                fields.push((*idx, field));
+            }
+            seen_serializers.insert(name.clone());
+            serializers.push((name, serializer.fields_index.clone()));
+        }
+        Ok(SendTableLayout {
+            fields,
+            serializers,
+            qf_mapper,
+        })
+    }
+}
+
 impl Parser {
     // This part is so insanely complicated. There are multiple versions of each serializer and
     // each serializer is this huge nested struct.
@@ -570,69 +629,44 @@ impl Parser {
         &mut self,
         tables: CDemoSendTables,
     ) -> Result<(AHashMap<String, Serializer>, QfMapper, PropController), DemoParserError> {
-        let mut bitreader = Bitreader::new(tables.data());
-        let n_bytes = bitreader.read_varint()?;
-        let bytes = bitreader.read_n_bytes(n_bytes as usize)?;
-        let serializer_msg: CSVCMsg_FlattenedSerializer = Message::parse_from_bytes(&bytes).unwrap();
+        let layout = SendTableLayout::from_msg(&tables)?;
+        let (serializers, prop_controller) = self.build_serializers(&layout)?;
+        let qf_mapper = layout.qf_mapper.clone();
+        self.sendtable_layout = Some(Arc::new(layout));
+        Ok((serializers, qf_mapper, prop_controller))
+    }
+    // The part of the sendtables that depends on the wanted props.
+    pub fn build_serializers(
+        &self,
+        layout: &SendTableLayout,
+    ) -> Result<(AHashMap<String, Serializer>, PropController), DemoParserError> {
         let mut serializers: AHashMap<String, Serializer> = AHashMap::default();
-        let mut qf_mapper = QfMapper {
-            idx: 0,
-            map: AHashMap::default(),
-        };
         let mut fields: HashMap<i32, Field> = HashMap::default();
+        let base_fields: AHashMap<i32, &Field> = layout.fields.iter().map(|(idx, f)| (*idx, f)).collect();
         let mut prop_controller = PropController::new(
             self.wanted_player_props.clone(),
             self.wanted_other_props.clone(),
             self.real_name_to_og_name.clone(),
-        );
-        for serializer in serializer_msg.serializers.iter() {
+        )
+        .with_entity_props(self.wanted_entity_props.clone());
+        for (name, fields_index) in &layout.serializers {
             let mut my_serializer = Serializer {
-                name: serializer_msg.symbols[serializer.serializer_name_sym() as usize].clone(),
+                name: name.clone(),
                 fields: vec![],
             };
-
-            for idx in &serializer.fields_index {
+            for idx in fields_index {
                 match fields.get(idx) {
                     Some(field) => my_serializer.fields.push(field.clone()),
                     None => {
-                        let field_msg = &serializer_msg.fields[*idx as usize];
-                        let mut field = field_from_msg(field_msg, &serializer_msg);
-                        match &field.serializer_name {
-                            Some(name) => match serializers.get(name) {
-                                Some(ser) => {
-                                    field.serializer = Some(ser.clone());
-                                }
-                                None => {}
-                            },
-                            None => {}
-                        }
-
-                        match &field.serializer {
-                            Some(_) => {
-                                if field.field_type.pointer || POINTER_TYPES.contains(&field.field_type.base_type.as_str()) {
-                                    field.find_decoder(FieldModelFixedTable, &mut qf_mapper)
-                                } else {
-                                    field.find_decoder(FieldModelVariableTable, &mut qf_mapper)
-                                }
-                            }
-                            None => {
-                                if field.field_type.count > 0 && field.field_type.base_type != "char" {
-                                    field.find_decoder(FieldModelFixedArray, &mut qf_mapper)
-                                } else if field.field_type.base_type == "CUtlVector"
-                                    || field.field_type.base_type == "CNetworkUtlVectorBase"
-                                {
-                                    field.find_decoder(FieldModelVariableArray, &mut qf_mapper)
-                                } else {
-                                    field.find_decoder(FieldModelSimple, &mut qf_mapper)
-                                }
+                        let mut field = match base_fields.get(idx) {
+                            Some(f) => (*f).clone(),
+                            None => continue,
+                        };
+                        if let Some(name) = &field.serializer_name {
+                            if let Some(ser) = serializers.get(name) {
+                                field.serializer = Some(ser.clone());
                             }
                         }
-                        if field.var_name == "m_pGameModeRules" {
-                            field.decoder = GameModeRulesDecoder
-                        }
-                        if field.encoder == "qangle_precise" {
-                            field.decoder = QanglePresDecoder;
-                        }
                         fields.insert(*idx, field.clone());
                         my_serializer.fields.push(field);
                     }
@@ -656,16 +690,21 @@ impl Parser {
                 || my_serializer.name.contains("Molo")
                 || my_serializer.name.contains("Inc")
                 || my_serializer.name.contains("Infer")
+                || prop_controller
+                    .wanted_entity_props
+                    .iter()
+                    .any(|p| p.split(".").next() == Some(my_serializer.name.as_str()))
             {
                 prop_controller.find_prop_name_paths(&mut my_serializer);
             }
             serializers.insert(my_serializer.name.clone(), my_serializer);
         }
         prop_controller.set_custom_propinfos();
+        prop_controller.check_raw_props()?;
         if !self.wanted_events.is_empty() && needs_velocity(&self.wanted_player_props) {
             prop_controller.event_with_velocity = true;
         }
-        Ok((serializers, qf_mapper, prop_controller))
+        Ok((serializers, prop_controller))
     }
 }
 
@@ -681,22 +720,41 @@ pub struct DebugFieldAndPath {
     pub path: [i32; 7],
 }
 
-fn field_from_msg(field: &ProtoFlattenedSerializerField_t, serializer_msg: &CSVCMsg_FlattenedSerializer) -> Field {
-    let field_type = find_field_type(&serializer_msg.symbols[field.var_type_sym() as usize]);
+fn symbol(serializer_msg: &CSVCMsg_FlattenedSerializer, sym: i32) -> Result<String, DemoParserError> {
+    match serializer_msg.symbols.get(sym as usize) {
+        Some(s) => Ok(s.clone()),
+        _ => Err(DemoParserError::MalformedSendTables(format!(
+            "symbol {} out of {}",
+            sym,
+            serializer_msg.symbols.len()
+        ))),
+    }
+}
+
+fn path_error(what: &str, name: &str, path: &FieldPath) -> DemoParserError {
+    DemoParserError::InvalidFieldPath(format!("{} in {} for {:?}", what, name, &path.path[..=path.last]))
+}
+
+fn field_from_msg(
+    field: &ProtoFlattenedSerializerField_t,
+    serializer_msg: &CSVCMsg_FlattenedSerializer,
+) -> Result<Field, DemoParserError> {
+    let var_type = symbol(serializer_msg, field.var_type_sym())?;
+    let field_type = find_field_type(&var_type)?;
 
     let ser_name = match field.has_field_serializer_name_sym() {
-        true => Some(serializer_msg.symbols[field.field_serializer_name_sym() as usize].clone()),
+        true => Some(symbol(serializer_msg, field.field_serializer_name_sym())?),
         false => None,
     };
     let enc_name = match field.has_var_encoder_sym() {
-        true => serializer_msg.symbols[field.var_encoder_sym() as usize].clone(),
+        true => symbol(serializer_msg, field.var_encoder_sym())?,
         false => "".to_string(),
     };
     let f = Field {
         bitcount: field.bit_count(),
-        var_name: serializer_msg.symbols[field.var_name_sym() as usize].clone(),
-        var_type: serializer_msg.symbols[field.var_type_sym() as usize].clone(),
-        send_node: serializer_msg.symbols[field.send_node_sym() as usize].clone(),
+        var_name: symbol(serializer_msg, field.var_name_sym())?,
+        var_type,
+        send_node: symbol(serializer_msg, field.send_node_sym())?,
         serializer_name: ser_name,
         encoder: enc_name,
         encode_flags: field.encode_flags(),
@@ -714,5 +772,73 @@ fn field_from_msg(field: &ProtoFlattenedSerializerField_t, serializer_msg: &CSVC
         controller_prop: None,
         idx: 0,
     };
-    f
+    Ok(f)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use csgoproto::netmessages::ProtoFlattenedSerializer_t;
+
+    fn sendtables(msg: &CSVCMsg_FlattenedSerializer) -> CDemoSendTables {
+        let bytes = msg.write_to_bytes().unwrap();
+        assert!(bytes.len() < 128);
+        let mut data = vec![bytes.len() as u8];
+        data.extend(bytes);
+        let mut tables = CDemoSendTables::new();
+        tables.set_data(data);
+        tables
+    }
+
+    #[test]
+    fn test_corrupt_sendtables_are_errors() {
+        let mut msg = CSVCMsg_FlattenedSerializer::new();
+        msg.symbols = vec!["CCSPlayerPawn".to_string(), "m_iHealth".to_string(), "int32".to_string()];
+        let mut serializer = ProtoFlattenedSerializer_t::new();
+        serializer.set_serializer_name_sym(0);
+        serializer.fields_index = vec![0];
+        msg.serializers.push(serializer);
+        let mut field = ProtoFlattenedSerializerField_t::new();
+        field.set_var_name_sym(1);
+        field.set_var_type_sym(2);
+        field.set_send_node_sym(0);
+        msg.fields.push(field);
+        assert!(SendTableLayout::from_msg(&sendtables(&msg)).is_ok());
+
+        // Symbol index past the symbol table
+        let mut bad_symbol = msg.clone();
+        bad_symbol.fields[0].set_var_type_sym(40);
+        assert!(matches!(
+            SendTableLayout::from_msg(&sendtables(&bad_symbol)),
+            Err(DemoParserError::MalformedSendTables(_))
+        ));
+        // Serializer pointing at a field that does not exist
+        let mut bad_field = msg.clone();
+        bad_field.serializers[0].fields_index = vec![3];
+        assert!(matches!(
+            SendTableLayout::from_msg(&sendtables(&bad_field)),
+            Err(DemoParserError::MalformedSendTables(_))
+        ));
+        // Cut off halfway through the message
+        let mut truncated = sendtables(&msg);
+        let half = truncated.data().len() / 2;
+        truncated.mut_data().truncate(half);
+        assert!(SendTableLayout::from_msg(&truncated).is_err());
+    }
+
+    #[test]
+    fn test_field_path_outside_serializer_is_error() {
+        let serializer = Serializer {
+            name: "CCSPlayerPawn".to_string(),
+            fields: vec![],
+        };
+        let path = FieldPath {
+            path: [3, 0, 0, 0, 0, 0, 0],
+            last: 0,
+        };
+        assert!(matches!(
+            serializer.find_decoder(&path, 0, false),
+            Err(DemoParserError::InvalidFieldPath(_))
+        ));
+    }
 }
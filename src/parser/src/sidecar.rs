@@ -0,0 +1,331 @@
+use crate::parser_settings::Parser;
+use crate::parser_threads::demo_cmd_type_from_int;
+use crate::read_bits::DemoParserError;
+use crate::seek::DemoIndex;
+use crate::sendtables::SendTableLayout;
+use crate::stringtables::StringTable;
+use crate::stringtables::UserInfo;
+use ahash::AHashMap;
+use csgoproto::demo::CDemoFileHeader;
+use csgoproto::demo::EDemoCommands::DEM_FileHeader;
+use csgoproto::netmessages::csvcmsg_game_event_list::Descriptor_t;
+use protobuf::Message;
+use serde::{Deserialize, Serialize};
+use snap::raw::Decoder as SnapDecoder;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::BufReader;
+use std::io::BufWriter;
+use std::sync::Arc;
+
+// Result of the first pass (header, sendtables, class info, game event list, fullpacket offsets) stored
+// next to the demo. Parsing the sendtables and building the serializers is a big part of the fixed cost
+// of every query, so demos that get queried many times can skip it by loading this instead.
+// Only the query independent part of the sendtables is stored, serializers are rebuilt for the wanted props.
+
+const SIDECAR_VERSION: u32 = 1;
+const HASHED_BYTES: usize = 1024 * 1024;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SidecarKey {
+    pub demo_version_guid: String,
+    pub file_size: usize,
+    // FNV-1a of the first 1MiB of the demo
+    pub hash: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Sidecar {
+    pub version: u32,
+    pub key: SidecarKey,
+    pub header: AHashMap<String, String>,
+    pub sendtables: SendTableLayout,
+    pub classes: Vec<(i32, String)>,
+    // Protobuf encoded Descriptor_t
+    pub ge_list: Vec<(i32, Vec<u8>)>,
+    pub baselines: AHashMap<u32, Vec<u8>>,
+    pub string_tables: Vec<StringTable>,
+    pub stringtable_players: BTreeMap<u64, UserInfo>,
+    pub fullpacket_offsets: Vec<usize>,
+    pub demo_index: DemoIndex,
+}
+
+pub fn sidecar_path(demo_path: &str) -> String {
+    demo_path.to_string() + ".idx"
+}
+
+fn fnv1a(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for b in bytes {
+        hash ^= *b as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+// Varint starting at bytes[*ptr], moves ptr past it
+fn varint_at(bytes: &[u8], ptr: &mut usize) -> Result<u32, DemoParserError> {
+    let mut result: u32 = 0;
+    for count in 0..5 {
+        let b = match bytes.get(*ptr) {
+            Some(b) => *b as u32,
+            None => return Err(DemoParserError::OutOfBytesError),
+        };
+        *ptr += 1;
+        result |= (b & 127) << (7 * count);
+        if b & 0x80 == 0 {
+            break;
+        }
+    }
+    Ok(result)
+}
+
+impl Parser {
+    // Identifies the demo a sidecar was made from. Reads the file header without moving the parser's pointer.
+    pub fn sidecar_key(&self) -> Result<SidecarKey, DemoParserError> {
+        let file_size = self.bytes.get_len();
+        if file_size < 16 {
+            return Err(DemoParserError::OutOfBytesError);
+        }
+        let bytes = &self.bytes[..file_size];
+        let hash = fnv1a(&bytes[..file_size.min(HASHED_BYTES)]);
+        // The file header is always the first command after the 16 byte demo header
+        let mut ptr = 16;
+        let cmd = varint_at(bytes, &mut ptr)?;
+        let _tick = varint_at(bytes, &mut ptr)?;
+        let size = varint_at(bytes, &mut ptr)? as usize;
+        let msg_type = cmd & !64;
+        let is_compressed = (cmd & 64) == 64;
+        if demo_cmd_type_from_int(msg_type as i32) != Some(DEM_FileHeader) {
+            return Err(DemoParserError::MalformedMessage);
+        }
+        let msg_bytes = match bytes.get(ptr..ptr + size) {
+            Some(b) => b,
+            None => return Err(DemoParserError::OutOfBytesError),
+        };
+        let bytes = match is_compressed {
+            true => match SnapDecoder::new().decompress_vec(msg_bytes) {
+                Ok(b) => b,
+                Err(e) => return Err(DemoParserError::DecompressionFailure(format!("{}", e))),
+            },
+            false => msg_bytes.to_vec(),
+        };
+        let header: CDemoFileHeader = match Message::parse_from_bytes(&bytes) {
+            Ok(header) => header,
+            Err(_) => return Err(DemoParserError::MalformedMessage),
+        };
+        Ok(SidecarKey {
+            demo_version_guid: header.demo_version_guid().to_string(),
+            file_size,
+            hash,
+        })
+    }
+    pub fn create_sidecar(&mut self) -> Result<Sidecar, DemoParserError> {
+        self.first_pass()?;
+        let sendtables = match &self.sendtable_layout {
+            Some(layout) => layout.as_ref().clone(),
+            None => return Err(DemoParserError::NoSendTableMessage),
+        };
+        let mut classes: Vec<(i32, String)> = match &self.cls_by_id {
+            Some(cls_by_id) => cls_by_id.values().map(|cls| (cls.class_id, cls.name.clone())).collect(),
+            None => vec![],
+        };
+        classes.sort();
+        let mut ge_list = vec![];
+        for (id, descriptor) in &self.ge_list {
+            match descriptor.write_to_bytes() {
+                Ok(bytes) => ge_list.push((*id, bytes)),
+                Err(_) => return Err(DemoParserError::MalformedMessage),
+            }
+        }
+        Ok(Sidecar {
+            version: SIDECAR_VERSION,
+            key: self.sidecar_key()?,
+            header: self.header.clone(),
+            sendtables,
+            classes,
+            ge_list,
+            baselines: self.baselines.clone(),
+            string_tables: self.string_tables.clone(),
+            stringtable_players: self.stringtable_players.clone(),
+            fullpacket_offsets: self.fullpacket_offsets.clone(),
+            demo_index: self.demo_index.clone(),
+        })
+    }
+    pub fn save_sidecar(&mut self, path: &str) -> Result<(), DemoParserError> {
+        let sidecar = self.create_sidecar()?;
+        let file = match File::create(path) {
+            Ok(f) => f,
+            Err(e) => return Err(DemoParserError::SidecarError(format!("{}", e))),
+        };
+        match bincode::serialize_into(BufWriter::new(file), &sidecar) {
+            Ok(_) => Ok(()),
+            Err(e) => Err(DemoParserError::SidecarError(format!("{}", e))),
+        }
+    }
+    // Replaces the first pass. Fails if the sidecar was made from another demo or by an older version.
+    pub fn load_sidecar(&mut self, path: &str) -> Result<(), DemoParserError> {
+        let file = match File::open(path) {
+            Ok(f) => f,
+            Err(e) => return Err(DemoParserError::FileNotFound(format!("{}", e))),
+        };
+        let sidecar: Sidecar = match bincode::deserialize_from(BufReader::new(file)) {
+            Ok(sidecar) => sidecar,
+            Err(e) => return Err(DemoParserError::SidecarError(format!("{}", e))),
+        };
+        self.apply_sidecar(sidecar)
+    }
+    pub fn apply_sidecar(&mut self, sidecar: Sidecar) -> Result<(), DemoParserError> {
+        if sidecar.version != SIDECAR_VERSION {
+            return Err(DemoParserError::SidecarError(format!(
+                "sidecar version {} does not match parser version {}",
+                sidecar.version, SIDECAR_VERSION
+            )));
+        }
+        if sidecar.key != self.sidecar_key()? {
+            return Err(DemoParserError::SidecarError(
+                "sidecar was created from a different demo".to_string(),
+            ));
+        }
+        let mut ge_list = AHashMap::default();
+        for (id, bytes) in &sidecar.ge_list {
+            let descriptor: Descriptor_t = match Message::parse_from_bytes(bytes) {
+                Ok(d) => d,
+                Err(_) => return Err(DemoParserError::MalformedMessage),
+            };
+            ge_list.insert(*id, descriptor);
+        }
+        let (serializers, prop_controller) = self.build_serializers(&sidecar.sendtables)?;
+        self.set_classes(
+            sidecar.classes,
+            serializers,
+            sidecar.sendtables.qf_mapper.clone(),
+            prop_controller,
+        )?;
+        self.sendtable_layout = Some(Arc::new(sidecar.sendtables));
+        self.header = sidecar.header;
+        self.ge_list = ge_list;
+        self.ge_list_set = true;
+        self.baselines = sidecar.baselines;
+        self.string_tables = sidecar.string_tables;
+        self.stringtable_players = sidecar.stringtable_players;
+        self.fullpacket_offsets = sidecar.fullpacket_offsets;
+        self.demo_index = sidecar.demo_index;
+        self.first_pass_done = true;
+        Ok(())
+    }
+    // Loads the sidecar if there is a valid one, otherwise does the first pass and (re)writes it.
+    pub fn first_pass_with_sidecar(&mut self, path: &str) -> Result<(), DemoParserError> {
+        if self.first_pass_done {
+            return Ok(());
+        }
+        if self.load_sidecar(path).is_ok() {
+            return Ok(());
+        }
+        self.first_pass()?;
+        self.save_sidecar(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::query::DemoQuery;
+    use crate::test_demo::TestDemo;
+    use crate::variants::BytesVariant;
+    use crate::variants::Variant;
+
+    fn demo(final_score: i32) -> Vec<u8> {
+        let mut demo = TestDemo::new();
+        demo.add_teams_and_rules();
+        demo.packet(1);
+        demo.set(52, "m_iScore", 1);
+        demo.packet(2);
+        demo.full_packet(3);
+        demo.set(52, "m_iScore", 2);
+        demo.packet(4);
+        demo.set(52, "m_iScore", final_score);
+        demo.packet(5);
+        demo.finish(5)
+    }
+    fn parser(bytes: Vec<u8>) -> Parser {
+        DemoQuery::new()
+            .entity_props(["CCSTeam.m_iScore"])
+            .parser(BytesVariant::Vec(bytes))
+            .unwrap()
+    }
+    fn sidecar_path(name: &str) -> String {
+        let path = std::env::temp_dir().join(format!("sidecar_{}_{}.idx", name, std::process::id()));
+        path.to_string_lossy().to_string()
+    }
+    fn score_at(parser: &mut Parser, tick: i32) -> (i32, Option<Variant>) {
+        let state = parser.seek(tick).unwrap();
+        (state.tick, state.view().get_prop(52, "CCSTeam.m_iScore").cloned())
+    }
+
+    #[test]
+    fn test_fnv1a() {
+        assert_eq!(fnv1a(b""), 0xcbf29ce484222325);
+        assert_eq!(fnv1a(b"a"), 0xaf63dc4c8601ec8c);
+    }
+    #[test]
+    fn test_demo_index_roundtrip() {
+        let mut index = DemoIndex::default();
+        index.push(0, 1000);
+        index.push(3840, 5000);
+        let bytes = bincode::serialize(&index).unwrap();
+        let back: DemoIndex = bincode::deserialize(&bytes).unwrap();
+        assert_eq!(back.entries, index.entries);
+    }
+    #[test]
+    fn test_save_load_seek_roundtrip() {
+        let path = sidecar_path("roundtrip");
+        parser(demo(3)).save_sidecar(&path).unwrap();
+
+        let mut loaded = parser(demo(3));
+        loaded.load_sidecar(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(loaded.first_pass_done);
+        assert_eq!(loaded.ptr, 0);
+
+        let mut fresh = parser(demo(3));
+        for tick in [2, 4, 5] {
+            assert_eq!(score_at(&mut loaded, tick), score_at(&mut fresh, tick));
+        }
+        assert_eq!(score_at(&mut loaded, 4), (4, Some(Variant::I32(2))));
+        assert_eq!(loaded.fullpacket_offsets, fresh.fullpacket_offsets);
+    }
+    #[test]
+    fn test_sidecar_key_does_not_move_pointer() {
+        let parser = parser(demo(3));
+        assert_eq!(parser.sidecar_key().unwrap(), parser.sidecar_key().unwrap());
+        assert_eq!(parser.ptr, 0);
+    }
+    #[test]
+    fn test_stale_sidecar_rejected() {
+        let path = sidecar_path("stale");
+        parser(demo(3)).save_sidecar(&path).unwrap();
+
+        // Same length, different content
+        let mut other = parser(demo(7));
+        let result = other.load_sidecar(&path);
+        assert!(matches!(result, Err(DemoParserError::SidecarError(_))));
+        assert!(!other.first_pass_done);
+
+        // first_pass_with_sidecar falls back to the first pass and replaces the stale sidecar
+        other.first_pass_with_sidecar(&path).unwrap();
+        let mut reloaded = parser(demo(7));
+        reloaded.load_sidecar(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(score_at(&mut reloaded, 5), (5, Some(Variant::I32(7))));
+    }
+    #[test]
+    fn test_sidecar_of_other_version_rejected() {
+        let mut sidecar = parser(demo(3)).create_sidecar().unwrap();
+        sidecar.version += 1;
+        assert!(matches!(
+            parser(demo(3)).apply_sidecar(sidecar),
+            Err(DemoParserError::SidecarError(_))
+        ));
+    }
+}
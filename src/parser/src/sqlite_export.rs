@@ -0,0 +1,542 @@
+use crate::game_events::GameEvent;
+use crate::parser::DemoOutput;
+use crate::query::DemoQuery;
+use crate::read_bits::DemoParserError;
+use crate::rounds::rounds_from_events;
+use crate::rounds::Round;
+use crate::rounds::ROUND_EVENTS;
+use crate::rounds::ROUND_PROPS;
+use crate::stream_writers::json_value;
+use crate::stringtables::UserInfo;
+use crate::variants::Variant;
+use rusqlite::params;
+use rusqlite::params_from_iter;
+use rusqlite::types::Value;
+use rusqlite::Connection;
+use rusqlite::Transaction;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+// Writes a demo into a SQLite file (feature "sqlite"):
+//
+// SqliteExport::new().tick_props(["X", "Y", "health"]).sample_every(64).write("match.dem", Path::new("match.db"))?;
+//
+// Every steamid column references players(steamid) and every tick column references ticks(tick),
+// foreign keys are checked while writing. Ticks are only the ticks that something happened on
+// (events, grenades, samples), not every tick of the demo. Samples go up to the last tick of the demo.
+// Rounds are the played rounds from rounds::rounds_from_events (no warmup, restarts and backups
+// replaced), kills and damage outside of them have no round.
+// The demo is parsed twice: once for events/grenades/chat and once for the sampled player ticks,
+// as events and ticks can't be collected in the same parse.
+
+const SCHEMA: &str = "
+CREATE TABLE header (key TEXT PRIMARY KEY, value TEXT);
+CREATE TABLE convars (name TEXT PRIMARY KEY, value TEXT);
+CREATE TABLE players (
+    steamid INTEGER PRIMARY KEY,
+    name TEXT,
+    userid INTEGER,
+    team_number INTEGER,
+    is_hltv INTEGER
+);
+CREATE TABLE ticks (tick INTEGER PRIMARY KEY);
+CREATE TABLE rounds (
+    round INTEGER PRIMARY KEY,
+    start_tick INTEGER NOT NULL REFERENCES ticks(tick),
+    freeze_end_tick INTEGER REFERENCES ticks(tick),
+    end_tick INTEGER REFERENCES ticks(tick),
+    winner TEXT,
+    reason TEXT,
+    reason_code INTEGER,
+    t_score INTEGER,
+    ct_score INTEGER
+);
+CREATE TABLE kills (
+    id INTEGER PRIMARY KEY,
+    tick INTEGER NOT NULL REFERENCES ticks(tick),
+    round INTEGER REFERENCES rounds(round),
+    attacker_steamid INTEGER REFERENCES players(steamid),
+    victim_steamid INTEGER REFERENCES players(steamid),
+    assister_steamid INTEGER REFERENCES players(steamid),
+    weapon TEXT,
+    headshot INTEGER,
+    penetrated INTEGER,
+    noscope INTEGER,
+    thrusmoke INTEGER,
+    attackerblind INTEGER
+);
+CREATE TABLE damage (
+    id INTEGER PRIMARY KEY,
+    tick INTEGER NOT NULL REFERENCES ticks(tick),
+    round INTEGER REFERENCES rounds(round),
+    attacker_steamid INTEGER REFERENCES players(steamid),
+    victim_steamid INTEGER REFERENCES players(steamid),
+    weapon TEXT,
+    dmg_health INTEGER,
+    dmg_armor INTEGER,
+    health INTEGER,
+    armor INTEGER,
+    hitgroup INTEGER
+);
+CREATE TABLE grenades (
+    id INTEGER PRIMARY KEY,
+    tick INTEGER REFERENCES ticks(tick),
+    entity_id INTEGER,
+    grenade_type TEXT,
+    thrower_steamid INTEGER REFERENCES players(steamid),
+    x REAL,
+    y REAL,
+    z REAL
+);
+CREATE TABLE chat (
+    id INTEGER PRIMARY KEY,
+    steamid INTEGER REFERENCES players(steamid),
+    entity_idx INTEGER,
+    name TEXT,
+    message TEXT,
+    param3 TEXT,
+    param4 TEXT
+);
+CREATE TABLE skins (
+    id INTEGER PRIMARY KEY,
+    steamid INTEGER REFERENCES players(steamid),
+    item_id INTEGER,
+    def_index INTEGER,
+    paint_index INTEGER,
+    paint_seed INTEGER,
+    paint_wear INTEGER,
+    quality INTEGER,
+    rarity INTEGER,
+    custom_name TEXT,
+    item_name TEXT,
+    skin_name TEXT
+);
+CREATE INDEX kills_round ON kills(round);
+CREATE INDEX damage_round ON damage(round);
+CREATE INDEX grenades_tick ON grenades(tick);
+";
+
+const EVENTS: [&str; 2] = ["player_death", "player_hurt"];
+
+#[derive(Debug, Clone)]
+pub struct SqliteExport {
+    pub tick_props: Vec<String>,
+    pub sample_every: i32,
+    pub lenient: bool,
+}
+
+impl Default for SqliteExport {
+    fn default() -> Self {
+        SqliteExport {
+            tick_props: ["X", "Y", "Z", "health", "armor_value", "team_num", "balance"]
+                .iter()
+                .map(|p| p.to_string())
+                .collect(),
+            sample_every: 64,
+            lenient: false,
+        }
+    }
+}
+
+impl SqliteExport {
+    pub fn new() -> Self {
+        SqliteExport::default()
+    }
+    /// Player props in the player_ticks table, replaces the defaults.
+    pub fn tick_props<I, S>(mut self, props: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.tick_props = props.into_iter().map(|p| p.into()).collect();
+        self
+    }
+    /// Every n:th tick goes into player_ticks, 64 is once per second.
+    pub fn sample_every(mut self, ticks: i32) -> Self {
+        self.sample_every = ticks.max(1);
+        self
+    }
+    pub fn lenient(mut self) -> Self {
+        self.lenient = true;
+        self
+    }
+    // Overwrites the file if it exists.
+    pub fn write(&self, demo_path: &str, db_path: &Path) -> Result<(), DemoParserError> {
+        let mut query = DemoQuery::new()
+            .events(EVENTS.iter().chain(ROUND_EVENTS.iter()).copied())
+            .other_props(ROUND_PROPS)
+            .projectiles();
+        if self.lenient {
+            query = query.lenient();
+        }
+        let mut parser = query.parser_from_path(demo_path)?;
+        let output = parser.parse_demo()?;
+
+        // Tick of the last frame, left there by the first pass
+        let last_tick = parser.tick.max(0);
+        let mut tick_query = DemoQuery::new()
+            .player_props(&self.tick_props)
+            .ticks((0..=last_tick).step_by(self.sample_every as usize));
+        if self.lenient {
+            tick_query = tick_query.lenient();
+        }
+        let ticks = tick_query.parser_from_path(demo_path)?.parse_demo()?;
+
+        if db_path.exists() {
+            if let Err(e) = fs::remove_file(db_path) {
+                return Err(DemoParserError::SqliteError(format!("{}: {}", db_path.display(), e)));
+            }
+        }
+        let mut conn = sqlite(Connection::open(db_path))?;
+        sqlite(conn.execute_batch("PRAGMA foreign_keys = ON;"))?;
+        let tx = sqlite(conn.transaction())?;
+        sqlite(tx.execute_batch(SCHEMA))?;
+        write_demo(&tx, &output, &ticks, &parser.stringtable_players)?;
+        sqlite(tx.commit())
+    }
+}
+
+fn write_demo(
+    tx: &Transaction,
+    output: &DemoOutput,
+    ticks: &DemoOutput,
+    stringtable_players: &BTreeMap<u64, UserInfo>,
+) -> Result<(), DemoParserError> {
+    if let Some(header) = &output.header {
+        for (k, v) in header {
+            sqlite(tx.execute("INSERT INTO header VALUES (?1, ?2)", params![k, v]))?;
+        }
+    }
+    for (k, v) in &output.convars {
+        sqlite(tx.execute("INSERT OR REPLACE INTO convars VALUES (?1, ?2)", params![k, v]))?;
+    }
+    for player in stringtable_players.values() {
+        sqlite(tx.execute(
+            "INSERT OR IGNORE INTO players (steamid, name, userid, is_hltv) VALUES (?1, ?2, ?3, ?4)",
+            params![player.steamid as i64, player.name, player.userid, player.is_hltv],
+        ))?;
+    }
+    for md in &output.player_md {
+        if let Some(steamid) = md.steamid {
+            ensure_player(tx, Some(steamid as i64), md.name.as_deref())?;
+            sqlite(tx.execute(
+                "UPDATE players SET team_number = ?1 WHERE steamid = ?2",
+                params![md.team_number, steamid as i64],
+            ))?;
+        }
+    }
+
+    let mut events: Vec<&GameEvent> = output.game_events.iter().collect();
+    events.sort_by_key(|e| e.tick);
+    let rounds = rounds_from_events(&output.game_events, &output.convars);
+    write_rounds(tx, &rounds)?;
+    let round_at = |tick: i32| round_at(&rounds, tick);
+
+    for event in events.iter().filter(|e| e.name == "player_death") {
+        ensure_tick(tx, event.tick)?;
+        let attacker = event_player(tx, event, "attacker")?;
+        let victim = event_player(tx, event, "user")?;
+        let assister = event_player(tx, event, "assister")?;
+        sqlite(tx.execute(
+            "INSERT INTO kills (tick, round, attacker_steamid, victim_steamid, assister_steamid, weapon, headshot, penetrated, noscope, thrusmoke, attackerblind)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+            params![
+                event.tick,
+                round_at(event.tick),
+                attacker,
+                victim,
+                assister,
+                sql_value(event.field("weapon")),
+                sql_value(event.field("headshot")),
+                sql_value(event.field("penetrated")),
+                sql_value(event.field("noscope")),
+                sql_value(event.field("thrusmoke")),
+                sql_value(event.field("attackerblind")),
+            ],
+        ))?;
+    }
+    for event in events.iter().filter(|e| e.name == "player_hurt") {
+        ensure_tick(tx, event.tick)?;
+        let attacker = event_player(tx, event, "attacker")?;
+        let victim = event_player(tx, event, "user")?;
+        sqlite(tx.execute(
+            "INSERT INTO damage (tick, round, attacker_steamid, victim_steamid, weapon, dmg_health, dmg_armor, health, armor, hitgroup)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+            params![
+                event.tick,
+                round_at(event.tick),
+                attacker,
+                victim,
+                sql_value(event.field("weapon")),
+                sql_value(event.field("dmg_health")),
+                sql_value(event.field("dmg_armor")),
+                sql_value(event.field("health")),
+                sql_value(event.field("armor")),
+                sql_value(event.field("hitgroup")),
+            ],
+        ))?;
+    }
+    for p in &output.projectiles {
+        if let Some(tick) = p.tick {
+            ensure_tick(tx, tick)?;
+        }
+        let thrower = p.steamid.map(|s| s as i64);
+        ensure_player(tx, thrower, p.name.as_deref())?;
+        sqlite(tx.execute(
+            "INSERT INTO grenades (tick, entity_id, grenade_type, thrower_steamid, x, y, z) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![p.tick, p.entity_id, p.grenade_type, thrower, p.x, p.y, p.z],
+        ))?;
+    }
+    for m in &output.chat_messages {
+        // Chat messages only have the name of the sender
+        let steamid = stringtable_players
+            .values()
+            .find(|p| Some(&p.name) == m.param1.as_ref())
+            .map(|p| p.steamid as i64);
+        sqlite(tx.execute(
+            "INSERT INTO chat (steamid, entity_idx, name, message, param3, param4) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![steamid, m.entity_idx, m.param1, m.param2, m.param3, m.param4],
+        ))?;
+    }
+    for s in &output.skins {
+        let steamid = s.steamid.map(|s| s as i64);
+        ensure_player(tx, steamid, None)?;
+        sqlite(tx.execute(
+            "INSERT INTO skins (steamid, item_id, def_index, paint_index, paint_seed, paint_wear, quality, rarity, custom_name, item_name, skin_name)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+            params![
+                steamid,
+                s.item_id.map(|i| i as i64),
+                s.def_index,
+                s.paint_index,
+                s.paint_seed,
+                s.paint_wear,
+                s.quality,
+                s.rarity,
+                s.custom_name,
+                s.item_name,
+                s.skin_name,
+            ],
+        ))?;
+    }
+    write_player_ticks(tx, ticks)
+}
+
+fn write_rounds(tx: &Transaction, rounds: &[Round]) -> Result<(), DemoParserError> {
+    for round in rounds {
+        for tick in [Some(round.start_tick), round.freeze_end_tick, round.end_tick]
+            .into_iter()
+            .flatten()
+        {
+            ensure_tick(tx, tick)?;
+        }
+        sqlite(tx.execute(
+            "INSERT INTO rounds (round, start_tick, freeze_end_tick, end_tick, winner, reason, reason_code, t_score, ct_score)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+            params![
+                round.round,
+                round.start_tick,
+                round.freeze_end_tick,
+                round.end_tick,
+                round.winner,
+                round.reason,
+                round.reason_code,
+                round.t_score,
+                round.ct_score,
+            ],
+        ))?;
+    }
+    Ok(())
+}
+
+// Round the tick is in, kills after round_end still count until the round officially ends
+fn round_at(rounds: &[Round], tick: i32) -> Option<i32> {
+    rounds
+        .iter()
+        .rev()
+        .find(|r| r.start_tick <= tick)
+        .filter(|r| tick <= r.officially_ended_tick.or(r.end_tick).unwrap_or(i32::MAX))
+        .map(|r| r.round)
+}
+
+// One row per player per sampled tick, one column per wanted prop
+fn write_player_ticks(tx: &Transaction, ticks: &DemoOutput) -> Result<(), DemoParserError> {
+    let mut columns = vec![];
+    let mut tick_col = None;
+    let mut steamid_col = None;
+    let mut name_col = None;
+    for prop_info in &ticks.prop_info.prop_infos {
+        if let Some(col) = ticks.df.get(&prop_info.id) {
+            match prop_info.prop_name.as_str() {
+                "tick" => tick_col = Some(col),
+                "steamid" => steamid_col = Some(col),
+                "name" => name_col = Some(col),
+                _ => columns.push((prop_info.prop_friendly_name.as_str(), col)),
+            }
+        }
+    }
+    let prop_columns: Vec<String> = columns
+        .iter()
+        .map(|(name, _)| format!("\"{}\"", name.replace('"', "\"\"")))
+        .collect();
+    let mut create = "CREATE TABLE player_ticks (
+        tick INTEGER NOT NULL REFERENCES ticks(tick),
+        steamid INTEGER NOT NULL REFERENCES players(steamid)"
+        .to_string();
+    for name in &prop_columns {
+        create.push_str(&format!(",\n        {}", name));
+    }
+    create.push_str(",\n        PRIMARY KEY (tick, steamid)\n    )");
+    sqlite(tx.execute_batch(&create))?;
+
+    let (tick_col, steamid_col) = match (tick_col, steamid_col) {
+        (Some(tick_col), Some(steamid_col)) => (tick_col, steamid_col),
+        _ => return Ok(()),
+    };
+    let placeholders: Vec<String> = (1..=prop_columns.len() + 2).map(|i| format!("?{}", i)).collect();
+    let insert = format!(
+        "INSERT OR IGNORE INTO player_ticks (tick, steamid{}{}) VALUES ({})",
+        if prop_columns.is_empty() { "" } else { ", " },
+        prop_columns.join(", "),
+        placeholders.join(", ")
+    );
+    let mut stmt = sqlite(tx.prepare(&insert))?;
+    for idx in 0..tick_col.len() {
+        let (tick, steamid) = match (tick_col.get(idx), steamid_to_i64(steamid_col.get(idx).as_ref())) {
+            (Some(Variant::I32(tick)), Some(steamid)) => (tick, steamid),
+            _ => continue,
+        };
+        let name = name_col.and_then(|col| col.get(idx));
+        let name = match &name {
+            Some(Variant::String(name)) => Some(name.as_str()),
+            _ => None,
+        };
+        ensure_tick(tx, tick)?;
+        ensure_player(tx, Some(steamid), name)?;
+        let mut values = vec![Value::Integer(tick as i64), Value::Integer(steamid)];
+        values.extend(columns.iter().map(|(_, col)| sql_value(col.get(idx).as_ref())));
+        sqlite(stmt.execute(params_from_iter(values)))?;
+    }
+    Ok(())
+}
+
+fn ensure_tick(tx: &Transaction, tick: i32) -> Result<(), DemoParserError> {
+    sqlite(tx.execute("INSERT OR IGNORE INTO ticks VALUES (?1)", params![tick]))?;
+    Ok(())
+}
+
+// Players that never showed up in the userinfo stringtable still need a row for the foreign keys
+fn ensure_player(tx: &Transaction, steamid: Option<i64>, name: Option<&str>) -> Result<(), DemoParserError> {
+    if let Some(steamid) = steamid {
+        sqlite(tx.execute(
+            "INSERT OR IGNORE INTO players (steamid, name) VALUES (?1, ?2)",
+            params![steamid, name],
+        ))?;
+    }
+    Ok(())
+}
+
+// "<prefix>_steamid" of an event, added to players if needed
+fn event_player(tx: &Transaction, event: &GameEvent, prefix: &str) -> Result<Option<i64>, DemoParserError> {
+    let steamid = event.steamid(prefix).map(|s| s as i64);
+    let name = event.string_field(&format!("{}_name", prefix));
+    ensure_player(tx, steamid, name)?;
+    Ok(steamid)
+}
+
+// The tick output has steamids as u64, 0 is used for bots.
+fn steamid_to_i64(v: Option<&Variant>) -> Option<i64> {
+    let steamid = match v {
+        Some(Variant::U64(s)) => *s,
+        Some(Variant::String(s)) => s.parse().ok()?,
+        _ => return None,
+    };
+    match steamid {
+        0 => None,
+        s => Some(s as i64),
+    }
+}
+
+fn sql_value(v: Option<&Variant>) -> Value {
+    match v {
+        None => Value::Null,
+        Some(Variant::Bool(b)) => Value::Integer(*b as i64),
+        Some(Variant::U32(x)) => Value::Integer(*x as i64),
+        Some(Variant::I32(x)) => Value::Integer(*x as i64),
+        Some(Variant::I16(x)) => Value::Integer(*x as i64),
+        Some(Variant::U8(x)) => Value::Integer(*x as i64),
+        Some(Variant::U64(x)) => Value::Integer(*x as i64),
+        Some(Variant::F32(x)) => Value::Real(*x as f64),
+        Some(Variant::String(s)) => Value::Text(s.clone()),
+        Some(v) => Value::Text(json_value(v)),
+    }
+}
+
+fn sqlite<T>(result: rusqlite::Result<T>) -> Result<T, DemoParserError> {
+    match result {
+        Ok(v) => Ok(v),
+        Err(e) => Err(DemoParserError::SqliteError(format!("{}", e))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game_events::test_event as event;
+    use ahash::AHashMap;
+
+    #[test]
+    fn test_rounds_and_kill_foreign_keys() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch("PRAGMA foreign_keys = ON;").unwrap();
+        let tx = conn.transaction().unwrap();
+        tx.execute_batch(SCHEMA).unwrap();
+        let events = [
+            event("round_start", 100, vec![("total_rounds_played", Variant::I32(0))]),
+            event("round_freeze_end", 200, vec![]),
+            event(
+                "round_end",
+                900,
+                vec![("winner", Variant::I32(3)), ("reason", Variant::I32(8))],
+            ),
+            event("round_officially_ended", 950, vec![]),
+            // Backup of round 1 loaded, only this one is kept
+            event("round_start", 1000, vec![("total_rounds_played", Variant::I32(0))]),
+            event("round_start", 2000, vec![("total_rounds_played", Variant::I32(1))]),
+        ];
+        let rounds = rounds_from_events(&events, &AHashMap::default());
+        write_rounds(&tx, &rounds).unwrap();
+        let starts: Vec<(i32, i32)> = tx
+            .prepare("SELECT round, start_tick FROM rounds ORDER BY round")
+            .unwrap()
+            .query_map([], |r| Ok((r.get(0)?, r.get(1)?)))
+            .unwrap()
+            .map(|r| r.unwrap())
+            .collect();
+        assert_eq!(starts, vec![(1, 1000), (2, 2000)]);
+        // Replaced round
+        assert_eq!(round_at(&rounds, 500), None);
+        assert_eq!(round_at(&rounds, 1500), Some(1));
+        assert_eq!(round_at(&rounds, 2500), Some(2));
+
+        let kill = event(
+            "player_death",
+            500,
+            vec![
+                ("attacker_steamid", Variant::String("76561198000000001".to_string())),
+                ("attacker_name", Variant::String("a".to_string())),
+                ("user_steamid", Variant::String("0".to_string())),
+            ],
+        );
+        assert_eq!(event_player(&tx, &kill, "attacker").unwrap(), Some(76561198000000001));
+        // bots have no steamid
+        assert_eq!(event_player(&tx, &kill, "user").unwrap(), None);
+        let name: String = tx
+            .query_row("SELECT name FROM players WHERE steamid = 76561198000000001", [], |r| r.get(0))
+            .unwrap();
+        assert_eq!(name, "a");
+        // tick that was never added
+        assert!(tx.execute("INSERT INTO kills (tick) VALUES (12345)", []).is_err());
+    }
+}
@@ -0,0 +1,591 @@
+use crate::damage::damage_ledger;
+use crate::damage::DamageRecord;
+use crate::game_events::GameEvent;
+use crate::kill_labels::DEFAULT_TRADE_WINDOW;
+use crate::parser::DemoOutput;
+use crate::parser_settings::Parser;
+use crate::read_bits::DemoParserError;
+use crate::rounds::Round;
+use crate::tick_selectors::variant_to_i64;
+use crate::variants::Variant;
+use ahash::AHashMap;
+use ahash::AHashSet;
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+// Scoreboard and per player match stats from the kill, damage and blind events and the controllers.
+// examples/stats.rs shows it in use.
+
+pub const STATS_EVENTS: [&str; 4] = ["player_death", "player_hurt", "player_blind", "round_start"];
+const STATS_PLAYER_PROPS: [&str; 3] = ["team_num", "mvps", "utility_damage_this_round"];
+
+const T: i32 = 2;
+const CT: i32 = 3;
+
+// One player at one tick, from the controller
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlayerSnapshot {
+    pub tick: i32,
+    pub steamid: u64,
+    pub name: String,
+    pub team_num: i32,
+    pub mvps: i32,
+    pub utility_damage_this_round: i32,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct PlayerRoundStats {
+    pub round: i32,
+    pub steamid: u64,
+    pub name: String,
+    // "T" or "CT"
+    pub side: Option<String>,
+    pub kills: i32,
+    pub deaths: i32,
+    pub assists: i32,
+    pub flash_assists: i32,
+    pub headshot_kills: i32,
+    // Health taken from enemies, a hit can not do more damage than the victim had health left
+    pub damage: i32,
+    pub utility_damage: i32,
+    pub enemies_flashed: i32,
+    // Seconds
+    pub enemy_blind_time: f32,
+    pub survived: bool,
+    pub traded: bool,
+    // Kill, assist, survived or traded
+    pub kast: bool,
+    pub entry_kill: bool,
+    pub entry_death: bool,
+    // Number of enemies alive when the player became the last one alive on their side
+    pub clutch_enemies: Option<i32>,
+    pub clutch_won: bool,
+    pub mvp: bool,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct PlayerStats {
+    pub steamid: u64,
+    pub name: String,
+    pub rounds_played: i32,
+    pub kills: i32,
+    pub deaths: i32,
+    pub assists: i32,
+    pub flash_assists: i32,
+    pub headshot_kills: i32,
+    // Percent of kills
+    pub headshot_pct: f32,
+    pub damage: i32,
+    pub adr: f32,
+    // Percent of rounds
+    pub kast: f32,
+    pub entry_kills: i32,
+    pub entry_deaths: i32,
+    // Rounds with exactly 1/2/3/4 kills and 5 or more
+    pub one_kill_rounds: i32,
+    pub two_kill_rounds: i32,
+    pub three_kill_rounds: i32,
+    pub four_kill_rounds: i32,
+    pub five_kill_rounds: i32,
+    pub clutches_attempted: i32,
+    pub clutches_won: i32,
+    pub utility_damage: i32,
+    pub enemies_flashed: i32,
+    pub mvps: i32,
+    // HLTV rating 1.0
+    pub rating: f32,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct MatchStats {
+    // Sorted by rating, best first
+    pub players: Vec<PlayerStats>,
+    // Sorted by round and steamid
+    pub rounds: Vec<PlayerRoundStats>,
+}
+
+impl Parser {
+    // Parses the rounds first, then the events and the controller snapshots at the round boundaries (two separate parses)
+    pub fn parse_stats(&mut self) -> Result<MatchStats, DemoParserError> {
+        let rounds = self.parse_rounds()?;
+        if rounds.is_empty() {
+            return Ok(MatchStats::default());
+        }
+        let mut ticks: Vec<i32> = rounds.iter().map(roster_tick).collect();
+        ticks.extend(rounds.iter().filter_map(end_tick));
+        ticks.sort();
+        ticks.dedup();
+
+        let events_query = self
+            .events_only_query(STATS_EVENTS)
+            .entities(true)
+            .player_props(["team_num"])
+            .kill_labels(DEFAULT_TRADE_WINDOW);
+        let events = self.parser_for(&events_query)?.parse_demo()?.game_events;
+        let snapshots = self
+            .parser_for(&self.player_ticks_query(STATS_PLAYER_PROPS, ticks))?
+            .parse_demo()?;
+        Ok(compute_stats(&rounds, &events, &snapshots_from_output(&snapshots)))
+    }
+}
+
+// Snapshots are used at the roster tick (freeze end, or start if freeze time never ended) and at the end tick
+// (officially ended, or end) of every round, MVPs and utility damage are read at the end tick. Events need
+// team_num as a player prop (user_team_num ...) for players missing from the snapshots, and the kill labels
+// with the clutch events. Damage is the clamped health_damage of damage_ledger.
+// Players are keyed by steamid, bots have none and are left out. Team kills and suicides are not kills.
+pub fn compute_stats(rounds: &[Round], events: &[GameEvent], snapshots: &[PlayerSnapshot]) -> MatchStats {
+    let ledger = damage_ledger(events);
+    let mut events: Vec<&GameEvent> = events.iter().collect();
+    events.sort_by_key(|e| e.tick);
+    let mut snapshots_at: AHashMap<i32, Vec<&PlayerSnapshot>> = AHashMap::default();
+    for snapshot in snapshots.iter().filter(|s| s.steamid != 0) {
+        snapshots_at.entry(snapshot.tick).or_default().push(snapshot);
+    }
+
+    let mut all_rounds = vec![];
+    let mut last_mvps: AHashMap<u64, i32> = AHashMap::default();
+    for (idx, round) in rounds.iter().enumerate() {
+        let start = round.start_tick;
+        // Events between a round and a replayed (backup) one belong to neither
+        let end = match (round.officially_ended_tick, rounds.get(idx + 1)) {
+            (Some(tick), _) => tick,
+            (None, Some(next)) => next.start_tick - 1,
+            (None, None) => i32::MAX,
+        };
+        let round_events: Vec<&GameEvent> = events.iter().filter(|e| (start..=end).contains(&e.tick)).copied().collect();
+        let round_damage: Vec<&DamageRecord> = ledger.iter().filter(|d| (start..=end).contains(&d.tick)).collect();
+        let roster = snapshots_at.get(&roster_tick(round)).cloned().unwrap_or_default();
+        let at_end = end_tick(round)
+            .and_then(|tick| snapshots_at.get(&tick))
+            .cloned()
+            .unwrap_or_default();
+        let mut players = round_stats(round, &round_events, &round_damage, &roster);
+
+        for snapshot in at_end {
+            let previous = last_mvps.insert(snapshot.steamid, snapshot.mvps).unwrap_or(0);
+            if let Some(p) = players.get_mut(&snapshot.steamid) {
+                p.mvp = snapshot.mvps > previous;
+                p.utility_damage = snapshot.utility_damage_this_round;
+            }
+        }
+        all_rounds.extend(players.into_values());
+    }
+    MatchStats {
+        players: totals(&all_rounds),
+        rounds: all_rounds,
+    }
+}
+
+fn round_stats(
+    round: &Round,
+    events: &[&GameEvent],
+    damage: &[&DamageRecord],
+    roster: &[&PlayerSnapshot],
+) -> BTreeMap<u64, PlayerRoundStats> {
+    let mut teams: AHashMap<u64, i32> = AHashMap::default();
+    let mut players: BTreeMap<u64, PlayerRoundStats> = BTreeMap::default();
+    for snapshot in roster.iter().filter(|s| s.team_num == T || s.team_num == CT) {
+        teams.insert(snapshot.steamid, snapshot.team_num);
+        player_entry(&mut players, round, snapshot.steamid, &snapshot.name, snapshot.team_num);
+    }
+    // Players missing from the roster (joined late, no snapshot) get their team from the events
+    for event in events {
+        for prefix in ["user", "attacker", "assister"] {
+            if let (Some(steamid), Some(team)) = (event.steamid(prefix), event.int_field(&format!("{}_team_num", prefix))) {
+                let team = team as i32;
+                if (team == T || team == CT) && !teams.contains_key(&steamid) {
+                    teams.insert(steamid, team);
+                    player_entry(
+                        &mut players,
+                        round,
+                        steamid,
+                        &event.player_name(prefix).unwrap_or_default(),
+                        team,
+                    );
+                }
+            }
+        }
+    }
+    let mut alive: AHashSet<u64> = teams.keys().copied().collect();
+    let enemies = |a: u64, b: u64| matches!((teams.get(&a), teams.get(&b)), (Some(x), Some(y)) if x != y);
+    let label = |event: &GameEvent, name: &str| event.int_field(name) == Some(1);
+
+    for event in events {
+        let victim = event.steamid("user");
+        let attacker = event.steamid("attacker");
+        match event.name.as_str() {
+            "player_death" => {
+                let victim = match victim {
+                    Some(victim) if players.contains_key(&victim) => victim,
+                    _ => continue,
+                };
+                let v = players.get_mut(&victim).unwrap();
+                v.deaths += 1;
+                v.traded |= label(event, "traded");
+                alive.remove(&victim);
+                let killer = attacker.filter(|a| enemies(*a, victim) && players.contains_key(a));
+                if let Some(killer) = killer {
+                    let k = players.get_mut(&killer).unwrap();
+                    k.kills += 1;
+                    if event.int_field("headshot") == Some(1) {
+                        k.headshot_kills += 1;
+                    }
+                    if label(event, "opening_kill") {
+                        k.entry_kill = true;
+                        players.get_mut(&victim).unwrap().entry_death = true;
+                    }
+                }
+                if let Some(assister) = event.steamid("assister") {
+                    if enemies(assister, victim) {
+                        if let Some(a) = players.get_mut(&assister) {
+                            match event.int_field("assistedflash") == Some(1) {
+                                true => a.flash_assists += 1,
+                                false => a.assists += 1,
+                            }
+                        }
+                    }
+                }
+            }
+            "clutch" => {
+                if let Some(p) = victim.and_then(|v| players.get_mut(&v)) {
+                    p.clutch_enemies = event.int_field("enemies").map(|e| e as i32);
+                    p.clutch_won = label(event, "won");
+                }
+            }
+            "player_blind" => {
+                let attacker = match (victim, attacker) {
+                    (Some(v), Some(a)) if enemies(a, v) => a,
+                    _ => continue,
+                };
+                if let Some(a) = players.get_mut(&attacker) {
+                    a.enemies_flashed += 1;
+                    if let Some(Variant::F32(duration)) = event.field("blind_duration") {
+                        a.enemy_blind_time += duration;
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    // The ledger follows the health through every hit (team damage, fall damage...), only enemy hits count
+    for d in damage {
+        if let (Some(victim), Some(attacker)) = (d.victim_steamid, d.attacker_steamid) {
+            if enemies(attacker, victim) {
+                if let Some(a) = players.get_mut(&attacker) {
+                    a.damage += d.health_damage.max(0);
+                }
+            }
+        }
+    }
+    for (steamid, p) in players.iter_mut() {
+        p.survived = alive.contains(steamid);
+        p.kast = p.kills > 0 || p.assists > 0 || p.flash_assists > 0 || p.survived || p.traded;
+    }
+    players
+}
+
+fn player_entry(players: &mut BTreeMap<u64, PlayerRoundStats>, round: &Round, steamid: u64, name: &str, team: i32) {
+    players.entry(steamid).or_insert_with(|| PlayerRoundStats {
+        round: round.round,
+        steamid,
+        name: name.to_string(),
+        side: Some(if team == T { "T" } else { "CT" }.to_string()),
+        ..Default::default()
+    });
+}
+
+fn totals(rounds: &[PlayerRoundStats]) -> Vec<PlayerStats> {
+    let mut players: BTreeMap<u64, PlayerStats> = BTreeMap::default();
+    let mut kast_rounds: AHashMap<u64, i32> = AHashMap::default();
+    for r in rounds {
+        let p = players.entry(r.steamid).or_insert_with(|| PlayerStats {
+            steamid: r.steamid,
+            ..Default::default()
+        });
+        // Latest name
+        p.name = r.name.clone();
+        p.rounds_played += 1;
+        p.kills += r.kills;
+        p.deaths += r.deaths;
+        p.assists += r.assists;
+        p.flash_assists += r.flash_assists;
+        p.headshot_kills += r.headshot_kills;
+        p.damage += r.damage;
+        p.entry_kills += r.entry_kill as i32;
+        p.entry_deaths += r.entry_death as i32;
+        match r.kills {
+            0 => {}
+            1 => p.one_kill_rounds += 1,
+            2 => p.two_kill_rounds += 1,
+            3 => p.three_kill_rounds += 1,
+            4 => p.four_kill_rounds += 1,
+            _ => p.five_kill_rounds += 1,
+        }
+        p.clutches_attempted += r.clutch_enemies.is_some() as i32;
+        p.clutches_won += r.clutch_won as i32;
+        p.utility_damage += r.utility_damage;
+        p.enemies_flashed += r.enemies_flashed;
+        p.mvps += r.mvp as i32;
+        *kast_rounds.entry(r.steamid).or_default() += r.kast as i32;
+    }
+    let mut players: Vec<PlayerStats> = players.into_values().collect();
+    for p in &mut players {
+        let rounds = p.rounds_played as f32;
+        p.adr = p.damage as f32 / rounds;
+        p.kast = kast_rounds[&p.steamid] as f32 / rounds * 100.0;
+        p.headshot_pct = match p.kills {
+            0 => 0.0,
+            kills => p.headshot_kills as f32 / kills as f32 * 100.0,
+        };
+        p.rating = rating(p);
+    }
+    players.sort_by(|a, b| b.rating.total_cmp(&a.rating).then(a.steamid.cmp(&b.steamid)));
+    players
+}
+
+// https://www.hltv.org/news/10697/hltv-rating-explained, averages are from the 1.0 definition
+fn rating(p: &PlayerStats) -> f32 {
+    let rounds = p.rounds_played as f32;
+    let kill_rating = p.kills as f32 / rounds / 0.679;
+    let survival_rating = (rounds - p.deaths as f32) / rounds / 0.317;
+    let multi_kills =
+        p.one_kill_rounds + 4 * p.two_kill_rounds + 9 * p.three_kill_rounds + 16 * p.four_kill_rounds + 25 * p.five_kill_rounds;
+    let multi_kill_rating = multi_kills as f32 / rounds / 1.277;
+    (kill_rating + 0.7 * survival_rating + multi_kill_rating) / 2.7
+}
+
+fn roster_tick(round: &Round) -> i32 {
+    round.freeze_end_tick.unwrap_or(round.start_tick)
+}
+
+fn end_tick(round: &Round) -> Option<i32> {
+    round.officially_ended_tick.or(round.end_tick)
+}
+
+// Rows of the player tick output
+fn snapshots_from_output(output: &DemoOutput) -> Vec<PlayerSnapshot> {
+    let column = |name: &str| {
+        output
+            .prop_info
+            .prop_infos
+            .iter()
+            .find(|info| info.prop_friendly_name == name)
+            .and_then(|info| output.df.get(&info.id))
+    };
+    let (ticks, steamids) = match (column("tick"), column("steamid")) {
+        (Some(ticks), Some(steamids)) => (ticks, steamids),
+        _ => return vec![],
+    };
+    let int_at = |name: &str, idx: usize| column(name).and_then(|c| c.get(idx)).and_then(|v| variant_to_i64(&v));
+    let mut snapshots = vec![];
+    for idx in 0..ticks.len() {
+        let (tick, steamid) = match (ticks.get(idx), steamids.get(idx)) {
+            (Some(Variant::I32(tick)), Some(Variant::U64(steamid))) => (tick, steamid),
+            _ => continue,
+        };
+        snapshots.push(PlayerSnapshot {
+            tick,
+            steamid,
+            name: match column("name").and_then(|c| c.get(idx)) {
+                Some(Variant::String(name)) => name,
+                _ => String::new(),
+            },
+            team_num: int_at("team_num", idx).unwrap_or(0) as i32,
+            mvps: int_at("mvps", idx).unwrap_or(0) as i32,
+            utility_damage_this_round: int_at("utility_damage_this_round", idx).unwrap_or(0) as i32,
+        });
+    }
+    snapshots
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game_events::test_event as event;
+    use crate::game_events::test_hurt as hurt;
+    use crate::game_events::test_kill as kill;
+    use crate::game_events::test_player_snapshots as snapshots;
+    use crate::game_events::test_round as round;
+    use crate::game_events::test_steamid as steamid;
+
+    const A1: u64 = 1;
+    const A2: u64 = 2;
+    const B1: u64 = 11;
+    const B2: u64 = 12;
+    const ROSTER: [(u64, i32, i32); 4] = [(A1, T, 0), (A2, T, 0), (B1, CT, 0), (B2, CT, 0)];
+
+    // As kill_labels::label_kills adds them
+    fn labels(opening_kill: bool, trade: bool, traded: bool) -> Vec<(&'static str, Variant)> {
+        vec![
+            ("opening_kill", Variant::Bool(opening_kill)),
+            ("trade", Variant::Bool(trade)),
+            ("traded", Variant::Bool(traded)),
+        ]
+    }
+    // One round from tick 0 with the roster at freeze end and at the end
+    fn one_round(events: Vec<GameEvent>) -> AHashMap<u64, PlayerRoundStats> {
+        let mut all_snapshots = snapshots(10, &ROSTER);
+        all_snapshots.extend(snapshots(990, &ROSTER));
+        let stats = compute_stats(&[round(1, 0, Some("T"))], &events, &all_snapshots);
+        stats.rounds.into_iter().map(|r| (r.steamid, r)).collect()
+    }
+
+    #[test]
+    fn test_damage_is_clamped_and_only_from_enemies() {
+        let r = one_round(vec![
+            // Fall damage, A2 can only do the 70 that are left
+            hurt(250, 0, B2, 30, 70, vec![]),
+            hurt(300, A2, B2, 50, 20, vec![]),
+            hurt(310, A2, B2, 50, 0, vec![]),
+            // Team damage
+            hurt(400, A1, A2, 10, 90, vec![]),
+        ]);
+        assert_eq!(r[&A2].damage, 70);
+        assert_eq!(r[&A1].damage, 0);
+    }
+    #[test]
+    fn test_entry_and_traded_death() {
+        // A1 opens on B1, B2 trades A1
+        let r = one_round(vec![
+            kill(
+                100,
+                A1,
+                B1,
+                [vec![("headshot", Variant::Bool(true))], labels(true, false, true)].concat(),
+            ),
+            kill(200, B2, A1, labels(false, true, false)),
+        ]);
+        assert_eq!((r[&A1].kills, r[&A1].headshot_kills, r[&A1].entry_kill), (1, 1, true));
+        // Died but was traded
+        assert!(r[&B1].entry_death && r[&B1].traded && !r[&B1].survived && r[&B1].kast);
+        assert!(!r[&A1].traded && r[&A1].kast);
+        assert!(r[&A2].survived && r[&A2].kast);
+    }
+    #[test]
+    fn test_clutch_lost() {
+        let r = one_round(vec![
+            kill(100, A1, B1, labels(true, false, false)),
+            event(
+                "clutch",
+                100,
+                vec![
+                    ("user_steamid", steamid(B2)),
+                    ("enemies", Variant::I32(2)),
+                    ("won", Variant::Bool(false)),
+                ],
+            ),
+            kill(200, A2, B2, labels(false, false, false)),
+        ]);
+        assert_eq!((r[&B2].clutch_enemies, r[&B2].clutch_won), (Some(2), false));
+        assert_eq!(r[&A1].clutch_enemies, None);
+    }
+    #[test]
+    fn test_bots_left_out() {
+        let mut roster = ROSTER.to_vec();
+        roster.push((0, T, 0));
+        let all_snapshots = snapshots(10, &roster);
+        let events = vec![kill(100, 0, B1, labels(true, false, false))];
+        let stats = compute_stats(&[round(1, 0, Some("T"))], &events, &all_snapshots);
+        assert_eq!(stats.players.len(), 4);
+        let b1 = stats.rounds.iter().find(|r| r.steamid == B1).unwrap();
+        assert_eq!((b1.deaths, b1.entry_death), (1, false));
+    }
+    #[test]
+    fn test_events_between_round_and_backup_ignored() {
+        // Round 2 started at 1000 and was replayed from a backup at 2000
+        let rounds = vec![round(1, 0, Some("T")), round(2, 2000, Some("CT"))];
+        let events = vec![
+            kill(1500, A1, B1, labels(true, false, false)),
+            kill(2100, B1, A1, labels(true, false, false)),
+        ];
+        let mut all_snapshots = snapshots(10, &ROSTER);
+        all_snapshots.extend(snapshots(2010, &ROSTER));
+        let stats = compute_stats(&rounds, &events, &all_snapshots);
+        let a1 = stats.players.iter().find(|p| p.steamid == A1).unwrap();
+        assert_eq!((a1.rounds_played, a1.kills, a1.deaths), (2, 0, 1));
+    }
+    #[test]
+    fn test_mvp_from_mvp_count_increase() {
+        let rounds = vec![round(1, 0, Some("T")), round(2, 1000, Some("CT"))];
+        let mut all_snapshots = snapshots(10, &ROSTER);
+        all_snapshots.extend(snapshots(990, &[(A1, T, 0), (A2, T, 1), (B1, CT, 0), (B2, CT, 0)]));
+        all_snapshots.extend(snapshots(1010, &[(A1, T, 0), (A2, T, 1), (B1, CT, 0), (B2, CT, 0)]));
+        all_snapshots.extend(snapshots(1990, &[(A1, T, 0), (A2, T, 1), (B1, CT, 1), (B2, CT, 0)]));
+        let stats = compute_stats(&rounds, &[], &all_snapshots);
+        let mvps: Vec<(i32, u64)> = stats.rounds.iter().filter(|r| r.mvp).map(|r| (r.round, r.steamid)).collect();
+        assert_eq!(mvps, vec![(1, A2), (2, B1)]);
+    }
+    #[test]
+    fn test_match_totals() {
+        let rounds = vec![round(1, 0, Some("T")), round(2, 1000, Some("CT"))];
+        let events = vec![
+            hurt(100, A1, B1, 100, 0, vec![]),
+            kill(100, A1, B1, labels(true, false, false)),
+            kill(1100, B1, A1, labels(true, false, false)),
+            kill(
+                1200,
+                B1,
+                A2,
+                [vec![("headshot", Variant::Bool(true))], labels(false, false, false)].concat(),
+            ),
+        ];
+        let mut all_snapshots = snapshots(10, &ROSTER);
+        all_snapshots.extend(snapshots(1010, &ROSTER));
+        let stats = compute_stats(&rounds, &events, &all_snapshots);
+        let b1 = stats.players.iter().find(|p| p.steamid == B1).unwrap();
+        assert_eq!(
+            (b1.rounds_played, b1.kills, b1.deaths, b1.two_kill_rounds, b1.entry_kills),
+            (2, 2, 1, 1, 1)
+        );
+        assert_eq!((b1.headshot_pct, b1.kast), (50.0, 50.0));
+        let a1 = stats.players.iter().find(|p| p.steamid == A1).unwrap();
+        assert_eq!(a1.adr, 50.0);
+        assert_eq!(stats.players[0].steamid, B1);
+    }
+    #[test]
+    fn test_parse_stats_reads_controllers_at_round_end() {
+        use crate::query::DemoQuery;
+        use crate::test_demo::TestDemo;
+        use crate::variants::BytesVariant;
+
+        let (alice, bob) = (76561198000000001, 76561198000000002);
+        let mut demo = TestDemo::new();
+        demo.add_teams_and_rules();
+        demo.add_player(1, alice, "alice", T);
+        demo.add_player(2, bob, "bob", CT);
+        demo.packet(1);
+        demo.full_packet(2);
+        demo.event("round_start", &[]);
+        demo.packet(10);
+        demo.event("round_freeze_end", &[]);
+        demo.packet(20);
+        demo.event(
+            "player_hurt",
+            &[("userid", 2.into()), ("attacker", 1.into()), ("dmg_health", 100.into())],
+        );
+        demo.event("player_death", &[("userid", 2.into()), ("attacker", 1.into())]);
+        demo.set(2, "m_pActionTrackingServices.m_perRoundStats.m_iUtilityDamage", 25);
+        demo.set(102, "m_iHealth", 0);
+        demo.packet(30);
+        demo.event("round_end", &[("winner", T.into()), ("reason", 9.into())]);
+        demo.set(2, "m_iMVPs", 1);
+        demo.packet(40);
+        demo.event("round_officially_ended", &[]);
+        demo.packet(50);
+        let bytes = demo.finish(60);
+
+        let stats = DemoQuery::new()
+            .parser(BytesVariant::Vec(bytes))
+            .unwrap()
+            .parse_stats()
+            .unwrap();
+        let alice_round = stats.rounds.iter().find(|r| r.steamid == alice).unwrap();
+        assert_eq!(
+            (alice_round.kills, alice_round.utility_damage, alice_round.mvp),
+            (1, 25, true)
+        );
+        let bob_round = stats.rounds.iter().find(|r| r.steamid == bob).unwrap();
+        assert_eq!((bob_round.deaths, bob_round.mvp), (1, false));
+    }
+}
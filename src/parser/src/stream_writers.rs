@@ -0,0 +1,314 @@
+use crate::callbacks::ParserCallbacks;
+use crate::game_events::GameEvent;
+use crate::parser::DemoOutput;
+use crate::parser_settings::Parser;
+use crate::prop_controller::PropInfo;
+use crate::read_bits::DemoParserError;
+use crate::variants::Variant;
+use std::cell::RefCell;
+use std::io;
+use std::io::Write;
+use std::ops::ControlFlow;
+use std::rc::Rc;
+
+// Writes events and player ticks while the demo is being parsed, one line per row, so memory use
+// stays flat no matter how long the demo is:
+//
+// let mut parser = DemoQuery::new().player_props(["X", "Y"]).parser_from_path("match.dem")?;
+// parser.stream_ticks(BufWriter::new(std::io::stdout()), StreamFormat::Ndjson)?;
+//
+// Tick rows are one player at one tick with a column for every PropInfo in prop_controller.prop_infos,
+// the same columns (and order) DemoOutput.df would have.
+// Event rows are "event_name", "tick" and then the event fields in the order the parser adds them.
+// CSV needs one header for the whole file. Events of one type all have the same fields, so it comes
+// from the first event. Streams with more than one event type are rejected for CSV, use NDJSON or
+// one file per event type.
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StreamFormat {
+    Csv,
+    Ndjson,
+}
+
+pub struct StreamWriter<W: Write> {
+    out: W,
+    format: StreamFormat,
+    columns: Option<Vec<String>>,
+    // Event type the CSV header was made from
+    event_name: Option<String>,
+    pub rows_written: usize,
+    // First write error, the parse is stopped when one happens
+    pub error: Option<io::Error>,
+}
+
+impl<W: Write> StreamWriter<W> {
+    pub fn new(out: W, format: StreamFormat) -> Self {
+        StreamWriter {
+            out,
+            format,
+            columns: None,
+            event_name: None,
+            rows_written: 0,
+            error: None,
+        }
+    }
+    // Only the first call does anything, the CSV header is written here.
+    pub fn set_columns(&mut self, columns: Vec<String>) -> io::Result<()> {
+        if self.columns.is_some() {
+            return Ok(());
+        }
+        if self.format == StreamFormat::Csv {
+            let header: Vec<String> = columns.iter().map(|c| csv_escape(c)).collect();
+            writeln!(self.out, "{}", header.join(","))?;
+        }
+        self.columns = Some(columns);
+        Ok(())
+    }
+    pub fn columns(&self) -> Option<&[String]> {
+        self.columns.as_deref()
+    }
+    // Values must be in the same order as the columns
+    pub fn write_row(&mut self, values: &[Option<&Variant>]) -> io::Result<()> {
+        let columns = match &self.columns {
+            Some(columns) => columns,
+            None => return Err(io::Error::other("columns must be set before writing rows")),
+        };
+        match self.format {
+            StreamFormat::Csv => {
+                let cells: Vec<String> = values.iter().map(|v| v.map(csv_value).unwrap_or_default()).collect();
+                writeln!(self.out, "{}", cells.join(","))?;
+            }
+            StreamFormat::Ndjson => writeln!(self.out, "{}", json_object(columns, values))?,
+        }
+        self.rows_written += 1;
+        Ok(())
+    }
+    pub fn write_event(&mut self, event: &GameEvent) -> io::Result<()> {
+        let name = Variant::String(event.name.clone());
+        let tick = Variant::I32(event.tick);
+        let mut columns = vec!["event_name".to_string(), "tick".to_string()];
+        let mut values = vec![Some(&name), Some(&tick)];
+        // "tick" is also added as a field by the parser
+        for field in event.fields.iter().filter(|f| f.name != "tick") {
+            columns.push(field.name.clone());
+            values.push(field.data.as_ref());
+        }
+        match self.format {
+            // Every line has exactly the fields of its own event
+            StreamFormat::Ndjson => {
+                self.set_columns(columns.clone())?;
+                writeln!(self.out, "{}", json_object(&columns, &values))?;
+                self.rows_written += 1;
+                Ok(())
+            }
+            StreamFormat::Csv => {
+                match &self.event_name {
+                    Some(first) if first != &event.name => {
+                        return Err(io::Error::other(format!(
+                            "CSV holds one event type, got {} after {}",
+                            event.name, first
+                        )));
+                    }
+                    Some(_) => {}
+                    None => self.event_name = Some(event.name.clone()),
+                }
+                self.set_columns(columns.clone())?;
+                let header = self.columns.as_deref().unwrap_or_default();
+                let row: Vec<Option<&Variant>> = header
+                    .iter()
+                    .map(|c| columns.iter().position(|x| x == c).and_then(|idx| values[idx]))
+                    .collect();
+                self.write_row(&row)
+            }
+        }
+    }
+    pub fn write_player_tick(&mut self, prop_infos: &[PropInfo], row: &[Option<Variant>]) -> io::Result<()> {
+        if self.columns.is_none() {
+            self.set_columns(prop_infos.iter().map(|p| p.prop_friendly_name.clone()).collect())?;
+        }
+        let values: Vec<Option<&Variant>> = row.iter().map(|v| v.as_ref()).collect();
+        self.write_row(&values)
+    }
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+    // For callbacks: remembers the error and stops the parse
+    fn continue_or_stop(&mut self, result: io::Result<()>) -> ControlFlow<()> {
+        match result {
+            Ok(()) => ControlFlow::Continue(()),
+            Err(e) => {
+                self.error.get_or_insert(e);
+                ControlFlow::Break(())
+            }
+        }
+    }
+}
+
+impl Parser {
+    // Wanted events (DemoQuery::events) are written as they come, DemoOutput.game_events stays empty.
+    pub fn stream_events<W: Write + 'static>(&mut self, out: W, format: StreamFormat) -> Result<DemoOutput, DemoParserError> {
+        let event_names = match self.wanted_events.iter().any(|e| e == "all") {
+            true => vec!["all".to_string()],
+            false => self.wanted_events.clone(),
+        };
+        if format == StreamFormat::Csv && (event_names.len() != 1 || event_names[0] == "all") {
+            return Err(DemoParserError::WriteError(format!(
+                "CSV holds one event type, got {:?}. Use NDJSON or stream one event at a time",
+                event_names
+            )));
+        }
+        self.stream_with(StreamWriter::new(out, format), |callbacks, writer| {
+            for event_name in &event_names {
+                let writer = writer.clone();
+                callbacks.on_event(event_name, move |event| {
+                    let mut writer = writer.borrow_mut();
+                    let result = writer.write_event(event);
+                    writer.continue_or_stop(result)
+                });
+            }
+        })
+    }
+    // Player props are written every tick (or every wanted tick) instead of being collected into DemoOutput.df.
+    pub fn stream_ticks<W: Write + 'static>(&mut self, out: W, format: StreamFormat) -> Result<DemoOutput, DemoParserError> {
+        self.stream_with(StreamWriter::new(out, format), |callbacks, writer| {
+            callbacks.on_player_tick(move |prop_infos, row| {
+                let mut writer = writer.borrow_mut();
+                let result = writer.write_player_tick(prop_infos, row);
+                writer.continue_or_stop(result)
+            });
+        })
+    }
+    fn stream_with<W, F>(&mut self, writer: StreamWriter<W>, register: F) -> Result<DemoOutput, DemoParserError>
+    where
+        W: Write + 'static,
+        F: FnOnce(&mut ParserCallbacks, Rc<RefCell<StreamWriter<W>>>),
+    {
+        let writer = Rc::new(RefCell::new(writer));
+        let mut callbacks = ParserCallbacks::new();
+        register(&mut callbacks, writer.clone());
+        let output = self.parse_demo_with_callbacks(callbacks)?;
+        let mut writer = writer.borrow_mut();
+        if let Some(e) = writer.error.take() {
+            return Err(DemoParserError::WriteError(format!("{}", e)));
+        }
+        if let Err(e) = writer.flush() {
+            return Err(DemoParserError::WriteError(format!("{}", e)));
+        }
+        Ok(output)
+    }
+}
+
+pub fn json_value(v: &Variant) -> String {
+    // Variant's Serialize does not handle vectors
+    let s = match v {
+        Variant::VecXY(xy) => serde_json::to_string(xy),
+        Variant::VecXYZ(xyz) => serde_json::to_string(xyz),
+        _ => serde_json::to_string(v),
+    };
+    s.unwrap_or_else(|_| "null".to_string())
+}
+
+// Written by hand to keep the column order
+pub fn json_object(columns: &[String], values: &[Option<&Variant>]) -> String {
+    let fields: Vec<String> = columns
+        .iter()
+        .zip(values)
+        .map(|(name, v)| {
+            let value = v.map(json_value).unwrap_or_else(|| "null".to_string());
+            format!("{}:{}", serde_json::to_string(name).unwrap_or_default(), value)
+        })
+        .collect();
+    format!("{{{}}}", fields.join(","))
+}
+
+pub fn csv_value(v: &Variant) -> String {
+    match v {
+        Variant::Bool(b) => b.to_string(),
+        Variant::U32(x) => x.to_string(),
+        Variant::I32(x) => x.to_string(),
+        Variant::I16(x) => x.to_string(),
+        Variant::F32(x) => x.to_string(),
+        Variant::U64(x) => x.to_string(),
+        Variant::U8(x) => x.to_string(),
+        Variant::String(s) => csv_escape(s),
+        // Lists as json arrays in one cell
+        Variant::VecXY(_) | Variant::VecXYZ(_) | Variant::StringVec(_) | Variant::U64Vec(_) => csv_escape(&json_value(v)),
+    }
+}
+
+fn csv_escape(s: &str) -> String {
+    if s.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game_events::test_event as event;
+    use crate::query::DemoQuery;
+    use crate::variants::BytesVariant;
+
+    #[test]
+    fn test_csv_events_use_first_header() {
+        let mut writer = StreamWriter::new(vec![], StreamFormat::Csv);
+        writer
+            .write_event(&event(
+                "player_death",
+                5,
+                vec![("tick", Variant::I32(5)), ("weapon", Variant::String("ak47".to_string()))],
+            ))
+            .unwrap();
+        writer
+            .write_event(&event(
+                "player_death",
+                9,
+                vec![
+                    ("headshot", Variant::Bool(true)),
+                    ("weapon", Variant::String("a,b".to_string())),
+                ],
+            ))
+            .unwrap();
+        let out = String::from_utf8(writer.into_inner()).unwrap();
+        assert_eq!(out, "event_name,tick,weapon\nplayer_death,5,ak47\nplayer_death,9,\"a,b\"\n");
+    }
+    #[test]
+    fn test_csv_rejects_second_event_type() {
+        let mut writer = StreamWriter::new(vec![], StreamFormat::Csv);
+        writer.write_event(&event("player_death", 5, vec![])).unwrap();
+        assert!(writer.write_event(&event("player_hurt", 6, vec![])).is_err());
+        assert_eq!(writer.rows_written, 1);
+
+        let mut parser = DemoQuery::new()
+            .events(["player_death", "player_hurt"])
+            .parser(BytesVariant::Vec(vec![]))
+            .unwrap();
+        assert!(matches!(
+            parser.stream_events(vec![], StreamFormat::Csv),
+            Err(DemoParserError::WriteError(_))
+        ));
+    }
+    #[test]
+    fn test_ndjson_keeps_column_order() {
+        let mut writer = StreamWriter::new(vec![], StreamFormat::Ndjson);
+        writer
+            .set_columns(vec!["tick".to_string(), "name".to_string(), "X".to_string()])
+            .unwrap();
+        let tick = Variant::I32(64);
+        let name = Variant::String("s1mple".to_string());
+        writer.write_row(&[Some(&tick), Some(&name), None]).unwrap();
+        let out = String::from_utf8(writer.into_inner()).unwrap();
+        assert_eq!(out, "{\"tick\":64,\"name\":\"s1mple\",\"X\":null}\n");
+    }
+    #[test]
+    fn test_row_before_columns_fails() {
+        let mut writer = StreamWriter::new(vec![], StreamFormat::Csv);
+        assert!(writer.write_row(&[None]).is_err());
+        assert_eq!(writer.rows_written, 0);
+    }
+}
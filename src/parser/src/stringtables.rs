@@ -5,9 +5,10 @@ use csgoproto::{
     networkbasetypes::CMsgPlayerInfo,
 };
 use protobuf::Message;
+use serde::{Deserialize, Serialize};
 use snap::raw::Decoder;
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct StringTable {
     name: String,
     user_data_size: i32,
@@ -17,13 +18,13 @@ pub struct StringTable {
     flags: i32,
     var_bit_counts: bool,
 }
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct StringTableEntry {
     pub idx: i32,
     pub key: String,
     pub value: Vec<u8>,
 }
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct UserInfo {
     pub steamid: u64,
     pub name: String,
@@ -33,7 +34,10 @@ pub struct UserInfo {
 
 impl Parser {
     pub fn update_string_table(&mut self, bytes: &[u8]) -> Result<(), DemoParserError> {
-        let table: CSVCMsg_UpdateStringTable = Message::parse_from_bytes(&bytes).unwrap();
+        let table: CSVCMsg_UpdateStringTable = match Message::parse_from_bytes(&bytes) {
+            Ok(table) => table,
+            Err(_e) => return Err(DemoParserError::MalformedMessage),
+        };
         match self.string_tables.get(table.table_id() as usize) {
             Some(st) => self.parse_string_table(
                 table.string_data().to_vec(),
@@ -50,14 +54,20 @@ impl Parser {
     }
 
     pub fn parse_create_stringtable(&mut self, bytes: &[u8]) -> Result<(), DemoParserError> {
-        let table: CSVCMsg_CreateStringTable = Message::parse_from_bytes(&bytes).unwrap();
+        let table: CSVCMsg_CreateStringTable = match Message::parse_from_bytes(&bytes) {
+            Ok(table) => table,
+            Err(_e) => return Err(DemoParserError::MalformedMessage),
+        };
 
         if !(table.name() == "instancebaseline" || table.name() == "userinfo") {
             return Ok(());
         }
 
         let bytes = match table.data_compressed() {
-            true => snap::raw::Decoder::new().decompress_vec(table.string_data()).unwrap(),
+            true => match snap::raw::Decoder::new().decompress_vec(table.string_data()) {
+                Ok(b) => b,
+                Err(e) => return Err(DemoParserError::DecompressionFailure(format!("{}", e))),
+            },
             false => table.string_data().to_vec(),
         };
         self.parse_string_table(
@@ -144,7 +154,10 @@ impl Parser {
                     }
                     value = bitreader.read_n_bytes((bits / 8) as usize)?;
                     value = if is_compressed {
-                        Decoder::new().decompress_vec(&value).unwrap()
+                        match Decoder::new().decompress_vec(&value) {
+                            Ok(b) => b,
+                            Err(e) => return Err(DemoParserError::DecompressionFailure(format!("{}", e))),
+                        }
                     } else {
                         value
                     };
@@ -195,7 +208,10 @@ pub fn parse_userinfo(bytes: &[u8]) -> Result<UserInfo, DemoParserError> {
 
 impl ParserThread {
     pub fn update_string_table(&mut self, bytes: &[u8]) -> Result<(), DemoParserError> {
-        let table: CSVCMsg_UpdateStringTable = Message::parse_from_bytes(&bytes).unwrap();
+        let table: CSVCMsg_UpdateStringTable = match Message::parse_from_bytes(&bytes) {
+            Ok(table) => table,
+            Err(_e) => return Err(DemoParserError::MalformedMessage),
+        };
         match self.string_tables.get(table.table_id() as usize) {
             Some(st) => self.parse_string_table(
                 table.string_data().to_vec(),
@@ -214,9 +230,15 @@ impl ParserThread {
     }
 
     pub fn parse_create_stringtable(&mut self, bytes: &[u8]) -> Result<(), DemoParserError> {
-        let table: CSVCMsg_CreateStringTable = Message::parse_from_bytes(&bytes).unwrap();
+        let table: CSVCMsg_CreateStringTable = match Message::parse_from_bytes(&bytes) {
+            Ok(table) => table,
+            Err(_e) => return Err(DemoParserError::MalformedMessage),
+        };
         let bytes = match table.data_compressed() {
-            true => snap::raw::Decoder::new().decompress_vec(table.string_data()).unwrap(),
+            true => match snap::raw::Decoder::new().decompress_vec(table.string_data()) {
+                Ok(b) => b,
+                Err(e) => return Err(DemoParserError::DecompressionFailure(format!("{}", e))),
+            },
             false => table.string_data().to_vec(),
         };
         self.parse_string_table(
@@ -303,7 +325,10 @@ impl ParserThread {
                     }
                     value = bitreader.read_n_bytes((bits / 8) as usize)?;
                     value = if is_compressed {
-                        Decoder::new().decompress_vec(&value).unwrap()
+                        match Decoder::new().decompress_vec(&value) {
+                            Ok(b) => b,
+                            Err(e) => return Err(DemoParserError::DecompressionFailure(format!("{}", e))),
+                        }
                     } else {
                         value
                     };
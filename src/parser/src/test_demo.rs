@@ -0,0 +1,711 @@
+// Writes small synthetic demos for tests. Only has the classes and events the tests need: player controllers
+// and pawns, teams, the game rules and chickens. Entity ids and userids are chosen by the caller, players
+// get their controller at userid + 1 and their pawn at userid + 100 (see add_player).
+//
+// Only depends on csgoproto and protobuf so main.rs can include it too.
+
+use csgoproto::demo::cdemo_class_info::Class_t;
+use csgoproto::demo::cdemo_string_tables::{Items_t, Table_t};
+use csgoproto::demo::{CDemoClassInfo, CDemoFileHeader, CDemoFullPacket, CDemoPacket, CDemoSendTables, CDemoStringTables};
+use csgoproto::netmessages::csvcmsg_game_event_list::{Descriptor_t, Key_t as DescriptorKey};
+use csgoproto::netmessages::{
+    CSVCMsg_FlattenedSerializer, CSVCMsg_GameEventList, CSVCMsg_PacketEntities, ProtoFlattenedSerializerField_t,
+    ProtoFlattenedSerializer_t,
+};
+use csgoproto::networkbasetypes::csvcmsg_game_event::Key_t;
+use csgoproto::networkbasetypes::{CMsgPlayerInfo, CSVCMsg_GameEvent};
+use csgoproto::usermessages::CUserMessageSayText2;
+use protobuf::{Message, MessageField};
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+
+pub const DEM_STOP: u32 = 0;
+pub const DEM_FILE_HEADER: u32 = 1;
+pub const DEM_SYNC_TICK: u32 = 3;
+pub const DEM_SEND_TABLES: u32 = 4;
+pub const DEM_CLASS_INFO: u32 = 5;
+pub const DEM_PACKET: u32 = 7;
+pub const DEM_SIGNON_PACKET: u32 = 8;
+pub const DEM_FULL_PACKET: u32 = 13;
+pub const DEM_ANIMATION_DATA: u32 = 16;
+
+const SVC_PACKET_ENTITIES: u32 = 55;
+const UM_SAY_TEXT2: u32 = 118;
+const GE_GAME_EVENT_LIST: u32 = 205;
+const GE_GAME_EVENT: u32 = 207;
+
+// Child serializers come before the classes that point to them, same as in real sendtables
+const SERIALIZERS: &[(&str, &[(&str, &str)])] = &[
+    (
+        "CCSPlayerController_InGameMoneyServices",
+        &[
+            ("m_iAccount", "int32"),
+            ("m_iStartAccount", "int32"),
+            ("m_iCashSpentThisRound", "int32"),
+        ],
+    ),
+    (
+        "CSPerRoundStats_t",
+        &[
+            ("m_iKills", "int32"),
+            ("m_iDamage", "int32"),
+            ("m_iUtilityDamage", "int32"),
+            ("m_iCashEarned", "int32"),
+        ],
+    ),
+    (
+        "CCSPlayerController_ActionTrackingServices",
+        &[("m_perRoundStats", "CSPerRoundStats_t*")],
+    ),
+    (
+        "CCSPlayerController",
+        &[
+            ("m_iTeamNum", "uint8"),
+            ("m_iszPlayerName", "char[128]"),
+            ("m_steamID", "uint64"),
+            ("m_hPlayerPawn", "CHandle< CCSPlayerPawn >"),
+            ("m_iMVPs", "int32"),
+            ("m_pInGameMoneyServices", "CCSPlayerController_InGameMoneyServices*"),
+            ("m_pActionTrackingServices", "CCSPlayerController_ActionTrackingServices*"),
+        ],
+    ),
+    (
+        "CCSPlayerPawn",
+        &[
+            ("m_iTeamNum", "uint8"),
+            ("m_iHealth", "int32"),
+            ("m_ArmorValue", "int32"),
+            ("m_unCurrentEquipmentValue", "uint16"),
+        ],
+    ),
+    (
+        "CCSTeam",
+        &[
+            ("m_iTeamNum", "uint8"),
+            ("m_iScore", "int32"),
+            ("m_szClanTeamname", "char[129]"),
+        ],
+    ),
+    (
+        "CCSGameRules",
+        &[
+            ("m_totalRoundsPlayed", "int32"),
+            ("m_bFreezePeriod", "bool"),
+            ("m_bWarmupPeriod", "bool"),
+            ("m_iRoundWinStatus", "int32"),
+            ("m_iNumConsecutiveCTLoses", "int32"),
+            ("m_iNumConsecutiveTerroristLoses", "int32"),
+        ],
+    ),
+    ("CCSGameRulesProxy", &[("m_pGameRules", "CCSGameRules*")]),
+    ("CChicken", &[("m_iHealth", "int32")]),
+];
+// Class id = position
+const CLASSES: &[&str] = &[
+    "CCSGameRulesProxy",
+    "CCSPlayerController",
+    "CCSPlayerPawn",
+    "CCSTeam",
+    "CChicken",
+];
+
+pub const EVENT_STRING: i32 = 1;
+pub const EVENT_FLOAT: i32 = 2;
+pub const EVENT_LONG: i32 = 3;
+pub const EVENT_SHORT: i32 = 4;
+pub const EVENT_BYTE: i32 = 5;
+pub const EVENT_BOOL: i32 = 6;
+
+// Event id = position
+const EVENTS: &[(&str, &[(&str, i32)])] = &[
+    ("begin_new_match", &[]),
+    ("round_start", &[("timelimit", EVENT_LONG)]),
+    ("round_freeze_end", &[]),
+    ("round_end", &[("winner", EVENT_BYTE), ("reason", EVENT_BYTE)]),
+    ("round_officially_ended", &[]),
+    ("announce_phase_end", &[]),
+    ("bomb_planted", &[("userid", EVENT_SHORT)]),
+    ("bomb_defused", &[("userid", EVENT_SHORT)]),
+    (
+        "player_death",
+        &[
+            ("userid", EVENT_SHORT),
+            ("attacker", EVENT_SHORT),
+            ("assister", EVENT_SHORT),
+            ("assistedflash", EVENT_BOOL),
+            ("headshot", EVENT_BOOL),
+            ("weapon", EVENT_STRING),
+        ],
+    ),
+    (
+        "player_hurt",
+        &[
+            ("userid", EVENT_SHORT),
+            ("attacker", EVENT_SHORT),
+            ("health", EVENT_BYTE),
+            ("armor", EVENT_BYTE),
+            ("weapon", EVENT_STRING),
+            ("dmg_health", EVENT_SHORT),
+            ("dmg_armor", EVENT_BYTE),
+            ("hitgroup", EVENT_BYTE),
+        ],
+    ),
+    (
+        "player_blind",
+        &[
+            ("userid", EVENT_SHORT),
+            ("attacker", EVENT_SHORT),
+            ("blind_duration", EVENT_FLOAT),
+        ],
+    ),
+    (
+        "item_purchase",
+        &[("userid", EVENT_SHORT), ("team", EVENT_SHORT), ("weapon", EVENT_STRING)],
+    ),
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Int(i64),
+    Float(f32),
+    Bool(bool),
+    Str(String),
+}
+impl From<i32> for Value {
+    fn from(v: i32) -> Self {
+        Value::Int(v as i64)
+    }
+}
+impl From<u64> for Value {
+    fn from(v: u64) -> Self {
+        Value::Int(v as i64)
+    }
+}
+impl From<f32> for Value {
+    fn from(v: f32) -> Self {
+        Value::Float(v)
+    }
+}
+impl From<bool> for Value {
+    fn from(v: bool) -> Self {
+        Value::Bool(v)
+    }
+}
+impl From<&str> for Value {
+    fn from(v: &str) -> Self {
+        Value::Str(v.to_string())
+    }
+}
+
+#[derive(Default)]
+pub struct BitWriter {
+    pub bytes: Vec<u8>,
+    n_bits: usize,
+}
+
+impl BitWriter {
+    pub fn bit(&mut self, b: bool) {
+        if self.n_bits == self.bytes.len() * 8 {
+            self.bytes.push(0);
+        }
+        if b {
+            *self.bytes.last_mut().unwrap() |= 1 << (self.n_bits % 8);
+        }
+        self.n_bits += 1;
+    }
+    // Lowest bit first, the order Bitreader::read_nbits reads them in
+    pub fn bits(&mut self, value: u64, n: u32) {
+        for i in 0..n {
+            self.bit(value >> i & 1 == 1);
+        }
+    }
+    // Huffman codes, first bit first
+    pub fn code(&mut self, code: &str) {
+        for c in code.chars() {
+            self.bit(c == '1');
+        }
+    }
+    pub fn varint(&mut self, mut value: u64) {
+        while value >= 0x80 {
+            self.bits(value & 0x7F | 0x80, 8);
+            value >>= 7;
+        }
+        self.bits(value, 8);
+    }
+    pub fn ubitvar(&mut self, value: u32) {
+        let (tag, extra) = match value {
+            0..=15 => (0, 0),
+            16..=255 => (16, 4),
+            256..=4095 => (32, 8),
+            _ => (48, 28),
+        };
+        self.bits((value & 15 | tag) as u64, 6);
+        self.bits((value >> 4) as u64, extra);
+    }
+    pub fn ubitvar_fp(&mut self, value: u32) {
+        let (prefix, n) = match value {
+            0..=3 => ("1", 2),
+            4..=15 => ("01", 4),
+            16..=1023 => ("001", 10),
+            1024..=131071 => ("0001", 17),
+            _ => ("0000", 31),
+        };
+        self.code(prefix);
+        self.bits(value as u64, n);
+    }
+    pub fn bytes(&mut self, bytes: &[u8]) {
+        for b in bytes {
+            self.bits(*b as u64, 8);
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Kind {
+    Unsigned,
+    Signed,
+    Unsigned64,
+    Bool,
+    Str,
+}
+
+// Field of a class, flattened through the pointer fields
+#[derive(Debug, Clone)]
+struct SchemaField {
+    name: String,
+    path: Vec<i32>,
+    kind: Kind,
+}
+
+fn serializer_fields(name: &str) -> &'static [(&'static str, &'static str)] {
+    SERIALIZERS.iter().find(|(n, _)| *n == name).map(|(_, f)| *f).unwrap()
+}
+
+fn flatten(serializer: &str, prefix: &str, path: &[i32], out: &mut Vec<SchemaField>) {
+    for (idx, (var_name, var_type)) in serializer_fields(serializer).iter().enumerate() {
+        let mut field_path = path.to_vec();
+        field_path.push(idx as i32);
+        let name = format!("{}{}", prefix, var_name);
+        match var_type.strip_suffix('*') {
+            Some(child) => flatten(child, &format!("{}.", name), &field_path, out),
+            None => {
+                let kind = match *var_type {
+                    "bool" => Kind::Bool,
+                    "uint64" => Kind::Unsigned64,
+                    "int8" | "int16" | "int32" | "int64" => Kind::Signed,
+                    t if t.starts_with("char") => Kind::Str,
+                    _ => Kind::Unsigned,
+                };
+                out.push(SchemaField {
+                    name,
+                    path: field_path,
+                    kind,
+                });
+            }
+        }
+    }
+}
+
+fn class_fields(class: &str) -> Vec<SchemaField> {
+    let mut fields = vec![];
+    flatten(class, "", &[], &mut fields);
+    fields
+}
+
+#[derive(Debug, Clone)]
+struct TestEntity {
+    cls_id: usize,
+    props: BTreeMap<Vec<i32>, (Kind, Value)>,
+}
+
+#[derive(Debug, Clone)]
+enum Change {
+    Create,
+    Update(BTreeSet<Vec<i32>>),
+    Delete,
+}
+
+pub struct TestDemo {
+    out: Vec<u8>,
+    entities: BTreeMap<i32, TestEntity>,
+    changes: BTreeMap<i32, Change>,
+    messages: Vec<(u32, Vec<u8>)>,
+    // userid -> (steamid, name)
+    players: BTreeMap<i32, (u64, String)>,
+    left_out_of_full_packets: Vec<String>,
+}
+
+impl Default for TestDemo {
+    fn default() -> Self {
+        TestDemo::new()
+    }
+}
+
+impl TestDemo {
+    pub fn new() -> Self {
+        let mut demo = TestDemo {
+            out: b"PBDEMS2\0\0\0\0\0\0\0\0\0".to_vec(),
+            entities: BTreeMap::new(),
+            changes: BTreeMap::new(),
+            messages: vec![],
+            players: BTreeMap::new(),
+            left_out_of_full_packets: vec![],
+        };
+        let mut header = CDemoFileHeader::new();
+        header.demo_file_stamp = Some("PBDEMS2\0".to_string());
+        header.map_name = Some("de_test".to_string());
+        header.server_name = Some("test server".to_string());
+        demo.frame(DEM_FILE_HEADER, 0, &header.write_to_bytes().unwrap());
+        demo.frame(DEM_SEND_TABLES, 0, &sendtables().write_to_bytes().unwrap());
+        demo.frame(DEM_CLASS_INFO, 0, &class_info().write_to_bytes().unwrap());
+        demo.frame(
+            DEM_SIGNON_PACKET,
+            0,
+            &packet(&[(GE_GAME_EVENT_LIST, event_list().write_to_bytes().unwrap())]),
+        );
+        demo.frame(DEM_SYNC_TICK, 0, &[]);
+        demo
+    }
+    pub fn frame(&mut self, cmd: u32, tick: i32, bytes: &[u8]) {
+        let mut w = BitWriter::default();
+        w.varint(cmd as u64);
+        w.varint(tick as u32 as u64);
+        w.varint(bytes.len() as u64);
+        w.bytes(bytes);
+        self.out.extend(w.bytes);
+    }
+    pub fn create(&mut self, entity_id: i32, class: &str) {
+        let cls_id = CLASSES.iter().position(|c| *c == class).unwrap();
+        self.entities.insert(
+            entity_id,
+            TestEntity {
+                cls_id,
+                props: BTreeMap::new(),
+            },
+        );
+        self.changes.insert(entity_id, Change::Create);
+    }
+    // Prop by its var names, through the pointer fields: "m_pInGameMoneyServices.m_iAccount"
+    pub fn set(&mut self, entity_id: i32, prop: &str, value: impl Into<Value>) {
+        let entity = self.entities.get_mut(&entity_id).unwrap();
+        let field = class_fields(CLASSES[entity.cls_id])
+            .into_iter()
+            .find(|f| f.name == prop)
+            .unwrap_or_else(|| panic!("no prop {} in {}", prop, CLASSES[entity.cls_id]));
+        entity.props.insert(field.path.clone(), (field.kind, value.into()));
+        match self.changes.entry(entity_id).or_insert(Change::Update(BTreeSet::new())) {
+            Change::Update(paths) => {
+                paths.insert(field.path);
+            }
+            Change::Create => {}
+            Change::Delete => panic!("entity {} is deleted", entity_id),
+        }
+    }
+    pub fn delete(&mut self, entity_id: i32) {
+        self.entities.remove(&entity_id);
+        self.changes.insert(entity_id, Change::Delete);
+    }
+    // Full packets don't have this prop, like the props in NON_MULTITHREADABLE_PROPS in real demos
+    pub fn leave_out_of_full_packets(&mut self, prop: &str) {
+        self.left_out_of_full_packets.push(prop.to_string());
+    }
+    // Controller at userid + 1, pawn at userid + 100. The userinfo goes into the next full packet.
+    pub fn add_player(&mut self, userid: i32, steamid: u64, name: &str, team: i32) {
+        let (controller, pawn) = (userid + 1, userid + 100);
+        self.create(controller, "CCSPlayerController");
+        self.set(controller, "m_iTeamNum", team);
+        self.set(controller, "m_iszPlayerName", name);
+        self.set(controller, "m_steamID", steamid);
+        self.set(controller, "m_hPlayerPawn", pawn);
+        self.create(pawn, "CCSPlayerPawn");
+        self.set(pawn, "m_iTeamNum", team);
+        self.set(pawn, "m_iHealth", 100);
+        self.players.insert(userid, (steamid, name.to_string()));
+    }
+    // Teams at 50 + team number, rules at 40
+    pub fn add_teams_and_rules(&mut self) {
+        for team in [2, 3] {
+            self.create(50 + team, "CCSTeam");
+            self.set(50 + team, "m_iTeamNum", team);
+            self.set(50 + team, "m_iScore", 0);
+        }
+        self.create(40, "CCSGameRulesProxy");
+        self.set(40, "m_pGameRules.m_totalRoundsPlayed", 0);
+        self.set(40, "m_pGameRules.m_bWarmupPeriod", false);
+    }
+    pub fn event(&mut self, name: &str, values: &[(&str, Value)]) {
+        let (eventid, (_, keys)) = EVENTS.iter().enumerate().find(|(_, (n, _))| *n == name).unwrap();
+        let mut msg = CSVCMsg_GameEvent::new();
+        msg.eventid = Some(eventid as i32);
+        for (key_name, key_type) in keys.iter() {
+            let value = values.iter().find(|(n, _)| n == key_name).map(|(_, v)| v.clone());
+            let mut key = Key_t::new();
+            key.type_ = Some(*key_type);
+            match (*key_type, value) {
+                (EVENT_STRING, Some(Value::Str(s))) => key.val_string = Some(s),
+                (EVENT_STRING, _) => key.val_string = Some(String::new()),
+                (EVENT_FLOAT, Some(Value::Float(f))) => key.val_float = Some(f),
+                (EVENT_FLOAT, _) => key.val_float = Some(0.0),
+                (EVENT_BOOL, Some(Value::Bool(b))) => key.val_bool = Some(b),
+                (EVENT_BOOL, _) => key.val_bool = Some(false),
+                (t, v) => {
+                    let v = match v {
+                        Some(Value::Int(v)) => v as i32,
+                        _ => 0,
+                    };
+                    match t {
+                        EVENT_LONG => key.val_long = Some(v),
+                        EVENT_SHORT => key.val_short = Some(v),
+                        _ => key.val_byte = Some(v),
+                    }
+                }
+            }
+            msg.keys.push(key);
+        }
+        self.messages.push((GE_GAME_EVENT, msg.write_to_bytes().unwrap()));
+    }
+    pub fn chat(&mut self, entity_index: i32, text: &str) {
+        let mut msg = CUserMessageSayText2::new();
+        msg.entityindex = Some(entity_index);
+        msg.chat = Some(true);
+        msg.param2 = Some(text.to_string());
+        self.messages.push((UM_SAY_TEXT2, msg.write_to_bytes().unwrap()));
+    }
+    // Everything created, set, deleted or sent since the last packet
+    pub fn packet(&mut self, tick: i32) {
+        let mut messages = std::mem::take(&mut self.messages);
+        let changes = std::mem::take(&mut self.changes);
+        if !changes.is_empty() {
+            messages.insert(0, (SVC_PACKET_ENTITIES, self.packet_entities(&changes, &[])));
+        }
+        self.frame(DEM_PACKET, tick, &packet(&messages));
+    }
+    // Every live entity and the userinfo of every player
+    pub fn full_packet(&mut self, tick: i32) {
+        let changes = self.entities.keys().map(|id| (*id, Change::Create)).collect();
+        let left_out = self.left_out_of_full_packets.clone();
+        let entities = self.packet_entities(&changes, &left_out);
+        let mut userinfo = Table_t::new();
+        userinfo.table_name = Some("userinfo".to_string());
+        for (userid, (steamid, name)) in &self.players {
+            let mut info = CMsgPlayerInfo::new();
+            info.userid = Some(*userid);
+            info.xuid = Some(*steamid);
+            info.steamid = Some(*steamid);
+            info.name = Some(name.clone());
+            let mut item = Items_t::new();
+            item.str = Some(userid.to_string());
+            item.data = Some(info.write_to_bytes().unwrap());
+            userinfo.items.push(item);
+        }
+        let mut full_packet = CDemoFullPacket::new();
+        let mut tables = CDemoStringTables::new();
+        tables.tables.push(userinfo);
+        full_packet.string_table = MessageField::some(tables);
+        let mut inner = CDemoPacket::new();
+        inner.data = Some(packet_data(&[(SVC_PACKET_ENTITIES, entities)]));
+        full_packet.packet = MessageField::some(inner);
+        self.frame(DEM_FULL_PACKET, tick, &full_packet.write_to_bytes().unwrap());
+    }
+    pub fn finish(mut self, tick: i32) -> Vec<u8> {
+        self.frame(DEM_STOP, tick, &[]);
+        let len = (self.out.len() - 18) as u32;
+        self.out[8..12].copy_from_slice(&len.to_le_bytes());
+        self.out
+    }
+
+    fn packet_entities(&self, changes: &BTreeMap<i32, Change>, left_out: &[String]) -> Vec<u8> {
+        let mut w = BitWriter::default();
+        let mut prev = -1;
+        for (entity_id, change) in changes {
+            w.ubitvar((entity_id - prev - 1) as u32);
+            prev = *entity_id;
+            let entity = match (change, self.entities.get(entity_id)) {
+                (Change::Delete, _) | (_, None) => {
+                    w.bits(1, 2);
+                    continue;
+                }
+                (_, Some(entity)) => entity,
+            };
+            let paths: Vec<&Vec<i32>> = match change {
+                Change::Create => {
+                    w.bits(2, 2);
+                    w.bits(entity.cls_id as u64, 8);
+                    w.bits(0, 17);
+                    w.varint(0);
+                    let left_out: Vec<Vec<i32>> = class_fields(CLASSES[entity.cls_id])
+                        .into_iter()
+                        .filter(|f| left_out.contains(&f.name))
+                        .map(|f| f.path)
+                        .collect();
+                    entity.props.keys().filter(|p| !left_out.contains(p)).collect()
+                }
+                Change::Update(paths) => {
+                    w.bits(0, 2);
+                    paths.iter().collect()
+                }
+                Change::Delete => unreachable!(),
+            };
+            write_field_paths(&mut w, &paths);
+            for path in paths {
+                let (kind, value) = &entity.props[path];
+                write_value(&mut w, *kind, value);
+            }
+        }
+        // The huffman decoding peeks past the end
+        w.bytes(&[0; 8]);
+        let mut msg = CSVCMsg_PacketEntities::new();
+        msg.updated_entries = Some(changes.len() as i32);
+        msg.entity_data = Some(w.bytes);
+        msg.write_to_bytes().unwrap()
+    }
+}
+
+// Paths in ascending order. Every op gives one path, so each path is reached from the previous one with a single op.
+fn write_field_paths(w: &mut BitWriter, paths: &[&Vec<i32>]) {
+    let mut cur = vec![-1];
+    for path in paths {
+        let same_parent = cur.len() == path.len() && cur[..cur.len() - 1] == path[..path.len() - 1];
+        if same_parent {
+            write_plus(w, path[path.len() - 1] - cur[cur.len() - 1]);
+        } else if path.len() > cur.len() {
+            // push_n_and_non_topological
+            w.code("110111011");
+            write_level_deltas(w, &cur, path, 1);
+            w.ubitvar((path.len() - cur.len()) as u32);
+            for idx in &path[cur.len()..] {
+                w.ubitvar_fp(*idx as u32);
+            }
+        } else {
+            // pop_n_and_non_topographical
+            w.code("1101100011000000");
+            w.ubitvar_fp((cur.len() - path.len()) as u32);
+            write_level_deltas(w, &cur, path, 0);
+        }
+        cur = path.to_vec();
+    }
+    w.code("10");
+}
+
+// Change of every level both paths have, the reader adds bias to the ones that are set
+fn write_level_deltas(w: &mut BitWriter, cur: &[i32], path: &[i32], bias: i32) {
+    for (from, to) in cur.iter().zip(path) {
+        w.bit(from != to);
+        if from != to {
+            w.varint(zigzag(to - from - bias));
+        }
+    }
+}
+
+fn zigzag(v: i32) -> u64 {
+    ((v << 1) ^ (v >> 31)) as u32 as u64
+}
+
+fn write_plus(w: &mut BitWriter, delta: i32) {
+    match delta {
+        1 => w.code("0"),
+        2 => w.code("1110"),
+        3 => w.code("110010"),
+        4 => w.code("11011111"),
+        _ => {
+            w.code("11010");
+            w.ubitvar_fp(delta as u32 - 5);
+        }
+    }
+}
+
+fn write_value(w: &mut BitWriter, kind: Kind, value: &Value) {
+    match (kind, value) {
+        (Kind::Bool, Value::Bool(b)) => w.bit(*b),
+        (Kind::Str, Value::Str(s)) => {
+            w.bytes(s.as_bytes());
+            w.bits(0, 8);
+        }
+        (Kind::Unsigned, Value::Int(v)) => w.varint(*v as u32 as u64),
+        (Kind::Unsigned64, Value::Int(v)) => w.varint(*v as u64),
+        (Kind::Signed, Value::Int(v)) => w.varint(zigzag(*v as i32)),
+        (kind, value) => panic!("can't write {:?} as {:?}", value, kind),
+    }
+}
+
+fn packet_data(messages: &[(u32, Vec<u8>)]) -> Vec<u8> {
+    let mut w = BitWriter::default();
+    for (msg_type, bytes) in messages {
+        w.ubitvar(*msg_type);
+        w.varint(bytes.len() as u64);
+        w.bytes(bytes);
+    }
+    w.bytes
+}
+
+fn packet(messages: &[(u32, Vec<u8>)]) -> Vec<u8> {
+    let mut packet = CDemoPacket::new();
+    packet.data = Some(packet_data(messages));
+    packet.write_to_bytes().unwrap()
+}
+
+fn sendtables() -> CDemoSendTables {
+    let mut msg = CSVCMsg_FlattenedSerializer::new();
+    let mut symbol = |s: &str| match msg.symbols.iter().position(|x| x == s) {
+        Some(idx) => idx as i32,
+        None => {
+            msg.symbols.push(s.to_string());
+            msg.symbols.len() as i32 - 1
+        }
+    };
+    let mut serializers = vec![];
+    let mut fields = vec![];
+    for (name, serializer_fields) in SERIALIZERS {
+        let mut serializer = ProtoFlattenedSerializer_t::new();
+        serializer.serializer_name_sym = Some(symbol(name));
+        for (var_name, var_type) in serializer_fields.iter() {
+            let mut field = ProtoFlattenedSerializerField_t::new();
+            field.var_name_sym = Some(symbol(var_name));
+            field.var_type_sym = Some(symbol(var_type));
+            field.send_node_sym = Some(symbol(name));
+            if let Some(child) = var_type.strip_suffix('*') {
+                field.field_serializer_name_sym = Some(symbol(child));
+            }
+            serializer.fields_index.push(fields.len() as i32);
+            fields.push(field);
+        }
+        serializers.push(serializer);
+    }
+    msg.serializers = serializers;
+    msg.fields = fields;
+    let bytes = msg.write_to_bytes().unwrap();
+    let mut w = BitWriter::default();
+    w.varint(bytes.len() as u64);
+    w.bytes(&bytes);
+    let mut tables = CDemoSendTables::new();
+    tables.data = Some(w.bytes);
+    tables
+}
+
+fn class_info() -> CDemoClassInfo {
+    let mut msg = CDemoClassInfo::new();
+    for (class_id, name) in CLASSES.iter().enumerate() {
+        let mut class = Class_t::new();
+        class.class_id = Some(class_id as i32);
+        class.network_name = Some(name.to_string());
+        msg.classes.push(class);
+    }
+    msg
+}
+
+fn event_list() -> CSVCMsg_GameEventList {
+    let mut msg = CSVCMsg_GameEventList::new();
+    for (eventid, (name, keys)) in EVENTS.iter().enumerate() {
+        let mut descriptor = Descriptor_t::new();
+        descriptor.eventid = Some(eventid as i32);
+        descriptor.name = Some(name.to_string());
+        for (key_name, key_type) in keys.iter() {
+            let mut key = DescriptorKey::new();
+            key.type_ = Some(*key_type);
+            key.name = Some(key_name.to_string());
+            descriptor.keys.push(key);
+        }
+        msg.descriptors.push(descriptor);
+    }
+    msg
+}
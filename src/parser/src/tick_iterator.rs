@@ -0,0 +1,254 @@
+use crate::entities::Entity;
+use crate::entities::PlayerMetaData;
+use crate::game_events::GameEvent;
+use crate::other_netmessages::Class;
+use crate::parser_settings::Parser;
+use crate::parser_thread_settings::ParserThread;
+use crate::parser_thread_settings::Teams;
+use crate::parser_threads::FrameResult;
+use crate::prop_controller::PropController;
+use crate::read_bits::DemoParserError;
+use crate::variants::Variant;
+use ahash::AHashMap;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+// Pull-based alternative to parse_demo. Steps trough the demo one tick at a time:
+//
+// let mut ticks = parser.ticks()?;
+// while let Some(state) = ticks.next_tick() {
+//     for (entity_id, player) in state.players { ... }
+// }
+//
+// The TickView borrows the parser's own state, nothing is copied per tick. It is only valid until the next
+// call to next_tick, use to_state() for an owned copy of a single tick. TickIterator is also an
+// Iterator<Item = TickState> for adapters like filter/take, which copies the state every tick.
+// Only props that are wanted (or always needed by the parser itself) are stored on the entities,
+// so wanted_player_props/wanted_other_props still decide what can be read from the state.
+
+#[derive(Debug, Clone, Copy)]
+pub struct TickView<'a> {
+    pub tick: i32,
+    pub entities: &'a AHashMap<i32, Entity>,
+    pub players: &'a BTreeMap<i32, PlayerMetaData>,
+    pub teams: &'a Teams,
+    pub rules_entity_id: Option<i32>,
+    // Events fired on this tick
+    pub events: &'a [GameEvent],
+    pub cls_by_id: &'a Arc<AHashMap<u32, Class>>,
+    pub prop_controller: &'a Arc<PropController>,
+}
+
+impl<'a> TickView<'a> {
+    pub fn rules_entity(&self) -> Option<&'a Entity> {
+        self.entities.get(&self.rules_entity_id?)
+    }
+    pub fn team_entity(&self, team_num: u32) -> Option<&'a Entity> {
+        let entid = match team_num {
+            1 => self.teams.team1_entid,
+            2 => self.teams.team2_entid,
+            3 => self.teams.team3_entid,
+            _ => None,
+        };
+        self.entities.get(&entid?)
+    }
+    pub fn class_name(&self, entity: &Entity) -> Option<&'a str> {
+        self.cls_by_id.get(&entity.cls_id).map(|cls| cls.name.as_str())
+    }
+    // Prop by its full name, for example "CCSPlayerPawn.m_iHealth".
+    pub fn get_prop(&self, entity_id: i32, prop_name: &str) -> Option<&'a Variant> {
+        let prop_id = self.prop_controller.name_to_id.get(prop_name)?;
+        self.entities.get(&entity_id)?.props.get(prop_id)
+    }
+    pub fn to_state(&self) -> TickState {
+        TickState {
+            tick: self.tick,
+            entities: self.entities.clone(),
+            players: self.players.clone(),
+            teams: self.teams.clone(),
+            rules_entity_id: self.rules_entity_id,
+            events: self.events.to_vec(),
+            cls_by_id: self.cls_by_id.clone(),
+            prop_controller: self.prop_controller.clone(),
+        }
+    }
+}
+
+// Owned copy of one tick, returned by seek
+#[derive(Debug, Clone)]
+pub struct TickState {
+    pub tick: i32,
+    pub entities: AHashMap<i32, Entity>,
+    pub players: BTreeMap<i32, PlayerMetaData>,
+    pub teams: Teams,
+    pub rules_entity_id: Option<i32>,
+    pub events: Vec<GameEvent>,
+    pub cls_by_id: Arc<AHashMap<u32, Class>>,
+    pub prop_controller: Arc<PropController>,
+}
+
+impl TickState {
+    pub fn view(&self) -> TickView<'_> {
+        TickView {
+            tick: self.tick,
+            entities: &self.entities,
+            players: &self.players,
+            teams: &self.teams,
+            rules_entity_id: self.rules_entity_id,
+            events: &self.events,
+            cls_by_id: &self.cls_by_id,
+            prop_controller: &self.prop_controller,
+        }
+    }
+}
+
+pub struct TickIterator {
+    pub parser: ParserThread,
+    pub error: Option<DemoParserError>,
+    finished: bool,
+    frames_since_yield: usize,
+}
+
+impl TickIterator {
+    pub fn new(parser: ParserThread) -> Self {
+        TickIterator {
+            parser,
+            error: None,
+            finished: false,
+            frames_since_yield: 0,
+        }
+    }
+    // Lending iterator: the view borrows the iterator so it can't be held across ticks.
+    // None at the end of the demo or on an error, which is then in self.error.
+    pub fn next_tick(&mut self) -> Option<TickView<'_>> {
+        if self.finished {
+            return None;
+        }
+        // Events of the previously yielded tick
+        self.parser.game_events.clear();
+        loop {
+            match self.parser.parse_frame() {
+                Ok(FrameResult::Parsed) => self.frames_since_yield += 1,
+                Ok(FrameResult::Skipped) => {}
+                Ok(FrameResult::Stop) => {
+                    self.finished = true;
+                    if self.frames_since_yield > 0 {
+                        return Some(self.view());
+                    }
+                    return None;
+                }
+                Err(e) => {
+                    self.finished = true;
+                    self.error = Some(e);
+                    return None;
+                }
+            }
+            // Yield once every frame of the current tick has been read, the last one may be a skipped frame
+            if self.frames_since_yield > 0 && self.parser.peek_next_tick() != Some(self.parser.tick) {
+                return Some(self.view());
+            }
+        }
+    }
+    pub fn for_each<F: FnMut(&TickView)>(mut self, mut f: F) -> Result<(), DemoParserError> {
+        while let Some(view) = self.next_tick() {
+            f(&view);
+        }
+        match self.error.take() {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+    fn view(&mut self) -> TickView<'_> {
+        self.frames_since_yield = 0;
+        TickView {
+            tick: self.parser.tick,
+            entities: &self.parser.entities,
+            players: &self.parser.players,
+            teams: &self.parser.teams,
+            rules_entity_id: self.parser.rules_entity_id,
+            events: &self.parser.game_events,
+            cls_by_id: &self.parser.cls_by_id,
+            prop_controller: &self.parser.prop_controller,
+        }
+    }
+}
+
+impl Iterator for TickIterator {
+    type Item = TickState;
+
+    fn next(&mut self) -> Option<TickState> {
+        self.next_tick().map(|view| view.to_state())
+    }
+}
+
+impl Parser {
+    pub fn ticks(&mut self) -> Result<TickIterator, DemoParserError> {
+        self.first_pass()?;
+        self.resolve_tick_selectors()?;
+        let input = self.create_parser_thread_input(16, true);
+        let parser = ParserThread::new(input)?;
+        Ok(TickIterator::new(parser))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::query::DemoQuery;
+    use crate::test_demo::TestDemo;
+    use crate::test_demo::DEM_ANIMATION_DATA;
+    use crate::variants::BytesVariant;
+    use crate::variants::Variant;
+
+    // Ticks 2 and 3 end in a skipped frame, tick 4 has two packets and tick 5 only a skipped frame
+    fn demo() -> Vec<u8> {
+        let mut demo = TestDemo::new();
+        demo.add_teams_and_rules();
+        demo.packet(1);
+        for (tick, score) in [(2, 1), (3, 2)] {
+            demo.set(52, "m_iScore", score);
+            demo.packet(tick);
+            demo.frame(DEM_ANIMATION_DATA, tick, &[]);
+        }
+        demo.set(52, "m_iScore", 3);
+        demo.packet(4);
+        demo.set(52, "m_iScore", 4);
+        demo.packet(4);
+        demo.frame(DEM_ANIMATION_DATA, 5, &[]);
+        demo.packet(6);
+        demo.finish(6)
+    }
+    fn query() -> DemoQuery {
+        DemoQuery::new().entity_props(["CCSTeam.m_iScore"])
+    }
+
+    #[test]
+    fn test_every_tick_yielded_once() {
+        let mut parser = query().parser(BytesVariant::Vec(demo())).unwrap();
+        let mut ticks = parser.ticks().unwrap();
+        let mut seen = vec![];
+        while let Some(view) = ticks.next_tick() {
+            seen.push(view.tick);
+        }
+        assert!(ticks.error.is_none());
+        assert_eq!(seen, vec![0, 1, 2, 3, 4, 6]);
+    }
+    #[test]
+    fn test_iterator_yields_owned_end_of_tick_states() {
+        let mut parser = query().parser(BytesVariant::Vec(demo())).unwrap();
+        let states: Vec<_> = parser.ticks().unwrap().filter(|state| state.tick > 1).collect();
+
+        let scores: Vec<_> = states
+            .iter()
+            .map(|state| (state.tick, state.view().get_prop(52, "CCSTeam.m_iScore").cloned()))
+            .collect();
+        assert_eq!(
+            scores,
+            vec![
+                (2, Some(Variant::I32(1))),
+                (3, Some(Variant::I32(2))),
+                (4, Some(Variant::I32(4))),
+                (6, Some(Variant::I32(4))),
+            ]
+        );
+    }
+}
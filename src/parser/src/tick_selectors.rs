@@ -0,0 +1,280 @@
+use crate::parser_settings::Parser;
+use crate::parser_thread_settings::ParserThread;
+use crate::query::DemoQuery;
+use crate::read_bits::DemoParserError;
+use crate::variants::Variant;
+
+// Ticks to collect props on, in addition to the explicit DemoQuery::ticks list:
+//
+// let parser = DemoQuery::new()
+//     .player_props(["X", "Y"])
+//     .rounds(3, 5)
+//     .around_event("player_death", 64, 64)
+//     .every_nth_tick(16)
+//     .parser_from_path("match.dem")?;
+//
+// A tick is collected if it is in the ticks list or matches any selector (every tick if there are neither).
+// every_nth_tick counts from the start of each window: the range start or the end of freeze time for rounds.
+// Rounds are read from the rules entity while parsing so every chunk knows which round it is in.
+// Events can come after the ticks they should include, so around_event ticks are found with an events-only
+// parse (no entities, fast) before the real one and turned into ranges.
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TickSelector {
+    // start..=end
+    Range { start: i32, end: i32 },
+    // From the end of freeze time to the end of the round, first round is 1. Warmup is never included.
+    Rounds { first: i32, last: i32 },
+    AroundEvent { event_name: String, before: i32, after: i32 },
+}
+
+impl Parser {
+    pub fn resolve_tick_selectors(&mut self) -> Result<(), DemoParserError> {
+        let mut event_names = vec![];
+        for selector in &self.settings.tick_selectors {
+            if let TickSelector::AroundEvent { event_name, .. } = selector {
+                if !event_names.contains(event_name) {
+                    event_names.push(event_name.clone());
+                }
+            }
+        }
+        if event_names.is_empty() {
+            return Ok(());
+        }
+        let events = self
+            .parser_for(&self.events_only_query(event_names))?
+            .parse_demo()?
+            .game_events;
+
+        let mut resolved = vec![];
+        for selector in &self.settings.tick_selectors {
+            match selector {
+                TickSelector::AroundEvent {
+                    event_name,
+                    before,
+                    after,
+                } => {
+                    for event in events.iter().filter(|e| &e.name == event_name) {
+                        resolved.push(TickSelector::Range {
+                            start: event.tick - before,
+                            end: event.tick + after,
+                        });
+                    }
+                }
+                other => resolved.push(other.clone()),
+            }
+        }
+        self.settings.tick_selectors = resolved;
+        Ok(())
+    }
+    // Only the given events, entities are not parsed. Keeps the leniency of the user's query.
+    pub fn events_only_query<I, S>(&self, event_names: I) -> DemoQuery
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.keep_leniency(DemoQuery::new().events(event_names).entities(false))
+    }
+    // Only the given player props on the given ticks, no events. Keeps the leniency of the user's query.
+    // Separate from the events parse because the tick output is left empty when events are wanted.
+    pub fn player_ticks_query<I, S>(&self, player_props: I, ticks: Vec<i32>) -> DemoQuery
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.keep_leniency(DemoQuery::new().player_props(player_props).ticks(ticks))
+    }
+    fn keep_leniency(&self, query: DemoQuery) -> DemoQuery {
+        match self.settings.lenient {
+            true => query.lenient(),
+            false => query,
+        }
+    }
+    // Parser for a separate parse of the same demo
+    pub fn parser_for(&self, query: &DemoQuery) -> Result<Parser, DemoParserError> {
+        Ok(Parser::new(query.build_shared(self.settings.bytes.clone())?))
+    }
+}
+
+impl ParserThread {
+    pub fn is_wanted_tick(&self) -> bool {
+        if self.wanted_ticks.is_empty() && self.tick_selectors.is_empty() {
+            return self.on_stride(0);
+        }
+        self.wanted_ticks.contains(&self.tick)
+            || self
+                .tick_selectors
+                .iter()
+                .any(|s| self.window_start(s).is_some_and(|start| self.on_stride(start)))
+    }
+    fn on_stride(&self, window_start: i32) -> bool {
+        self.tick_stride <= 1 || (self.tick - window_start) % self.tick_stride == 0
+    }
+    // First tick of the selector's window if the current tick is inside it
+    fn window_start(&self, selector: &TickSelector) -> Option<i32> {
+        match selector {
+            TickSelector::Range { start, end } => (*start..=*end).contains(&self.tick).then_some(*start),
+            TickSelector::Rounds { first, last } => match self.round_in_progress() {
+                Some(round) if (*first..=*last).contains(&round) => Some(self.round_start_tick().unwrap_or(0)),
+                _ => None,
+            },
+            // Turned into ranges by Parser::resolve_tick_selectors
+            TickSelector::AroundEvent { .. } => None,
+        }
+    }
+    // m_fRoundStartTime is the game time freeze time ended at. Game time runs on the server tick, so the
+    // distance to the current server tick gives the demo tick. Chunks starting mid round agree on it.
+    fn round_start_tick(&self) -> Option<i32> {
+        let id = self.prop_controller.special_ids.round_start_time?;
+        match self.get_prop_from_ent(&id, &self.rules_entity_id?).ok()? {
+            Variant::F32(start_time) => Some(self.tick - (self.net_tick as i32 - (start_time * 64.0).round() as i32)),
+            _ => None,
+        }
+    }
+    // Round number while the round is being played: freeze time over and no winner yet
+    pub fn round_in_progress(&self) -> Option<i32> {
+        let ids = &self.prop_controller.special_ids;
+        let rules_prop = |id: Option<u32>| -> Option<i64> {
+            let value = self.get_prop_from_ent(&id?, &self.rules_entity_id?).ok()?;
+            variant_to_i64(&value)
+        };
+        if rules_prop(ids.warmup_period) == Some(1) || rules_prop(ids.freeze_period) == Some(1) {
+            return None;
+        }
+        // Set when the round ends and cleared when the next one starts
+        if rules_prop(ids.round_win_status).unwrap_or(0) != 0 {
+            return None;
+        }
+        rules_prop(ids.total_rounds_played).map(|played| played as i32 + 1)
+    }
+}
+
+pub fn variant_to_i64(v: &Variant) -> Option<i64> {
+    match v {
+        Variant::Bool(b) => Some(*b as i64),
+        Variant::U32(x) => Some(*x as i64),
+        Variant::I32(x) => Some(*x as i64),
+        Variant::I16(x) => Some(*x as i64),
+        Variant::U64(x) => Some(*x as i64),
+        Variant::U8(x) => Some(*x as i64),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entities::Entity;
+    use crate::entities::EntityType;
+    use crate::prop_controller::PropController;
+    use crate::variants::BytesVariant;
+    use ahash::AHashMap;
+    use std::sync::Arc;
+
+    const RULES_ENTITY_ID: i32 = 10;
+
+    fn thread(query: DemoQuery) -> ParserThread {
+        let parser = query.parser(BytesVariant::Vec(vec![])).unwrap();
+        ParserThread::new(parser.create_parser_thread_input(0, false)).unwrap()
+    }
+    fn set_rules(thread: &mut ParserThread, rounds_played: i32, freeze: bool, win_status: i32) {
+        let mut prop_controller = PropController::new(vec![], vec![], AHashMap::default());
+        prop_controller.special_ids.total_rounds_played = Some(1);
+        prop_controller.special_ids.freeze_period = Some(2);
+        prop_controller.special_ids.warmup_period = Some(3);
+        prop_controller.special_ids.round_win_status = Some(4);
+        prop_controller.special_ids.round_start_time = Some(5);
+        thread.prop_controller = Arc::new(prop_controller);
+        thread.rules_entity_id = Some(RULES_ENTITY_ID);
+        let props = AHashMap::from_iter([
+            (1, Variant::I32(rounds_played)),
+            (2, Variant::Bool(freeze)),
+            (3, Variant::Bool(false)),
+            (4, Variant::I32(win_status)),
+            // Freeze time ended on server tick 6400
+            (5, Variant::F32(100.0)),
+        ]);
+        thread.entities.insert(
+            RULES_ENTITY_ID,
+            Entity {
+                cls_id: 0,
+                entity_id: RULES_ENTITY_ID,
+                props,
+                entity_type: EntityType::Rules,
+            },
+        );
+    }
+
+    #[test]
+    fn test_events_only_query_keeps_leniency() {
+        let parser = DemoQuery::new()
+            .player_props(["X"])
+            .lenient()
+            .parser(BytesVariant::Vec(vec![]))
+            .unwrap();
+        let query = parser.events_only_query(["player_death"]);
+        assert_eq!(query.events, vec!["player_death".to_string()]);
+        assert!(query.player_props.is_empty());
+        assert!(!query.parse_entities);
+        assert!(query.lenient);
+        let sub_parser = parser.parser_for(&query).unwrap();
+        assert!(Arc::ptr_eq(&sub_parser.settings.bytes, &parser.settings.bytes));
+    }
+    #[test]
+    fn test_range_and_stride() {
+        let mut thread = thread(DemoQuery::new().ticks([5]).tick_range(100, 200).every_nth_tick(16));
+        let wanted: Vec<i32> = (0..300)
+            .filter(|tick| {
+                thread.tick = *tick;
+                thread.is_wanted_tick()
+            })
+            .collect();
+        assert_eq!(wanted, vec![5, 100, 116, 132, 148, 164, 180, 196]);
+    }
+    #[test]
+    fn test_stride_counts_from_each_window() {
+        let mut thread = thread(
+            DemoQuery::new()
+                .tick_range(101, 350)
+                .tick_range(1000, 1100)
+                .every_nth_tick(100),
+        );
+        let wanted: Vec<i32> = (0..2000)
+            .filter(|tick| {
+                thread.tick = *tick;
+                thread.is_wanted_tick()
+            })
+            .collect();
+        assert_eq!(wanted, vec![101, 201, 301, 1000, 1100]);
+    }
+    #[test]
+    fn test_stride_counts_from_freeze_end() {
+        let mut thread = thread(DemoQuery::new().rounds(3, 3).every_nth_tick(32));
+        set_rules(&mut thread, 2, false, 0);
+        // Demo tick 500 is server tick 6410, so freeze time ended on demo tick 490
+        let wanted: Vec<i32> = (500..600)
+            .filter(|tick| {
+                thread.tick = *tick;
+                thread.net_tick = (*tick + 5910) as u32;
+                thread.is_wanted_tick()
+            })
+            .collect();
+        assert_eq!(wanted, vec![522, 554, 586]);
+    }
+    #[test]
+    fn test_rounds() {
+        let mut thread = thread(DemoQuery::new().rounds(3, 5));
+        set_rules(&mut thread, 2, false, 0);
+        assert_eq!(thread.round_in_progress(), Some(3));
+        assert!(thread.is_wanted_tick());
+        // Freeze time
+        set_rules(&mut thread, 2, true, 0);
+        assert!(!thread.is_wanted_tick());
+        // Round 5 is over
+        set_rules(&mut thread, 5, false, 2);
+        assert!(!thread.is_wanted_tick());
+        set_rules(&mut thread, 5, false, 0);
+        assert_eq!(thread.round_in_progress(), Some(6));
+        assert!(!thread.is_wanted_tick());
+    }
+}
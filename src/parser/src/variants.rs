@@ -78,6 +78,20 @@ impl PropColumn {
             None => self.num_nones,
         }
     }
+    // Value of one row, None if the row is missing or the value was not set
+    pub fn get(&self, idx: usize) -> Option<Variant> {
+        match &self.data {
+            Some(VarVec::Bool(v)) => v.get(idx).copied().flatten().map(Variant::Bool),
+            Some(VarVec::I32(v)) => v.get(idx).copied().flatten().map(Variant::I32),
+            Some(VarVec::F32(v)) => v.get(idx).copied().flatten().map(Variant::F32),
+            Some(VarVec::String(v)) => v.get(idx).cloned().flatten().map(Variant::String),
+            Some(VarVec::U32(v)) => v.get(idx).copied().flatten().map(Variant::U32),
+            Some(VarVec::U64(v)) => v.get(idx).copied().flatten().map(Variant::U64),
+            Some(VarVec::StringVec(v)) => v.get(idx).cloned().map(Variant::StringVec),
+            Some(VarVec::U64Vec(v)) => v.get(idx).cloned().map(Variant::U64Vec),
+            None => None,
+        }
+    }
     pub fn extend_from(&mut self, other: &mut PropColumn) {
         match &mut self.data {
             Some(VarVec::Bool(v)) => match &other.data {
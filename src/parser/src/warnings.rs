@@ -0,0 +1,184 @@
+use crate::parser_settings::Parser;
+use crate::parser_thread_settings::ParserThread;
+use crate::parser_threads::FrameResult;
+use crate::read_bits::DemoParserError;
+use serde::Serialize;
+
+// Lenient mode (ParserInputs.lenient / DemoQuery::lenient) is meant for demos that got cut short by
+// server crashes or have a few broken frames. Instead of failing, frames that can't be decoded are
+// skipped and parsing stops at the first frame that can't even be read. Everything parsed up to that
+// point is returned and what happened is listed in DemoOutput.warnings.
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub enum ParseWarningKind {
+    // File is shorter than the header says it should be
+    DemoEndsEarly,
+    // Frame could not be decoded and was skipped
+    FrameSkipped,
+    // A packet was only partly applied. Packets are skipped until the next fullpacket rebuilds the entities
+    PacketsSkipped,
+    // Nothing after this point was parsed
+    Stopped,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ParseWarning {
+    pub kind: ParseWarningKind,
+    pub tick: Option<i32>,
+    pub offset: Option<usize>,
+    pub message: String,
+}
+
+impl ParseWarning {
+    pub fn from_error(kind: ParseWarningKind, e: &DemoParserError) -> Self {
+        let (tick, offset) = match e.context() {
+            Some(ctx) => (ctx.tick, ctx.offset),
+            None => (None, None),
+        };
+        ParseWarning {
+            kind,
+            tick,
+            offset,
+            message: format!("{}", e),
+        }
+    }
+    pub fn stopped(tick: i32, offset: usize, message: &str) -> Self {
+        ParseWarning {
+            kind: ParseWarningKind::Stopped,
+            tick: Some(tick),
+            offset: Some(offset),
+            message: message.to_string(),
+        }
+    }
+}
+
+// The first pass and the threads can both report the same frame
+pub fn merge_warnings(mut warnings: Vec<ParseWarning>) -> Vec<ParseWarning> {
+    warnings.sort_by_key(|w| w.offset);
+    warnings.dedup_by(|a, b| a.kind == b.kind && a.offset == b.offset);
+    warnings
+}
+
+impl Parser {
+    // Continues after the frame in lenient mode, otherwise returns the error.
+    pub fn skip_frame_or_fail(&mut self, e: DemoParserError, frame_ends_at: usize) -> Result<(), DemoParserError> {
+        if !self.settings.lenient {
+            return Err(e);
+        }
+        self.warnings
+            .push(ParseWarning::from_error(ParseWarningKind::FrameSkipped, &e));
+        self.ptr = frame_ends_at;
+        Ok(())
+    }
+    pub fn stop_or_fail(&mut self, e: DemoParserError) -> Result<(), DemoParserError> {
+        if !self.settings.lenient {
+            return Err(e);
+        }
+        self.warnings.push(ParseWarning::from_error(ParseWarningKind::Stopped, &e));
+        Ok(())
+    }
+}
+
+impl ParserThread {
+    pub fn skip_frame_or_fail(&mut self, e: DemoParserError, frame_ends_at: usize) -> Result<FrameResult, DemoParserError> {
+        if !self.lenient {
+            return Err(e);
+        }
+        self.warnings
+            .push(ParseWarning::from_error(ParseWarningKind::FrameSkipped, &e));
+        self.ptr = frame_ends_at;
+        Ok(FrameResult::Skipped)
+    }
+    // Entities may have been partly updated by the packet that failed
+    pub fn skip_packet_or_fail(&mut self, e: DemoParserError, frame_ends_at: usize) -> Result<FrameResult, DemoParserError> {
+        let result = self.skip_frame_or_fail(e, frame_ends_at)?;
+        if !self.desynced {
+            self.desynced = true;
+            self.warnings.push(ParseWarning {
+                kind: ParseWarningKind::PacketsSkipped,
+                tick: Some(self.tick),
+                offset: Some(frame_ends_at),
+                message: "entity state is out of sync, packets are skipped until the next fullpacket".to_string(),
+            });
+        }
+        Ok(result)
+    }
+    pub fn stop_or_fail(&mut self, e: DemoParserError) -> Result<FrameResult, DemoParserError> {
+        if !self.lenient {
+            return Err(e);
+        }
+        self.warnings.push(ParseWarning::from_error(ParseWarningKind::Stopped, &e));
+        Ok(FrameResult::Stop)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::DemoOutput;
+    use crate::query::DemoQuery;
+    use crate::variants::BytesVariant;
+
+    fn frame(bytes: &mut Vec<u8>, cmd: u8, tick: u8, size: u8, payload: &[u8]) {
+        bytes.extend([cmd, tick, size]);
+        bytes.extend(payload);
+    }
+    // Two corrupt packets and then a frame that is cut off by the end of the file
+    fn truncated_demo(claimed_len: Option<u32>) -> Vec<u8> {
+        let mut bytes = b"PBDEMS2\0".to_vec();
+        bytes.extend([0; 8]);
+        frame(&mut bytes, 7, 1, 2, &[0xff, 0xff]);
+        frame(&mut bytes, 7, 2, 2, &[0xff, 0xff]);
+        frame(&mut bytes, 7, 3, 100, &[0; 5]);
+        let claimed_len = claimed_len.unwrap_or(bytes.len() as u32 - 18);
+        bytes[8..12].copy_from_slice(&claimed_len.to_le_bytes());
+        bytes
+    }
+    fn parse(bytes: Vec<u8>, lenient: bool) -> Result<DemoOutput, DemoParserError> {
+        let query = match lenient {
+            true => DemoQuery::new().lenient(),
+            false => DemoQuery::new(),
+        };
+        query.parser(BytesVariant::Vec(bytes))?.parse_demo()
+    }
+
+    #[test]
+    fn test_truncated_demo() {
+        let kinds = |output: DemoOutput| output.warnings.iter().map(|w| w.kind.clone()).collect::<Vec<_>>();
+
+        let output = parse(truncated_demo(None), true).unwrap();
+        assert_eq!(
+            kinds(output),
+            vec![
+                ParseWarningKind::FrameSkipped,
+                ParseWarningKind::PacketsSkipped,
+                ParseWarningKind::Stopped
+            ]
+        );
+        let output = parse(truncated_demo(Some(1000)), true).unwrap();
+        assert!(kinds(output).contains(&ParseWarningKind::DemoEndsEarly));
+
+        match parse(truncated_demo(None), false) {
+            Err(e) => assert_eq!(e.root(), &DemoParserError::MalformedMessage),
+            Ok(_) => panic!("corrupt packet should fail without lenient"),
+        }
+        assert!(matches!(
+            parse(truncated_demo(Some(1000)), false),
+            Err(DemoParserError::DemoEndsEarly(_))
+        ));
+    }
+
+    #[test]
+    fn test_merge_warnings() {
+        let e = DemoParserError::MalformedMessage.with_frame(10, 500, None);
+        let merged = merge_warnings(vec![
+            ParseWarning::stopped(20, 900, "demo ends early"),
+            ParseWarning::from_error(ParseWarningKind::FrameSkipped, &e),
+            ParseWarning::from_error(ParseWarningKind::FrameSkipped, &e),
+        ]);
+        assert_eq!(merged.len(), 2);
+        assert_eq!(merged[0].offset, Some(500));
+        assert_eq!(merged[0].tick, Some(10));
+        assert_eq!(merged[1].kind, ParseWarningKind::Stopped);
+    }
+}
@@ -1,18 +1,16 @@
 use crate::arrow::array::*;
-use ahash::AHashMap;
 use arrow::ffi;
+use arrow_rs::array::ArrayData;
+use arrow_rs::array::StructArray;
+use arrow_rs::ffi::FFI_ArrowArray;
+use arrow_rs::ffi::FFI_ArrowSchema;
+use arrow_rs::record_batch::RecordBatch;
 use itertools::Itertools;
-use parser::game_events::EventField;
+use parser::arrow_export::event_record_batch;
 use parser::game_events::GameEvent;
 use parser::parser_settings::create_mmap;
-use parser::parser_settings::rm_user_friendly_names;
-use parser::parser_settings::Parser;
-use parser::parser_settings::ParserInputs;
-use parser::parser_thread_settings::create_huffman_lookup_table;
-use parser::read_bits::DemoParserError;
+use parser::query::DemoQuery;
 use parser::variants::BytesVariant;
-use parser::variants::VarVec;
-use parser::variants::Variant;
 use polars::prelude::ArrowField;
 use polars::prelude::NamedFrom;
 use polars::series::Series;
@@ -26,7 +24,6 @@ use pyo3::types::PyDict;
 use pyo3::types::PyList;
 use pyo3::Python;
 use pyo3::{PyAny, PyObject, PyResult};
-use std::sync::Arc;
 
 use pyo3::create_exception;
 create_exception!(DemoParser, Exception, pyo3::exceptions::PyException);
@@ -59,23 +56,10 @@ impl DemoParser {
                 )))
             }
         };
-        let settings = ParserInputs {
-            real_name_to_og_name: AHashMap::default(),
-            bytes: Arc::new(BytesVariant::Mmap(mmap)),
-            wanted_player_props: vec![],
-            wanted_player_props_og_names: vec![],
-            wanted_other_props: vec![],
-            wanted_other_props_og_names: vec![],
-            wanted_events: vec![],
-            parse_ents: false,
-            wanted_ticks: vec![],
-            parse_projectiles: false,
-            only_header: true,
-            count_props: false,
-            only_convars: false,
-            huffman_lookup_table: Arc::new(create_huffman_lookup_table()),
+        let mut parser = match DemoQuery::new().entities(false).parser(BytesVariant::Mmap(mmap)) {
+            Ok(parser) => parser,
+            Err(e) => return Err(Exception::new_err(format!("{}", e))),
         };
-        let mut parser = Parser::new(settings);
         let _output = match parser.parse_demo() {
             Ok(output) => output,
             Err(e) => return Err(Exception::new_err(format!("{}", e))),
@@ -95,25 +79,11 @@ impl DemoParser {
                 )))
             }
         };
-        let arc_huf = Arc::new(create_huffman_lookup_table());
 
-        let settings = ParserInputs {
-            real_name_to_og_name: AHashMap::default(),
-            bytes: Arc::new(BytesVariant::Mmap(mmap)),
-            wanted_player_props: vec![],
-            wanted_player_props_og_names: vec![],
-            wanted_other_props: vec![],
-            wanted_other_props_og_names: vec![],
-            wanted_events: vec![],
-            parse_ents: false,
-            wanted_ticks: vec![],
-            parse_projectiles: false,
-            only_header: true,
-            count_props: false,
-            only_convars: false,
-            huffman_lookup_table: arc_huf.clone(),
+        let mut parser = match DemoQuery::new().entities(false).parser(BytesVariant::Mmap(mmap)) {
+            Ok(parser) => parser,
+            Err(e) => return Err(Exception::new_err(format!("{}", e))),
         };
-        let mut parser = Parser::new(settings);
         let output = match parser.parse_demo() {
             Ok(output) => output,
             Err(e) => return Err(PyValueError::new_err(format!("{}", e))),
@@ -132,25 +102,12 @@ impl DemoParser {
                 )))
             }
         };
-        let arc_huf = Arc::new(create_huffman_lookup_table());
 
-        let settings = ParserInputs {
-            real_name_to_og_name: AHashMap::default(),
-            bytes: Arc::new(BytesVariant::Mmap(mmap)),
-            wanted_player_props: vec![],
-            wanted_player_props_og_names: vec![],
-            wanted_other_props: vec![],
-            wanted_other_props_og_names: vec![],
-            wanted_events: vec!["all".to_string()],
-            parse_ents: false,
-            wanted_ticks: vec![],
-            parse_projectiles: false,
-            only_header: true,
-            count_props: false,
-            only_convars: false,
-            huffman_lookup_table: arc_huf.clone(),
+        let query = DemoQuery::new().events(["all"]).entities(false);
+        let mut parser = match query.parser(BytesVariant::Mmap(mmap)) {
+            Ok(parser) => parser,
+            Err(e) => return Err(Exception::new_err(format!("{}", e))),
         };
-        let mut parser = Parser::new(settings);
         let output = match parser.parse_demo() {
             Ok(output) => output,
             Err(e) => return Err(Exception::new_err(format!("{}", e))),
@@ -179,25 +136,11 @@ impl DemoParser {
                 )))
             }
         };
-        let arc_huf = Arc::new(create_huffman_lookup_table());
 
-        let settings = ParserInputs {
-            real_name_to_og_name: AHashMap::default(),
-            bytes: Arc::new(BytesVariant::Mmap(mmap)),
-            wanted_player_props: vec![],
-            wanted_player_props_og_names: vec![],
-            wanted_other_props: vec![],
-            wanted_other_props_og_names: vec![],
-            wanted_events: vec![],
-            parse_ents: true,
-            wanted_ticks: vec![],
-            parse_projectiles: true,
-            only_header: true,
-            count_props: false,
-            only_convars: false,
-            huffman_lookup_table: arc_huf.clone(),
+        let mut parser = match DemoQuery::new().projectiles().parser(BytesVariant::Mmap(mmap)) {
+            Ok(parser) => parser,
+            Err(e) => return Err(Exception::new_err(format!("{}", e))),
         };
-        let mut parser = Parser::new(settings);
         let output = match parser.parse_demo() {
             Ok(output) => output,
             Err(e) => return Err(Exception::new_err(format!("{}", e))),
@@ -269,25 +212,11 @@ impl DemoParser {
                 )))
             }
         };
-        let arc_huf = Arc::new(create_huffman_lookup_table());
 
-        let settings = ParserInputs {
-            real_name_to_og_name: AHashMap::default(),
-            bytes: Arc::new(BytesVariant::Mmap(mmap)),
-            wanted_player_props: vec![],
-            wanted_player_props_og_names: vec![],
-            wanted_other_props: vec![],
-            wanted_other_props_og_names: vec![],
-            wanted_events: vec![],
-            parse_ents: false,
-            wanted_ticks: vec![],
-            parse_projectiles: false,
-            only_header: true,
-            count_props: false,
-            only_convars: false,
-            huffman_lookup_table: arc_huf.clone(),
+        let mut parser = match DemoQuery::new().entities(false).parser(BytesVariant::Mmap(mmap)) {
+            Ok(parser) => parser,
+            Err(e) => return Err(Exception::new_err(format!("{}", e))),
         };
-        let mut parser = Parser::new(settings);
         let output = match parser.parse_demo() {
             Ok(output) => output,
             Err(e) => return Err(Exception::new_err(format!("{}", e))),
@@ -343,25 +272,11 @@ impl DemoParser {
                 )))
             }
         };
-        let arc_huf = Arc::new(create_huffman_lookup_table());
 
-        let settings = ParserInputs {
-            real_name_to_og_name: AHashMap::default(),
-            bytes: Arc::new(BytesVariant::Mmap(mmap)),
-            wanted_player_props: vec![],
-            wanted_player_props_og_names: vec![],
-            wanted_other_props: vec![],
-            wanted_other_props_og_names: vec![],
-            wanted_events: vec![],
-            parse_ents: false,
-            wanted_ticks: vec![],
-            parse_projectiles: false,
-            only_header: true,
-            count_props: false,
-            only_convars: false,
-            huffman_lookup_table: arc_huf.clone(),
+        let mut parser = match DemoQuery::new().entities(false).parser(BytesVariant::Mmap(mmap)) {
+            Ok(parser) => parser,
+            Err(e) => return Err(Exception::new_err(format!("{}", e))),
         };
-        let mut parser = Parser::new(settings);
         let output = match parser.parse_demo() {
             Ok(output) => output,
             Err(e) => return Err(Exception::new_err(format!("{}", e))),
@@ -400,25 +315,11 @@ impl DemoParser {
                 )))
             }
         };
-        let arc_huf = Arc::new(create_huffman_lookup_table());
 
-        let settings = ParserInputs {
-            real_name_to_og_name: AHashMap::default(),
-            bytes: Arc::new(BytesVariant::Mmap(mmap)),
-            wanted_player_props: vec![],
-            wanted_player_props_og_names: vec![],
-            wanted_other_props: vec![],
-            wanted_other_props_og_names: vec![],
-            wanted_events: vec![],
-            parse_ents: false,
-            wanted_ticks: vec![],
-            parse_projectiles: false,
-            only_header: true,
-            count_props: false,
-            only_convars: false,
-            huffman_lookup_table: arc_huf.clone(),
+        let mut parser = match DemoQuery::new().entities(false).parser(BytesVariant::Mmap(mmap)) {
+            Ok(parser) => parser,
+            Err(e) => return Err(Exception::new_err(format!("{}", e))),
         };
-        let mut parser = Parser::new(settings);
         let output = match parser.parse_demo() {
             Ok(output) => output,
             Err(e) => return Err(Exception::new_err(format!("{}", e))),
@@ -494,25 +395,11 @@ impl DemoParser {
                 )))
             }
         };
-        let arc_huf = Arc::new(create_huffman_lookup_table());
 
-        let settings = ParserInputs {
-            real_name_to_og_name: AHashMap::default(),
-            bytes: Arc::new(BytesVariant::Mmap(mmap)),
-            wanted_player_props: vec![],
-            wanted_player_props_og_names: vec![],
-            wanted_other_props: vec![],
-            wanted_other_props_og_names: vec![],
-            wanted_events: vec![],
-            parse_ents: false,
-            wanted_ticks: vec![],
-            parse_projectiles: false,
-            only_header: true,
-            count_props: false,
-            only_convars: false,
-            huffman_lookup_table: arc_huf.clone(),
+        let mut parser = match DemoQuery::new().entities(false).parser(BytesVariant::Mmap(mmap)) {
+            Ok(parser) => parser,
+            Err(e) => return Err(Exception::new_err(format!("{}", e))),
         };
-        let mut parser = Parser::new(settings);
         let output = match parser.parse_demo() {
             Ok(output) => output,
             Err(e) => return Err(Exception::new_err(format!("{}", e))),
@@ -575,24 +462,6 @@ impl DemoParser {
         py_kwargs: Option<&PyDict>,
     ) -> PyResult<Py<PyAny>> {
         let (wanted_player_props, wanted_other_props) = parse_kwargs_event(py_kwargs);
-        let real_player_props = rm_user_friendly_names(&wanted_player_props);
-        let real_other_props = rm_user_friendly_names(&wanted_other_props);
-
-        let real_player_props = match real_player_props {
-            Ok(real_props) => real_props,
-            Err(e) => return Err(PyValueError::new_err(format!("{}", e))),
-        };
-        let real_other_props = match real_other_props {
-            Ok(real_props) => real_props,
-            Err(e) => return Err(PyValueError::new_err(format!("{}", e))),
-        };
-        let mut real_name_to_og_name = AHashMap::default();
-        for (real_name, user_friendly_name) in real_player_props.iter().zip(&wanted_player_props) {
-            real_name_to_og_name.insert(real_name.clone(), user_friendly_name.clone());
-        }
-        for (real_name, user_friendly_name) in real_other_props.iter().zip(&wanted_other_props) {
-            real_name_to_og_name.insert(real_name.clone(), user_friendly_name.clone());
-        }
         let mmap = match create_mmap(self.path.clone()) {
             Ok(mmap) => mmap,
             Err(e) => {
@@ -603,34 +472,28 @@ impl DemoParser {
                 )))
             }
         };
-        let arc_huf = Arc::new(create_huffman_lookup_table());
 
-        let settings = ParserInputs {
-            real_name_to_og_name: real_name_to_og_name,
-            bytes: Arc::new(BytesVariant::Mmap(mmap)),
-            wanted_player_props: real_player_props.clone(),
-            wanted_player_props_og_names: wanted_player_props.clone(),
-            wanted_other_props: real_other_props,
-            wanted_other_props_og_names: vec![],
-            wanted_events: vec![event_name.clone()],
-            parse_ents: true,
-            wanted_ticks: vec![],
-            parse_projectiles: false,
-            only_header: true,
-            count_props: false,
-            only_convars: false,
-            huffman_lookup_table: arc_huf.clone(),
+        let query = DemoQuery::new()
+            .player_props(wanted_player_props)
+            .other_props(wanted_other_props)
+            .events([event_name.clone()]);
+        let mut parser = match query.parser(BytesVariant::Mmap(mmap)) {
+            Ok(parser) => parser,
+            Err(e) => return Err(PyValueError::new_err(format!("{}", e))),
         };
-        let mut parser = Parser::new(settings);
         let output = match parser.parse_demo() {
             Ok(output) => output,
             Err(e) => return Err(Exception::new_err(format!("{}", e))),
         };
-        let event_series = match series_from_event(&output.game_events, py) {
-            Ok(ser) => ser,
-            Err(_e) => return Ok(PyList::empty(py).into()),
-        };
-        Ok(event_series)
+        if output.game_events.is_empty() {
+            return Ok(PyList::empty(py).into());
+        }
+        let events: Vec<&GameEvent> = output.game_events.iter().collect();
+        let descriptor = parser.ge_list.values().find(|d| d.name() == event_name);
+        match event_record_batch(&events, descriptor) {
+            Ok(batch) => sort_columns(py, record_batch_to_pandas(py, batch)?),
+            Err(e) => Err(Exception::new_err(format!("{}", e))),
+        }
     }
     #[args(py_kwargs = "**")]
     pub fn parse_events(
@@ -640,24 +503,6 @@ impl DemoParser {
         py_kwargs: Option<&PyDict>,
     ) -> PyResult<Py<PyAny>> {
         let (wanted_player_props, wanted_other_props) = parse_kwargs_event(py_kwargs);
-        let real_player_props = rm_user_friendly_names(&wanted_player_props);
-        let real_other_props = rm_user_friendly_names(&wanted_other_props);
-
-        let real_player_props = match real_player_props {
-            Ok(real_props) => real_props,
-            Err(e) => return Err(PyValueError::new_err(format!("{}", e))),
-        };
-        let real_other_props = match real_other_props {
-            Ok(real_props) => real_props,
-            Err(e) => return Err(PyValueError::new_err(format!("{}", e))),
-        };
-        let mut real_name_to_og_name = AHashMap::default();
-        for (real_name, user_friendly_name) in real_player_props.iter().zip(&wanted_player_props) {
-            real_name_to_og_name.insert(real_name.clone(), user_friendly_name.clone());
-        }
-        for (real_name, user_friendly_name) in real_other_props.iter().zip(&wanted_other_props) {
-            real_name_to_og_name.insert(real_name.clone(), user_friendly_name.clone());
-        }
         let mmap = match create_mmap(self.path.clone()) {
             Ok(mmap) => mmap,
             Err(e) => {
@@ -668,34 +513,29 @@ impl DemoParser {
                 )))
             }
         };
-        let arc_huf = Arc::new(create_huffman_lookup_table());
 
-        let settings = ParserInputs {
-            real_name_to_og_name: real_name_to_og_name,
-            bytes: Arc::new(BytesVariant::Mmap(mmap)),
-            wanted_player_props: real_player_props.clone(),
-            wanted_player_props_og_names: wanted_player_props.clone(),
-            wanted_other_props: wanted_other_props,
-            wanted_other_props_og_names: vec![],
-            wanted_events: event_name.clone(),
-            parse_ents: true,
-            wanted_ticks: vec![],
-            parse_projectiles: false,
-            only_header: true,
-            count_props: false,
-            only_convars: false,
-            huffman_lookup_table: arc_huf.clone(),
+        let query = DemoQuery::new()
+            .player_props(wanted_player_props)
+            .other_props(wanted_other_props)
+            .events(event_name);
+        let mut parser = match query.parser(BytesVariant::Mmap(mmap)) {
+            Ok(parser) => parser,
+            Err(e) => return Err(PyValueError::new_err(format!("{}", e))),
         };
-        let mut parser = Parser::new(settings);
         let output = match parser.parse_demo() {
             Ok(output) => output,
             Err(e) => return Err(Exception::new_err(format!("{}", e))),
         };
-        let event_series = match series_from_multiple_events(&output.game_events, py) {
-            Ok(ser) => ser,
-            Err(e) => return Err(Exception::new_err(format!("{}", e))),
-        };
-        Ok(event_series)
+        let mut dfs = vec![];
+        for (name, events) in output.game_events.iter().into_group_map_by(|e| e.name.as_str()) {
+            let descriptor = parser.ge_list.values().find(|d| d.name() == name);
+            let batch = match event_record_batch(&events, descriptor) {
+                Ok(batch) => batch,
+                Err(e) => return Err(Exception::new_err(format!("{}", e))),
+            };
+            dfs.push((name, sort_columns(py, record_batch_to_pandas(py, batch)?)?));
+        }
+        Ok(dfs.to_object(py))
     }
 
     #[args(py_kwargs = "**")]
@@ -706,12 +546,6 @@ impl DemoParser {
         py_kwargs: Option<&PyDict>,
     ) -> PyResult<PyObject> {
         let (_, wanted_ticks) = parse_kwargs_ticks(py_kwargs);
-        let real_props = rm_user_friendly_names(&wanted_props);
-
-        let mut real_props = match real_props {
-            Ok(real_props) => real_props,
-            Err(e) => return Err(Exception::new_err(format!("{}", e))),
-        };
         let mmap = match create_mmap(self.path.clone()) {
             Ok(mmap) => mmap,
             Err(e) => {
@@ -723,106 +557,54 @@ impl DemoParser {
             }
         };
 
-        let huf = create_huffman_lookup_table();
-        let arc_huf = Arc::new(huf);
-        let mut real_name_to_og_name = AHashMap::default();
-        for (real_name, user_friendly_name) in real_props.iter().zip(&wanted_props) {
-            real_name_to_og_name.insert(real_name.clone(), user_friendly_name.clone());
-        }
-
-        let settings = ParserInputs {
-            real_name_to_og_name: real_name_to_og_name,
-            bytes: Arc::new(BytesVariant::Mmap(mmap)),
-            wanted_player_props: real_props.clone(),
-            wanted_player_props_og_names: wanted_props.clone(),
-            wanted_other_props: vec![],
-            wanted_other_props_og_names: vec![],
-            wanted_events: vec![],
-            parse_ents: true,
-            wanted_ticks: wanted_ticks,
-            parse_projectiles: false,
-            only_header: true,
-            count_props: false,
-            only_convars: false,
-            huffman_lookup_table: arc_huf.clone(),
-            //huf: huf,
+        let query = DemoQuery::new().player_props(wanted_props).ticks(wanted_ticks);
+        let mut parser = match query.parser(BytesVariant::Mmap(mmap)) {
+            Ok(parser) => parser,
+            Err(e) => return Err(Exception::new_err(format!("{}", e))),
         };
-        let mut parser = Parser::new(settings);
         let output = match parser.parse_demo() {
             Ok(output) => output,
             Err(e) => return Err(Exception::new_err(format!("{}", e))),
         };
-        let mut all_series = vec![];
-        let mut all_pyobjects = vec![];
-        let prop_infos = output.prop_info.prop_infos;
-        let mut df_column_names_arrow = vec![];
-        let mut df_column_names_py = vec![];
-
-        for prop_info in prop_infos {
-            if output.df.contains_key(&prop_info.id) {
-                match &output.df[&prop_info.id].data {
-                    Some(VarVec::F32(data)) => {
-                        df_column_names_arrow.push(prop_info.prop_friendly_name);
-                        all_series.push(arr_to_py(Box::new(Float32Array::from(data))).unwrap());
-                    }
-                    Some(VarVec::I32(data)) => {
-                        df_column_names_arrow.push(prop_info.prop_friendly_name);
-                        all_series.push(arr_to_py(Box::new(Int32Array::from(data))).unwrap());
-                    }
-                    Some(VarVec::U64(data)) => {
-                        df_column_names_arrow.push(prop_info.prop_friendly_name);
-                        all_series.push(arr_to_py(Box::new(UInt64Array::from(data))).unwrap());
-                    }
-                    Some(VarVec::U32(data)) => {
-                        df_column_names_arrow.push(prop_info.prop_friendly_name);
-                        all_series.push(arr_to_py(Box::new(UInt32Array::from(data))).unwrap());
-                    }
-                    Some(VarVec::Bool(data)) => {
-                        df_column_names_arrow.push(prop_info.prop_friendly_name);
-                        all_series.push(arr_to_py(Box::new(BooleanArray::from(data))).unwrap());
-                    }
-                    Some(VarVec::String(data)) => {
-                        df_column_names_arrow.push(prop_info.prop_friendly_name.clone());
-                        let s = Series::new(&prop_info.prop_friendly_name.clone(), data);
-                        let py_series = rust_series_to_py_series(&s).unwrap();
-                        all_series.push(py_series);
-                    }
-                    Some(VarVec::StringVec(data)) => {
-                        df_column_names_py.push(prop_info.prop_friendly_name);
-                        all_pyobjects.push(data.to_object(py))
-                    }
-                    Some(VarVec::U64Vec(data)) => {
-                        df_column_names_py.push(prop_info.prop_friendly_name);
-                        all_pyobjects.push(data.to_object(py))
-                    }
-                    _ => {}
-                }
-            }
+        match output.ticks_record_batch() {
+            Ok(batch) => record_batch_to_pandas(py, batch),
+            Err(e) => Err(Exception::new_err(format!("{}", e))),
         }
-        Python::with_gil(|py| {
-            let polars = py.import("polars")?;
-            let all_series_py = all_series.to_object(py);
-            let df = polars.call_method1("DataFrame", (all_series_py,))?;
-            df.setattr("columns", df_column_names_arrow.to_object(py))
-                .unwrap();
-            let pandas_df = df.call_method0("to_pandas").unwrap();
-            for (pyobj, col_name) in all_pyobjects.iter().zip(&df_column_names_py) {
-                pandas_df
-                    .call_method1("insert", (0, col_name, pyobj))
-                    .unwrap();
-            }
-            df_column_names_arrow.extend(df_column_names_py);
-            df_column_names_arrow.sort();
-            let kwargs = vec![("axis", 1)].into_py_dict(py);
-            let args = (df_column_names_arrow,);
-            pandas_df
-                .call_method("reindex", args, Some(kwargs))
-                .unwrap();
-            Ok(pandas_df.to_object(py))
-        })
     }
 }
 
+/// Moves a RecordBatch from the parser crate to pyarrow through the Arrow C data interface.
+/// pyarrow takes over the buffers, nothing is copied.
+/// The parser's batches are arrow-rs, the older functions still build polars Series with polars-arrow.
+/// Both only meet pyarrow through the C data interface, so the two versions never have to agree.
+pub fn record_batch_to_py(py: Python, batch: RecordBatch) -> PyResult<PyObject> {
+    let pyarrow = py.import("pyarrow")?;
+    let data: ArrayData = StructArray::from(batch).into();
+    let (array, schema) = match arrow_rs::ffi::to_ffi(&data) {
+        Ok(ffi) => ffi,
+        Err(e) => return Err(Exception::new_err(format!("{}", e))),
+    };
+    let array_ptr: *const FFI_ArrowArray = &array;
+    let schema_ptr: *const FFI_ArrowSchema = &schema;
+    let batch = pyarrow.getattr("RecordBatch")?.call_method1(
+        "_import_from_c",
+        (array_ptr as Py_uintptr_t, schema_ptr as Py_uintptr_t),
+    )?;
+    Ok(batch.to_object(py))
+}
+pub fn record_batch_to_pandas(py: Python, batch: RecordBatch) -> PyResult<PyObject> {
+    record_batch_to_py(py, batch)?.call_method0(py, "to_pandas")
+}
+// Event dataframes have always had their columns in alphabetical order
+fn sort_columns(py: Python, df: PyObject) -> PyResult<PyObject> {
+    let mut columns: Vec<String> = df
+        .getattr(py, "columns")?
+        .call_method0(py, "tolist")?
+        .extract(py)?;
+    columns.sort();
+    let kwargs = vec![("axis", 1)].into_py_dict(py);
+    df.call_method(py, "reindex", (columns,), Some(kwargs))
+}
 /// https://github.com/pola-rs/polars/blob/master/examples/python_rust_compiled_function/src/ffi.rs
 pub(crate) fn to_py_array(py: Python, pyarrow: &PyModule, array: ArrayRef) -> PyResult<PyObject> {
     let schema = Box::new(ffi::export_field_to_c(&ArrowField::new(
@@ -926,300 +708,6 @@ pub fn parse_kwargs_event(kwargs: Option<&PyDict>) -> (Vec<String>, Vec<String>)
     }
 }
 
-pub fn series_from_multiple_events(
-    events: &Vec<GameEvent>,
-    py: Python,
-) -> Result<Py<PyAny>, DemoParserError> {
-    let per_ge = events.iter().into_group_map_by(|x| x.name.clone());
-    let mut vv = vec![];
-    for (k, v) in per_ge {
-        let pairs: Vec<EventField> = v.iter().map(|x| x.fields.clone()).flatten().collect();
-        let per_key_name = pairs.iter().into_group_map_by(|x| &x.name);
-
-        let mut series_columns = vec![];
-        let mut py_columns = vec![];
-        let mut rows = 0;
-
-        for (name, vals) in per_key_name {
-            match column_from_pairs(&vals, name, py)? {
-                DataFrameColumn::Pyany(p) => py_columns.push((p, name)),
-                DataFrameColumn::Series(s) => {
-                    rows = s.len().max(rows);
-                    series_columns.push((s, name))
-                }
-            };
-        }
-        let mut series_col_names: Vec<String> = series_columns
-            .iter()
-            .map(|(_, name)| name.to_string())
-            .collect();
-        let series_columns: Vec<PyObject> = series_columns
-            .iter()
-            .map(|(ser, _)| rust_series_to_py_series(&ser).unwrap())
-            .collect();
-        let py_col_names: Vec<String> = py_columns
-            .iter()
-            .map(|(_, name)| name.to_string())
-            .collect();
-
-        if rows != 0 {
-            let dfp = Python::with_gil(|py| {
-                let polars = py.import("polars").unwrap();
-                let all_series_py = series_columns.to_object(py);
-                let df = polars.call_method1("DataFrame", (all_series_py,)).unwrap();
-                df.setattr("columns", series_col_names.to_object(py))
-                    .unwrap();
-                let pandas_df = df.call_method0("to_pandas").unwrap();
-                for (pyobj, col_name) in py_columns {
-                    pandas_df
-                        .call_method1("insert", (0, col_name, pyobj))
-                        .unwrap();
-                }
-
-                series_col_names.extend(py_col_names);
-                series_col_names.sort();
-
-                let kwargs = vec![("axis", 1)].into_py_dict(py);
-                let args = (series_col_names,);
-                let df = pandas_df
-                    .call_method("reindex", args, Some(kwargs))
-                    .unwrap();
-                df.to_object(py)
-            });
-            vv.push((k, dfp));
-        }
-    }
-    Ok(vv.to_object(py))
-}
-
-pub enum DataFrameColumn {
-    Series(Series),
-    Pyany(pyo3::Py<PyAny>),
-}
-
-pub fn series_from_event(
-    events: &Vec<GameEvent>,
-    py: Python,
-) -> Result<Py<PyAny>, DemoParserError> {
-    let pairs: Vec<EventField> = events.iter().map(|x| x.fields.clone()).flatten().collect();
-    let per_key_name = pairs.iter().into_group_map_by(|x| &x.name);
-
-    let mut series_columns = vec![];
-    let mut py_columns = vec![];
-    let mut rows = 0;
-
-    for (name, vals) in per_key_name {
-        match column_from_pairs(&vals, name, py)? {
-            DataFrameColumn::Pyany(p) => py_columns.push((p, name)),
-            DataFrameColumn::Series(s) => {
-                rows = s.len().max(rows);
-                series_columns.push((s, name))
-            }
-        };
-    }
-    let mut series_col_names: Vec<String> = series_columns
-        .iter()
-        .map(|(_, name)| name.to_string())
-        .collect();
-    let series_columns: Vec<PyObject> = series_columns
-        .iter()
-        .map(|(ser, _)| rust_series_to_py_series(&ser).unwrap())
-        .collect();
-    let py_col_names: Vec<String> = py_columns
-        .iter()
-        .map(|(_, name)| name.to_string())
-        .collect();
-    if rows == 0 {
-        return Err(DemoParserError::NoEvents);
-    }
-    let dfp = Python::with_gil(|py| {
-        let polars = py.import("polars").unwrap();
-        let all_series_py = series_columns.to_object(py);
-        let df = polars.call_method1("DataFrame", (all_series_py,)).unwrap();
-        df.setattr("columns", series_col_names.to_object(py))
-            .unwrap();
-        let pandas_df = df.call_method0("to_pandas").unwrap();
-        for (pyobj, col_name) in py_columns {
-            pandas_df
-                .call_method1("insert", (0, col_name, pyobj))
-                .unwrap();
-        }
-        series_col_names.extend(py_col_names);
-        series_col_names.sort();
-        let kwargs = vec![("axis", 1)].into_py_dict(py);
-        let args = (series_col_names,);
-        let df = pandas_df
-            .call_method("reindex", args, Some(kwargs))
-            .unwrap();
-        df.to_object(py)
-    });
-    Ok(dfp)
-}
-fn to_f32_series(pairs: &Vec<&EventField>, name: &String) -> DataFrameColumn {
-    let mut v = vec![];
-    for pair in pairs {
-        match &pair.data {
-            Some(f) => match f {
-                Variant::F32(val) => v.push(Some(*val)),
-                _ => v.push(None),
-            },
-            None => v.push(None),
-        }
-    }
-    DataFrameColumn::Series(Series::new(name, v))
-}
-fn to_u32_series(pairs: &Vec<&EventField>, name: &String) -> DataFrameColumn {
-    let mut v = vec![];
-    for pair in pairs {
-        match &pair.data {
-            Some(f) => match f {
-                Variant::U32(val) => v.push(Some(*val)),
-                _ => v.push(None),
-            },
-            None => v.push(None),
-        }
-    }
-    DataFrameColumn::Series(Series::new(name, v))
-}
-fn to_i32_series(pairs: &Vec<&EventField>, name: &String) -> DataFrameColumn {
-    let mut v = vec![];
-    for pair in pairs {
-        match &pair.data {
-            Some(k) => match k {
-                Variant::I32(val) => v.push(Some(*val)),
-                _ => v.push(None),
-            },
-            None => v.push(None),
-        }
-    }
-    DataFrameColumn::Series(Series::new(name, v))
-}
-fn to_u64_series(pairs: &Vec<&EventField>, name: &String) -> DataFrameColumn {
-    let mut v = vec![];
-    for pair in pairs {
-        match &pair.data {
-            Some(k) => match k {
-                Variant::U64(val) => v.push(Some(*val)),
-                _ => v.push(None),
-            },
-            None => v.push(None),
-        }
-    }
-    DataFrameColumn::Series(Series::new(name, v))
-}
-fn to_py_string_col(pairs: &Vec<&EventField>, name: &String, py: Python) -> DataFrameColumn {
-    let mut v = vec![];
-    for pair in pairs {
-        match &pair.data {
-            Some(k) => match k {
-                Variant::StringVec(val) => v.push(Some(val.clone())),
-                _ => v.push(None),
-            },
-            None => v.push(None),
-        }
-    }
-    DataFrameColumn::Pyany(v.to_object(py))
-}
-fn to_py_u64_col(pairs: &Vec<&EventField>, name: &String, py: Python) -> DataFrameColumn {
-    let mut v = vec![];
-    for pair in pairs {
-        match &pair.data {
-            Some(k) => match k {
-                Variant::U64Vec(val) => v.push(Some(val.clone())),
-                _ => v.push(None),
-            },
-            None => v.push(None),
-        }
-    }
-    DataFrameColumn::Pyany(v.to_object(py))
-}
-
-fn to_string_series(pairs: &Vec<&EventField>, name: &String) -> DataFrameColumn {
-    let mut v = vec![];
-    for pair in pairs {
-        match &pair.data {
-            Some(k) => match k {
-                Variant::String(val) => v.push(Some(val.to_owned())),
-                _ => v.push(None),
-            },
-            None => v.push(None),
-        }
-    }
-    DataFrameColumn::Series(Series::new(name, v))
-}
-
-fn to_bool_series(pairs: &Vec<&EventField>, name: &String) -> DataFrameColumn {
-    let mut v = vec![];
-    for pair in pairs {
-        match &pair.data {
-            Some(k) => match k {
-                Variant::Bool(val) => v.push(Some(val.to_owned())),
-                _ => v.push(None),
-            },
-            None => v.push(None),
-        }
-    }
-    DataFrameColumn::Series(Series::new(name, v))
-}
-
-fn to_null_series(pairs: &Vec<&EventField>, name: &String) -> DataFrameColumn {
-    // All series are null can pick any type
-    let mut v: Vec<Option<i32>> = vec![];
-    for _ in pairs {
-        v.push(None);
-    }
-    DataFrameColumn::Series(Series::new(name, v))
-}
-
-pub fn column_from_pairs(
-    pairs: &Vec<&EventField>,
-    name: &String,
-    py: Python,
-) -> Result<DataFrameColumn, DemoParserError> {
-    let field_type = find_type_of_vals(pairs)?;
-
-    let s = match field_type {
-        None => to_null_series(pairs, name),
-        Some(Variant::Bool(_)) => to_bool_series(pairs, name),
-        Some(Variant::F32(_)) => to_f32_series(pairs, name),
-        Some(Variant::U32(_)) => to_u32_series(pairs, name),
-        Some(Variant::I32(_)) => to_i32_series(pairs, name),
-        Some(Variant::U64(_)) => to_u64_series(pairs, name),
-        Some(Variant::String(_)) => to_string_series(pairs, name),
-        Some(Variant::StringVec(_)) => to_py_string_col(pairs, name, py),
-        Some(Variant::U64Vec(_)) => to_py_u64_col(pairs, name, py),
-        _ => panic!("unkown ge key: {:?}", field_type),
-    };
-    Ok(s)
-}
-fn find_type_of_vals(pairs: &Vec<&EventField>) -> Result<Option<Variant>, DemoParserError> {
-    // Need to find the correct type for outgoing series,
-    let mut all_types = vec![];
-    for pair in pairs {
-        all_types.push(match &pair.data {
-            Some(Variant::Bool(v)) => Some(Variant::Bool(*v)),
-            Some(Variant::I32(v)) => Some(Variant::I32(*v)),
-            Some(Variant::F32(v)) => Some(Variant::F32(*v)),
-            Some(Variant::String(s)) => Some(Variant::String(s.clone())),
-            Some(Variant::U64(u)) => Some(Variant::U64(*u)),
-            Some(Variant::U32(u)) => Some(Variant::U32(*u)),
-            Some(Variant::StringVec(_u)) => Some(Variant::StringVec(vec![])),
-            Some(Variant::U64Vec(_u)) => Some(Variant::U64Vec(vec![])),
-            None => None,
-            _ => {
-                return Err(DemoParserError::UnknownGameEventVariant(pair.name.clone()));
-            }
-        });
-    }
-
-    for t in &all_types {
-        if t.is_some() {
-            return Ok(t.clone());
-        }
-    }
-    return Ok(None);
-}
-
 #[pymodule]
 fn demoparser2(_py: Python, m: &PyModule) -> PyResult<()> {
     m.add_class::<DemoParser>()?;